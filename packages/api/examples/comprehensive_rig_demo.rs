@@ -276,6 +276,7 @@ async fn demo_agent_extensions() -> Result<()> {
             enable_autopilot: false,
             enable_extensions: true,
             extension_timeout: 30,
+            ..AgentConfig::default()
         }),
         tools: Some(vec![
             Tool {
@@ -283,6 +284,7 @@ async fn demo_agent_extensions() -> Result<()> {
                 description: "Get current time".to_string(),
                 input_schema: json!({"type": "object"}),
                 is_mcp: false,
+                category: api::ToolCategory::ReadOnly,
             },
             Tool {
                 name: "get_weather".to_string(),
@@ -295,6 +297,7 @@ async fn demo_agent_extensions() -> Result<()> {
                     "required": ["location"]
                 }),
                 is_mcp: false,
+                category: api::ToolCategory::Network,
             },
         ]),
     };
@@ -449,6 +452,7 @@ async fn demo_enhanced_streaming() -> Result<()> {
             enable_autopilot: false,
             enable_extensions: true,
             extension_timeout: 45,
+            ..AgentConfig::default()
         }),
         tools: Some(vec![
             Tool {
@@ -456,6 +460,7 @@ async fn demo_enhanced_streaming() -> Result<()> {
                 description: "Get current time".to_string(),
                 input_schema: json!({"type": "object"}),
                 is_mcp: false,
+                category: api::ToolCategory::ReadOnly,
             },
             Tool {
                 name: "knowledge_base".to_string(),
@@ -469,6 +474,7 @@ async fn demo_enhanced_streaming() -> Result<()> {
                     "required": ["action"]
                 }),
                 is_mcp: false,
+                category: api::ToolCategory::ReadOnly,
             },
         ]),
     };
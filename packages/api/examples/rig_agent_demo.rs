@@ -36,6 +36,7 @@ async fn main() -> anyhow::Result<()> {
         enable_autopilot: false,
         enable_extensions: false,
         extension_timeout: 30,
+        ..AgentConfig::default()
     };
 
     // Agent 模式
@@ -51,6 +52,7 @@ async fn main() -> anyhow::Result<()> {
         enable_autopilot: false,
         enable_extensions: true,
         extension_timeout: 60,
+        ..AgentConfig::default()
     };
 
     // 自主模式
@@ -66,6 +68,7 @@ async fn main() -> anyhow::Result<()> {
         enable_autopilot: true,
         enable_extensions: true,
         extension_timeout: 90,
+        ..AgentConfig::default()
     };
 
     println!("✅ Agent 配置创建完成");
@@ -129,6 +132,7 @@ async fn main() -> anyhow::Result<()> {
                         "properties": {}
                     }),
                     is_mcp: false,
+                    category: api::ToolCategory::ReadOnly,
                 },
                 api::Tool {
                     name: "get_weather".to_string(),
@@ -144,6 +148,7 @@ async fn main() -> anyhow::Result<()> {
                         "required": ["location"]
                     }),
                     is_mcp: false,
+                    category: api::ToolCategory::Network,
                 }
             ]),
         },
@@ -0,0 +1,49 @@
+// Registering an agent extension: the safety filter stops messages that mention secrets
+use api::{ChatMessage, ChatRequest, ChatService, Role, SafetyFilterExtension};
+
+fn request(content: &str) -> ChatRequest {
+    ChatRequest {
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: content.to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        }],
+        model: "mock-local".to_string(),
+        system_prompt: None,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        stream: false,
+        agent_config: None,
+        tools: None,
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let service = ChatService::with_db_path(":memory:")?;
+
+    let mut filter = SafetyFilterExtension::new();
+    filter.add_blocked_pattern("credit card".to_string());
+    service.register_extension(Box::new(filter)).await?;
+
+    let session = service.create_session("Safety filter demo", Some("mock-local"))?;
+    for message in ["What is a lifetime in Rust?", "My password is hunter2"] {
+        println!("> {}", message);
+        let response = service.agent_reply(&session.id, request(message)).await?;
+        match (response.message, response.notification) {
+            (_, Some(notification)) => println!(
+                "[{:?}] {}",
+                notification.notification_type, notification.message
+            ),
+            (Some(reply), None) => println!("{}", reply.content),
+            (None, None) => println!("(no reply)"),
+        }
+    }
+
+    Ok(())
+}
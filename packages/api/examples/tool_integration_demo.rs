@@ -172,6 +172,7 @@ async fn main() -> anyhow::Result<()> {
                 "properties": {}
             }),
             is_mcp: false,
+            category: api::ToolCategory::ReadOnly,
         },
         Tool {
             name: "get_weather".to_string(),
@@ -187,6 +188,7 @@ async fn main() -> anyhow::Result<()> {
                 "required": ["location"]
             }),
             is_mcp: false,
+            category: api::ToolCategory::Network,
         }
     ];
 
@@ -3,9 +3,6 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use rig::completion::Prompt;
-use rig::providers;
-use rig::client::CompletionClient;
 
 use crate::rig_agent_service::{CustomTool, DateTimeTool, WeatherTool};
 use crate::chat_service_simple::{AgentConfig, GooseMode, Tool as ApiTool};
@@ -60,8 +57,8 @@ impl ToolRegistry {
         self.tools.insert(name, Box::new(tool));
     }
 
-    pub fn get_tool(&self, name: &str) -> Option<&Box<dyn CustomTool>> {
-        self.tools.get(name)
+    pub fn get_tool(&self, name: &str) -> Option<&dyn CustomTool> {
+        self.tools.get(name).map(|tool| tool.as_ref())
     }
 
     pub fn list_tools(&self) -> Vec<String> {
@@ -185,19 +182,6 @@ impl RigAgentBuilder {
         preamble
     }
 
-    async fn create_openai_agent(&self) -> Result<impl Prompt> {
-        let client = providers::openai::Client::from_env();
-
-        // Build the agent with the current rig API
-        let agent = client.agent(&self.config.model_id);
-
-        // Note: The current rig API doesn't seem to support all the configuration options
-        // from the old AgentBuilder. We'll need to work with what's available.
-        let built_agent = agent.build();
-
-        Ok(built_agent)
-    }
-
     async fn create_mock_agent(&self) -> Result<impl std::fmt::Display> {
         // Simplified mock agent that just returns a formatted response
         // We'll return a simple string that implements Display
@@ -1,13 +1,15 @@
-// Agent Extension System for Rig Agents
+// Agent Extension System: hooks that run around a reply and can veto it
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
-use crate::{AgentConfig, ChatMessage, ChatRequest, ChatResponse, Role, ToolCall, ToolResult};
+use crate::agent_loop::ToolExecutor;
+use crate::{ChatMessage, ChatRequest, ChatResponse, Role, ToolCall, ToolResult};
 
 /// Extension context for agent extensions
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +23,21 @@ pub struct ExtensionContext {
     pub capabilities: Vec<String>,
 }
 
+impl ExtensionContext {
+    /// Context for the reply being generated in `session_id`
+    pub fn for_session(session_id: &str) -> Self {
+        Self {
+            agent_id: "default_agent".to_string(),
+            conversation_id: session_id.to_string(),
+            user_id: None,
+            session_id: session_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            metadata: HashMap::new(),
+            capabilities: vec!["chat".to_string(), "tools".to_string()],
+        }
+    }
+}
+
 /// Extension execution result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtensionResult {
@@ -31,8 +48,9 @@ pub struct ExtensionResult {
     pub next_actions: Vec<String>,
 }
 
-/// Extension execution phase
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Extension execution phase. `agent_reply` runs `Validation` then `PreProcessing` on the
+/// request, `ToolCall` before each tool call and `PostProcessing` on the response.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ExtensionPhase {
     PreProcessing,
     ToolCall,
@@ -41,6 +59,16 @@ pub enum ExtensionPhase {
     Cleanup,
 }
 
+/// What running the extensions of one phase produced
+#[derive(Debug, Clone)]
+pub struct PhaseOutcome {
+    /// The input, as changed by the extensions that returned data
+    pub output: serde_json::Value,
+    pub results: Vec<ExtensionResult>,
+    /// Why an extension rejected the input. Extensions after it didn't run.
+    pub blocked: Option<String>,
+}
+
 /// Agent extension trait
 #[async_trait]
 pub trait AgentExtension: Send + Sync {
@@ -64,7 +92,7 @@ pub trait AgentExtension: Send + Sync {
     }
 
     /// Initialize extension with context
-    async fn initialize(&mut self, context: &ExtensionContext) -> Result<()> {
+    async fn initialize(&mut self, _context: &ExtensionContext) -> Result<()> {
         Ok(())
     }
 
@@ -79,9 +107,9 @@ pub trait AgentExtension: Send + Sync {
     /// Check if extension should execute for given input
     async fn should_execute(
         &self,
-        phase: ExtensionPhase,
-        context: &ExtensionContext,
-        input: &serde_json::Value,
+        _phase: ExtensionPhase,
+        _context: &ExtensionContext,
+        _input: &serde_json::Value,
     ) -> bool {
         true
     }
@@ -97,7 +125,7 @@ pub trait AgentExtension: Send + Sync {
     }
 
     /// Update extension configuration
-    async fn update_config(&mut self, config: serde_json::Value) -> Result<()> {
+    async fn update_config(&mut self, _config: serde_json::Value) -> Result<()> {
         Ok(())
     }
 }
@@ -148,46 +176,84 @@ impl ExtensionManager {
         Ok(())
     }
 
-    /// Execute extensions for a specific phase
+    /// Execute extensions for a specific phase, in registration order. An extension that
+    /// returns an unsuccessful result blocks the input and stops the phase. One that fails or
+    /// takes longer than `timeout` is recorded and skipped, so a broken extension can't stall
+    /// or veto every reply.
     pub async fn execute_phase(
         &self,
         phase: ExtensionPhase,
         context: &ExtensionContext,
         input: serde_json::Value,
-    ) -> Result<(serde_json::Value, Vec<ExtensionResult>)> {
-        let extensions = self.extensions.read().await;
+        timeout: Duration,
+    ) -> Result<PhaseOutcome> {
+        let mut extensions = self.extensions.write().await;
+        let order = self.loaded_extensions.read().await.clone();
         let mut results = Vec::new();
         let mut current_input = input;
 
-        for (name, extension) in extensions.iter() {
-            if extension.phases().contains(&phase)
-                && extension
+        for name in order {
+            let Some(extension) = extensions.get_mut(&name) else {
+                continue;
+            };
+            if !extension.phases().contains(&phase) {
+                continue;
+            }
+
+            let run = async {
+                if extension
                     .should_execute(phase, context, &current_input)
                     .await
-            {
-                match extension.execute(phase, context, &current_input).await {
-                    Ok(result) => {
-                        // Update input for next extension if data is provided
-                        if let Some(data) = &result.data {
-                            current_input = data.clone();
-                        }
-                        results.push(result);
-                    }
-                    Err(e) => {
-                        let error_result = ExtensionResult {
-                            success: false,
-                            data: None,
-                            error: Some(format!("Extension {} failed: {}", name, e)),
-                            metadata: HashMap::new(),
-                            next_actions: Vec::new(),
-                        };
-                        results.push(error_result);
+                {
+                    Some(extension.execute(phase, context, &current_input).await)
+                } else {
+                    None
+                }
+            };
+            let failure = match tokio::time::timeout(timeout, run).await {
+                Ok(None) => continue,
+                Ok(Some(Ok(result))) if !result.success => {
+                    let reason = result
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| format!("Rejected by extension {}", name));
+                    results.push(result);
+                    return Ok(PhaseOutcome {
+                        output: current_input,
+                        results,
+                        blocked: Some(reason),
+                    });
+                }
+                Ok(Some(Ok(result))) => {
+                    // Update input for next extension if data is provided
+                    if let Some(data) = &result.data {
+                        current_input = data.clone();
                     }
+                    results.push(result);
+                    continue;
                 }
-            }
+                Ok(Some(Err(e))) => format!("Extension {} failed: {}", name, e),
+                Err(_) => format!(
+                    "Extension {} timed out after {}s",
+                    name,
+                    timeout.as_secs_f32()
+                ),
+            };
+            tracing::warn!("{}", failure);
+            results.push(ExtensionResult {
+                success: false,
+                data: None,
+                error: Some(failure),
+                metadata: HashMap::new(),
+                next_actions: Vec::new(),
+            });
         }
 
-        Ok((current_input, results))
+        Ok(PhaseOutcome {
+            output: current_input,
+            results,
+            blocked: None,
+        })
     }
 
     /// Get all registered extensions
@@ -233,6 +299,51 @@ impl Default for ExtensionManager {
     }
 }
 
+impl std::fmt::Debug for ExtensionManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("ExtensionManager");
+        if let Ok(loaded) = self.loaded_extensions.try_read() {
+            debug.field("extensions", &*loaded);
+        }
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// Runs the `ToolCall` phase before handing each call to `inner`; a blocked call comes back as
+/// an error result without running
+pub struct ExtensionToolExecutor<'a> {
+    pub extensions: &'a ExtensionManager,
+    pub context: ExtensionContext,
+    pub timeout: Duration,
+    pub inner: &'a dyn ToolExecutor,
+}
+
+#[async_trait]
+impl ToolExecutor for ExtensionToolExecutor<'_> {
+    async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let outcome = self
+            .extensions
+            .execute_phase(
+                ExtensionPhase::ToolCall,
+                &self.context,
+                json!(call),
+                self.timeout,
+            )
+            .await;
+        match outcome {
+            Ok(PhaseOutcome {
+                blocked: Some(reason),
+                ..
+            }) => ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::Null,
+                error: Some(format!("Blocked by extension: {}", reason)),
+            },
+            _ => self.inner.execute(call).await,
+        }
+    }
+}
+
 /// Extension information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtensionInfo {
@@ -243,7 +354,7 @@ pub struct ExtensionInfo {
     pub config_schema: Option<serde_json::Value>,
 }
 
-/// Pre-built extensions
+// Pre-built extensions
 
 /// Conversation summarization extension
 pub struct ConversationSummarizerExtension {
@@ -404,7 +515,7 @@ impl AgentExtension for ToolUsageMonitorExtension {
     async fn execute(
         &mut self,
         phase: ExtensionPhase,
-        context: &ExtensionContext,
+        _context: &ExtensionContext,
         input: &serde_json::Value,
     ) -> Result<ExtensionResult> {
         match phase {
@@ -492,6 +603,12 @@ impl SafetyFilterExtension {
     }
 }
 
+impl Default for SafetyFilterExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl AgentExtension for SafetyFilterExtension {
     fn name(&self) -> &'static str {
@@ -515,7 +632,7 @@ impl AgentExtension for SafetyFilterExtension {
         match phase {
             ExtensionPhase::Validation => {
                 // Validate input for safety violations
-                let input_str = input.to_string();
+                let input_str = checked_text(input);
 
                 // Check for blocked patterns
                 for pattern in &self.blocked_patterns {
@@ -571,6 +688,27 @@ impl AgentExtension for SafetyFilterExtension {
     }
 }
 
+/// The text the safety filter looks at: the latest user message of a request, so field names
+/// like `max_tokens` and earlier turns don't trip it, or the whole input otherwise
+fn checked_text(input: &serde_json::Value) -> String {
+    match input
+        .get("messages")
+        .and_then(|messages| messages.as_array())
+    {
+        Some(messages) => messages
+            .iter()
+            .rev()
+            .find(|message| message["role"] == "user")
+            .and_then(|message| message["content"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        None => match input.as_str() {
+            Some(text) => text.to_string(),
+            None => input.to_string(),
+        },
+    }
+}
+
 /// Enhanced agent with extension support
 pub struct ExtendedRigAgentService {
     base_service: crate::RigAgentService,
@@ -616,7 +754,7 @@ impl ExtendedRigAgentService {
     /// Send message with extension processing
     pub async fn send_message_with_extensions(
         &self,
-        mut request: ChatRequest,
+        request: ChatRequest,
         context: Option<ExtensionContext>,
     ) -> Result<ChatResponse> {
         // Create context if not provided
@@ -635,32 +773,48 @@ impl ExtendedRigAgentService {
         });
 
         // Pre-processing phase
+        let timeout = Duration::from_secs(
+            request
+                .agent_config
+                .as_ref()
+                .map_or(crate::AgentConfig::default().extension_timeout, |config| {
+                    config.extension_timeout
+                }),
+        );
         let request_json = json!(request);
-        let (processed_request, _) = self
+        let outcome = self
             .extension_manager
             .execute_phase(
-                crate::agent_extensions::ExtensionPhase::PreProcessing,
+                ExtensionPhase::PreProcessing,
                 &ext_context,
                 request_json,
+                timeout,
             )
             .await?;
+        if let Some(reason) = outcome.blocked {
+            return Err(anyhow::anyhow!("Message blocked: {}", reason));
+        }
 
         let processed_request: ChatRequest =
-            serde_json::from_value(processed_request).unwrap_or(request);
+            serde_json::from_value(outcome.output).unwrap_or(request);
 
         // Send message through base service
-        let mut response = self.base_service.send_message(processed_request).await?;
+        let response = self.base_service.send_message(processed_request).await?;
 
         // Post-processing phase
         let response_json = json!(response);
-        let (_, _) = self
+        let outcome = self
             .extension_manager
             .execute_phase(
-                crate::agent_extensions::ExtensionPhase::PostProcessing,
+                ExtensionPhase::PostProcessing,
                 &ext_context,
                 response_json,
+                timeout,
             )
             .await?;
+        if let Some(reason) = outcome.blocked {
+            return Err(anyhow::anyhow!("Response withheld: {}", reason));
+        }
 
         Ok(response)
     }
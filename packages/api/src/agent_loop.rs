@@ -0,0 +1,619 @@
+// Agent loop: run every tool call from a model turn and feed the results back before the next turn
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+
+use crate::chat_service_simple::{
+    is_content_filter, AgentConfig, ChatMessage, ChatResponse, ContentFilterPolicy, Role,
+    SessionEvent, SystemNotification, SystemNotificationType, ToolCall, ToolResult,
+};
+use crate::mcp::{check_readonly, execute_builtin_tool, ShellPolicy};
+use crate::trace::TraceKind;
+
+/// Upper bound on tool calls from one turn that run at the same time
+pub const MAX_PARALLEL_TOOL_CALLS: usize = 4;
+
+/// How long a tool call waits for the user before it counts as declined
+pub const TOOL_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[async_trait]
+pub trait ToolExecutor: Send + Sync {
+    /// Run one call. Failures belong in `ToolResult::error` so the model can react to them.
+    async fn execute(&self, call: &ToolCall) -> ToolResult;
+}
+
+/// Runs the built-in tools (shell, file editor, ...). The default one refuses shell commands.
+#[derive(Debug, Clone, Default)]
+pub struct BuiltinToolExecutor {
+    shell: ShellPolicy,
+    /// Tools that may only read, from the agent config
+    readonly_tools: Vec<String>,
+}
+
+impl BuiltinToolExecutor {
+    /// Shell access and read-only tools as the agent config allows them
+    pub fn new(config: &AgentConfig) -> Self {
+        Self {
+            shell: ShellPolicy::from_config(config),
+            readonly_tools: config.readonly_tools.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for BuiltinToolExecutor {
+    async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let outputs = match check_readonly(call, &self.readonly_tools) {
+            Ok(()) => execute_builtin_tool(call, &self.shell).await,
+            Err(e) => Err(e),
+        };
+        match outputs {
+            Ok(outputs) => ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::String(outputs.join("\n")),
+                error: None,
+            },
+            Err(e) => ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::Null,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Approval channels keyed by session and tool call id
+type PendingConfirmations = HashMap<(String, String), oneshot::Sender<bool>>;
+
+/// Tool calls waiting for the user to approve or decline them, by session and call id
+#[derive(Debug, Clone, Default)]
+pub struct ToolConfirmations {
+    pending: Arc<Mutex<PendingConfirmations>>,
+}
+
+impl ToolConfirmations {
+    fn request(&self, session_id: &str, tool_call_id: &str) -> PendingConfirmation<'_> {
+        let (tx, rx) = oneshot::channel();
+        let key = (session_id.to_string(), tool_call_id.to_string());
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.clone(), tx);
+        PendingConfirmation {
+            confirmations: self,
+            key,
+            answer: rx,
+        }
+    }
+
+    /// Deliver the user's answer. Returns false when no such call is waiting.
+    pub fn answer(&self, session_id: &str, tool_call_id: &str, approved: bool) -> bool {
+        let waiting = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&(session_id.to_string(), tool_call_id.to_string()));
+        waiting.is_some_and(|tx| tx.send(approved).is_ok())
+    }
+
+    /// Decline every call the session is waiting on, e.g. when its reply is cancelled
+    pub fn clear_session(&self, session_id: &str) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(session, _), _| session != session_id);
+    }
+}
+
+/// One call's wait for an answer. Dropping it, after a timeout or when the reply is
+/// abandoned, stops the call from waiting.
+struct PendingConfirmation<'a> {
+    confirmations: &'a ToolConfirmations,
+    key: (String, String),
+    answer: oneshot::Receiver<bool>,
+}
+
+impl Drop for PendingConfirmation<'_> {
+    fn drop(&mut self) {
+        self.confirmations
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+/// Holds each call to a tool outside `readonly_tools` until the user answers the published
+/// `SessionEvent::ToolConfirmationRequest`. Declined calls, and calls still unanswered after
+/// `timeout`, come back as an error result without running.
+pub struct ConfirmingToolExecutor<'a> {
+    pub session_id: String,
+    pub readonly_tools: &'a [String],
+    pub confirmations: &'a ToolConfirmations,
+    pub events: &'a broadcast::Sender<SessionEvent>,
+    pub timeout: Duration,
+    pub inner: &'a dyn ToolExecutor,
+}
+
+#[async_trait]
+impl ToolExecutor for ConfirmingToolExecutor<'_> {
+    async fn execute(&self, call: &ToolCall) -> ToolResult {
+        if self.readonly_tools.contains(&call.name) {
+            return self.inner.execute(call).await;
+        }
+
+        // Register before publishing so an immediate answer isn't lost
+        let mut pending = self.confirmations.request(&self.session_id, &call.id);
+        let _ = self.events.send(SessionEvent::ToolConfirmationRequest {
+            session_id: self.session_id.clone(),
+            tool_call: call.clone(),
+        });
+        let error = match tokio::time::timeout(self.timeout, &mut pending.answer).await {
+            Ok(Ok(true)) => return self.inner.execute(call).await,
+            Ok(_) => format!("The user declined to run {}", call.name),
+            Err(_) => format!("Nobody approved {} in time, so it was not run", call.name),
+        };
+        ToolResult {
+            tool_call_id: call.id.clone(),
+            result: serde_json::Value::Null,
+            error: Some(error),
+        }
+    }
+}
+
+/// Tool calls from one turn, in the order the model emitted them
+fn turn_tool_calls(response: &ChatResponse) -> Vec<ToolCall> {
+    response
+        .tool_calls
+        .clone()
+        .or_else(|| {
+            response
+                .message
+                .as_ref()
+                .and_then(|message| message.tool_calls.clone())
+        })
+        .unwrap_or_default()
+}
+
+/// Run a turn's tool calls concurrently (at most `max_parallel` at once). Results come back
+/// in call order, each carrying the id of the call it answers.
+pub async fn execute_tool_calls(
+    executor: &dyn ToolExecutor,
+    calls: &[ToolCall],
+    max_parallel: usize,
+) -> Vec<ToolResult> {
+    // Owned calls keep the future Send; borrowed ones trip up higher-ranked lifetime checks
+    stream::iter(calls.iter().cloned())
+        .map(|call| async move {
+            let mut result = executor.execute(&call).await;
+            result.tool_call_id = call.id;
+            result
+        })
+        .buffered(max_parallel.max(1))
+        .collect()
+        .await
+}
+
+/// The tool-response message appended to the conversation for one call
+pub fn tool_response_message(result: &ToolResult) -> ChatMessage {
+    let content = match (&result.error, &result.result) {
+        (Some(error), _) => format!("Error: {}", error),
+        (None, serde_json::Value::String(text)) => text.clone(),
+        (None, value) => value.to_string(),
+    };
+
+    ChatMessage {
+        role: Role::Tool,
+        content,
+        timestamp: Some(Utc::now()),
+        tool_calls: None,
+        tool_results: Some(vec![result.clone()]),
+    }
+}
+
+/// Ask the model for turns until one comes back without tool calls. After a turn with tool
+/// calls, the assistant message and one tool response per call (in call order) are appended
+/// before the model is asked again. Returns the final response and the full conversation.
+pub async fn run_tool_loop<F, Fut>(
+    messages: Vec<ChatMessage>,
+    max_iterations: usize,
+    executor: &dyn ToolExecutor,
+    next_turn: F,
+) -> Result<(ChatResponse, Vec<ChatMessage>)>
+where
+    F: FnMut(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<ChatResponse>>,
+{
+    run_tool_loop_traced(
+        messages,
+        max_iterations,
+        ContentFilterPolicy::default(),
+        executor,
+        &|_, _| {},
+        next_turn,
+    )
+    .await
+}
+
+/// `run_tool_loop` that reports each step (turn start, tool selection, each call and result,
+/// finish) to `trace` as it happens. A turn stopped by the provider's content filter ends the
+/// loop under `ContentFilterPolicy::Stop`, or is flagged and carried on from under `Continue`.
+pub async fn run_tool_loop_traced<F, Fut>(
+    mut messages: Vec<ChatMessage>,
+    max_iterations: usize,
+    on_content_filter: ContentFilterPolicy,
+    executor: &dyn ToolExecutor,
+    trace: &(dyn Fn(TraceKind, String) + Sync),
+    mut next_turn: F,
+) -> Result<(ChatResponse, Vec<ChatMessage>)>
+where
+    F: FnMut(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<ChatResponse>>,
+{
+    let mut filtered_warning = None;
+    for iteration in 1..=max_iterations.max(1) {
+        trace(TraceKind::TurnStart, format!("Turn {}", iteration));
+        let mut response = next_turn(messages.clone()).await?;
+
+        if is_content_filter(response.finish_reason.as_deref()) {
+            let message = format!(
+                "Turn {} was stopped by the model's content filter",
+                iteration
+            );
+            if on_content_filter == ContentFilterPolicy::Stop {
+                trace(TraceKind::Finish, message.clone());
+                response.notification = Some(SystemNotification {
+                    notification_type: SystemNotificationType::ErrorMessage,
+                    message: format!("{}, so the agent stopped instead of retrying.", message),
+                    code: None,
+                });
+                if let Some(message) = response.message.clone() {
+                    messages.push(message);
+                }
+                return Ok((response, messages));
+            }
+            filtered_warning = Some(SystemNotification {
+                notification_type: SystemNotificationType::Warning,
+                message: format!("{}.", message),
+                code: None,
+            });
+        }
+
+        let calls = turn_tool_calls(&response);
+        if calls.is_empty() {
+            trace(
+                TraceKind::Finish,
+                format!(
+                    "Answered after {} turn(s), finish reason: {}",
+                    iteration,
+                    response.finish_reason.as_deref().unwrap_or("none")
+                ),
+            );
+            if response.notification.is_none() {
+                response.notification = filtered_warning;
+            }
+            if let Some(message) = response.message.clone() {
+                messages.push(message);
+            }
+            return Ok((response, messages));
+        }
+
+        let names: Vec<&str> = calls.iter().map(|call| call.name.as_str()).collect();
+        trace(TraceKind::ToolSelection, format!("Selected {}", names.join(", ")));
+
+        let mut assistant = response.message.clone().unwrap_or(ChatMessage {
+            role: Role::Assistant,
+            content: String::new(),
+            timestamp: Some(Utc::now()),
+            tool_calls: None,
+            tool_results: None,
+        });
+        assistant.tool_calls = Some(calls.clone());
+        messages.push(assistant);
+
+        for call in &calls {
+            trace(
+                TraceKind::ToolCall,
+                format!("{} ({}) {}", call.name, call.id, call.arguments),
+            );
+        }
+        let results = execute_tool_calls(executor, &calls, MAX_PARALLEL_TOOL_CALLS).await;
+        for result in &results {
+            let outcome = match result.error {
+                Some(ref error) => format!("failed: {}", error),
+                None => "ok".to_string(),
+            };
+            trace(
+                TraceKind::ToolResult,
+                format!("{} {}", result.tool_call_id, outcome),
+            );
+        }
+        messages.extend(results.iter().map(tool_response_message));
+    }
+
+    trace(
+        TraceKind::Finish,
+        format!("Stopped after {} turn(s) without a final answer", max_iterations.max(1)),
+    );
+    Err(anyhow::anyhow!(
+        "Agent stopped after {} iterations without a final answer",
+        max_iterations.max(1)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::TraceLog;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Finishes calls in reverse order, so results must be reordered to match the calls
+    struct SlowFirstExecutor;
+
+    #[async_trait]
+    impl ToolExecutor for SlowFirstExecutor {
+        async fn execute(&self, call: &ToolCall) -> ToolResult {
+            let delay = if call.id == "call-1" { 50 } else { 0 };
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::String(format!("{} done", call.name)),
+                error: None,
+            }
+        }
+    }
+
+    fn response(content: &str, tool_calls: Option<Vec<ToolCall>>) -> ChatResponse {
+        ChatResponse {
+            message: Some(ChatMessage {
+                role: Role::Assistant,
+                content: content.to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }),
+            tool_calls,
+            token_usage: None,
+            model: "mock".to_string(),
+            finish_reason: None,
+            is_streaming: false,
+            reasoning_content: None,
+            thinking_content: None,
+            notification: None,
+        }
+    }
+
+    fn call(id: &str, name: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments: serde_json::json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_all_tool_results_are_appended_before_follow_up() -> Result<()> {
+        let seen = Mutex::new(Vec::new());
+        let user = ChatMessage {
+            role: Role::User,
+            content: "Check both".to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        };
+
+        let (final_response, messages) =
+            run_tool_loop(vec![user], 5, &SlowFirstExecutor, |messages| {
+                let turn = {
+                    let mut seen = seen.lock().unwrap();
+                    seen.push(messages);
+                    seen.len()
+                };
+                async move {
+                    Ok(if turn == 1 {
+                        response(
+                            "",
+                            Some(vec![call("call-1", "weather"), call("call-2", "time")]),
+                        )
+                    } else {
+                        response("All done", None)
+                    })
+                }
+            })
+            .await?;
+
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), 2);
+
+        // The follow-up turn saw the assistant turn plus one response per call, in call order
+        let follow_up = &seen[1];
+        assert_eq!(follow_up.len(), 4);
+        assert_eq!(follow_up[1].tool_calls.as_ref().unwrap().len(), 2);
+        for (message, id) in follow_up[2..].iter().zip(["call-1", "call-2"]) {
+            assert_eq!(message.role, Role::Tool);
+            assert_eq!(message.tool_results.as_ref().unwrap()[0].tool_call_id, id);
+        }
+        assert_eq!(follow_up[2].content, "weather done");
+
+        assert_eq!(final_response.message.unwrap().content, "All done");
+        assert_eq!(messages.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tool_turn_is_traced_in_order() -> Result<()> {
+        let log = TraceLog::default();
+        log.set_enabled(true);
+        let turns = Mutex::new(0);
+
+        run_tool_loop_traced(
+            vec![],
+            5,
+            ContentFilterPolicy::Stop,
+            &SlowFirstExecutor,
+            &|kind, detail| log.record("session-1", kind, detail),
+            |_| {
+                let turn = {
+                    let mut turns = turns.lock().unwrap();
+                    *turns += 1;
+                    *turns
+                };
+                async move {
+                    Ok(if turn == 1 {
+                        response(
+                            "",
+                            Some(vec![call("call-1", "weather"), call("call-2", "time")]),
+                        )
+                    } else {
+                        response("All done", None)
+                    })
+                }
+            },
+        )
+        .await?;
+
+        let kinds: Vec<TraceKind> = log.entries("session-1").iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TraceKind::TurnStart,
+                TraceKind::ToolSelection,
+                TraceKind::ToolCall,
+                TraceKind::ToolCall,
+                TraceKind::ToolResult,
+                TraceKind::ToolResult,
+                TraceKind::TurnStart,
+                TraceKind::Finish,
+            ]
+        );
+        let entries = log.entries("session-1");
+        assert_eq!(entries[1].detail, "Selected weather, time");
+        assert!(entries[4].detail.starts_with("call-1"));
+        assert!(log.entries("session-2").is_empty());
+        Ok(())
+    }
+
+    async fn answer_next(
+        requests: &mut broadcast::Receiver<SessionEvent>,
+        confirmations: &ToolConfirmations,
+        approved: bool,
+    ) -> Result<()> {
+        let SessionEvent::ToolConfirmationRequest { tool_call, .. } = requests.recv().await? else {
+            anyhow::bail!("expected a confirmation request");
+        };
+        assert!(confirmations.answer("session-1", &tool_call.id, approved));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_wait_for_confirmation() -> Result<()> {
+        let confirmations = ToolConfirmations::default();
+        let (events, mut requests) = broadcast::channel(8);
+        let readonly_tools = vec!["time".to_string()];
+        let executor = ConfirmingToolExecutor {
+            session_id: "session-1".to_string(),
+            readonly_tools: &readonly_tools,
+            confirmations: &confirmations,
+            events: &events,
+            timeout: TOOL_CONFIRMATION_TIMEOUT,
+            inner: &SlowFirstExecutor,
+        };
+
+        // Read-only tools run without asking
+        let result = executor.execute(&call("call-1", "time")).await;
+        assert_eq!(result.result, "time done");
+        assert!(requests.try_recv().is_err());
+
+        let shell = call("call-2", "shell");
+        let (declined, answered) = tokio::join!(
+            executor.execute(&shell),
+            answer_next(&mut requests, &confirmations, false)
+        );
+        answered?;
+        assert_eq!(
+            tool_response_message(&declined).content,
+            "Error: The user declined to run shell"
+        );
+
+        let shell = call("call-3", "shell");
+        let (approved, answered) = tokio::join!(
+            executor.execute(&shell),
+            answer_next(&mut requests, &confirmations, true)
+        );
+        answered?;
+        assert_eq!(approved.result, "shell done");
+        assert!(!confirmations.answer("session-1", "call-3", true));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_confirmations_are_declined() -> Result<()> {
+        let confirmations = ToolConfirmations::default();
+        let (events, mut requests) = broadcast::channel(8);
+        let executor = ConfirmingToolExecutor {
+            session_id: "session-1".to_string(),
+            readonly_tools: &[],
+            confirmations: &confirmations,
+            events: &events,
+            timeout: Duration::from_millis(50),
+            inner: &SlowFirstExecutor,
+        };
+
+        let timed_out = executor.execute(&call("call-1", "shell")).await;
+        assert!(timed_out.error.unwrap().contains("in time"));
+        assert!(!confirmations.answer("session-1", "call-1", true));
+        requests.recv().await?;
+
+        // Clearing the session declines what it is waiting on
+        let shell = call("call-2", "shell");
+        let (cleared, received) = tokio::join!(executor.execute(&shell), async {
+            requests.recv().await?;
+            confirmations.clear_session("session-1");
+            anyhow::Ok(())
+        });
+        received?;
+        assert_eq!(
+            cleared.error.as_deref(),
+            Some("The user declined to run shell")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_stops_the_loop_without_retrying() -> Result<()> {
+        let turns = Mutex::new(0);
+
+        let (final_response, _) = run_tool_loop_traced(
+            vec![],
+            10,
+            ContentFilterPolicy::Stop,
+            &SlowFirstExecutor,
+            &|_, _| {},
+            |_| {
+                *turns.lock().unwrap() += 1;
+                async move {
+                    // Filtered mid-way through asking for a tool
+                    let mut filtered = response("", Some(vec![call("call-1", "weather")]));
+                    filtered.finish_reason = Some("content_filter".to_string());
+                    Ok(filtered)
+                }
+            },
+        )
+        .await?;
+
+        assert_eq!(*turns.lock().unwrap(), 1);
+        let notification = final_response.notification.expect("stop is reported");
+        assert_eq!(
+            notification.notification_type,
+            SystemNotificationType::ErrorMessage
+        );
+        Ok(())
+    }
+}
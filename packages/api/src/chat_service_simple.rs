@@ -4,8 +4,55 @@ use chrono::{DateTime, Utc};
 use futures::stream::{self, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+use crate::agent_extensions::{
+    AgentExtension, ExtensionContext, ExtensionManager, ExtensionPhase, ExtensionToolExecutor,
+};
+use crate::agent_loop::{
+    run_tool_loop_traced, ConfirmingToolExecutor, ToolConfirmations, ToolExecutor,
+    TOOL_CONFIRMATION_TIMEOUT,
+};
+use crate::db::{self, DbPool};
+use crate::embeddings::{cosine_similarity, EmbeddingService, MockEmbeddingService};
+use crate::export::{self, ExportFormat, SessionExport};
+use crate::file_processing::{
+    default_data_dir, default_files_dir, process_image, FileJobs, FileProcessingResult, FileStatus,
+    ImageMetadata,
+};
+use crate::memory::{MemoryEntry, MemoryStore, SessionMemory, MAX_RECALLED_MEMORIES};
+use crate::moderation::{Moderation, ModerationDirection, ModerationVerdict};
+use crate::multimodal::{
+    MultimodalChatRequest, MultimodalService, SpeechToTextTool, TranscriptionProvider,
+    VisionProvider,
+};
+use crate::providers::{
+    api_key_env, complete_in_time, configured_providers, provider_timeouts, stream_in_time,
+    timeout_error, with_retry, CompletionProvider, OllamaProvider, ProviderTimeouts, RateLimiter,
+    RetryConfig,
+};
+use crate::rag_system::{
+    DocumentMetadata, InMemoryVectorStore, KnowledgeBaseExecutor, RAGSystem, RAGTool, SearchResult,
+    RAG_TOOL_NAME,
+};
+use crate::reasoning::{
+    keyword_plan, parse_plan, ExecutionPlan, ReasoningStep, ReasoningStepType, ReasoningStore,
+    PLANNING_PROMPT,
+};
+use crate::response_cache::ResponseCache;
+use crate::rig_agent_service::{provider_display_name, MOCK_FALLBACK_ENV};
+use crate::session_store::{
+    title_snippet, MessageCursor, MessagePage, MessageSearchHit, SessionPreview, SessionStore,
+    StoredMessage, StoredSession,
+};
+use crate::streaming_service::AgentEvent;
+use crate::tokenizer::{estimate_token_usage, tokenizer_for};
+use crate::trace::{TraceEntry, TraceKind, TraceLog};
+use crate::usage::{estimate_cost, UsageLedger, UsageRange, UsageRecord, UsageSummary};
 
 // Define essential types here to avoid importing from the complex chat_service module
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -43,17 +90,97 @@ pub enum MessageContent {
         url: String,
         description: Option<String>,
     },
+    /// An uploaded file, by the id `upload_file` returned
+    #[serde(rename = "file")]
+    File {
+        file_id: String,
+        name: String,
+        mime_type: String,
+    },
+    /// Recorded speech as base64 (or a data URL); models read its transcript, added after it
+    #[serde(rename = "audio")]
+    Audio {
+        name: String,
+        content: String,
+        mime_type: String,
+        /// Length in seconds, when known
+        #[serde(default)]
+        duration: Option<f64>,
+        /// Size in bytes, when known
+        #[serde(default)]
+        size: Option<u64>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Message {
     pub id: String,
     pub role: Role,
-    pub content: MessageContent,
+    pub content: Vec<MessageContent>,
     pub timestamp: Option<DateTime<Utc>>,
     pub metadata: Option<MessageMetadata>,
 }
 
+impl Message {
+    /// Plain text of the message for display and token estimation. Lossy: non-text parts are
+    /// dropped, so persistence must store `content` itself.
+    pub fn as_concat_text(&self) -> String {
+        concat_text(&self.content)
+    }
+}
+
+/// Structured content of a chat message for storage: its reasoning, text, tool requests and
+/// tool responses. Tool responses carry the call id as their name, since results don't
+/// repeat the tool's name.
+pub(crate) fn message_parts(message: &ChatMessage, reasoning: Option<&str>) -> Vec<MessageContent> {
+    let mut parts: Vec<MessageContent> = reasoning
+        .map(|content| MessageContent::Reasoning {
+            content: content.to_string(),
+        })
+        .into_iter()
+        .collect();
+    if !message.content.is_empty()
+        || (message.tool_calls.is_none() && message.tool_results.is_none())
+    {
+        parts.push(MessageContent::Text {
+            text: message.content.clone(),
+        });
+    }
+    parts.extend(
+        message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| MessageContent::ToolRequest {
+                id: call.id.clone(),
+                name: call.name.clone(),
+                arguments: call.arguments.clone(),
+            }),
+    );
+    parts.extend(message.tool_results.iter().flatten().map(|result| {
+        MessageContent::ToolResponse {
+            id: result.tool_call_id.clone(),
+            name: result.tool_call_id.clone(),
+            result: match &result.error {
+                Some(error) => serde_json::json!({ "error": error }),
+                None => result.result.clone(),
+            },
+        }
+    }));
+    parts
+}
+
+pub(crate) fn concat_text(parts: &[MessageContent]) -> String {
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            MessageContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MessageMetadata {
     pub model: Option<String>,
@@ -65,7 +192,10 @@ pub struct MessageMetadata {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
+    /// Registry key; may be a friendly alias such as `gpt-4-turbo`
     pub id: String,
+    /// Real model id sent to the provider API
+    pub model: String,
     pub name: String,
     pub provider: String,
     pub description: Option<String>,
@@ -75,15 +205,95 @@ pub struct ModelConfig {
     pub supports_vision: bool,
     pub supports_function_calling: bool,
     pub pricing: Option<ModelPricing>,
+    /// Sampling defaults for this model; a request's own values take precedence
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Whether a model streams its reasoning before answering, as DeepSeek R1 and the reasoner
+/// models do
+pub fn is_reasoning_model(model: &str) -> bool {
+    let model = model.to_lowercase();
+    model.contains("r1") || model.contains("reasoning") || model.contains("reasoner")
+}
+
+/// Broad category of a provider failure, used to decide how to report or retry it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProviderErrorKind {
+    /// Connect, overall or stream inactivity timeout
+    Timeout,
+    Network,
+    Authentication,
+    RateLimited,
+    InvalidRequest,
+    Server,
+    #[default]
+    Other,
+}
+
+/// `ProviderError::code` for a provider that has no API key configured
+pub const NO_CREDENTIALS: &str = "no_credentials";
+
+/// `ProviderError::code` for a request or stream that ran past its timeout
+pub const TIMED_OUT: &str = "timeout";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProviderError {
+    #[serde(default)]
+    pub kind: ProviderErrorKind,
     pub message: String,
     pub code: Option<String>,
     pub retry_after: Option<u64>,
 }
 
+impl ProviderError {
+    pub fn new(kind: ProviderErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            code: None,
+            retry_after: None,
+        }
+    }
+
+    /// The provider needs an API key and `key_var` is not set, tagged `NO_CREDENTIALS` so the
+    /// UI can show its own guidance instead of the raw error
+    pub fn no_credentials(provider: &str, key_var: &str) -> Self {
+        Self {
+            code: Some(NO_CREDENTIALS.to_string()),
+            ..Self::new(
+                ProviderErrorKind::Authentication,
+                format!("{} has no API key; set {} and restart", provider, key_var),
+            )
+        }
+    }
+
+    pub fn is_no_credentials(&self) -> bool {
+        self.code.as_deref() == Some(NO_CREDENTIALS)
+    }
+
+    /// Worth trying again: timeouts, rate limits and server errors by HTTP status, or network
+    /// failures that never got a status. Authentication and other client errors are not.
+    pub fn is_retryable(&self) -> bool {
+        match self
+            .code
+            .as_deref()
+            .and_then(|code| code.parse::<u16>().ok())
+        {
+            Some(status) => matches!(status, 408 | 429 | 500..=599),
+            None => matches!(
+                self.kind,
+                ProviderErrorKind::Timeout
+                    | ProviderErrorKind::Network
+                    | ProviderErrorKind::RateLimited
+                    | ProviderErrorKind::Server
+            ),
+        }
+    }
+}
+
 impl std::fmt::Display for ProviderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.message)
@@ -92,12 +302,30 @@ impl std::fmt::Display for ProviderError {
 
 impl std::error::Error for ProviderError {}
 
+/// What a tool touches, so tools can be grouped in the UI and agents scoped to some of them
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCategory {
+    /// Runs commands or inspects the host
+    System,
+    /// Reads or writes files
+    Filesystem,
+    /// Reaches out over the network
+    Network,
+    /// Only reads; no side effects
+    ReadOnly,
+    #[default]
+    General,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
     pub name: String,
     pub description: String,
     pub input_schema: serde_json::Value,
     pub is_mcp: bool,
+    #[serde(default)]
+    pub category: ToolCategory,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -107,7 +335,7 @@ pub struct ToolCall {
     pub arguments: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ToolResult {
     pub tool_call_id: String,
     pub result: serde_json::Value,
@@ -115,7 +343,12 @@ pub struct ToolResult {
 }
 
 // Agent configuration types (for UI to pass as parameters)
+/// Read-only programs the shell tool may run once it is enabled
+pub const DEFAULT_SHELL_ALLOWLIST: &[&str] =
+    &["ls", "pwd", "cat", "head", "tail", "wc", "grep", "echo"];
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct AgentConfig {
     pub max_iterations: usize,
     pub require_confirmation: bool,
@@ -128,6 +361,34 @@ pub struct AgentConfig {
     pub enable_extensions: bool,
     pub extension_timeout: u64,
     pub goose_mode: GooseMode,
+    /// Refuse further sends once a session's tokens would pass this
+    pub max_session_tokens: Option<u64>,
+    /// Refuse further sends once a session's estimated cost would pass this
+    pub max_session_cost: Option<f64>,
+    /// What the agent loop does when the provider stops a turn with its content filter
+    pub on_content_filter: ContentFilterPolicy,
+    /// Send only this many of the most recent messages (system messages always go). Older
+    /// messages stay stored and visible; this is separate from token-based compaction.
+    pub history_window: Option<usize>,
+    /// Most recent messages auto-compaction keeps verbatim; older ones are summarized
+    pub messages_to_keep: usize,
+    /// Let the built-in `shell` tool run commands at all. Off by default.
+    pub shell_tool_enabled: bool,
+    /// Programs a shell command may start with; `*` allows any command, operators included
+    pub shell_allowlist: Vec<String>,
+    /// Seconds a shell command may run before it is killed
+    pub shell_timeout: u64,
+}
+
+/// Retrying a filtered turn just trips the filter again, so by default the loop stops there
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFilterPolicy {
+    /// End the agent loop with an error notification, whatever `max_iterations` allows
+    #[default]
+    Stop,
+    /// Warn about the filtered turn and keep running its tool calls
+    Continue,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -151,7 +412,67 @@ impl Default for AgentConfig {
             enable_extensions: true,
             extension_timeout: 30,
             goose_mode: GooseMode::Agent,
+            max_session_tokens: None,
+            max_session_cost: None,
+            on_content_filter: ContentFilterPolicy::Stop,
+            history_window: None,
+            messages_to_keep: 10,
+            shell_tool_enabled: false,
+            shell_allowlist: DEFAULT_SHELL_ALLOWLIST
+                .iter()
+                .map(|program| program.to_string())
+                .collect(),
+            shell_timeout: 30,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// `~/.dioxus-chat/agent.toml`, where teams can override the built-in defaults
+    pub fn default_path() -> Option<std::path::PathBuf> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(std::path::Path::new(&home).join(".dioxus-chat").join("agent.toml"))
+    }
+
+    /// Load and validate a config file; fields it leaves out keep their defaults
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let config: AgentConfig = toml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid agent config {}: {}", path.display(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.max_iterations == 0 {
+            return Err(anyhow::anyhow!("max_iterations must be at least 1"));
+        }
+        if self.max_turns_without_tools == 0 {
+            return Err(anyhow::anyhow!("max_turns_without_tools must be at least 1"));
+        }
+        if !(self.compact_threshold > 0.0 && self.compact_threshold <= 1.0) {
+            return Err(anyhow::anyhow!(
+                "compact_threshold must be between 0 and 1, got {}",
+                self.compact_threshold
+            ));
         }
+        if self.extension_timeout == 0 {
+            return Err(anyhow::anyhow!("extension_timeout must be at least 1 second"));
+        }
+        if self.shell_timeout == 0 {
+            return Err(anyhow::anyhow!("shell_timeout must be at least 1 second"));
+        }
+        if self.history_window == Some(0) {
+            return Err(anyhow::anyhow!("history_window must be at least 1 message"));
+        }
+        if self.messages_to_keep == 0 {
+            return Err(anyhow::anyhow!("messages_to_keep must be at least 1"));
+        }
+        if self.max_session_cost.is_some_and(|cost| cost < 0.0) {
+            return Err(anyhow::anyhow!("max_session_cost can't be negative"));
+        }
+        Ok(())
     }
 }
 
@@ -193,10 +514,380 @@ pub struct ChatResponse {
     pub tool_calls: Option<Vec<ToolCall>>,
     pub token_usage: Option<TokenUsage>,
     pub model: String,
+    /// The provider's reason verbatim ("stop", "length", "tool_calls", ...); `None` when it
+    /// didn't give one
     pub finish_reason: Option<String>,
     pub is_streaming: bool,
     pub reasoning_content: Option<String>,
     pub thinking_content: Option<String>,
+    /// Set instead of `message` when there is nothing to show from the model
+    pub notification: Option<SystemNotification>,
+}
+
+impl ChatResponse {
+    /// The reply was cut off by the token limit
+    pub fn is_truncated(&self) -> bool {
+        is_truncated(self.finish_reason.as_deref())
+    }
+}
+
+/// One model's column in a side-by-side comparison
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelComparison {
+    pub model: String,
+    pub response: Option<ChatResponse>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub estimated_cost: Option<f64>,
+}
+
+/// Result of a lightweight reachability probe against a model's provider
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderHealth {
+    pub model: String,
+    pub reachable: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SystemNotificationType {
+    Info,
+    Warning,
+    ErrorMessage,
+}
+
+/// A message for the user that is shown in the chat but never persisted as an assistant reply
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SystemNotification {
+    pub notification_type: SystemNotificationType,
+    pub message: String,
+    /// The failed request's `ProviderError::code`, for the UI to act on (e.g. `NO_CREDENTIALS`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl SystemNotification {
+    pub fn is_no_credentials(&self) -> bool {
+        self.code.as_deref() == Some(NO_CREDENTIALS)
+    }
+}
+
+impl From<&ProviderError> for SystemNotification {
+    fn from(error: &ProviderError) -> Self {
+        Self {
+            notification_type: SystemNotificationType::ErrorMessage,
+            message: error.message.clone(),
+            code: error.code.clone(),
+        }
+    }
+}
+
+/// Raw output of a single provider completion
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Completion {
+    content: String,
+    thinking: Option<String>,
+    finish_reason: Option<String>,
+}
+
+impl Completion {
+    fn is_empty(&self) -> bool {
+        self.content.trim().is_empty()
+    }
+}
+
+/// Whether a finish reason means the reply hit the token limit
+pub fn is_truncated(finish_reason: Option<&str>) -> bool {
+    finish_reason == Some("length")
+}
+
+pub(crate) fn is_content_filter(finish_reason: Option<&str>) -> bool {
+    matches!(
+        finish_reason,
+        Some("content_filter") | Some("safety") | Some("blocked")
+    )
+}
+
+/// Run a completion, retrying once if it comes back empty with an ordinary stop. An empty
+/// result is returned together with a notification explaining why there is no reply;
+/// content-filter stops are reported straight away since retrying would be filtered again.
+fn complete_with_retry(
+    mut complete: impl FnMut() -> Completion,
+) -> (Completion, Option<SystemNotification>) {
+    let mut completion = complete();
+    if completion.is_empty() && !is_content_filter(completion.finish_reason.as_deref()) {
+        tracing::warn!(
+            "Model returned no content (finish_reason: {:?}), retrying once",
+            completion.finish_reason
+        );
+        completion = complete();
+    }
+
+    if !completion.is_empty() {
+        return (completion, None);
+    }
+
+    let message = if is_content_filter(completion.finish_reason.as_deref()) {
+        "The model returned no content because the response was blocked by its content filter."
+            .to_string()
+    } else {
+        format!(
+            "The model returned no content ({}). Try rephrasing your message or switching models.",
+            completion.finish_reason.as_deref().unwrap_or("stop")
+        )
+    };
+
+    (
+        completion,
+        Some(SystemNotification {
+            notification_type: SystemNotificationType::ErrorMessage,
+            message,
+            code: None,
+        }),
+    )
+}
+
+/// Instructions sent ahead of the transcript when auto-compaction summarizes old messages
+const SUMMARY_PROMPT: &str = "Summarize the conversation below so the summary can replace it. \
+Keep every fact, decision, open question and user preference. Keep tool results that later \
+messages may rely on, together with the tool call they answer.";
+
+/// Instructions for naming a session after its first message
+const TITLE_PROMPT: &str = "Write a title of at most six words for a conversation that starts \
+with the message below. Reply with the title only, without quotes or punctuation at the end.";
+
+/// Dropped messages as plain text for the summarizer, tool calls and results included
+fn summary_transcript(messages: &[ChatMessage]) -> String {
+    let mut transcript = String::new();
+    for msg in messages {
+        transcript.push_str(&format!("{:?}: {}\n", msg.role, msg.content));
+        for call in msg.tool_calls.iter().flatten() {
+            transcript.push_str(&format!(
+                "Tool call {} {}({})\n",
+                call.id, call.name, call.arguments
+            ));
+        }
+        for result in msg.tool_results.iter().flatten() {
+            match &result.error {
+                Some(error) => transcript.push_str(&format!(
+                    "Tool result {} failed: {}\n",
+                    result.tool_call_id, error
+                )),
+                None => transcript.push_str(&format!(
+                    "Tool result {}: {}\n",
+                    result.tool_call_id, result.result
+                )),
+            }
+        }
+    }
+    transcript
+}
+
+/// Replace every non-system message before the `keep` most recent ones (at least one) with a
+/// single system message. `summarize` gets the replaced messages; when it fails or comes back
+/// empty, a note saying how many were dropped stands in. Returns how many were replaced.
+pub(crate) async fn compact_messages<F>(
+    messages: &mut Vec<ChatMessage>,
+    keep: usize,
+    summarize: impl FnOnce(Vec<ChatMessage>) -> F,
+) -> usize
+where
+    F: std::future::Future<Output = Option<String>>,
+{
+    let conversation: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, msg)| !matches!(msg.role, Role::System))
+        .map(|(index, _)| index)
+        .collect();
+    let compacted = conversation.len().saturating_sub(keep.max(1));
+    if compacted == 0 {
+        return 0;
+    }
+
+    let kept = messages.split_off(conversation[compacted]);
+    let (system, dropped): (Vec<ChatMessage>, Vec<ChatMessage>) = messages
+        .drain(..)
+        .partition(|msg| matches!(msg.role, Role::System));
+    let summary = summarize(dropped).await;
+    let content = match summary.filter(|summary| !summary.trim().is_empty()) {
+        Some(summary) => format!(
+            "Summary of {} earlier messages:\n{}",
+            compacted,
+            summary.trim()
+        ),
+        None => format!(
+            "{} earlier messages were dropped to fit the context window.",
+            compacted
+        ),
+    };
+
+    messages.extend(system);
+    messages.push(ChatMessage {
+        role: Role::System,
+        content,
+        timestamp: None,
+        tool_calls: None,
+        tool_results: None,
+    });
+    messages.extend(kept);
+    compacted
+}
+
+/// The conversation as the provider receives it: the system prompt, every system message,
+/// and only the `history_window` most recent other messages when the agent config sets one
+pub(crate) fn provider_messages(request: &ChatRequest) -> Vec<ChatMessage> {
+    let window = request
+        .agent_config
+        .as_ref()
+        .and_then(|config| config.history_window);
+    let conversation = request
+        .messages
+        .iter()
+        .filter(|msg| !matches!(msg.role, Role::System))
+        .count();
+    let mut skip = window.map_or(0, |window| conversation.saturating_sub(window));
+
+    let mut messages: Vec<ChatMessage> = request
+        .system_prompt
+        .iter()
+        .map(|prompt| ChatMessage {
+            role: Role::System,
+            content: prompt.clone(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        })
+        .collect();
+    for msg in &request.messages {
+        if skip > 0 && !matches!(msg.role, Role::System) {
+            skip -= 1;
+            continue;
+        }
+        messages.push(msg.clone());
+    }
+    messages
+}
+
+/// A response for a turn that was refused before any assistant message was produced
+fn refused_response(model: &str, message: String) -> ChatResponse {
+    ChatResponse {
+        message: None,
+        tool_calls: None,
+        token_usage: None,
+        model: model.to_string(),
+        finish_reason: None,
+        is_streaming: false,
+        reasoning_content: None,
+        thinking_content: None,
+        notification: Some(SystemNotification {
+            notification_type: SystemNotificationType::ErrorMessage,
+            message,
+            code: None,
+        }),
+    }
+}
+
+/// A response with `error` as its notification, code included, in place of a reply
+pub fn error_response(model: &str, error: &ProviderError) -> ChatResponse {
+    ChatResponse {
+        notification: Some(error.into()),
+        ..refused_response(model, String::new())
+    }
+}
+
+/// What `agent_reply` returns when the session's reply was cancelled: no message to store
+fn cancelled_response(model: &str) -> ChatResponse {
+    ChatResponse {
+        message: None,
+        tool_calls: None,
+        token_usage: None,
+        model: model.to_string(),
+        finish_reason: Some("cancelled".to_string()),
+        is_streaming: false,
+        reasoning_content: None,
+        thinking_content: None,
+        notification: Some(SystemNotification {
+            notification_type: SystemNotificationType::Info,
+            message: "Reply cancelled".to_string(),
+            code: None,
+        }),
+    }
+}
+
+/// Configuration changes within a session, published so open transcripts can mark them
+/// inline, and tool calls waiting on the user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SessionEvent {
+    ModelChange {
+        session_id: String,
+        model: String,
+        mode: GooseMode,
+    },
+    /// The agent is paused until `confirm_tool` approves or declines this call
+    ToolConfirmationRequest {
+        session_id: String,
+        tool_call: ToolCall,
+    },
+}
+
+/// Words that ask for something to be done rather than talked about
+const ACTION_WORDS: &[&str] = &[
+    "run", "execute", "search", "find", "look", "fetch", "download", "open", "read", "write",
+    "edit", "create", "delete", "list", "check", "compute", "analyze", "install", "build", "fix",
+    "file", "files", "folder", "command",
+];
+
+/// Messages up to this many words with no action in them are treated as conversation
+const CONVERSATIONAL_WORDS: usize = 12;
+
+/// The mode a `GooseMode::Auto` turn runs in. Without tools there is nothing to act with, so
+/// it chats; otherwise it chats on short messages that neither ask for an action nor name a
+/// tool, and works as an agent on everything else.
+pub fn auto_mode(request: &ChatRequest) -> GooseMode {
+    let tools = request.tools.as_deref().unwrap_or_default();
+    if tools.is_empty() {
+        return GooseMode::Chat;
+    }
+    let message = request
+        .messages
+        .iter()
+        .rev()
+        .find(|msg| matches!(msg.role, Role::User))
+        .map(|msg| msg.content.to_lowercase())
+        .unwrap_or_default();
+
+    let words: Vec<&str> = message
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let asks_for_action = words.iter().any(|word| ACTION_WORDS.contains(word));
+    let names_a_tool = tools.iter().any(|tool| {
+        let name = tool.name.to_lowercase();
+        message.contains(&name) || message.contains(&name.replace('_', " "))
+    });
+
+    if asks_for_action || names_a_tool || words.len() > CONVERSATIONAL_WORDS {
+        GooseMode::Agent
+    } else {
+        GooseMode::Chat
+    }
+}
+
+/// Transcript separator for a model or mode change, e.g. "Switched to gpt-4, Agent mode"
+pub fn model_change_notice(model: &str, mode: &GooseMode) -> String {
+    format!("Switched to {}, {:?} mode", model, mode)
+}
+
+/// Whether `message` is a `model_change_notice` separator. They mark the transcript for the
+/// reader and are kept out of the history sent to the model.
+fn is_model_change_notice(message: &ChatMessage) -> bool {
+    matches!(message.role, Role::System)
+        && message.content.starts_with("Switched to ")
+        && [GooseMode::Chat, GooseMode::Agent, GooseMode::Auto]
+            .iter()
+            .any(|mode| message.content.ends_with(&format!(", {:?} mode", mode)))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -205,8 +896,42 @@ pub struct StreamChunk {
     pub delta: Option<String>,
     pub token_usage: Option<TokenUsage>,
     pub model: String,
+    /// Set on the terminal chunk, verbatim from the provider
     pub finish_reason: Option<String>,
     pub is_complete: bool,
+    /// Reasoning the model streams before its answer; never part of `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
+    /// Tool calls the model asked for, whole, on the chunk that finishes them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+impl StreamChunk {
+    /// The reply was cut off by the token limit
+    pub fn is_truncated(&self) -> bool {
+        is_truncated(self.finish_reason.as_deref())
+    }
+
+    /// The terminal chunk of a stream the provider failed partway through. Timeouts finish
+    /// with `TIMED_OUT`, anything else with "error".
+    pub fn failed(model: &str, error: &ProviderError) -> Self {
+        let finish_reason = if error.kind == ProviderErrorKind::Timeout {
+            TIMED_OUT
+        } else {
+            "error"
+        };
+        Self {
+            content: Some(format!("Error: {}", error.message)),
+            delta: None,
+            token_usage: None,
+            model: model.to_string(),
+            finish_reason: Some(finish_reason.to_string()),
+            is_complete: true,
+            thinking: None,
+            tool_calls: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -216,20 +941,165 @@ pub struct TokenUsage {
     pub total_tokens: u32,
 }
 
+impl TokenUsage {
+    /// Cost of this usage at `pricing`, which is per 1K tokens
+    pub fn estimated_cost(&self, pricing: &ModelPricing) -> f64 {
+        (self.prompt_tokens as f64 / 1000.0) * pricing.input_tokens
+            + (self.completion_tokens as f64 / 1000.0) * pricing.output_tokens
+    }
+}
+
+/// A provider, and the limiter holding requests to it back to its configured rate limit
+#[derive(Debug, Clone)]
+struct Backend {
+    provider: Arc<dyn CompletionProvider>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl Backend {
+    fn new(provider: Arc<dyn CompletionProvider>) -> Self {
+        Self {
+            rate_limiter: provider.rate_limit().map(RateLimiter::new),
+            provider,
+        }
+    }
+
+    /// Wait until `request` fits under the provider's rate limit, if it has one
+    async fn wait_for_rate_limit(&self, request: &ChatRequest, model: &ModelConfig) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter
+                .acquire(estimate_token_usage(&request.messages, Some(model)))
+                .await;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SimpleChatService {
-    models: HashMap<String, ModelConfig>,
+    models: Arc<RwLock<HashMap<String, ModelConfig>>>,
     default_model: Option<String>,
+    usage_ledger: UsageLedger,
+    sessions: SessionStore,
+    reasoning: ReasoningStore,
+    memory: MemoryStore,
+    /// Embeds memory entries for `search_memory_semantic`
+    embeddings: Arc<dyn EmbeddingService>,
+    moderation: Moderation,
+    /// Used by `agent_reply` when a request doesn't carry its own config
+    default_agent_config: Arc<RwLock<AgentConfig>>,
+    session_events: tokio::sync::broadcast::Sender<SessionEvent>,
+    /// Tool calls held for the user under `require_confirmation`
+    tool_confirmations: ToolConfirmations,
+    trace: TraceLog,
+    /// Replies to deterministic requests, returned again for identical ones
+    response_cache: ResponseCache,
+    files: FileJobs,
+    /// Cancellation token and number of running replies, by session
+    active_replies: Arc<Mutex<HashMap<String, (CancellationToken, usize)>>>,
+    /// Documents attached to a session for `rag_search`; kept in memory only
+    knowledge_bases: Arc<Mutex<HashMap<String, RAGSystem>>>,
+    /// Receives image messages for vision models; without one they are sent as text
+    vision: Option<Arc<dyn VisionProvider>>,
+    /// Transcribes audio in user messages before it reaches a model
+    speech_to_text: SpeechToTextTool,
+    /// Hooks `agent_reply` runs around each reply and tool call
+    extensions: Arc<ExtensionManager>,
+    /// Answers requests for every model instead of the built-in mock models when set
+    provider: Option<Backend>,
+    /// Providers set up by `initialize`, by the id models name in `ModelConfig::provider`
+    backends: HashMap<String, Backend>,
+    /// Fail requests to a hosted model whose provider has no API key instead of mocking them
+    require_credentials: bool,
+    /// Applied to every request sent to a provider
+    retry: RetryConfig,
+    /// Deadlines for requests to a provider; the network timeout setting when unset
+    timeouts: Option<ProviderTimeouts>,
+    /// Asked for its installed models by `list_models`
+    ollama: Option<OllamaProvider>,
 }
 
 impl SimpleChatService {
+    /// Open the session database and file store. Providers and the agent config are set up
+    /// by `initialize`, so this never blocks on them and is safe inside an async runtime.
     pub fn new() -> Result<Self> {
+        let mut service = Self::with_db_path(default_data_dir().join("chat_sessions.db"))?;
+        service.files = FileJobs::open(default_files_dir())?;
+        Ok(service)
+    }
+
+    /// Attach the providers configured in the environment, set up Ollama model discovery
+    /// and load the default agent config
+    pub async fn initialize(&mut self) -> Result<()> {
+        for (id, provider) in configured_providers() {
+            self.backends.insert(id, Backend::new(provider));
+        }
+        // As in `RigAgentService`, a missing API key is an error unless mocking is allowed
+        self.require_credentials = std::env::var_os(MOCK_FALLBACK_ENV).is_none();
+        self.ollama = Some(OllamaProvider::from_env());
+
+        // A broken config file shouldn't take the chat down; fall back to the built-in defaults
+        if let Some(path) = AgentConfig::default_path().filter(|path| path.exists()) {
+            match AgentConfig::load_from_file(&path) {
+                Ok(config) => {
+                    *self
+                        .default_agent_config
+                        .write()
+                        .unwrap_or_else(|e| e.into_inner()) = config
+                }
+                Err(e) => tracing::warn!("Ignoring default agent config: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// `new` and `initialize` for callers without an async runtime. Inside one this fails
+    /// instead of panicking; await `initialize` there.
+    pub fn new_blocking() -> Result<Self> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            anyhow::bail!(
+                "new_blocking can't run inside an async runtime; use new() and initialize().await"
+            );
+        }
+        let mut service = Self::new()?;
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(service.initialize())?;
+        Ok(service)
+    }
+
+    /// Build the service on the SQLite database at `path`, creating it and its directory if
+    /// needed. `:memory:` gives a throwaway database. The connection pool size comes from
+    /// `DIOXUS_CHAT_DB_POOL_SIZE`.
+    pub fn with_db_path(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::with_db_path_and_pool_size(path, db::pool_size_from_env())
+    }
+
+    /// `with_db_path` with up to `pool_size` connections open to the database at once
+    pub fn with_db_path_and_pool_size(path: impl Into<PathBuf>, pool_size: u32) -> Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        Self::with_pool(db::open_pool(&path, pool_size)?)
+    }
+
+    /// Build the service on a throwaway in-memory database
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Result<Self> {
+        Self::with_pool(db::memory_pool()?)
+    }
+
+    /// Build the service on an existing connection pool, shared by all of its stores
+    pub(crate) fn with_pool(db: DbPool) -> Result<Self> {
         let mut models = HashMap::new();
 
         // Add some default models for testing
         let models_config = vec![
             ModelConfig {
                 id: "mock-local".to_string(),
+                model: "mock-local".to_string(),
                 name: "Mock Local Model".to_string(),
                 provider: "local".to_string(),
                 description: Some("A simple mock model for testing".to_string()),
@@ -239,9 +1109,12 @@ impl SimpleChatService {
                 supports_vision: false,
                 supports_function_calling: false,
                 pricing: None,
+                temperature: None,
+                max_tokens: None,
             },
             ModelConfig {
                 id: "deepseek-chat".to_string(),
+                model: "deepseek-chat".to_string(),
                 name: "DeepSeek Chat".to_string(),
                 provider: "deepseek".to_string(),
                 description: Some("DeepSeek's chat model optimized for conversations".to_string()),
@@ -255,9 +1128,12 @@ impl SimpleChatService {
                     output_tokens: 0.00028,
                     currency: "USD".to_string(),
                 }),
+                temperature: None,
+                max_tokens: None,
             },
             ModelConfig {
                 id: "deepseek-r1-distill-llama-70b".to_string(),
+                model: "deepseek-r1-distill-llama-70b".to_string(),
                 name: "DeepSeek R1 Distill Llama 70B".to_string(),
                 provider: "deepseek".to_string(),
                 description: Some(
@@ -273,9 +1149,31 @@ impl SimpleChatService {
                     output_tokens: 0.00028,
                     currency: "USD".to_string(),
                 }),
+                temperature: None,
+                max_tokens: None,
+            },
+            ModelConfig {
+                id: "gpt-4-turbo".to_string(),
+                model: "gpt-4-1106-preview".to_string(),
+                name: "GPT-4 Turbo".to_string(),
+                provider: "openai".to_string(),
+                description: Some("OpenAI's GPT-4 Turbo (alias for gpt-4-1106-preview)".to_string()),
+                context_limit: Some(128000),
+                supports_tools: true,
+                supports_streaming: true,
+                supports_vision: false,
+                supports_function_calling: true,
+                pricing: Some(ModelPricing {
+                    input_tokens: 0.01,
+                    output_tokens: 0.03,
+                    currency: "USD".to_string(),
+                }),
+                temperature: None,
+                max_tokens: None,
             },
             ModelConfig {
                 id: "anthropic/claude-3.5-sonnet".to_string(),
+                model: "anthropic/claude-3.5-sonnet".to_string(),
                 name: "Claude 3.5 Sonnet (via OpenRouter)".to_string(),
                 provider: "openrouter".to_string(),
                 description: Some("Anthropic's most intelligent model".to_string()),
@@ -289,9 +1187,12 @@ impl SimpleChatService {
                     output_tokens: 0.015,
                     currency: "USD".to_string(),
                 }),
+                temperature: None,
+                max_tokens: None,
             },
             ModelConfig {
                 id: "openai/gpt-4o".to_string(),
+                model: "openai/gpt-4o".to_string(),
                 name: "GPT-4o (via OpenRouter)".to_string(),
                 provider: "openrouter".to_string(),
                 description: Some("OpenAI's flagship multimodal model".to_string()),
@@ -305,9 +1206,12 @@ impl SimpleChatService {
                     output_tokens: 0.015,
                     currency: "USD".to_string(),
                 }),
+                temperature: None,
+                max_tokens: None,
             },
             ModelConfig {
                 id: "google/gemini-1.5-pro".to_string(),
+                model: "google/gemini-1.5-pro".to_string(),
                 name: "Gemini 1.5 Pro (via OpenRouter)".to_string(),
                 provider: "openrouter".to_string(),
                 description: Some("Google's advanced multimodal model".to_string()),
@@ -321,6 +1225,8 @@ impl SimpleChatService {
                     output_tokens: 0.00375,
                     currency: "USD".to_string(),
                 }),
+                temperature: None,
+                max_tokens: None,
             },
         ];
 
@@ -329,275 +1235,3578 @@ impl SimpleChatService {
         }
 
         let default_model = Some("mock-local".to_string());
+        let usage_ledger = UsageLedger::new(db.clone())?;
+        let sessions = SessionStore::new(db.clone())?;
+        let reasoning = ReasoningStore::new(db.clone())?;
+        let memory = MemoryStore::new(db)?;
 
         Ok(Self {
-            models,
+            models: Arc::new(RwLock::new(models)),
             default_model,
+            usage_ledger,
+            sessions,
+            reasoning,
+            memory,
+            embeddings: Arc::new(MockEmbeddingService),
+            moderation: Moderation::default(),
+            default_agent_config: Arc::default(),
+            session_events: tokio::sync::broadcast::channel(64).0,
+            tool_confirmations: ToolConfirmations::default(),
+            trace: TraceLog::default(),
+            response_cache: ResponseCache::default(),
+            files: FileJobs::default(),
+            active_replies: Arc::default(),
+            knowledge_bases: Arc::default(),
+            vision: None,
+            speech_to_text: SpeechToTextTool::default(),
+            extensions: Arc::default(),
+            provider: None,
+            backends: HashMap::new(),
+            require_credentials: false,
+            retry: RetryConfig::default(),
+            timeouts: None,
+            ollama: None,
         })
     }
 
-    pub fn get_available_models(&self) -> Vec<ModelConfig> {
-        self.models.values().cloned().collect()
+    pub fn default_agent_config(&self) -> AgentConfig {
+        self.default_agent_config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
-    pub async fn send_message(&self, request: ChatRequest) -> Result<ChatResponse> {
-        let model_id = if request.model.is_empty() {
-            self.default_model
-                .as_ref()
-                .ok_or_else(|| anyhow::anyhow!("No default model configured"))?
-                .clone()
-        } else {
-            request.model.clone()
-        };
+    pub fn set_default_agent_config(&self, config: AgentConfig) -> Result<()> {
+        config.validate()?;
+        *self
+            .default_agent_config
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = config;
+        Ok(())
+    }
 
-        // Get the last user message for context
-        let last_user_message = request
-            .messages
-            .iter()
-            .rev()
-            .find(|msg| matches!(msg.role, Role::User))
-            .map(|msg| msg.content.clone())
-            .unwrap_or_default();
+    /// The request's own config if it has one, otherwise the service default
+    pub fn effective_agent_config(&self, config: Option<&AgentConfig>) -> AgentConfig {
+        config
+            .cloned()
+            .unwrap_or_else(|| self.default_agent_config())
+    }
 
-        // Generate response with thinking content if applicable
-        let (thinking_content, response_content) = if model_id.contains("r1")
-            || model_id.contains("reasoning")
-        {
-            // Generate thinking content for reasoning models
-            let thinking = format!("Let me think about this step by step:\n\n1. First, I need to understand what the user is asking about.\n2. The user's message is: \"{}\"\n3. I should provide a thoughtful and comprehensive response.\n4. I'll structure my answer to be clear and helpful.\n\nBased on this analysis, I'll now provide my response.", last_user_message);
+    fn apply_agent_config(&self, request: &mut ChatRequest) {
+        let mut agent_config = self.effective_agent_config(request.agent_config.as_ref());
+        // Auto settles on chat or agent for this turn; later passes see the decided mode
+        if agent_config.goose_mode == GooseMode::Auto {
+            agent_config.goose_mode = auto_mode(request);
+        }
+        // Chat mode answers from the conversation alone
+        if agent_config.goose_mode == GooseMode::Chat {
+            request.tools = None;
+        }
+        request.agent_config = Some(agent_config);
+    }
 
-            let response = self.generate_standard_response(&last_user_message, &model_id);
-            (Some(thinking), response)
-        } else {
-            (
-                None,
-                self.generate_standard_response(&last_user_message, &model_id),
-            )
-        };
+    /// Moderate outbound user messages and inbound replies in `agent_reply`
+    pub fn with_moderation(mut self, moderation: Moderation) -> Self {
+        self.moderation = moderation;
+        self
+    }
 
-        // Calculate mock token usage
-        let prompt_tokens = (last_user_message.len() + 3) / 4; // Rough estimate
-        let mut completion_tokens = (response_content.len() + 3) / 4;
-        if let Some(ref thinking) = thinking_content {
-            completion_tokens += (thinking.len() + 3) / 4;
-        }
-        let total_tokens = prompt_tokens + completion_tokens;
+    /// Embed memory with a real model instead of the word-hashing default
+    pub fn with_embeddings(mut self, embeddings: Arc<dyn EmbeddingService>) -> Self {
+        self.embeddings = embeddings;
+        self
+    }
 
-        Ok(ChatResponse {
-            message: Some(ChatMessage {
-                role: Role::Assistant,
-                content: response_content,
-                timestamp: Some(Utc::now()),
-                tool_calls: None,
-                tool_results: None,
-            }),
-            tool_calls: None,
-            token_usage: Some(TokenUsage {
-                prompt_tokens: prompt_tokens as u32,
-                completion_tokens: completion_tokens as u32,
-                total_tokens: total_tokens as u32,
-            }),
-            model: model_id.clone(),
-            finish_reason: Some("stop".to_string()),
-            is_streaming: request.stream,
-            reasoning_content: thinking_content.clone(),
-            thinking_content: thinking_content,
-        })
+    /// Send image messages for vision models to `vision` instead of describing the images in text
+    pub fn with_vision_provider(mut self, vision: Arc<dyn VisionProvider>) -> Self {
+        self.vision = Some(vision);
+        self
+    }
+
+    /// Transcribe audio with `transcriber` instead of the mock
+    pub fn with_transcription_provider(
+        mut self,
+        transcriber: Arc<dyn TranscriptionProvider>,
+    ) -> Self {
+        self.speech_to_text = SpeechToTextTool::new(transcriber);
+        self
+    }
+
+    /// Send requests for every model to `provider` instead of the providers `initialize` set up
+    pub fn with_provider(mut self, provider: Arc<dyn CompletionProvider>) -> Self {
+        self.provider = Some(Backend::new(provider));
+        self
+    }
+
+    /// Send requests for models whose `ModelConfig::provider` is `id` to `provider`
+    pub fn with_backend(mut self, id: &str, provider: Arc<dyn CompletionProvider>) -> Self {
+        self.backends.insert(id.to_string(), Backend::new(provider));
+        self
+    }
+
+    /// Include the models installed in `ollama` in `list_models`
+    pub fn with_ollama(mut self, ollama: OllamaProvider) -> Self {
+        self.ollama = Some(ollama);
+        self
+    }
+
+    /// How transient provider failures are retried before they reach the caller
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use `timeouts` for provider requests instead of following the network timeout setting
+    pub fn with_timeouts(mut self, timeouts: ProviderTimeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    fn timeouts(&self) -> ProviderTimeouts {
+        self.timeouts.unwrap_or_else(provider_timeouts)
+    }
+
+    /// The provider that answers `model`: the one given to `with_provider`, else the one set
+    /// up for the model's provider. `None` leaves it to the mock models, except that a hosted
+    /// model without an API key is a `NO_CREDENTIALS` error when credentials are required.
+    fn backend(&self, model: &ModelConfig) -> Result<Option<&Backend>, ProviderError> {
+        let backend = self
+            .provider
+            .as_ref()
+            .or_else(|| self.backends.get(&model.provider));
+        match api_key_env(&model.provider) {
+            Some(var) if backend.is_none() && self.require_credentials => Err(
+                ProviderError::no_credentials(&provider_display_name(&model.provider), var),
+            ),
+            _ => Ok(backend),
+        }
     }
 
-    fn generate_response(&self, user_message: &str, model_id: &str) -> String {
-        // Add thinking content for reasoning models
-        let (thinking_content, response_content) = if model_id.contains("r1")
-            || model_id.contains("reasoning")
+    /// Send `prompt` on its own to `model`'s provider, for requests the service makes itself
+    /// such as context summaries. `None` when no provider answers the model.
+    async fn prompt_provider(
+        &self,
+        model: &ModelConfig,
+        prompt: String,
+        session_id: Option<&str>,
+    ) -> Option<Result<ChatResponse, ProviderError>> {
+        let backend = self.backend(model).ok().flatten()?;
+        let request = ChatRequest {
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: prompt,
+                timestamp: Some(Utc::now()),
+                tool_calls: None,
+                tool_results: None,
+            }],
+            model: model.id.clone(),
+            system_prompt: None,
+            temperature: model.temperature,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            agent_config: None,
+            tools: None,
+        };
+        backend.wait_for_rate_limit(&request, model).await;
+        let (provider, timeouts) = (backend.provider.as_ref(), self.timeouts());
+        let response = with_retry(&self.retry, || {
+            complete_in_time(provider, &request, &model.model, timeouts)
+        })
+        .await;
+        if let Ok(ChatResponse {
+            token_usage: Some(usage),
+            ..
+        }) = &response
         {
-            // Generate thinking content for reasoning models
-            let thinking = format!("Let me think about this step by step:\n\n1. First, I need to understand what the user is asking about.\n2. The user's message is: \"{}\"\n3. I should provide a thoughtful and comprehensive response.\n4. I'll structure my answer to be clear and helpful.\n\nBased on this analysis, I'll now provide my response.", user_message);
+            self.record_usage(&model.id, usage, false, session_id);
+        }
+        Some(response)
+    }
 
-            let response = match model_id {
-                "deepseek-r1-distill-llama-70b" => {
-                    let lower_message = user_message.to_lowercase();
-                    if lower_message.contains("hello") {
-                        "Hello! I'm DeepSeek R1, a reasoning model that thinks through problems step by step. My reasoning process allows me to provide more thoughtful and detailed responses. How can I help you today with something that requires careful consideration?"
-                    } else if lower_message.contains("code") {
-                        "I'd be happy to help you with coding! As a reasoning model, I can break down complex programming problems into manageable steps, explain algorithms thoroughly, and provide well-reasoned solutions. What programming challenge would you like me to think through?"
-                    } else if lower_message.contains("solve") {
-                        "I'd be glad to help solve your problem! My reasoning capabilities allow me to approach challenges systematically, considering multiple angles and potential solutions before presenting the most effective approach. What specific problem would you like me to work through?"
-                    } else {
-                        "I understand you're asking about something that deserves careful thought. As a reasoning model, I can analyze your request methodically and provide a well-reasoned response. Could you share more details about what you'd like me to help you with?"
-                    }
-                }
-                _ => {
-                    "I'll think through your request and provide a reasoned response based on my analysis."
-                }
-            }.to_string();
+    /// Add an extension to every later `agent_reply`. Extensions run in registration order.
+    pub async fn register_extension(&self, extension: Box<dyn AgentExtension>) -> Result<()> {
+        self.extensions.register_extension(extension).await
+    }
 
-            (Some(thinking), response.to_string())
+    /// Look up a model by its registry id or alias, falling back to the default model for an
+    /// empty id. Provider calls must use the returned config's `model`, not the alias.
+    pub fn resolve_model(&self, alias: &str) -> Result<ModelConfig> {
+        let key = if alias.is_empty() {
+            self.default_model
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("No default model configured"))?
         } else {
-            (
-                None,
-                self.generate_standard_response(user_message, model_id),
-            )
+            alias
         };
 
-        // Combine thinking and response
-        match thinking_content {
-            Some(thinking) => format!(
-                "🧠 **Thinking Process:**\n{}\n\n**Response:**\n{}",
-                thinking, response_content
-            ),
-            None => response_content,
-        }
+        self.models()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Model {} not found", key))
     }
 
-    fn generate_standard_response(&self, user_message: &str, model_id: &str) -> String {
-        let lower_message = user_message.to_lowercase();
+    fn models(&self) -> RwLockReadGuard<'_, HashMap<String, ModelConfig>> {
+        self.models.read().unwrap_or_else(|e| e.into_inner())
+    }
 
-        // Model-specific responses for DeepSeek and OpenRouter
-        match model_id {
-            "deepseek-chat" => {
-                if lower_message.contains("hello") {
-                    "Hello! I'm DeepSeek Chat, a powerful conversational AI model developed by DeepSeek. I'm optimized for helpful dialogue, reasoning, and providing accurate information across a wide range of topics. How can I assist you today?".to_string()
-                } else if lower_message.contains("code") {
-                    "I'd be happy to help you with coding! DeepSeek Chat has strong programming capabilities and can assist with code writing, debugging, explanations, and best practices. What programming language or coding challenge would you like help with?".to_string()
-                } else if lower_message.contains("math") || lower_message.contains("calculation") {
-                    "I can help with mathematical problems! I can solve equations, explain mathematical concepts, perform calculations, and help with math-related questions across various levels of difficulty. What math problem can I assist you with?".to_string()
-                } else if lower_message.contains("chinese") || lower_message.contains("中文") {
-                    "你好！我可以帮助您处理中文相关的任务。DeepSeek Chat对中文有很好的理解能力，可以用于中文对话、写作、翻译等各种场景。有什么我可以帮助您的吗？".to_string()
-                } else if lower_message.contains("help") {
-                    "I'm here to help! DeepSeek Chat can assist with:\n• Answering questions and providing information\n• Writing and content creation\n• Coding and programming\n• Mathematical reasoning\n• Translation and language tasks\n• Problem-solving and analysis\n\nHow can I help you today?".to_string()
-                } else {
-                    format!("I understand you're asking about '{}'. I'll provide you with a helpful and accurate response based on my training. Could you clarify what specific aspect you'd like me to focus on?", user_message)
-                }
-            }
-            "anthropic/claude-3.5-sonnet" => {
-                if lower_message.contains("hello") {
-                    "Hello! I'm Claude 3.5 Sonnet, Anthropic's most intelligent model. I excel at nuanced reasoning, complex analysis, and thoughtful communication. I can help with writing, analysis, coding, creative tasks, and in-depth conversation. What would you like to explore?".to_string()
-                } else if lower_message.contains("safety") || lower_message.contains("ethical") {
-                    "That's an important topic. I'm designed with strong safety principles and ethical guidelines. I aim to be helpful, harmless, and honest in all my responses. I can discuss safety considerations, ethical frameworks, and responsible AI practices. What specific aspect of safety or ethics interests you?".to_string()
-                } else if lower_message.contains("analyze") || lower_message.contains("analysis") {
-                    "I'd be delighted to help with analysis! I excel at breaking down complex topics, identifying patterns, examining arguments, and providing insights across domains. Whether it's text analysis, data interpretation, or conceptual analysis, I can approach it systematically. What would you like me to analyze?".to_string()
-                } else if lower_message.contains("help") {
-                    "I'm here to help! As Claude 3.5 Sonnet, I can assist with:\n• In-depth analysis and research\n• Writing and editing\n• Complex problem-solving\n• Creative thinking and brainstorming\n• Ethical reasoning and safety\n• Detailed explanations\n\nI strive to be thoughtful and thorough. How can I assist you?".to_string()
-                } else {
-                    format!("I see you're interested in '{}'. I'd be happy to provide a thoughtful analysis or assistance with this topic. Is there a particular aspect you'd like me to explore in depth, or do you have specific questions I can help address?", user_message)
-                }
-            }
-            "openai/gpt-4o" => {
-                if lower_message.contains("hello") {
-                    "Hello! I'm GPT-4o, OpenAI's flagship multimodal model. I can understand text, images, and audio while providing intelligent responses across a wide range of tasks. How can I assist you today?".to_string()
-                } else if lower_message.contains("vision") || lower_message.contains("image") {
-                    "I can help you with visual analysis! As GPT-4o, I can interpret images, analyze visual content, describe what I see, and help with image-related tasks. You can share images and I'll provide detailed descriptions or answer questions about them. What visual content would you like me to analyze?".to_string()
-                } else if lower_message.contains("multimodal") {
-                    "I'm designed for multimodal understanding! I can process and connect information across text, images, and audio formats. This allows me to provide more comprehensive assistance when working with different types of content. How can I help you with multimodal tasks?".to_string()
-                } else if lower_message.contains("help") {
-                    "I'm here to help! GPT-4o can assist with:\n• Text understanding and generation\n• Image analysis and description\n• Audio processing\n• Multimodal reasoning\n• Coding and programming\n• Creative tasks\n• Problem-solving\n\nWhat would you like help with?".to_string()
-                } else {
-                    format!("I understand you're asking about '{}'. As GPT-4o, I can provide intelligent responses and, if relevant, analyze any visual content you share. What specific aspect would you like me to focus on?", user_message)
-                }
-            }
-            "google/gemini-1.5-pro" => {
-                if lower_message.contains("hello") {
-                    "Hello! I'm Gemini 1.5 Pro, Google's advanced multimodal model with a massive context window. I can handle long documents, complex reasoning, and provide thoughtful analysis across many topics. How can I assist you today?".to_string()
-                } else if lower_message.contains("long") || lower_message.contains("document") {
-                    "I excel at handling long-form content! With my 2 million token context window, I can analyze extensive documents, maintain context over long conversations, and work with complex materials. Feel free to share lengthy texts or documents. What long-form content would you like me to help with?".to_string()
-                } else if lower_message.contains("reasoning") {
-                    "I'd be glad to help with reasoning tasks! Gemini 1.5 Pro is excellent at logical thinking, problem-solving, and step-by-step analysis. I can work through complex problems systematically and provide well-reasoned conclusions. What reasoning challenge would you like me to tackle?".to_string()
-                } else if lower_message.contains("help") {
-                    "I'm here to help! Gemini 1.5 Pro can assist with:\n• Long-document analysis\n• Complex reasoning\n• Multimodal understanding\n• Research and synthesis\n• Technical documentation\n• Creative writing\n\nWith my extensive context window, I can maintain focus on complex, lengthy tasks. How can I help you?".to_string()
-                } else {
-                    format!("I see you're interested in '{}'. I'm well-equipped to provide detailed analysis and maintain context over complex discussions. What specific aspect would you like me to explore in depth?", user_message)
-                }
-            }
-            _ => {
-                // Default model
-                if lower_message.contains("hello") {
-                    "Hello! I'm a mock AI assistant. How can I help you today? I can demonstrate various capabilities including text generation, question answering, and basic conversation skills.".to_string()
-                } else if lower_message.contains("code") {
-                    "I can help you with coding questions! I can write code snippets, explain programming concepts, debug issues, and suggest best practices. What programming language or topic would you like help with?".to_string()
-                } else {
-                    format!("I received your message: '{}'. This is a mock response from the {} model. In a real implementation, this would be generated by an actual AI model with access to up-to-date information and advanced reasoning capabilities.",
-                        user_message, model_id)
+    pub fn get_available_models(&self) -> Vec<ModelConfig> {
+        self.models().values().cloned().collect()
+    }
+
+    /// Registered models plus the ones installed in Ollama, when discovery is on. A registered
+    /// model wins over a discovered one with the same id.
+    pub async fn list_models(&self) -> Vec<ModelConfig> {
+        let mut models = self.get_available_models();
+        if let Some(ollama) = self.ollama.as_ref() {
+            for model in ollama.list_models().await {
+                if !self.models().contains_key(&model.id) {
+                    models.push(model);
                 }
             }
         }
+        models
     }
 
-    pub async fn list_tools(&self, _model: &str) -> Vec<Tool> {
-        // Return empty tools for now - can be expanded later
-        vec![]
+    /// Register a model, replacing any registered under the same id
+    pub fn add_model(&self, config: ModelConfig) {
+        self.models
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(config.id.clone(), config);
     }
 
-    /// Send a message with streaming response
-    pub async fn send_message_stream(
-        &self,
-        request: ChatRequest,
-    ) -> Result<impl Stream<Item = StreamChunk>> {
-        let model_id = if request.model.is_empty() {
-            self.default_model
-                .as_ref()
-                .ok_or_else(|| anyhow::anyhow!("No default model configured"))?
-                .clone()
-        } else {
-            request.model.clone()
+    /// Unregister a model. Sessions that used it keep working on the default model.
+    pub fn remove_model(&self, model_id: &str) -> Option<ModelConfig> {
+        self.models
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(model_id)
+    }
+
+    /// Aggregate recorded token usage and estimated cost by model, provider and day
+    pub fn usage_summary(&self, range: UsageRange) -> Result<UsageSummary> {
+        self.usage_ledger.summary(&range)
+    }
+
+    pub fn create_session(&self, title: &str, model: Option<&str>) -> Result<StoredSession> {
+        self.sessions.create_session(title, model)
+    }
+
+    pub fn list_sessions(&self) -> Result<Vec<StoredSession>> {
+        self.sessions.list_sessions()
+    }
+
+    /// `list_sessions` with each session's latest message, flagging sessions whose model is gone
+    pub fn list_sessions_with_preview(&self) -> Result<Vec<SessionPreview>> {
+        Ok(self
+            .sessions
+            .list_sessions_with_last_message()?
+            .into_iter()
+            .map(|(session, last_message)| SessionPreview {
+                model_missing: session
+                    .model
+                    .as_deref()
+                    .is_some_and(|model| self.resolve_model(model).is_err()),
+                session,
+                last_message,
+            })
+            .collect())
+    }
+
+    /// Permanently switch a session to another (registered) model. The switch is written into
+    /// the transcript as a system message and published as `SessionEvent::ModelChange`.
+    pub fn set_session_model(&self, session_id: &str, model: &str) -> Result<()> {
+        self.resolve_model(model)?;
+        self.sessions.set_session_model(session_id, model)?;
+
+        // The mode the session has been replying in, until its first reply sets one
+        let mode = match self.sessions.session_mode(session_id)? {
+            Some(mode) => mode,
+            None => self.default_agent_config().goose_mode,
         };
+        self.sessions
+            .append_message(session_id, Role::System, &model_change_notice(model, &mode))?;
+        // Nobody listening is fine
+        let _ = self.session_events.send(SessionEvent::ModelChange {
+            session_id: session_id.to_string(),
+            model: model.to_string(),
+            mode,
+        });
+        Ok(())
+    }
 
-        // Get the last user message for context
-        let last_user_message = request
-            .messages
-            .iter()
-            .rev()
+    /// Model and mode changes for every session, as they happen
+    pub fn subscribe_session_events(&self) -> tokio::sync::broadcast::Receiver<SessionEvent> {
+        self.session_events.subscribe()
+    }
+
+    /// Answer a `SessionEvent::ToolConfirmationRequest`; the paused agent runs the call or
+    /// tells the model it was declined. Returns false when the call isn't waiting.
+    pub fn confirm_tool(&self, session_id: &str, tool_call_id: &str, approved: bool) -> bool {
+        self.tool_confirmations
+            .answer(session_id, tool_call_id, approved)
+    }
+
+    /// Accept an uploaded file and extract its text in the background. Poll `file_status`
+    /// with the returned id to follow it from Pending through Processing to Completed or Failed.
+    pub fn upload_file(&self, file_name: &str, mime_type: &str, data: Vec<u8>) -> String {
+        self.files.start(file_name, mime_type, data)
+    }
+
+    pub fn file_status(&self, file_id: &str) -> Option<FileProcessingResult> {
+        self.files.status(file_id)
+    }
+
+    /// Dimensions and transparency of a base64 image (a `data:` URL works too), so the UI can
+    /// reserve layout space and vision requests can carry aspect hints
+    pub fn process_image(&self, base64_data: &str) -> Result<ImageMetadata> {
+        use base64::Engine as _;
+
+        let encoded = base64_data
+            .split_once(";base64,")
+            .map_or(base64_data, |(_, data)| data);
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| anyhow::anyhow!("Image is not valid base64: {}", e))?;
+        Ok(process_image(&data))
+    }
+
+    /// An uploaded file's bytes, read from disk
+    pub async fn get_file(&self, file_id: &str) -> Result<Vec<u8>> {
+        self.files.read(file_id).await
+    }
+
+    /// Delete an uploaded file from disk, cancelling extraction if it's still running
+    pub async fn delete_file(&self, file_id: &str) -> bool {
+        self.files.remove(file_id).await
+    }
+
+    /// Combine `sources` into `target` in chronological order and delete the sources
+    pub fn merge_sessions(&self, target: &str, sources: &[String]) -> Result<()> {
+        self.sessions.merge_sessions(target, sources)
+    }
+
+    /// Drop every cached response (the "Clear Cache" button)
+    pub fn clear_cache(&self) {
+        self.response_cache.clear();
+    }
+
+    /// Bound the response cache to `PerformanceSettings.cache_size_mb`
+    pub fn set_cache_size_mb(&self, size_mb: u32) {
+        self.response_cache
+            .set_capacity(size_mb as usize * 1024 * 1024);
+    }
+
+    /// Turn the decision trace on or off for every session (the "Show Debug Information" toggle)
+    pub fn set_trace_enabled(&self, enabled: bool) {
+        self.trace.set_enabled(enabled);
+    }
+
+    /// Record a step taken outside the service, e.g. a compaction by the agent runtime
+    pub fn record_trace(&self, session_id: &str, kind: TraceKind, detail: impl Into<String>) {
+        self.trace.record(session_id, kind, detail);
+    }
+
+    /// Decision trace for a session, oldest first. Empty unless tracing was enabled.
+    pub fn get_trace(&self, session_id: &str) -> Vec<TraceEntry> {
+        self.trace.entries(session_id)
+    }
+
+    /// Delete a session and all of its messages
+    pub fn delete_session(&self, session_id: &str) -> Result<()> {
+        self.sessions.delete_session(session_id)
+    }
+
+    pub fn rename_session(&self, session_id: &str, title: &str) -> Result<()> {
+        self.sessions.rename_session(session_id, title)
+    }
+
+    /// Name a session after its first user message and return the title. With a provider the
+    /// model summarizes the message; otherwise, or when that fails, the title is the start of
+    /// the message.
+    pub async fn auto_title_session(&self, session_id: &str) -> Result<String> {
+        let session = self
+            .sessions
+            .get_session(session_id)?
+            .ok_or_else(|| anyhow::anyhow!("Session {} not found", session_id))?;
+        let first_message = self
+            .sessions
+            .load_messages(session_id)?
+            .into_iter()
             .find(|msg| matches!(msg.role, Role::User))
-            .map(|msg| msg.content.clone())
+            .ok_or_else(|| anyhow::anyhow!("Session {} has no message to title", session_id))?;
+
+        let model = session
+            .model
+            .or_else(|| self.default_model.clone())
             .unwrap_or_default();
+        let has_provider = self
+            .resolve_model(&model)
+            .is_ok_and(|config| matches!(self.backend(&config), Ok(Some(_))));
+        let mut title = title_snippet(&first_message.content);
+        if has_provider {
+            let request = ChatRequest {
+                messages: vec![ChatMessage {
+                    role: Role::User,
+                    content: format!("{}\n\n{}", TITLE_PROMPT, first_message.content),
+                    timestamp: Some(Utc::now()),
+                    tool_calls: None,
+                    tool_results: None,
+                }],
+                model,
+                system_prompt: None,
+                temperature: Some(0.2),
+                max_tokens: Some(20),
+                top_p: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stream: false,
+                agent_config: None,
+                tools: None,
+            };
+            match self.send_message(request).await {
+                Ok(response) => {
+                    let quote = |c: char| c == '"' || c == '\'' || c.is_whitespace();
+                    let generated = response
+                        .message
+                        .map(|msg| title_snippet(msg.content.trim_matches(quote)))
+                        .unwrap_or_default();
+                    if !generated.is_empty() {
+                        title = generated;
+                    }
+                }
+                Err(e) => tracing::warn!("Falling back to a snippet title: {}", e),
+            }
+        }
 
-        // Generate the full response
-        let full_response = self.generate_response(&last_user_message, &model_id);
+        self.sessions.rename_session(session_id, &title)?;
+        Ok(title)
+    }
 
-        // Split into words for streaming effect
-        let words: Vec<String> = full_response
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
-        let words_len = words.len();
-        let model_id_clone = model_id.clone();
+    /// Pin or unpin a session so it stays at the top of `list_sessions`
+    pub fn set_session_pinned(&self, session_id: &str, pinned: bool) -> Result<()> {
+        self.sessions.set_session_pinned(session_id, pinned)
+    }
 
-        // Create a stream that yields chunks with delays
-        let stream = stream::iter(words.into_iter().enumerate())
-            .map(move |(index, word)| {
-                let is_complete = index == words_len - 1;
-                let chunk = StreamChunk {
-                    content: Some(format!("{} ", word)),
-                    delta: Some(format!("{} ", word)),
-                    token_usage: None,
-                    model: model_id_clone.clone(),
-                    finish_reason: if is_complete {
-                        Some("stop".to_string())
-                    } else {
-                        None
-                    },
-                    is_complete,
-                };
-                async move {
-                    // Add delay to simulate real streaming
-                    sleep(Duration::from_millis(50)).await;
-                    chunk
-                }
-            })
-            .buffer_unordered(1);
+    pub fn append_message(&self, session_id: &str, role: Role, content: &str) -> Result<StoredMessage> {
+        self.sessions.append_message(session_id, role, content)
+    }
 
-        Ok(stream)
+    /// Persist a message with all of its content parts (tool calls, images, ...)
+    pub fn append_message_parts(
+        &self,
+        session_id: &str,
+        role: Role,
+        parts: Vec<MessageContent>,
+    ) -> Result<StoredMessage> {
+        self.sessions.append_message_parts(session_id, role, parts)
     }
 
-    /// Send a message with streaming response using Server-Sent Events format
-    pub async fn send_message_sse(
+    /// Persist a user message, adding a transcript after each of its audio parts. The
+    /// transcripts are part of the stored text, so the model reads them with the history.
+    pub async fn append_user_message_parts(
         &self,
-        request: ChatRequest,
-    ) -> Result<impl Stream<Item = String>> {
-        let stream = self.send_message_stream(request).await?;
+        session_id: &str,
+        parts: Vec<MessageContent>,
+    ) -> Result<StoredMessage> {
+        let parts = self.speech_to_text.transcribe_parts(parts).await?;
+        self.sessions
+            .append_message_parts(session_id, Role::User, parts)
+    }
 
-        let sse_stream = stream.map(|chunk| serde_json::to_string(&chunk).unwrap_or_default());
+    /// Cut a session's history back to `message_id`, deleting everything after it; `None`
+    /// clears the session. Used before resending an edited prompt, so the stored session
+    /// matches what the model is sent.
+    pub fn truncate_session_after(
+        &self,
+        session_id: &str,
+        message_id: Option<&str>,
+    ) -> Result<usize> {
+        self.sessions.truncate_after(session_id, message_id)
+    }
 
-        Ok(sse_stream)
+    /// Branch a session at `message_id` into a new session holding the history up to and
+    /// including it, returning the new session's id. The original session is left alone.
+    pub fn fork_session(&self, session_id: &str, message_id: &str) -> Result<String> {
+        Ok(self.sessions.fork_session(session_id, message_id)?.id)
+    }
+
+    /// Messages of a session in the order they were added
+    pub fn load_messages(&self, session_id: &str) -> Result<Vec<StoredMessage>> {
+        self.sessions.load_messages(session_id)
+    }
+
+    /// A session's transcript as Markdown or JSON. Uploaded images are embedded in Markdown
+    /// as data URIs.
+    pub async fn export_session(&self, session_id: &str, format: ExportFormat) -> Result<String> {
+        use base64::Engine as _;
+
+        let session = self
+            .sessions
+            .get_session(session_id)?
+            .ok_or_else(|| anyhow::anyhow!("Session {} not found", session_id))?;
+        let messages = self.load_messages(session_id)?;
+
+        match format {
+            ExportFormat::Json => Ok(export::to_json(session, messages)?),
+            ExportFormat::Markdown => {
+                let mut images = HashMap::new();
+                for part in messages.iter().flat_map(|message| &message.parts) {
+                    if let MessageContent::File {
+                        file_id, mime_type, ..
+                    } = part
+                    {
+                        if !mime_type.starts_with("image/") {
+                            continue;
+                        }
+                        // A file deleted since it was attached is listed by name instead
+                        if let Ok(data) = self.files.read(file_id).await {
+                            images.insert(
+                                file_id.clone(),
+                                format!(
+                                    "data:{};base64,{}",
+                                    mime_type,
+                                    base64::engine::general_purpose::STANDARD.encode(data)
+                                ),
+                            );
+                        }
+                    }
+                }
+                Ok(export::to_markdown(&session, &messages, &images))
+            }
+        }
+    }
+
+    /// Recreate a session from a JSON export as a new session, returning its id. The original
+    /// session is left alone if it exists here too.
+    pub fn import_session(&self, json: &str) -> Result<String> {
+        let export: SessionExport = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Not a session export: {}", e))?;
+        Ok(self
+            .sessions
+            .import_session(&export.session, &export.messages)?
+            .id)
+    }
+
+    /// Search every stored session's messages, best matches first
+    pub fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageSearchHit>> {
+        self.sessions.search_messages(query, limit)
+    }
+
+    /// A page of a session's history, newest first by page and oldest first within it
+    pub fn load_messages_page(
+        &self,
+        session_id: &str,
+        limit: usize,
+        before: Option<MessageCursor>,
+    ) -> Result<MessagePage> {
+        self.sessions.load_messages_page(session_id, limit, before)
     }
-}
 
+    fn record_usage(
+        &self,
+        model_id: &str,
+        usage: &TokenUsage,
+        is_estimated: bool,
+        session_id: Option<&str>,
+    ) {
+        let model = self.models().get(model_id).cloned();
+        let record = UsageRecord {
+            model: model_id.to_string(),
+            provider: model
+                .as_ref()
+                .map(|m| m.provider.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated_cost: estimate_cost(usage, model.and_then(|m| m.pricing).as_ref()),
+            is_estimated,
+            timestamp: Utc::now(),
+            session_id: session_id.map(str::to_string),
+        };
+        match session_id {
+            // Recorded inline so the next turn's ceiling check already counts it
+            Some(_) => {
+                if let Err(e) = self.usage_ledger.record(&record) {
+                    tracing::warn!("Failed to record usage for {}: {}", record.model, e);
+                }
+            }
+            None => self.usage_ledger.record_in_background(record),
+        }
+    }
+
+    /// With `enable_auto_compact`, replace all but the `messages_to_keep` most recent
+    /// non-system messages with a summary from the model once the conversation passes
+    /// `compact_threshold` of its context window. The stored session is untouched. Without a
+    /// provider for the model, or when the summary request fails, the messages are dropped.
+    async fn compact_context(&self, session_id: &str, request: &mut ChatRequest) {
+        let config = self.effective_agent_config(request.agent_config.as_ref());
+        let Ok(model) = &self.resolve_model(&request.model) else {
+            return;
+        };
+        let Some(limit) = model.context_limit.filter(|_| config.enable_auto_compact) else {
+            return;
+        };
+
+        let budget = (limit as f64 * config.compact_threshold as f64) as u64;
+        let before = estimate_token_usage(&request.messages, Some(model));
+        if before <= budget {
+            return;
+        }
+
+        let keep = config.messages_to_keep;
+        let mut summarized = false;
+        let compacted = compact_messages(&mut request.messages, keep, |dropped| {
+            let prompt = format!("{}\n\n{}", SUMMARY_PROMPT, summary_transcript(&dropped));
+            let summarized = &mut summarized;
+            async move {
+                let summary = match self.prompt_provider(model, prompt, Some(session_id)).await {
+                    Some(Ok(response)) if !is_content_filter(response.finish_reason.as_deref()) => {
+                        response.message.map(|message| message.content)
+                    }
+                    Some(Ok(_)) | None => None,
+                    Some(Err(e)) => {
+                        tracing::warn!("Dropping messages instead of summarizing them: {}", e);
+                        None
+                    }
+                };
+                *summarized = summary.is_some();
+                summary
+            }
+        })
+        .await;
+
+        if compacted > 0 {
+            let after = estimate_token_usage(&request.messages, Some(model));
+            let how = if summarized { "summarized" } else { "dropped" };
+            self.trace.record(
+                session_id,
+                TraceKind::Compaction,
+                format!(
+                    "Context was {} of {} tokens; {} the {} oldest messages to get to {}",
+                    before, limit, how, compacted, after
+                ),
+            );
+        }
+    }
+
+    /// Why a session can't send `request` without going over its token or cost ceiling, if it can't.
+    /// The pending prompt counts towards the ceiling, estimated with the model's tokenizer.
+    fn session_ceiling_notice(&self, session_id: &str, request: &ChatRequest) -> Result<Option<String>> {
+        let config = self.effective_agent_config(request.agent_config.as_ref());
+        if config.max_session_tokens.is_none() && config.max_session_cost.is_none() {
+            return Ok(None);
+        }
+
+        let used = self.usage_ledger.session_totals(session_id)?;
+        let pending_tokens = estimate_token_usage(
+            &request.messages,
+            self.resolve_model(&request.model).ok().as_ref(),
+        );
+
+        if let Some(limit) = config.max_session_tokens {
+            if used.total_tokens() + pending_tokens > limit {
+                return Ok(Some(format!(
+                    "This session has used {} of its {} token limit and this message needs about {} more. Raise the session token limit to keep chatting.",
+                    used.total_tokens(),
+                    limit,
+                    pending_tokens
+                )));
+            }
+        }
+        if let Some(limit) = config.max_session_cost {
+            let pending = TokenUsage {
+                prompt_tokens: pending_tokens as u32,
+                completion_tokens: 0,
+                total_tokens: pending_tokens as u32,
+            };
+            let pricing = self
+                .resolve_model(&request.model)
+                .ok()
+                .and_then(|model| model.pricing);
+            if used.estimated_cost + estimate_cost(&pending, pricing.as_ref()) > limit {
+                return Ok(Some(format!(
+                    "This session has spent ${:.4} of its ${:.4} limit. Raise the session cost limit to keep chatting.",
+                    used.estimated_cost, limit
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run one turn of a stored session: moderate and persist the user's message, get a reply,
+    /// then moderate and persist it. Blocked turns and empty replies come back as a notification
+    /// and leave nothing behind for the assistant.
+    pub async fn agent_reply(&self, session_id: &str, request: ChatRequest) -> Result<ChatResponse> {
+        let model = request.model.clone();
+        self.cancellable(session_id, &model, self.reply(session_id, request, true))
+            .await
+    }
+
+    /// Stop every reply running in a session, declining the tool calls it is waiting on.
+    /// Returns false when nothing was running.
+    pub fn cancel_session(&self, session_id: &str) -> bool {
+        self.tool_confirmations.clear_session(session_id);
+        let removed = self
+            .active_replies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session_id);
+        match removed {
+            Some((token, _)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run a reply under the session's cancellation token. A cancelled reply is dropped at its
+    /// next await point and comes back as an empty response with a `cancelled` finish reason.
+    async fn cancellable(
+        &self,
+        session_id: &str,
+        model: &str,
+        reply: impl std::future::Future<Output = Result<ChatResponse>>,
+    ) -> Result<ChatResponse> {
+        let token = {
+            let mut active = self
+                .active_replies
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            let (token, running) = active
+                .entry(session_id.to_string())
+                .or_insert_with(|| (CancellationToken::new(), 0));
+            *running += 1;
+            token.clone()
+        };
+
+        let result = tokio::select! {
+            result = reply => result,
+            _ = token.cancelled() => {
+                self.trace.record(session_id, TraceKind::Finish, "Cancelled");
+                Ok(cancelled_response(model))
+            }
+        };
+
+        // `cancel_session` already removed the entry for cancelled tokens
+        if !token.is_cancelled() {
+            let mut active = self
+                .active_replies
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if let Some((_, running)) = active.get_mut(session_id) {
+                *running -= 1;
+                if *running == 0 {
+                    active.remove(session_id);
+                }
+            }
+        }
+        result
+    }
+
+    /// Answer the last user message again, optionally on another model for this reply only.
+    /// The new reply is stored after the original so the two can be compared; the session
+    /// keeps its model.
+    pub async fn regenerate_reply(
+        &self,
+        session_id: &str,
+        mut request: ChatRequest,
+        model_override: Option<&str>,
+    ) -> Result<ChatResponse> {
+        while request
+            .messages
+            .last()
+            .is_some_and(|msg| matches!(msg.role, Role::Assistant))
+        {
+            request.messages.pop();
+        }
+        if let Some(model) = model_override {
+            self.resolve_model(model)?;
+            request.model = model.to_string();
+        }
+        let model = request.model.clone();
+        self.cancellable(session_id, &model, self.reply(session_id, request, false))
+            .await
+    }
+
+    async fn reply(
+        &self,
+        session_id: &str,
+        mut request: ChatRequest,
+        store_user_message: bool,
+    ) -> Result<ChatResponse> {
+        let mut warnings = Vec::new();
+        let mode = self
+            .effective_agent_config(request.agent_config.as_ref())
+            .goose_mode;
+        self.sessions.set_session_mode(session_id, &mode)?;
+        let auto = mode == GooseMode::Auto;
+        self.apply_agent_config(&mut request);
+        request.messages.retain(|msg| !is_model_change_notice(msg));
+
+        // Fall back to the default model rather than failing when the session's model was removed
+        if request.model.is_empty() {
+            if let Some(model) = self.sessions.get_session(session_id)?.and_then(|s| s.model) {
+                request.model = model;
+            }
+        }
+        if self.resolve_model(&request.model).is_err() {
+            if let Some(default_model) = self
+                .default_model
+                .clone()
+                .filter(|model| self.models().contains_key(model))
+            {
+                warnings.push(format!(
+                    "Model \"{}\" is no longer available, so this reply used \"{}\". Switch the session to \"{}\" to stop seeing this.",
+                    request.model, default_model, default_model
+                ));
+                request.model = default_model;
+            }
+        }
+
+        self.trace
+            .record(session_id, TraceKind::TurnStart, format!("Reply with {}", request.model));
+
+        if auto {
+            let mode = request
+                .agent_config
+                .as_ref()
+                .map_or(GooseMode::Agent, |config| config.goose_mode.clone());
+            self.trace.record(
+                session_id,
+                TraceKind::TurnStart,
+                format!("Auto mode chose {:?} mode", mode),
+            );
+            let _ = self.session_events.send(SessionEvent::ModelChange {
+                session_id: session_id.to_string(),
+                model: request.model.clone(),
+                mode,
+            });
+        }
+
+        if let Some(notice) = self.session_ceiling_notice(session_id, &request)? {
+            self.trace.record(session_id, TraceKind::Finish, notice.clone());
+            return Ok(refused_response(&request.model, notice));
+        }
+
+        let extension_context = ExtensionContext::for_session(session_id);
+        let extension_timeout = Duration::from_secs(
+            self.effective_agent_config(request.agent_config.as_ref())
+                .extension_timeout,
+        );
+        for phase in [ExtensionPhase::Validation, ExtensionPhase::PreProcessing] {
+            let outcome = self
+                .extensions
+                .execute_phase(
+                    phase,
+                    &extension_context,
+                    serde_json::to_value(&request)?,
+                    extension_timeout,
+                )
+                .await?;
+            if let Some(reason) = outcome.blocked {
+                self.trace
+                    .record(session_id, TraceKind::Finish, reason.clone());
+                return Ok(refused_response(
+                    &request.model,
+                    format!("Your message was not sent: {}", reason),
+                ));
+            }
+            // Extensions may rewrite the request; any other output is informational
+            if let Ok(rewritten) = serde_json::from_value(outcome.output) {
+                request = rewritten;
+            }
+        }
+
+        if let Some(user_message) = request
+            .messages
+            .iter_mut()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::User))
+        {
+            match self
+                .moderation
+                .check(&user_message.content, ModerationDirection::Outbound)
+                .await
+            {
+                ModerationVerdict::Allow => {}
+                ModerationVerdict::Warn(reason) => warnings.push(reason),
+                ModerationVerdict::Redact(redacted) => user_message.content = redacted,
+                ModerationVerdict::Block(reason) => {
+                    return Ok(refused_response(
+                        &request.model,
+                        format!("Your message was not sent: {}", reason),
+                    ));
+                }
+            }
+            if store_user_message {
+                self.sessions.append_message_parts(
+                    session_id,
+                    Role::User,
+                    message_parts(user_message, None),
+                )?;
+            }
+        }
+
+        self.recall_memories(session_id, &mut request)?;
+        self.compact_context(session_id, &mut request).await;
+
+        let reply_model = request.model.clone();
+        let mut response = self.send_in_session(request, Some(session_id)).await?;
+
+        if let Some(message) = response.message.as_mut() {
+            match self
+                .moderation
+                .check(&message.content, ModerationDirection::Inbound)
+                .await
+            {
+                ModerationVerdict::Allow => {}
+                ModerationVerdict::Warn(reason) => warnings.push(reason),
+                ModerationVerdict::Redact(redacted) => message.content = redacted,
+                ModerationVerdict::Block(reason) => {
+                    return Ok(refused_response(
+                        &response.model,
+                        format!("The response was withheld: {}", reason),
+                    ));
+                }
+            }
+        }
+
+        let outcome = self
+            .extensions
+            .execute_phase(
+                ExtensionPhase::PostProcessing,
+                &extension_context,
+                serde_json::to_value(&response)?,
+                extension_timeout,
+            )
+            .await?;
+        if let Some(reason) = outcome.blocked {
+            self.trace
+                .record(session_id, TraceKind::Finish, reason.clone());
+            return Ok(refused_response(
+                &response.model,
+                format!("The response was withheld: {}", reason),
+            ));
+        }
+
+        if let Some(ref message) = response.message {
+            let mut message = message.clone();
+            if message.tool_calls.is_none() {
+                message.tool_calls = response.tool_calls.clone();
+            }
+            let parts = message_parts(&message, response.thinking_content.as_deref());
+            self.sessions
+                .append_reply(session_id, parts, &reply_model)?;
+        }
+
+        if response.notification.is_none() && !warnings.is_empty() {
+            response.notification = Some(SystemNotification {
+                notification_type: SystemNotificationType::Warning,
+                message: warnings.join("\n"),
+                code: None,
+            });
+        }
+
+        self.trace.record(
+            session_id,
+            TraceKind::Finish,
+            match response.notification {
+                Some(ref notification) => notification.message.clone(),
+                None => format!(
+                    "Replied, finish reason: {}",
+                    response.finish_reason.as_deref().unwrap_or("none")
+                ),
+            },
+        );
+
+        Ok(response)
+    }
+
+    /// `agent_reply` that records its decision process as a reasoning chain for the session
+    pub async fn agent_reply_with_planning(
+        &self,
+        session_id: &str,
+        mut request: ChatRequest,
+    ) -> Result<ChatResponse> {
+        self.apply_agent_config(&mut request);
+
+        let last_user_message = request
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::User))
+            .map(|msg| msg.content.chars().take(200).collect::<String>())
+            .unwrap_or_default();
+        self.add_reasoning_step(
+            session_id,
+            ReasoningStep::new(
+                ReasoningStepType::Analysis,
+                format!("The user asked: \"{}\"", last_user_message),
+                0.9,
+            ),
+        )?;
+
+        let tools: Vec<String> = request
+            .tools
+            .iter()
+            .flatten()
+            .map(|tool| tool.name.clone())
+            .collect();
+        let planning = if tools.is_empty() {
+            ReasoningStep::new(
+                ReasoningStepType::Planning,
+                "Answer directly from the conversation context",
+                0.8,
+            )
+        } else {
+            let (plan, from_model) = self
+                .plan_steps(&request.model, &last_user_message, &tools)
+                .await;
+            let estimate = plan
+                .estimated_duration
+                .map(|seconds| format!("\nEstimated time: {}s", seconds))
+                .unwrap_or_default();
+            let confidence = if from_model { 0.8 } else { 0.4 };
+            ReasoningStep::new(
+                ReasoningStepType::Planning,
+                format!("{}{}", plan.summary(), estimate),
+                confidence,
+            )
+        };
+        self.add_reasoning_step(session_id, planning)?;
+        let tool_selection = if tools.is_empty() {
+            ReasoningStep::new(ReasoningStepType::ToolSelection, "No tools available", 1.0)
+        } else {
+            ReasoningStep::new(
+                ReasoningStepType::ToolSelection,
+                format!("Candidate tools: {}", tools.join(", ")),
+                0.6,
+            )
+        };
+        self.add_reasoning_step(session_id, tool_selection)?;
+
+        let response = self.agent_reply(session_id, request).await?;
+
+        let reflection = match response.notification {
+            Some(ref notification) => ReasoningStep::new(
+                ReasoningStepType::Reflection,
+                format!("No usable reply: {}", notification.message),
+                0.3,
+            ),
+            None => ReasoningStep::new(
+                ReasoningStepType::Reflection,
+                "Produced a reply that passed moderation",
+                0.8,
+            ),
+        };
+        self.add_reasoning_step(session_id, reflection)?;
+
+        Ok(response)
+    }
+
+    /// Ask `model`'s provider for an execution plan, falling back to the keyword heuristic
+    /// when there is no provider, the request fails or the reply can't be parsed. The flag
+    /// says whether the plan came from the model.
+    pub async fn plan_steps(
+        &self,
+        model: &str,
+        request: &str,
+        tools: &[String],
+    ) -> (ExecutionPlan, bool) {
+        let mut from_model = None;
+        if let Ok(model) = &self.resolve_model(model) {
+            let prompt = format!(
+                "{}\n\nTools: {}\n\nRequest: {}",
+                PLANNING_PROMPT,
+                tools.join(", "),
+                request
+            );
+            match self.prompt_provider(model, prompt, None).await {
+                Some(Ok(response)) => from_model = response.message.map(|msg| msg.content),
+                Some(Err(e)) => tracing::warn!("Planning request failed: {}", e),
+                None => {}
+            }
+        }
+
+        match from_model.and_then(|output| parse_plan(&output, tools)) {
+            Some(plan) => (plan, true),
+            None => {
+                tracing::debug!("Model plan was unusable, falling back to keyword planning");
+                (keyword_plan(request, tools), false)
+            }
+        }
+    }
+
+    pub fn add_reasoning_step(&self, session_id: &str, step: ReasoningStep) -> Result<()> {
+        self.reasoning.add_step(session_id, &step)
+    }
+
+    /// Reasoning steps recorded for a session, oldest first
+    pub fn get_reasoning_chain(&self, session_id: &str) -> Result<Vec<ReasoningStep>> {
+        self.reasoning.chain(session_id)
+    }
+
+    /// Remember something for a session
+    pub fn add_memory(
+        &self,
+        session_id: &str,
+        content: &str,
+        importance: f32,
+    ) -> Result<MemoryEntry> {
+        let entry = MemoryEntry::new(content, importance);
+        let memory = SessionMemory {
+            entries: vec![entry.clone()],
+            ..SessionMemory::default()
+        };
+        self.memory.persist(session_id, &memory)?;
+        Ok(entry)
+    }
+
+    /// Memory entries matching `query`; each one returned counts as an access
+    pub fn search_memory(
+        &self,
+        session_id: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<MemoryEntry>> {
+        self.memory.search(session_id, query, limit)
+    }
+
+    /// The `top_k` memory entries closest in meaning to `query`, best first, with their cosine
+    /// similarity. Entries are embedded on first use; returned ones count as an access.
+    pub async fn search_memory_semantic(
+        &self,
+        session_id: &str,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<(MemoryEntry, f32)>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+        let query = self.embeddings.embed(query).await?;
+
+        let mut scored = Vec::new();
+        for (entry, embedding) in self.memory.entries_with_embeddings(session_id)? {
+            // Vectors of another length came from a different embedder
+            let embedding = match embedding.filter(|e| e.len() == query.len()) {
+                Some(embedding) => embedding,
+                None => {
+                    let embedding = self.embeddings.embed(&entry.content).await?;
+                    self.memory.set_embedding(&entry.id, &embedding)?;
+                    embedding
+                }
+            };
+            scored.push((cosine_similarity(&query, &embedding), entry));
+        }
+        scored.retain(|(score, _)| *score > 0.0);
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        scored.truncate(top_k);
+
+        let (scores, mut entries): (Vec<f32>, Vec<MemoryEntry>) = scored.into_iter().unzip();
+        self.memory.record_access(&mut entries)?;
+        Ok(entries.into_iter().zip(scores).collect())
+    }
+
+    pub fn persist_memory(&self, session_id: &str, memory: &SessionMemory) -> Result<()> {
+        self.memory.persist(session_id, memory)
+    }
+
+    /// Everything remembered for a session, as stored by earlier runs
+    pub fn load_memory(&self, session_id: &str) -> Result<SessionMemory> {
+        self.memory.load(session_id)
+    }
+
+    /// Chunk, embed and attach a document to a session's knowledge base, which offers the
+    /// `rag_search` tool in that session from then on. Returns the number of chunks stored.
+    pub async fn ingest_document(
+        &self,
+        session_id: &str,
+        title: &str,
+        content: &str,
+    ) -> Result<usize> {
+        if self.sessions.get_session(session_id)?.is_none() {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        let rag = self
+            .knowledge_bases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(session_id.to_string())
+            .or_insert_with(|| {
+                RAGSystem::new(
+                    Arc::new(InMemoryVectorStore::default()),
+                    self.embeddings.clone(),
+                )
+            })
+            .clone();
+        let metadata = DocumentMetadata {
+            title: title.to_string(),
+            source: None,
+        };
+        rag.ingest(content, metadata).await
+    }
+
+    /// Ingest the text extracted from an upload, titled with its file name. The upload must
+    /// have finished processing; PDFs and DOCX files need the `document-extraction` feature.
+    pub async fn ingest_file(&self, session_id: &str, file_id: &str) -> Result<usize> {
+        let file = self
+            .files
+            .status(file_id)
+            .ok_or_else(|| anyhow::anyhow!("File {} not found", file_id))?;
+        match (&file.status, &file.extracted_text) {
+            (FileStatus::Completed, Some(text)) => {
+                self.ingest_document(session_id, &file.file_name, text)
+                    .await
+            }
+            (FileStatus::Failed { error }, _) => {
+                anyhow::bail!("{} could not be processed: {}", file.file_name, error)
+            }
+            (FileStatus::Completed, None) => {
+                anyhow::bail!("{} has no text to ingest", file.file_name)
+            }
+            _ => anyhow::bail!("{} is still being processed", file.file_name),
+        }
+    }
+
+    /// Passages from the session's documents closest to `query`; empty without a knowledge base
+    pub async fn search_knowledge(
+        &self,
+        session_id: &str,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<SearchResult>> {
+        match self.knowledge_base(session_id) {
+            Some(rag) => rag.search(query, top_k).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn knowledge_base(&self, session_id: &str) -> Option<RAGSystem> {
+        self.knowledge_bases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(session_id)
+            .cloned()
+    }
+
+    /// Put the session's memories relevant to the last user message in front of the model
+    fn recall_memories(&self, session_id: &str, request: &mut ChatRequest) -> Result<()> {
+        let Some(query) = request
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::User))
+            .map(|msg| msg.content.clone())
+        else {
+            return Ok(());
+        };
+        let recalled = self
+            .memory
+            .search(session_id, &query, MAX_RECALLED_MEMORIES)?;
+        if recalled.is_empty() {
+            return Ok(());
+        }
+
+        let notes: Vec<String> = recalled
+            .iter()
+            .map(|entry| format!("- {}", entry.content))
+            .collect();
+        let position = request
+            .messages
+            .iter()
+            .take_while(|msg| matches!(msg.role, Role::System))
+            .count();
+        request.messages.insert(
+            position,
+            ChatMessage {
+                role: Role::System,
+                content: format!("Remembered from this session:\n{}", notes.join("\n")),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Send the same conversation to several models concurrently. Results keep the order the
+    /// models were given in, and a model that fails only fails its own entry.
+    pub async fn send_to_models(
+        &self,
+        messages: Vec<ChatMessage>,
+        models: Vec<String>,
+    ) -> Vec<(String, Result<ChatResponse>)> {
+        self.send_to_models_timed(messages, models)
+            .await
+            .into_iter()
+            .map(|(model, result, _)| (model, result))
+            .collect()
+    }
+
+    /// `send_to_models` with each reply's latency and estimated cost, for the compare view
+    pub async fn compare_models(
+        &self,
+        messages: Vec<ChatMessage>,
+        models: Vec<String>,
+    ) -> Vec<ModelComparison> {
+        self.send_to_models_timed(messages, models)
+            .await
+            .into_iter()
+            .map(|(model, result, latency)| {
+                let latency_ms = latency.as_millis() as u64;
+                match result {
+                    Ok(response) => {
+                        let pricing = self
+                            .resolve_model(&model)
+                            .ok()
+                            .and_then(|config| config.pricing);
+                        ModelComparison {
+                            estimated_cost: response
+                                .token_usage
+                                .as_ref()
+                                .map(|usage| estimate_cost(usage, pricing.as_ref())),
+                            model,
+                            response: Some(response),
+                            error: None,
+                            latency_ms,
+                        }
+                    }
+                    // A missing key comes back as a notification the UI can point at Settings
+                    Err(e) => match e.downcast_ref::<ProviderError>() {
+                        Some(error) if error.is_no_credentials() => ModelComparison {
+                            response: Some(error_response(&model, error)),
+                            model,
+                            error: None,
+                            latency_ms,
+                            estimated_cost: None,
+                        },
+                        _ => ModelComparison {
+                            model,
+                            response: None,
+                            error: Some(e.to_string()),
+                            latency_ms,
+                            estimated_cost: None,
+                        },
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Check a model's provider with its `health_check`, which costs no tokens; an empty
+    /// `model` checks the default. A check that fails, or runs past the request timeout,
+    /// marks the provider unreachable. Models the built-in mock answers are always reachable.
+    pub async fn check_health(&self, model: &str) -> ProviderHealth {
+        let model = match model {
+            "" => self.default_model.clone().unwrap_or_default(),
+            model => model.to_string(),
+        };
+        let started = Instant::now();
+        let result = match self.resolve_model(&model) {
+            Ok(config) => match self.backend(&config) {
+                Ok(Some(backend)) => {
+                    let limit = self.timeouts().request;
+                    tokio::time::timeout(limit, backend.provider.health_check())
+                        .await
+                        .unwrap_or_else(|_| Err(timeout_error("Health check", limit)))
+                        .map_err(anyhow::Error::from)
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e.into()),
+            },
+            Err(e) => Err(e),
+        };
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        ProviderHealth {
+            model,
+            reachable: result.is_ok(),
+            latency_ms,
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+
+    async fn send_to_models_timed(
+        &self,
+        messages: Vec<ChatMessage>,
+        models: Vec<String>,
+    ) -> Vec<(String, Result<ChatResponse>, Duration)> {
+        let requests = models.into_iter().map(|model| {
+            let request = ChatRequest {
+                messages: messages.clone(),
+                model: model.clone(),
+                system_prompt: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stream: false,
+                agent_config: None,
+                tools: None,
+            };
+            async move {
+                let started = Instant::now();
+                let result = self.send_message(request).await;
+                (model, result, started.elapsed())
+            }
+        });
+        futures::future::join_all(requests).await
+    }
+
+    /// Send a message that may contain images. Models with `supports_vision` get the images
+    /// through the vision provider; other models get each image's description instead.
+    pub async fn send_multimodal_message(
+        &self,
+        mut request: MultimodalChatRequest,
+    ) -> Result<ChatResponse> {
+        let model = self.resolve_model(&request.model)?;
+        let vision_enabled = request
+            .multimodal_config
+            .as_ref()
+            .is_none_or(|config| config.enable_vision);
+
+        match self.vision.as_ref() {
+            Some(vision) if model.supports_vision && vision_enabled => {
+                MultimodalService::new()
+                    .inline_media(&mut request.messages)
+                    .await?;
+                vision.complete(&request, &model.model).await
+            }
+            _ => self.send_message(request.to_text_request()).await,
+        }
+    }
+
+    /// `send_message` that runs the model's tool calls through `executor` and asks again until
+    /// it answers without calling tools, up to the agent config's `max_iterations` turns
+    pub async fn send_message_with_tools(
+        &self,
+        request: ChatRequest,
+        executor: &dyn ToolExecutor,
+    ) -> Result<ChatResponse> {
+        self.tool_loop(request, executor, None).await
+    }
+
+    /// `send_message_with_tools` with usage and the decision trace attributed to a session
+    pub async fn send_session_message_with_tools(
+        &self,
+        session_id: &str,
+        request: ChatRequest,
+        executor: &dyn ToolExecutor,
+    ) -> Result<ChatResponse> {
+        self.tool_loop(request, executor, Some(session_id)).await
+    }
+
+    /// `send_session_message_with_tools` as a stream of events, cancellable with
+    /// `cancel_session`. Each tool call that waits on `confirm_tool` is sent as it comes up,
+    /// then the reply itself, ending with `AgentEvent::Done`.
+    pub fn session_message_events<'a>(
+        &'a self,
+        session_id: &'a str,
+        request: ChatRequest,
+        executor: &'a dyn ToolExecutor,
+    ) -> impl Stream<Item = AgentEvent> + Send + 'a {
+        async_stream::stream! {
+            let mut session_events = self.subscribe_session_events();
+            let model = request.model.clone();
+            let reply = self.cancellable(
+                session_id,
+                &model,
+                self.send_session_message_with_tools(session_id, request, executor),
+            );
+            tokio::pin!(reply);
+            let result = loop {
+                tokio::select! {
+                    result = &mut reply => break result,
+                    Ok(event) = session_events.recv() => {
+                        if let SessionEvent::ToolConfirmationRequest { session_id: id, tool_call } = event {
+                            if id == session_id {
+                                yield AgentEvent::ToolConfirmationRequest { tool_call };
+                            }
+                        }
+                    }
+                }
+            };
+
+            let response = match result {
+                Ok(response) => response,
+                Err(e) => {
+                    yield AgentEvent::SystemNotification {
+                        notification: SystemNotification {
+                            notification_type: SystemNotificationType::ErrorMessage,
+                            message: e.to_string(),
+                            code: None,
+                        },
+                    };
+                    yield AgentEvent::Done {
+                        model: model.clone(),
+                        token_usage: None,
+                        finish_reason: None,
+                    };
+                    return;
+                }
+            };
+            if let Some(text) = response.reasoning_content.filter(|text| !text.is_empty()) {
+                yield AgentEvent::Thinking { text };
+            }
+            if let Some(message) = response.message.filter(|message| !message.content.is_empty()) {
+                yield AgentEvent::Content { text: message.content };
+            }
+            if let Some(notification) = response.notification {
+                yield AgentEvent::SystemNotification { notification };
+            }
+            yield AgentEvent::Done {
+                model: response.model,
+                token_usage: response.token_usage,
+                finish_reason: response.finish_reason,
+            };
+        }
+    }
+
+    async fn tool_loop(
+        &self,
+        mut request: ChatRequest,
+        executor: &dyn ToolExecutor,
+        session_id: Option<&str>,
+    ) -> Result<ChatResponse> {
+        self.apply_agent_config(&mut request);
+        let max_iterations = request
+            .agent_config
+            .as_ref()
+            .map_or(1, |config| config.max_iterations);
+        let messages = std::mem::take(&mut request.messages);
+        let trace = |kind: TraceKind, detail: String| {
+            if let Some(session_id) = session_id {
+                self.trace.record(session_id, kind, detail);
+            }
+        };
+
+        let on_content_filter = request
+            .agent_config
+            .as_ref()
+            .map_or_else(ContentFilterPolicy::default, |config| config.on_content_filter);
+
+        // Sessions with documents attached can search them, unless tools are off in chat mode
+        let tools_enabled = request
+            .agent_config
+            .as_ref()
+            .is_some_and(|config| config.goose_mode != GooseMode::Chat);
+        let knowledge_executor = session_id
+            .and_then(|session_id| self.knowledge_base(session_id))
+            .filter(|_| tools_enabled)
+            .map(|rag| {
+                let tools = request.tools.get_or_insert_with(Vec::new);
+                if !tools.iter().any(|tool| tool.name == RAG_TOOL_NAME) {
+                    tools.push(RAGTool::definition());
+                }
+                KnowledgeBaseExecutor {
+                    tool: RAGTool::new(rag),
+                    inner: executor,
+                }
+            });
+        let executor: &dyn ToolExecutor = match &knowledge_executor {
+            Some(knowledge_executor) => knowledge_executor,
+            None => executor,
+        };
+
+        // Under `require_confirmation`, session tool calls wait for the user's approval
+        let agent_config = self.effective_agent_config(request.agent_config.as_ref());
+        let confirming_executor = session_id
+            .filter(|_| agent_config.require_confirmation)
+            .map(|session_id| ConfirmingToolExecutor {
+                session_id: session_id.to_string(),
+                readonly_tools: &agent_config.readonly_tools,
+                confirmations: &self.tool_confirmations,
+                events: &self.session_events,
+                timeout: TOOL_CONFIRMATION_TIMEOUT,
+                inner: executor,
+            });
+        let executor: &dyn ToolExecutor = match &confirming_executor {
+            Some(confirming_executor) => confirming_executor,
+            None => executor,
+        };
+
+        // Session tool calls go through the extensions' pre-tool hook first
+        let extension_executor = session_id.map(|session_id| ExtensionToolExecutor {
+            extensions: &self.extensions,
+            context: ExtensionContext::for_session(session_id),
+            timeout: Duration::from_secs(agent_config.extension_timeout),
+            inner: executor,
+        });
+        let executor: &dyn ToolExecutor = match &extension_executor {
+            Some(extension_executor) => extension_executor,
+            None => executor,
+        };
+
+        let (response, _) = run_tool_loop_traced(
+            messages,
+            max_iterations,
+            on_content_filter,
+            executor,
+            &trace,
+            |messages| {
+                let turn = ChatRequest {
+                    messages,
+                    ..request.clone()
+                };
+                async move { self.send_in_session(turn, session_id).await }
+            },
+        )
+        .await?;
+        Ok(response)
+    }
+
+    pub async fn send_message(&self, request: ChatRequest) -> Result<ChatResponse> {
+        self.send_in_session(request, None).await
+    }
+
+    /// `send_message` with usage attributed to a stored session. Requests at temperature 0
+    /// are answered from the response cache when an identical one was answered before.
+    async fn send_in_session(
+        &self,
+        request: ChatRequest,
+        session_id: Option<&str>,
+    ) -> Result<ChatResponse> {
+        let key = ResponseCache::key(&request);
+        if let Some(response) = key.as_ref().and_then(|key| self.response_cache.get(key)) {
+            return Ok(response);
+        }
+
+        let response = self.send_uncached(request, session_id).await?;
+        // Warnings and errors are worth another try, so only clean replies are kept
+        if let Some(key) = key.filter(|_| response.notification.is_none()) {
+            self.response_cache.insert(key, &response);
+        }
+        Ok(response)
+    }
+
+    async fn send_uncached(
+        &self,
+        request: ChatRequest,
+        session_id: Option<&str>,
+    ) -> Result<ChatResponse> {
+        let model_config = &self.resolve_model(&request.model)?;
+        let model_id = model_config.id.clone();
+        // Always hand the provider the real API id, never the alias
+        let provider_model = model_config.model.clone();
+
+        if let Some(backend) = self.backend(model_config)? {
+            let request = ChatRequest {
+                messages: provider_messages(&request),
+                system_prompt: None,
+                temperature: request.temperature.or(model_config.temperature),
+                max_tokens: request.max_tokens.or(model_config.max_tokens),
+                ..request
+            };
+            backend.wait_for_rate_limit(&request, model_config).await;
+            let (provider, timeouts) = (backend.provider.as_ref(), self.timeouts());
+            let response = with_retry(&self.retry, || {
+                complete_in_time(provider, &request, &provider_model, timeouts)
+            })
+            .await?;
+            if let Some(ref usage) = response.token_usage {
+                self.record_usage(&model_id, usage, false, session_id);
+            }
+            return Ok(response);
+        }
+
+        // Get the last user message for context
+        let last_user_message = provider_messages(&request)
+            .iter()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::User))
+            .map(|msg| msg.content.clone())
+            .unwrap_or_default();
+
+        // Providers occasionally return nothing (immediate stop, filtered content); retry
+        // once where that makes sense and never hand back a blank assistant message
+        let (completion, notification) =
+            complete_with_retry(|| self.complete(&provider_model, &last_user_message));
+
+        // Calculate mock token usage
+        let tokenizer = tokenizer_for(model_config);
+        let prompt_tokens = tokenizer.count_tokens(&last_user_message);
+        let mut completion_tokens = tokenizer.count_tokens(&completion.content);
+        if let Some(ref thinking) = completion.thinking {
+            completion_tokens += tokenizer.count_tokens(thinking);
+        }
+        let total_tokens = prompt_tokens + completion_tokens;
+        let token_usage = TokenUsage {
+            prompt_tokens: prompt_tokens as u32,
+            completion_tokens: completion_tokens as u32,
+            total_tokens: total_tokens as u32,
+        };
+
+        // The mock models don't report usage, so the ledger entry is flagged as an estimate
+        self.record_usage(&model_id, &token_usage, true, session_id);
+
+        let message = if notification.is_none() {
+            Some(ChatMessage {
+                role: Role::Assistant,
+                content: completion.content,
+                timestamp: Some(Utc::now()),
+                tool_calls: None,
+                tool_results: None,
+            })
+        } else {
+            None
+        };
+
+        Ok(ChatResponse {
+            message,
+            tool_calls: None,
+            token_usage: Some(token_usage),
+            model: provider_model,
+            finish_reason: completion.finish_reason,
+            is_streaming: request.stream,
+            reasoning_content: completion.thinking.clone(),
+            thinking_content: completion.thinking,
+            notification,
+        })
+    }
+
+    /// Run one completion against the (mock) provider
+    fn complete(&self, provider_model: &str, user_message: &str) -> Completion {
+        // Generate thinking content for reasoning models
+        let thinking = if is_reasoning_model(provider_model) {
+            Some(format!("Let me think about this step by step:\n\n1. First, I need to understand what the user is asking about.\n2. The user's message is: \"{}\"\n3. I should provide a thoughtful and comprehensive response.\n4. I'll structure my answer to be clear and helpful.\n\nBased on this analysis, I'll now provide my response.", user_message))
+        } else {
+            None
+        };
+
+        Completion {
+            content: self.generate_standard_response(user_message, provider_model),
+            thinking,
+            finish_reason: Some("stop".to_string()),
+        }
+    }
+
+    /// The mock reply to stream, as thinking (for reasoning models) and response
+    fn generate_response(&self, user_message: &str, model_id: &str) -> (Option<String>, String) {
+        if is_reasoning_model(model_id) {
+            // Generate thinking content for reasoning models
+            let thinking = format!("Let me think about this step by step:\n\n1. First, I need to understand what the user is asking about.\n2. The user's message is: \"{}\"\n3. I should provide a thoughtful and comprehensive response.\n4. I'll structure my answer to be clear and helpful.\n\nBased on this analysis, I'll now provide my response.", user_message);
+
+            let response = match model_id {
+                "deepseek-r1-distill-llama-70b" => {
+                    let lower_message = user_message.to_lowercase();
+                    if lower_message.contains("hello") {
+                        "Hello! I'm DeepSeek R1, a reasoning model that thinks through problems step by step. My reasoning process allows me to provide more thoughtful and detailed responses. How can I help you today with something that requires careful consideration?"
+                    } else if lower_message.contains("code") {
+                        "I'd be happy to help you with coding! As a reasoning model, I can break down complex programming problems into manageable steps, explain algorithms thoroughly, and provide well-reasoned solutions. What programming challenge would you like me to think through?"
+                    } else if lower_message.contains("solve") {
+                        "I'd be glad to help solve your problem! My reasoning capabilities allow me to approach challenges systematically, considering multiple angles and potential solutions before presenting the most effective approach. What specific problem would you like me to work through?"
+                    } else {
+                        "I understand you're asking about something that deserves careful thought. As a reasoning model, I can analyze your request methodically and provide a well-reasoned response. Could you share more details about what you'd like me to help you with?"
+                    }
+                }
+                _ => {
+                    "I'll think through your request and provide a reasoned response based on my analysis."
+                }
+            }.to_string();
+
+            (Some(thinking), response.to_string())
+        } else {
+            (
+                None,
+                self.generate_standard_response(user_message, model_id),
+            )
+        }
+    }
+
+    fn generate_standard_response(&self, user_message: &str, model_id: &str) -> String {
+        let lower_message = user_message.to_lowercase();
+
+        // Model-specific responses for DeepSeek and OpenRouter
+        match model_id {
+            "deepseek-chat" => {
+                if lower_message.contains("hello") {
+                    "Hello! I'm DeepSeek Chat, a powerful conversational AI model developed by DeepSeek. I'm optimized for helpful dialogue, reasoning, and providing accurate information across a wide range of topics. How can I assist you today?".to_string()
+                } else if lower_message.contains("code") {
+                    "I'd be happy to help you with coding! DeepSeek Chat has strong programming capabilities and can assist with code writing, debugging, explanations, and best practices. What programming language or coding challenge would you like help with?".to_string()
+                } else if lower_message.contains("math") || lower_message.contains("calculation") {
+                    "I can help with mathematical problems! I can solve equations, explain mathematical concepts, perform calculations, and help with math-related questions across various levels of difficulty. What math problem can I assist you with?".to_string()
+                } else if lower_message.contains("chinese") || lower_message.contains("中文") {
+                    "你好！我可以帮助您处理中文相关的任务。DeepSeek Chat对中文有很好的理解能力，可以用于中文对话、写作、翻译等各种场景。有什么我可以帮助您的吗？".to_string()
+                } else if lower_message.contains("help") {
+                    "I'm here to help! DeepSeek Chat can assist with:\n• Answering questions and providing information\n• Writing and content creation\n• Coding and programming\n• Mathematical reasoning\n• Translation and language tasks\n• Problem-solving and analysis\n\nHow can I help you today?".to_string()
+                } else {
+                    format!("I understand you're asking about '{}'. I'll provide you with a helpful and accurate response based on my training. Could you clarify what specific aspect you'd like me to focus on?", user_message)
+                }
+            }
+            "anthropic/claude-3.5-sonnet" => {
+                if lower_message.contains("hello") {
+                    "Hello! I'm Claude 3.5 Sonnet, Anthropic's most intelligent model. I excel at nuanced reasoning, complex analysis, and thoughtful communication. I can help with writing, analysis, coding, creative tasks, and in-depth conversation. What would you like to explore?".to_string()
+                } else if lower_message.contains("safety") || lower_message.contains("ethical") {
+                    "That's an important topic. I'm designed with strong safety principles and ethical guidelines. I aim to be helpful, harmless, and honest in all my responses. I can discuss safety considerations, ethical frameworks, and responsible AI practices. What specific aspect of safety or ethics interests you?".to_string()
+                } else if lower_message.contains("analyze") || lower_message.contains("analysis") {
+                    "I'd be delighted to help with analysis! I excel at breaking down complex topics, identifying patterns, examining arguments, and providing insights across domains. Whether it's text analysis, data interpretation, or conceptual analysis, I can approach it systematically. What would you like me to analyze?".to_string()
+                } else if lower_message.contains("help") {
+                    "I'm here to help! As Claude 3.5 Sonnet, I can assist with:\n• In-depth analysis and research\n• Writing and editing\n• Complex problem-solving\n• Creative thinking and brainstorming\n• Ethical reasoning and safety\n• Detailed explanations\n\nI strive to be thoughtful and thorough. How can I assist you?".to_string()
+                } else {
+                    format!("I see you're interested in '{}'. I'd be happy to provide a thoughtful analysis or assistance with this topic. Is there a particular aspect you'd like me to explore in depth, or do you have specific questions I can help address?", user_message)
+                }
+            }
+            "openai/gpt-4o" => {
+                if lower_message.contains("hello") {
+                    "Hello! I'm GPT-4o, OpenAI's flagship multimodal model. I can understand text, images, and audio while providing intelligent responses across a wide range of tasks. How can I assist you today?".to_string()
+                } else if lower_message.contains("vision") || lower_message.contains("image") {
+                    "I can help you with visual analysis! As GPT-4o, I can interpret images, analyze visual content, describe what I see, and help with image-related tasks. You can share images and I'll provide detailed descriptions or answer questions about them. What visual content would you like me to analyze?".to_string()
+                } else if lower_message.contains("multimodal") {
+                    "I'm designed for multimodal understanding! I can process and connect information across text, images, and audio formats. This allows me to provide more comprehensive assistance when working with different types of content. How can I help you with multimodal tasks?".to_string()
+                } else if lower_message.contains("help") {
+                    "I'm here to help! GPT-4o can assist with:\n• Text understanding and generation\n• Image analysis and description\n• Audio processing\n• Multimodal reasoning\n• Coding and programming\n• Creative tasks\n• Problem-solving\n\nWhat would you like help with?".to_string()
+                } else {
+                    format!("I understand you're asking about '{}'. As GPT-4o, I can provide intelligent responses and, if relevant, analyze any visual content you share. What specific aspect would you like me to focus on?", user_message)
+                }
+            }
+            "google/gemini-1.5-pro" => {
+                if lower_message.contains("hello") {
+                    "Hello! I'm Gemini 1.5 Pro, Google's advanced multimodal model with a massive context window. I can handle long documents, complex reasoning, and provide thoughtful analysis across many topics. How can I assist you today?".to_string()
+                } else if lower_message.contains("long") || lower_message.contains("document") {
+                    "I excel at handling long-form content! With my 2 million token context window, I can analyze extensive documents, maintain context over long conversations, and work with complex materials. Feel free to share lengthy texts or documents. What long-form content would you like me to help with?".to_string()
+                } else if lower_message.contains("reasoning") {
+                    "I'd be glad to help with reasoning tasks! Gemini 1.5 Pro is excellent at logical thinking, problem-solving, and step-by-step analysis. I can work through complex problems systematically and provide well-reasoned conclusions. What reasoning challenge would you like me to tackle?".to_string()
+                } else if lower_message.contains("help") {
+                    "I'm here to help! Gemini 1.5 Pro can assist with:\n• Long-document analysis\n• Complex reasoning\n• Multimodal understanding\n• Research and synthesis\n• Technical documentation\n• Creative writing\n\nWith my extensive context window, I can maintain focus on complex, lengthy tasks. How can I help you?".to_string()
+                } else {
+                    format!("I see you're interested in '{}'. I'm well-equipped to provide detailed analysis and maintain context over complex discussions. What specific aspect would you like me to explore in depth?", user_message)
+                }
+            }
+            _ => {
+                // Default model
+                if lower_message.contains("hello") {
+                    "Hello! I'm a mock AI assistant. How can I help you today? I can demonstrate various capabilities including text generation, question answering, and basic conversation skills.".to_string()
+                } else if lower_message.contains("code") {
+                    "I can help you with coding questions! I can write code snippets, explain programming concepts, debug issues, and suggest best practices. What programming language or topic would you like help with?".to_string()
+                } else {
+                    format!("I received your message: '{}'. This is a mock response from the {} model. In a real implementation, this would be generated by an actual AI model with access to up-to-date information and advanced reasoning capabilities.",
+                        user_message, model_id)
+                }
+            }
+        }
+    }
+
+    /// Built-in tools available to a model, optionally only those in `categories`
+    pub async fn list_tools(&self, model: &str, categories: Option<&[ToolCategory]>) -> Vec<Tool> {
+        if !self
+            .resolve_model(model)
+            .is_ok_and(|model_config| model_config.supports_tools)
+        {
+            return vec![];
+        }
+
+        crate::mcp::create_builtin_tools()
+            .into_iter()
+            .filter(|tool| categories.is_none_or(|categories| categories.contains(&tool.category)))
+            .collect()
+    }
+
+    /// Send a message with streaming response
+    pub async fn send_message_stream(
+        &self,
+        request: ChatRequest,
+    ) -> Result<impl Stream<Item = StreamChunk>> {
+        let model_config = &self.resolve_model(&request.model)?;
+        // Always hand the provider the real API id, never the alias
+        let provider_model = model_config.model.clone();
+
+        if let Some(backend) = self.backend(model_config)? {
+            let request = ChatRequest {
+                messages: provider_messages(&request),
+                system_prompt: None,
+                temperature: request.temperature.or(model_config.temperature),
+                max_tokens: request.max_tokens.or(model_config.max_tokens),
+                ..request
+            };
+            backend.wait_for_rate_limit(&request, model_config).await;
+            // Only opening the stream is retried; a failure after text has arrived ends the
+            // stream with an error chunk, since trying again would repeat what was already shown
+            let (provider, timeouts) = (backend.provider.as_ref(), self.timeouts());
+            let mut chunks = with_retry(&self.retry, || {
+                stream_in_time(provider, &request, &provider_model, timeouts)
+            })
+            .await?;
+            let chunks = async_stream::stream! {
+                while let Some(chunk) = chunks.next().await {
+                    match chunk {
+                        Ok(chunk) => yield chunk,
+                        Err(e) => {
+                            tracing::warn!("Provider stream failed: {}", e);
+                            yield StreamChunk::failed(&provider_model, &e);
+                            return;
+                        }
+                    }
+                }
+            };
+            return Ok(chunks.boxed());
+        }
+
+        // Get the last user message for context
+        let last_user_message = request
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::User))
+            .map(|msg| msg.content.clone())
+            .unwrap_or_default();
+
+        // Generate the full response
+        let (thinking, full_response) = self.generate_response(&last_user_message, &provider_model);
+
+        // Split into words for streaming effect; thinking words come first, flagged as thinking
+        let thinking_words = thinking
+            .iter()
+            .flat_map(|thinking| thinking.split_whitespace())
+            .map(|word| (true, word.to_string()));
+        let response_words = full_response
+            .split_whitespace()
+            .map(|word| (false, word.to_string()));
+        let words: Vec<(bool, String)> = thinking_words.chain(response_words).collect();
+        let words_len = words.len();
+        let model_id_clone = provider_model;
+
+        // Create a stream that yields chunks with delays
+        let stream = stream::iter(words.into_iter().enumerate())
+            .map(move |(index, (is_thinking, word))| {
+                let is_complete = index == words_len - 1;
+                let text = format!("{} ", word);
+                let (content, thinking) = if is_thinking {
+                    (None, Some(text))
+                } else {
+                    (Some(text), None)
+                };
+                let chunk = StreamChunk {
+                    delta: content.clone(),
+                    content,
+                    token_usage: None,
+                    model: model_id_clone.clone(),
+                    finish_reason: if is_complete {
+                        Some("stop".to_string())
+                    } else {
+                        None
+                    },
+                    is_complete,
+                    thinking,
+                    tool_calls: None,
+                };
+                async move {
+                    // Add delay to simulate real streaming
+                    sleep(Duration::from_millis(50)).await;
+                    chunk
+                }
+            })
+            .buffer_unordered(1);
+
+        Ok(stream.boxed())
+    }
+
+    /// Send a message with streaming response using Server-Sent Events format
+    pub async fn send_message_sse(
+        &self,
+        request: ChatRequest,
+    ) -> Result<impl Stream<Item = String>> {
+        let stream = self.send_message_stream(request).await?;
+
+        let sse_stream = stream.map(|chunk| serde_json::to_string(&chunk).unwrap_or_default());
+
+        Ok(sse_stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent_extensions::SafetyFilterExtension;
+    use crate::memory::SemanticMemory;
+    use crate::multimodal::{
+        MediaContent, MediaData, MediaMetadata, MediaType, MultimodalContent, MultimodalMessage,
+    };
+
+    fn user_request(model: &str, content: &str) -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: content.to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }],
+            model: model.to_string(),
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            agent_config: None,
+            tools: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_blocking_refuses_to_run_inside_a_runtime() {
+        let err = SimpleChatService::new_blocking().err().unwrap();
+        assert!(err.to_string().contains("initialize().await"));
+    }
+
+    #[tokio::test]
+    async fn test_alias_resolves_to_provider_model_id() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        assert_eq!(service.resolve_model("gpt-4-turbo")?.model, "gpt-4-1106-preview");
+        assert!(service.resolve_model("no-such-model").is_err());
+
+        let response = service
+            .send_message(user_request("gpt-4-turbo", "What day is it?"))
+            .await?;
+
+        assert_eq!(response.model, "gpt-4-1106-preview");
+        assert!(response.message.unwrap().content.contains("gpt-4-1106-preview"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_to_models_isolates_failures() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let messages = user_request("mock-local", "Compare us").messages;
+
+        let results = service
+            .send_to_models(
+                messages,
+                vec!["mock-local".to_string(), "no-such-model".to_string()],
+            )
+            .await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "mock-local");
+        assert!(results[0].1.as_ref().unwrap().message.is_some());
+        assert_eq!(results[1].0, "no-such-model");
+        assert!(results[1].1.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_agent_config_file_applies_to_replies_without_config() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("agent-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "goose_mode = \"Chat\"\nrequire_confirmation = true\nmax_iterations = 4\n",
+        )?;
+        let loaded = AgentConfig::load_from_file(&path);
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+        assert_eq!(loaded.goose_mode, GooseMode::Chat);
+        assert_eq!(loaded.compact_threshold, AgentConfig::default().compact_threshold);
+
+        let service = SimpleChatService::in_memory()?;
+        service.set_default_agent_config(loaded.clone())?;
+        assert_eq!(service.effective_agent_config(None), loaded);
+
+        let session = service.create_session("Configured", None)?;
+        let mut request = user_request("mock-local", "Use a tool");
+        request.tools = Some(vec![Tool {
+            name: "shell".to_string(),
+            description: "Run a command".to_string(),
+            input_schema: serde_json::json!({}),
+            is_mcp: false,
+            category: ToolCategory::General,
+        }]);
+        service
+            .agent_reply_with_planning(&session.id, request)
+            .await?;
+
+        // Chat mode from the file means the tools were never offered
+        let chain = service.get_reasoning_chain(&session.id)?;
+        let tool_selection = chain
+            .iter()
+            .find(|step| step.step_type == ReasoningStepType::ToolSelection)
+            .unwrap();
+        assert_eq!(tool_selection.content, "No tools available");
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_agent_config_is_rejected() {
+        let config = AgentConfig {
+            compact_threshold: 1.5,
+            ..AgentConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        let service = SimpleChatService::in_memory().unwrap();
+        assert!(service
+            .set_default_agent_config(AgentConfig {
+                max_iterations: 0,
+                ..AgentConfig::default()
+            })
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_removed_session_model_falls_back_to_default() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Orphaned", Some("gpt-4-turbo"))?;
+        service.remove_model("gpt-4-turbo");
+
+        let previews = service.list_sessions_with_preview()?;
+        assert!(previews[0].model_missing);
+
+        let response = service
+            .agent_reply(&session.id, user_request("", "Still there?"))
+            .await?;
+        assert_eq!(response.model, "mock-local");
+        let notification = response.notification.unwrap();
+        assert_eq!(notification.notification_type, SystemNotificationType::Warning);
+        assert!(notification.message.contains("gpt-4-turbo"));
+
+        service.set_session_model(&session.id, "mock-local")?;
+        let previews = service.list_sessions_with_preview()?;
+        assert!(!previews[0].model_missing);
+        assert_eq!(
+            previews[0].last_message,
+            response.message.map(|message| message.content)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_with_override_is_attributed_to_override_model() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Second opinion", Some("mock-local"))?;
+
+        let request = user_request("", "Explain lifetimes");
+        let original = service.agent_reply(&session.id, request.clone()).await?;
+        let mut history = request;
+        history.messages.push(original.message.unwrap());
+
+        let regenerated = service
+            .regenerate_reply(&session.id, history, Some("gpt-4-turbo"))
+            .await?;
+        assert_eq!(regenerated.model, "gpt-4-1106-preview");
+
+        // Both replies are kept, each labelled with its model; the user message isn't repeated
+        let messages = service.load_messages(&session.id)?;
+        let models: Vec<_> = messages.iter().map(|m| m.model.as_deref()).collect();
+        assert_eq!(models, vec![None, Some("mock-local"), Some("gpt-4-turbo")]);
+
+        assert_eq!(service.list_sessions()?[0].model.as_deref(), Some("mock-local"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_safety_filter_extension_blocks_message_before_it_is_sent() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        service
+            .register_extension(Box::new(SafetyFilterExtension::new()))
+            .await?;
+        let session = service.create_session("Filtered", Some("mock-local"))?;
+
+        let response = service
+            .agent_reply(&session.id, user_request("", "My password is hunter2"))
+            .await?;
+        assert!(response.message.is_none());
+        let notification = response.notification.unwrap();
+        assert_eq!(
+            notification.notification_type,
+            SystemNotificationType::ErrorMessage
+        );
+        assert!(notification.message.contains("blocked pattern: password"));
+        assert!(service.load_messages(&session.id)?.is_empty());
+
+        // Field names such as max_tokens in the request don't trip the filter
+        let response = service
+            .agent_reply(&session.id, user_request("", "Explain lifetimes"))
+            .await?;
+        assert!(response.notification.is_none());
+        assert_eq!(service.load_messages(&session.id)?.len(), 2);
+        Ok(())
+    }
+
+    /// Fails with `error` for the first `failures` calls, then answers
+    #[derive(Debug)]
+    struct FlakyProvider {
+        error: ProviderError,
+        failures: u32,
+        calls: std::sync::atomic::AtomicU32,
+        /// Temperature of every request received
+        temperatures: std::sync::Mutex<Vec<Option<f32>>>,
+    }
+
+    impl FlakyProvider {
+        fn new(error: ProviderError, failures: u32) -> Arc<Self> {
+            Arc::new(Self {
+                error,
+                failures,
+                calls: Default::default(),
+                temperatures: Default::default(),
+            })
+        }
+
+        fn calls(&self) -> u32 {
+            self.calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl CompletionProvider for FlakyProvider {
+        async fn complete(
+            &self,
+            request: &ChatRequest,
+            model: &str,
+        ) -> Result<ChatResponse, ProviderError> {
+            self.temperatures.lock().unwrap().push(request.temperature);
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call < self.failures {
+                return Err(self.error.clone());
+            }
+            Ok(ChatResponse {
+                message: Some(ChatMessage {
+                    role: Role::Assistant,
+                    content: "Recovered".to_string(),
+                    timestamp: None,
+                    tool_calls: None,
+                    tool_results: None,
+                }),
+                tool_calls: None,
+                token_usage: None,
+                model: model.to_string(),
+                finish_reason: Some("stop".to_string()),
+                is_streaming: false,
+                reasoning_content: None,
+                thinking_content: None,
+                notification: None,
+            })
+        }
+
+        async fn stream(
+            &self,
+            request: &ChatRequest,
+            model: &str,
+        ) -> Result<crate::ChatChunkStream, ProviderError> {
+            let response = self.complete(request, model).await?;
+            let chunk = StreamChunk {
+                content: response.message.map(|message| message.content),
+                delta: None,
+                token_usage: None,
+                model: response.model,
+                finish_reason: Some("stop".to_string()),
+                is_complete: true,
+                thinking: None,
+                tool_calls: None,
+            };
+            Ok(Box::pin(stream::once(async move { Ok(chunk) })))
+        }
+    }
+
+    /// Answers nothing, or streams one chunk and then nothing
+    #[derive(Debug)]
+    struct HangingProvider;
+
+    #[async_trait::async_trait]
+    impl CompletionProvider for HangingProvider {
+        async fn complete(
+            &self,
+            _request: &ChatRequest,
+            _model: &str,
+        ) -> Result<ChatResponse, ProviderError> {
+            std::future::pending().await
+        }
+
+        async fn stream(
+            &self,
+            _request: &ChatRequest,
+            model: &str,
+        ) -> Result<crate::ChatChunkStream, ProviderError> {
+            let chunk = StreamChunk {
+                content: Some("Partial".to_string()),
+                delta: Some("Partial".to_string()),
+                token_usage: None,
+                model: model.to_string(),
+                finish_reason: None,
+                is_complete: false,
+                thinking: None,
+                tool_calls: None,
+            };
+            Ok(Box::pin(
+                stream::once(async move { Ok(chunk) }).chain(stream::pending()),
+            ))
+        }
+
+        async fn health_check(&self) -> Result<(), ProviderError> {
+            std::future::pending().await
+        }
+    }
+
+    /// Answers every request with the same text
+    #[derive(Debug)]
+    struct CannedProvider(&'static str);
+
+    #[async_trait::async_trait]
+    impl CompletionProvider for CannedProvider {
+        async fn complete(
+            &self,
+            _request: &ChatRequest,
+            model: &str,
+        ) -> Result<ChatResponse, ProviderError> {
+            Ok(ChatResponse {
+                message: Some(ChatMessage {
+                    role: Role::Assistant,
+                    content: self.0.to_string(),
+                    timestamp: None,
+                    tool_calls: None,
+                    tool_results: None,
+                }),
+                tool_calls: None,
+                token_usage: None,
+                model: model.to_string(),
+                finish_reason: Some("stop".to_string()),
+                is_streaming: false,
+                reasoning_content: None,
+                thinking_content: None,
+                notification: None,
+            })
+        }
+
+        async fn stream(
+            &self,
+            _request: &ChatRequest,
+            _model: &str,
+        ) -> Result<crate::ChatChunkStream, ProviderError> {
+            Ok(Box::pin(stream::empty()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_check_asks_the_provider_and_gives_up_at_the_timeout() -> Result<()> {
+        let limit = Duration::from_millis(50);
+        let service = SimpleChatService::in_memory()?
+            .with_provider(Arc::new(HangingProvider))
+            .with_timeouts(ProviderTimeouts {
+                connect: limit,
+                request: limit,
+                stream_idle: limit,
+            });
+        let health = service.check_health("gpt-4-turbo").await;
+        assert!(!health.reachable);
+        assert!(health.error.unwrap().contains("timed out"));
+
+        let service = SimpleChatService::in_memory()?.with_provider(Arc::new(CannedProvider("")));
+        assert!(service.check_health("").await.reachable);
+
+        // A hosted model without a key can't be reached
+        let mut service = SimpleChatService::in_memory()?;
+        service.require_credentials = true;
+        let health = service.check_health("gpt-4-turbo").await;
+        assert!(!health.reachable);
+        assert!(health.error.unwrap().contains("OPENAI_API_KEY"));
+        assert!(service.check_health("mock-local").await.reachable);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hosted_model_without_a_key_is_a_no_credentials_error() -> Result<()> {
+        let mut service = SimpleChatService::in_memory()?;
+        service.require_credentials = true;
+
+        let err = service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<ProviderError>().unwrap();
+        assert!(err.is_no_credentials());
+        let messages = user_request("gpt-4-turbo", "Hi").messages;
+        let comparisons = service
+            .compare_models(messages, vec!["gpt-4-turbo".to_string()])
+            .await;
+        let response = comparisons[0].response.as_ref().unwrap();
+        assert!(response.notification.as_ref().unwrap().is_no_credentials());
+        // Local models never need a key
+        assert!(service
+            .send_message(user_request("mock-local", "Hi"))
+            .await?
+            .message
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_models_are_answered_by_their_own_providers_backend() -> Result<()> {
+        let service = SimpleChatService::in_memory()?
+            .with_backend("openai", Arc::new(CannedProvider("Hello from OpenAI")));
+
+        let response = service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await?;
+        assert_eq!(response.message.unwrap().content, "Hello from OpenAI");
+        // A model of another provider is still answered by the mock models
+        let response = service
+            .send_message(user_request("mock-local", "Hi"))
+            .await?;
+        assert_ne!(response.message.unwrap().content, "Hello from OpenAI");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hung_provider_times_out_instead_of_stalling() -> Result<()> {
+        let limit = Duration::from_millis(50);
+        let service = SimpleChatService::in_memory()?
+            .with_provider(Arc::new(HangingProvider))
+            .with_retry_config(RetryConfig {
+                max_attempts: 1,
+                ..RetryConfig::default()
+            })
+            .with_timeouts(ProviderTimeouts {
+                connect: limit,
+                request: limit,
+                stream_idle: limit,
+            });
+
+        let err = service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<ProviderError>().unwrap();
+        assert_eq!(err.code.as_deref(), Some(TIMED_OUT));
+        assert_eq!(err.kind, ProviderErrorKind::Timeout);
+
+        // The stream keeps what arrived and then reports the timeout once the provider goes quiet
+        let chunks = service
+            .send_message_stream(user_request("gpt-4-turbo", "Hi"))
+            .await?
+            .collect::<Vec<_>>();
+        let chunks = tokio::time::timeout(Duration::from_secs(5), chunks).await?;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].content.as_deref(), Some("Partial"));
+        assert!(!chunks[0].is_complete);
+        assert!(chunks[1].is_complete);
+        assert_eq!(chunks[1].finish_reason.as_deref(), Some(TIMED_OUT));
+        assert!(chunks[1]
+            .content
+            .as_deref()
+            .is_some_and(|content| content.contains("Provider stream timed out")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transient_provider_errors_are_retried_and_auth_errors_are_not() -> Result<()> {
+        let retry = RetryConfig {
+            max_attempts: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            jitter: 0.0,
+        };
+        let unavailable = ProviderError {
+            kind: ProviderErrorKind::Server,
+            message: "Provider returned 503 Service Unavailable".to_string(),
+            code: Some("503".to_string()),
+            retry_after: None,
+        };
+
+        let provider = FlakyProvider::new(unavailable.clone(), 2);
+        let service = SimpleChatService::in_memory()?
+            .with_provider(provider.clone())
+            .with_retry_config(retry);
+        let response = service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await?;
+        assert_eq!(response.message.unwrap().content, "Recovered");
+        assert_eq!(provider.calls(), 3);
+
+        let provider = FlakyProvider::new(unavailable, 2);
+        let service = SimpleChatService::in_memory()?
+            .with_provider(provider.clone())
+            .with_retry_config(retry);
+        let chunks: Vec<StreamChunk> = service
+            .send_message_stream(user_request("gpt-4-turbo", "Hi"))
+            .await?
+            .collect()
+            .await;
+        assert_eq!(chunks[0].content.as_deref(), Some("Recovered"));
+        assert_eq!(provider.calls(), 3);
+
+        // A bad API key won't get better by waiting
+        let unauthorized = ProviderError {
+            kind: ProviderErrorKind::Authentication,
+            message: "Provider returned 401 Unauthorized".to_string(),
+            code: Some("401".to_string()),
+            retry_after: None,
+        };
+        let provider = FlakyProvider::new(unauthorized, 2);
+        let service = SimpleChatService::in_memory()?
+            .with_provider(provider.clone())
+            .with_retry_config(retry);
+        let err = service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401"));
+        assert_eq!(provider.calls(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_title_asks_the_provider_and_falls_back_to_a_snippet() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("", Some("gpt-4-turbo"))?;
+        service.append_message(&session.id, Role::User, "Help me plan a garden\nfor shade")?;
+        assert_eq!(
+            service.auto_title_session(&session.id).await?,
+            "Help me plan a garden"
+        );
+
+        let provider =
+            FlakyProvider::new(ProviderError::new(ProviderErrorKind::Network, "unused"), 0);
+        let service = service.with_provider(provider.clone());
+        assert_eq!(service.auto_title_session(&session.id).await?, "Recovered");
+        assert_eq!(service.list_sessions()?[0].title, "Recovered");
+        assert_eq!(provider.calls(), 1);
+
+        let empty = service.create_session("", None)?;
+        assert!(service.auto_title_session(&empty.id).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_sampling_settings_override_model_defaults() -> Result<()> {
+        let provider =
+            FlakyProvider::new(ProviderError::new(ProviderErrorKind::Network, "unused"), 0);
+        let service = SimpleChatService::in_memory()?.with_provider(provider.clone());
+        let mut model = service.resolve_model("gpt-4-turbo")?;
+        model.temperature = Some(0.9);
+        service.add_model(model);
+
+        let mut request = user_request("gpt-4-turbo", "Hi");
+        request.temperature = Some(0.0);
+        service.send_message(request).await?;
+        service
+            .send_message(user_request("gpt-4-turbo", "Hi"))
+            .await?;
+
+        assert_eq!(
+            *provider.temperatures.lock().unwrap(),
+            vec![Some(0.0), Some(0.9)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_requests_are_answered_from_the_cache() -> Result<()> {
+        let provider =
+            FlakyProvider::new(ProviderError::new(ProviderErrorKind::Network, "unused"), 0);
+        let service = SimpleChatService::in_memory()?.with_provider(provider.clone());
+        let mut request = user_request("gpt-4-turbo", "Hi");
+        request.temperature = Some(0.0);
+
+        let first = service.send_message(request.clone()).await?;
+        let second = service.send_message(request.clone()).await?;
+        let content = |response: ChatResponse| response.message.unwrap().content;
+        assert_eq!(content(second), content(first));
+        assert_eq!(provider.temperatures.lock().unwrap().len(), 1);
+
+        service.clear_cache();
+        service.send_message(request).await?;
+        assert_eq!(provider.temperatures.lock().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_tools_filters_by_category() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+
+        let all = service.list_tools("deepseek-chat", None).await;
+        assert!(all.len() > 1);
+
+        let network = service
+            .list_tools("deepseek-chat", Some(&[ToolCategory::Network]))
+            .await;
+        let names: Vec<_> = network.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, vec!["web_search"]);
+
+        // Models without tool support get nothing regardless of the filter
+        assert!(service.list_tools("mock-local", None).await.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_is_refused_once_session_token_ceiling_is_reached() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Budgeted", Some("mock-local"))?;
+        service.usage_ledger.record(&UsageRecord {
+            model: "mock-local".to_string(),
+            provider: "local".to_string(),
+            prompt_tokens: 800,
+            completion_tokens: 200,
+            estimated_cost: 0.0,
+            is_estimated: true,
+            timestamp: Utc::now(),
+            session_id: Some(session.id.clone()),
+        })?;
+
+        let mut request = user_request("mock-local", "One more question");
+        request.agent_config = Some(AgentConfig {
+            max_session_tokens: Some(1000),
+            ..AgentConfig::default()
+        });
+        let response = service.agent_reply(&session.id, request.clone()).await?;
+        assert!(response.message.is_none());
+        let notification = response.notification.unwrap();
+        assert_eq!(notification.notification_type, SystemNotificationType::ErrorMessage);
+        assert!(notification.message.contains("token limit"));
+        assert!(service.load_messages(&session.id)?.is_empty());
+
+        // Raising the limit lets the session continue, and the new turn counts towards it
+        request.agent_config.as_mut().unwrap().max_session_tokens = Some(10_000);
+        let response = service.agent_reply(&session.id, request).await?;
+        assert!(response.message.is_some());
+        assert_eq!(service.usage_ledger.session_totals(&session.id)?.requests, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_window_limits_messages_sent_to_provider() {
+        let mut request = user_request("mock-local", "question 0");
+        request.system_prompt = Some("You are terse.".to_string());
+        let message = |role: Role, content: String| ChatMessage {
+            role,
+            content,
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        };
+        for turn in 1..10 {
+            request
+                .messages
+                .push(message(Role::Assistant, format!("answer {}", turn - 1)));
+            request
+                .messages
+                .push(message(Role::User, format!("question {}", turn)));
+        }
+        request.agent_config = Some(AgentConfig {
+            history_window: Some(4),
+            ..AgentConfig::default()
+        });
+
+        let sent = provider_messages(&request);
+        let contents: Vec<&str> = sent.iter().map(|msg| msg.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["You are terse.", "answer 7", "question 8", "answer 8", "question 9"]
+        );
+        // The request itself still carries the whole conversation
+        assert_eq!(request.messages.len(), 19);
+    }
+
+    #[tokio::test]
+    async fn test_auto_compact_summarizes_oldest_messages_past_the_threshold() -> Result<()> {
+        let auth = ProviderError::new(ProviderErrorKind::Authentication, "bad key");
+        let service = SimpleChatService::in_memory()?.with_provider(FlakyProvider::new(auth, 0));
+        service.set_trace_enabled(true);
+        let mut request = user_request("mock-local", "latest question");
+        let message = |role: Role, content: String| ChatMessage {
+            role,
+            content,
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        };
+        let mut history = vec![message(Role::System, "Stay on topic.".to_string())];
+        for turn in 0..5 {
+            history.push(message(Role::User, format!("{} {}", turn, "x".repeat(100))));
+        }
+        request.messages.splice(0..0, history);
+        // mock-local has a 4096 token window, so this leaves room for about 40 tokens
+        request.agent_config = Some(AgentConfig {
+            compact_threshold: 0.01,
+            messages_to_keep: 1,
+            ..AgentConfig::default()
+        });
+
+        let original = request.clone();
+        service.compact_context("session-1", &mut request).await;
+        let roles: Vec<&Role> = request.messages.iter().map(|msg| &msg.role).collect();
+        assert_eq!(roles, vec![&Role::System, &Role::System, &Role::User]);
+        assert_eq!(
+            request.messages[1].content,
+            "Summary of 5 earlier messages:\nRecovered"
+        );
+        assert_eq!(request.messages[2].content, "latest question");
+        let trace = service.get_trace("session-1");
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0].kind, TraceKind::Compaction);
+
+        // Under the threshold nothing is dropped
+        let mut short = user_request("mock-local", "hi");
+        service.compact_context("session-1", &mut short).await;
+        assert_eq!(short.messages.len(), 1);
+
+        // A provider that fails leaves a note in place of the messages
+        let auth = ProviderError::new(ProviderErrorKind::Authentication, "bad key");
+        let service =
+            SimpleChatService::in_memory()?.with_provider(FlakyProvider::new(auth, u32::MAX));
+        let mut request = original;
+        service.compact_context("session-1", &mut request).await;
+        assert_eq!(
+            request.messages[1].content,
+            "5 earlier messages were dropped to fit the context window."
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compact_messages_keeps_tool_results_for_the_summary_and_falls_back_to_a_note() {
+        let message = |role: Role, content: &str| ChatMessage {
+            role,
+            content: content.to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        };
+        let mut tool_turn = message(Role::Tool, "");
+        tool_turn.tool_results = Some(vec![ToolResult {
+            tool_call_id: "call-1".to_string(),
+            result: serde_json::json!("72F and sunny"),
+            error: None,
+        }]);
+        let conversation = vec![
+            message(Role::User, "weather?"),
+            tool_turn,
+            message(Role::Assistant, "It is sunny."),
+            message(Role::User, "thanks"),
+        ];
+
+        let mut messages = conversation.clone();
+        let compacted = compact_messages(&mut messages, 2, |dropped| async move {
+            assert_eq!(dropped.len(), 2);
+            assert!(summary_transcript(&dropped).contains("Tool result call-1: \"72F and sunny\""));
+            Some("Checked the weather: 72F and sunny.".to_string())
+        })
+        .await;
+        assert_eq!(compacted, 2);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0].content,
+            "Summary of 2 earlier messages:\nChecked the weather: 72F and sunny."
+        );
+        assert_eq!(messages[2].content, "thanks");
+
+        let mut messages = conversation;
+        compact_messages(&mut messages, 1, |_| async { None }).await;
+        assert_eq!(
+            messages[0].content,
+            "3 earlier messages were dropped to fit the context window."
+        );
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_session_stops_a_running_reply() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        assert!(!service.cancel_session("session-1"));
+
+        let (response, cancelled) = tokio::join!(
+            service.cancellable("session-1", "mock-local", std::future::pending()),
+            async {
+                tokio::task::yield_now().await;
+                service.cancel_session("session-1")
+            }
+        );
+        assert!(cancelled);
+        let response = response?;
+        assert!(response.message.is_none());
+        assert_eq!(response.finish_reason.as_deref(), Some("cancelled"));
+
+        // Nothing is left running, and later replies get a fresh token
+        assert!(!service.cancel_session("session-1"));
+        let session = service.create_session("After cancel", Some("mock-local"))?;
+        let response = service
+            .agent_reply(&session.id, user_request("", "hello"))
+            .await?;
+        assert!(response.message.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agent_reply_is_stored_with_its_reasoning() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Thinking", Some("deepseek-r1-distill-llama-70b"))?;
+        let response = service
+            .agent_reply(&session.id, user_request("", "Why is the sky blue?"))
+            .await?;
+
+        let stored = service.load_messages(&session.id)?.pop().unwrap();
+        assert!(matches!(
+            &stored.parts[..],
+            [MessageContent::Reasoning { .. }, MessageContent::Text { .. }]
+        ));
+        // Reasoning is kept out of the display text
+        assert_eq!(stored.content, response.message.unwrap().content);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_db_path_persists_sessions_between_opens() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dioxus-chat-db-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("nested").join("chat_sessions.db");
+
+        let session = SimpleChatService::with_db_path(&path)?.create_session("Kept", None)?;
+        let reopened = SimpleChatService::with_db_path(&path)?;
+        assert_eq!(reopened.list_sessions()?[0].id, session.id);
+
+        let scratch = SimpleChatService::with_db_path(":memory:")?;
+        assert!(scratch.list_sessions()?.is_empty());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    /// Holds each request until `release` is notified, signalling `started` when one arrives,
+    /// and records the messages of every request
+    #[derive(Debug, Default)]
+    struct GatedProvider {
+        started: tokio::sync::Notify,
+        release: tokio::sync::Notify,
+        received: std::sync::Mutex<Vec<Vec<ChatMessage>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl CompletionProvider for GatedProvider {
+        async fn complete(
+            &self,
+            request: &ChatRequest,
+            model: &str,
+        ) -> Result<ChatResponse, ProviderError> {
+            self.received.lock().unwrap().push(request.messages.clone());
+            self.started.notify_one();
+            self.release.notified().await;
+            CannedProvider("Done thinking")
+                .complete(request, model)
+                .await
+        }
+
+        async fn stream(
+            &self,
+            _request: &ChatRequest,
+            _model: &str,
+        ) -> Result<crate::ChatChunkStream, ProviderError> {
+            Ok(Box::pin(stream::empty()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_session_model_mid_reply_emits_model_change() -> Result<()> {
+        let provider = Arc::new(GatedProvider::default());
+        let service = SimpleChatService::in_memory()?.with_provider(provider.clone());
+        let session = service.create_session("Switching", Some("mock-local"))?;
+        let mut events = service.subscribe_session_events();
+        let chat_request = |content: &str| ChatRequest {
+            agent_config: Some(AgentConfig {
+                goose_mode: GooseMode::Chat,
+                ..AgentConfig::default()
+            }),
+            ..user_request("", content)
+        };
+
+        let reply = service.agent_reply(&session.id, chat_request("Before the switch"));
+        let switch = async {
+            provider.started.notified().await;
+            let switched = service.set_session_model(&session.id, "gpt-4-turbo");
+            provider.release.notify_one();
+            switched
+        };
+        let (response, switched) = tokio::join!(reply, switch);
+        switched?;
+        assert_eq!(response?.model, "mock-local");
+
+        // The session's own mode, not the default Agent mode
+        assert_eq!(
+            events.try_recv()?,
+            SessionEvent::ModelChange {
+                session_id: session.id.clone(),
+                model: "gpt-4-turbo".to_string(),
+                mode: GooseMode::Chat,
+            }
+        );
+        let messages = service.load_messages(&session.id)?;
+        let separator = messages
+            .iter()
+            .find(|message| message.role == Role::System)
+            .unwrap();
+        assert_eq!(separator.content, "Switched to gpt-4-turbo, Chat mode");
+
+        // The interrupted reply is still the preview, and the separator isn't sent back as history
+        let previews = service.list_sessions_with_preview()?;
+        assert_eq!(previews[0].last_message.as_deref(), Some("Done thinking"));
+        let mut request = chat_request("After the switch");
+        request.messages = messages
+            .iter()
+            .map(|message| ChatMessage {
+                role: message.role.clone(),
+                content: message.content.clone(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            })
+            .chain(request.messages)
+            .collect();
+        let reply = service.agent_reply(&session.id, request);
+        let release = async {
+            provider.started.notified().await;
+            provider.release.notify_one();
+        };
+        let (response, ()) = tokio::join!(reply, release);
+        response?;
+        let sent = provider.received.lock().unwrap().pop().unwrap();
+        assert!(sent.iter().all(|message| message.role != Role::System));
+        assert_eq!(sent.len(), 3);
+        Ok(())
+    }
+
+    /// Asks for `shell` until a tool response comes back, then repeats the response
+    #[derive(Debug)]
+    struct ShellCallingProvider;
+
+    #[async_trait::async_trait]
+    impl CompletionProvider for ShellCallingProvider {
+        async fn complete(
+            &self,
+            request: &ChatRequest,
+            model: &str,
+        ) -> Result<ChatResponse, ProviderError> {
+            let last = request.messages.last().unwrap();
+            let (content, tool_calls) = match last.role {
+                Role::Tool => (last.content.clone(), None),
+                _ => (
+                    String::new(),
+                    Some(vec![ToolCall {
+                        id: "call-1".to_string(),
+                        name: "shell".to_string(),
+                        arguments: serde_json::json!({ "command": "ls" }),
+                    }]),
+                ),
+            };
+            Ok(ChatResponse {
+                message: Some(ChatMessage {
+                    role: Role::Assistant,
+                    content,
+                    timestamp: None,
+                    tool_calls: tool_calls.clone(),
+                    tool_results: None,
+                }),
+                tool_calls,
+                token_usage: None,
+                model: model.to_string(),
+                finish_reason: Some("stop".to_string()),
+                is_streaming: false,
+                reasoning_content: None,
+                thinking_content: None,
+                notification: None,
+            })
+        }
+
+        async fn stream(
+            &self,
+            _request: &ChatRequest,
+            _model: &str,
+        ) -> Result<crate::ChatChunkStream, ProviderError> {
+            Ok(Box::pin(stream::empty()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_message_events_ask_for_confirmation() -> Result<()> {
+        let service = SimpleChatService::in_memory()?.with_provider(Arc::new(ShellCallingProvider));
+        let session = service.create_session("Careful", Some("mock-local"))?;
+        let request = ChatRequest {
+            agent_config: Some(AgentConfig {
+                require_confirmation: true,
+                ..AgentConfig::default()
+            }),
+            tools: Some(crate::mcp::create_builtin_tools()),
+            ..user_request("mock-local", "List the files")
+        };
+        let executor = crate::agent_loop::BuiltinToolExecutor::default();
+
+        let mut events =
+            std::pin::pin!(service.session_message_events(&session.id, request, &executor));
+        let Some(AgentEvent::ToolConfirmationRequest { tool_call }) = events.next().await else {
+            anyhow::bail!("expected a confirmation request");
+        };
+        assert_eq!(tool_call.name, "shell");
+        assert!(service.confirm_tool(&session.id, &tool_call.id, false));
+
+        let rest: Vec<AgentEvent> = events.collect().await;
+        assert_eq!(
+            rest[0],
+            AgentEvent::Content {
+                text: "Error: The user declined to run shell".to_string()
+            }
+        );
+        assert!(matches!(rest.last(), Some(AgentEvent::Done { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_mode_picks_chat_or_agent_per_turn() -> Result<()> {
+        let with_tools = |content: &str| ChatRequest {
+            tools: Some(crate::mcp::create_builtin_tools()),
+            ..user_request("mock-local", content)
+        };
+        // Nothing to act with, so even a request for action is answered in chat
+        assert_eq!(
+            auto_mode(&user_request("mock-local", "Run the tests")),
+            GooseMode::Chat
+        );
+        assert_eq!(
+            auto_mode(&with_tools("Thanks, that helps!")),
+            GooseMode::Chat
+        );
+        assert_eq!(
+            auto_mode(&with_tools("Can you search for the latest Rust releases?")),
+            GooseMode::Agent
+        );
+        assert_eq!(
+            auto_mode(&with_tools("Use web search please")),
+            GooseMode::Agent
+        );
+
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Auto", Some("mock-local"))?;
+        let mut events = service.subscribe_session_events();
+        let request = ChatRequest {
+            agent_config: Some(AgentConfig {
+                goose_mode: GooseMode::Auto,
+                ..AgentConfig::default()
+            }),
+            ..with_tools("Hello there")
+        };
+        service.agent_reply(&session.id, request).await?;
+
+        assert_eq!(
+            events.try_recv()?,
+            SessionEvent::ModelChange {
+                session_id: session.id.clone(),
+                model: "mock-local".to_string(),
+                mode: GooseMode::Chat,
+            }
+        );
+        Ok(())
+    }
+
+    async fn wait_for_file(service: &SimpleChatService, file_id: &str) -> FileProcessingResult {
+        loop {
+            let result = service.file_status(file_id).expect("file is tracked");
+            if matches!(result.status, FileStatus::Completed | FileStatus::Failed { .. }) {
+                return result;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_processing_reports_completion_and_failure() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+
+        let notes = service.upload_file("notes.txt", "text/plain", b"meeting notes".to_vec());
+        let garbled = service.upload_file("notes2.txt", "text/plain", vec![0xff, 0xfe, 0xfd]);
+        // Nothing has run yet on the test's single thread
+        assert_eq!(service.file_status(&notes).unwrap().status, FileStatus::Pending);
+
+        let done = wait_for_file(&service, &notes).await;
+        assert_eq!(done.status, FileStatus::Completed);
+        assert_eq!(done.extracted_text.as_deref(), Some("meeting notes"));
+
+        let failed = wait_for_file(&service, &garbled).await;
+        assert!(
+            matches!(failed.status, FileStatus::Failed { ref error } if error.contains("UTF-8")),
+            "{:?}",
+            failed.status
+        );
+        assert_eq!(failed.extracted_text, None);
+
+        assert!(service.delete_file(&notes).await);
+        assert_eq!(service.file_status(&notes), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extracted_upload_text_feeds_the_knowledge_base() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Docs", None)?;
+        let manual = service.upload_file(
+            "manual.md",
+            "text/markdown",
+            b"Restart the worker with systemctl".to_vec(),
+        );
+        let photo = service.upload_file("photo.png", "image/png", vec![0u8; 8]);
+        wait_for_file(&service, &manual).await;
+        wait_for_file(&service, &photo).await;
+
+        assert_eq!(service.ingest_file(&session.id, &manual).await?, 1);
+        let results = service
+            .search_knowledge(&session.id, "restart the worker", 1)
+            .await?;
+        assert_eq!(results[0].chunk.metadata.title, "manual.md");
+        let error = service.ingest_file(&session.id, &photo).await.unwrap_err();
+        assert_eq!(error.to_string(), "photo.png has no text to ingest");
+        Ok(())
+    }
+
+    fn completion(content: &str, finish_reason: &str) -> Completion {
+        Completion {
+            content: content.to_string(),
+            thinking: None,
+            finish_reason: Some(finish_reason.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_empty_stop_response_is_retried_then_reported() {
+        let mut calls = 0;
+        let (_, notification) = complete_with_retry(|| {
+            calls += 1;
+            completion("  \n", "stop")
+        });
+        assert_eq!(calls, 2);
+        let notification = notification.expect("empty reply must produce a notification");
+        assert_eq!(notification.notification_type, SystemNotificationType::ErrorMessage);
+        assert!(notification.message.contains("no content (stop)"));
+
+        let mut calls = 0;
+        let (reply, notification) = complete_with_retry(|| {
+            calls += 1;
+            if calls == 1 {
+                completion("", "stop")
+            } else {
+                completion("Hello again", "stop")
+            }
+        });
+        assert!(notification.is_none());
+        assert_eq!(reply.content, "Hello again");
+    }
+
+    #[test]
+    fn test_empty_content_filter_response_is_not_retried() {
+        let mut calls = 0;
+        let (_, notification) = complete_with_retry(|| {
+            calls += 1;
+            completion("", "content_filter")
+        });
+        assert_eq!(calls, 1);
+        assert!(notification.unwrap().message.contains("content filter"));
+    }
+
+    struct BannedPhraseModerator;
+
+    #[async_trait::async_trait]
+    impl crate::moderation::ModerationProvider for BannedPhraseModerator {
+        async fn check(&self, content: &str, _direction: ModerationDirection) -> Result<ModerationVerdict> {
+            if content.contains("banned phrase") {
+                Ok(ModerationVerdict::Block("banned phrase".to_string()))
+            } else {
+                Ok(ModerationVerdict::Allow)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moderator_refuses_turn_with_banned_phrase() -> Result<()> {
+        let service = SimpleChatService::in_memory()?.with_moderation(Moderation::new(
+            std::sync::Arc::new(BannedPhraseModerator),
+            Duration::from_secs(1),
+        ));
+        let session = service.create_session("Moderated", None)?;
+
+        let mut request = user_request("mock-local", "please say the banned phrase");
+        let response = service.agent_reply(&session.id, request.clone()).await?;
+        assert!(response.message.is_none());
+        let notification = response.notification.unwrap();
+        assert_eq!(notification.notification_type, SystemNotificationType::ErrorMessage);
+        assert!(service.load_messages(&session.id)?.is_empty());
+
+        request.messages[0].content = "hello".to_string();
+        let response = service.agent_reply(&session.id, request).await?;
+        assert!(response.message.is_some());
+        assert_eq!(service.load_messages(&session.id)?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_plan_steps_parses_model_json_and_falls_back_to_keywords() -> Result<()> {
+        let tools = vec!["file_editor".to_string(), "web_search".to_string()];
+        let output = "Here is the plan:\n```json\n{\"estimated_duration_seconds\": 42, \"steps\": [\
+            {\"description\": \"Find docs\", \"tool_name\": \"web_search\", \"parameters\": {\"query\": \"tokio\"}},\
+            {\"description\": \"Summarize\", \"tool_name\": null, \"dependencies\": [0]}]}\n```";
+        let plan = parse_plan(output, &tools).unwrap();
+        assert_eq!(plan.estimated_duration, Some(42));
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].parameters["query"], "tokio");
+        assert_eq!(plan.steps[1].dependencies, vec![0]);
+
+        // Unknown tools and dependency cycles make the plan unusable
+        assert!(parse_plan(&output.replace("web_search", "rm_rf"), &tools).is_none());
+        assert!(parse_plan(&output.replace("[0]", "[1]"), &tools).is_none());
+        assert!(parse_plan("I'd search the web first.", &tools).is_none());
+
+        // The plan comes from the model's provider
+        let service =
+            SimpleChatService::in_memory()?.with_provider(Arc::new(CannedProvider(output)));
+        let (plan, from_model) = service
+            .plan_steps("mock-local", "look up tokio", &tools)
+            .await;
+        assert!(from_model);
+        assert_eq!(plan.steps[0].description, "Find docs");
+
+        // Without a provider planning falls back to keywords
+        let service = SimpleChatService::in_memory()?;
+        let (plan, from_model) = service
+            .plan_steps("mock-local", "search for the config file", &tools)
+            .await;
+        assert!(!from_model);
+        assert_eq!(plan.estimated_duration, None);
+        let planned_tools: Vec<_> = plan.steps.iter().map(|s| s.tool_name.as_deref()).collect();
+        assert_eq!(
+            planned_tools,
+            vec![Some("file_editor"), Some("web_search"), None]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_survives_a_restart_and_counts_accesses() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dioxus-chat-memory-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("chat.db");
+
+        let service = SimpleChatService::with_db_path(&path)?;
+        let session = service.create_session("Remembering", None)?;
+        let entry = service.add_memory(&session.id, "The user's cat is called Miso", 0.9)?;
+        service.add_memory(&session.id, "Prefers metric units", 0.5)?;
+        service.persist_memory(
+            &session.id,
+            &SessionMemory {
+                facts: vec![SemanticMemory {
+                    id: "fact-1".to_string(),
+                    concept: "Miso".to_string(),
+                    knowledge: "A tabby cat".to_string(),
+                    confidence: 0.8,
+                }],
+                ..SessionMemory::default()
+            },
+        )?;
+        drop(service);
+
+        let service = SimpleChatService::with_db_path(&path)?;
+        let memory = service.load_memory(&session.id)?;
+        assert_eq!(memory.entries.len(), 2);
+        assert_eq!(memory.facts[0].knowledge, "A tabby cat");
+
+        // Replying recalls the matching entry, which counts as an access
+        service
+            .agent_reply(
+                &session.id,
+                user_request("mock-local", "What is my cat called?"),
+            )
+            .await?;
+        let found = service.search_memory(&session.id, "cat", 10)?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, entry.id);
+        assert_eq!(found[0].access_count, 2);
+        assert!(found[0].last_accessed.is_some());
+
+        let stored = service.load_memory(&session.id)?;
+        let stored = stored.entries.iter().find(|e| e.id == entry.id).unwrap();
+        assert_eq!(stored.access_count, 2);
+        drop(service);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_semantic_memory_search_ranks_by_similarity() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Semantic", None)?;
+        service.add_memory(&session.id, "Deploys use the staging cluster", 0.5)?;
+        let best = service.add_memory(&session.id, "Staging cluster runs Kubernetes", 0.5)?;
+        let lunch = service.add_memory(&session.id, "Lunch is at noon", 0.5)?;
+
+        let found = service
+            .search_memory_semantic(&session.id, "which cluster runs kubernetes", 2)
+            .await?;
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0.id, best.id);
+        assert!(found[0].1 > found[1].1);
+        assert_eq!(found[0].0.access_count, 1);
+        assert!(found.iter().all(|(entry, _)| entry.id != lunch.id));
+
+        // Embeddings are stored once computed
+        let stored = service.memory.entries_with_embeddings(&session.id)?;
+        assert!(stored.iter().all(|(_, embedding)| embedding.is_some()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_documents_are_searchable_only_in_their_session() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Docs", None)?;
+        let other = service.create_session("Other", None)?;
+
+        let chunks = service
+            .ingest_document(&session.id, "Runbook", "Restart the worker with systemctl")
+            .await?;
+        assert_eq!(chunks, 1);
+        assert!(service
+            .ingest_document("missing", "Runbook", "text")
+            .await
+            .is_err());
+
+        let results = service
+            .search_knowledge(&session.id, "how do I restart the worker", 3)
+            .await?;
+        assert_eq!(results[0].chunk.metadata.title, "Runbook");
+        assert!(service
+            .search_knowledge(&other.id, "restart the worker", 3)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    /// Vision provider that records the OpenAI messages it would have sent
+    #[derive(Debug, Default)]
+    struct RecordingVision {
+        sent: Mutex<Vec<Vec<serde_json::Value>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl VisionProvider for RecordingVision {
+        async fn complete(
+            &self,
+            request: &MultimodalChatRequest,
+            model: &str,
+        ) -> Result<ChatResponse> {
+            let messages = crate::providers::openai::multimodal_messages(request);
+            self.sent.lock().unwrap().push(messages);
+            Ok(ChatResponse {
+                message: Some(ChatMessage {
+                    role: Role::Assistant,
+                    content: format!("A cat, says {}", model),
+                    timestamp: None,
+                    tool_calls: None,
+                    tool_results: None,
+                }),
+                tool_calls: None,
+                token_usage: None,
+                model: model.to_string(),
+                finish_reason: Some("stop".to_string()),
+                is_streaming: false,
+                reasoning_content: None,
+                thinking_content: None,
+                notification: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_images_reach_vision_models_as_image_url_parts() -> Result<()> {
+        let vision = Arc::new(RecordingVision::default());
+        let service = SimpleChatService::in_memory()?.with_vision_provider(vision.clone());
+        let request = |model: &str| MultimodalChatRequest {
+            messages: vec![MultimodalMessage {
+                role: Role::User,
+                content: vec![
+                    MultimodalContent::Text("What is this?".to_string()),
+                    MultimodalContent::Media(MediaContent {
+                        media_type: MediaType::Image,
+                        content: MediaData::Raw(b"png".to_vec()),
+                        metadata: MediaMetadata {
+                            filename: Some("cat.png".to_string()),
+                            mime_type: Some("image/png".to_string()),
+                            size: None,
+                            dimensions: None,
+                            created_at: None,
+                        },
+                    }),
+                ],
+                timestamp: None,
+            }],
+            model: model.to_string(),
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            agent_config: None,
+            tools: None,
+            multimodal_config: None,
+        };
+
+        let response = service
+            .send_multimodal_message(request("openai/gpt-4o"))
+            .await?;
+        assert_eq!(response.message.unwrap().content, "A cat, says openai/gpt-4o");
+        let sent = vision.sent.lock().unwrap().clone();
+        assert_eq!(sent[0][0]["content"][0]["text"], "What is this?");
+        assert_eq!(sent[0][0]["content"][1]["type"], "image_url");
+        assert_eq!(
+            sent[0][0]["content"][1]["image_url"]["url"],
+            "data:image/png;base64,cG5n"
+        );
+
+        // Without vision the model only hears about the image
+        let response = service
+            .send_multimodal_message(request("mock-local"))
+            .await?;
+        assert!(response.message.unwrap().content.contains("[Image: cat.png]"));
+        assert_eq!(vision.sent.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    /// Transcriber that hears the same words in every recording
+    #[derive(Debug)]
+    struct FixedTranscriber;
+
+    #[async_trait::async_trait]
+    impl TranscriptionProvider for FixedTranscriber {
+        async fn transcribe(
+            &self,
+            audio: &[u8],
+            mime_type: &str,
+            _language: Option<&str>,
+        ) -> Result<String> {
+            let bytes = audio.len();
+            Ok(format!("book a table ({bytes} bytes of {mime_type})"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audio_parts_are_stored_with_their_transcript() -> Result<()> {
+        let service =
+            SimpleChatService::in_memory()?.with_transcription_provider(Arc::new(FixedTranscriber));
+        let session = service.create_session("Voice", None)?;
+        // Older clients send no duration or size
+        let audio: MessageContent = serde_json::from_value(serde_json::json!({
+            "audio": {
+                "name": "memo.webm",
+                "content": "data:audio/webm;base64,cmVjb3JkaW5n",
+                "mime_type": "audio/webm",
+            }
+        }))?;
+
+        let message = service
+            .append_user_message_parts(&session.id, vec![audio])
+            .await?;
+        assert!(matches!(
+            &message.parts[0],
+            MessageContent::Audio {
+                duration: None,
+                size: None,
+                ..
+            }
+        ));
+        assert_eq!(
+            message.content,
+            "[Transcript of memo.webm]\nbook a table (9 bytes of audio/webm)"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_steps_are_returned_in_order() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Planning", None)?;
+
+        service.add_reasoning_step(
+            &session.id,
+            ReasoningStep::new(ReasoningStepType::Analysis, "understand the question", 0.9),
+        )?;
+        service.add_reasoning_step(
+            &session.id,
+            ReasoningStep::new(ReasoningStepType::Reflection, "answer looks right", 1.5),
+        )?;
+
+        let chain = service.get_reasoning_chain(&session.id)?;
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].step_type, ReasoningStepType::Analysis);
+        assert_eq!(chain[0].content, "understand the question");
+        assert_eq!(chain[1].step_type, ReasoningStepType::Reflection);
+        assert_eq!(chain[1].confidence, 1.0);
+        assert!(service.get_reasoning_chain("other")?.is_empty());
+
+        service
+            .agent_reply_with_planning(&session.id, user_request("mock-local", "hello"))
+            .await?;
+        let types: Vec<_> = service
+            .get_reasoning_chain(&session.id)?
+            .into_iter()
+            .skip(2)
+            .map(|step| step.step_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ReasoningStepType::Analysis,
+                ReasoningStepType::Planning,
+                ReasoningStepType::ToolSelection,
+                ReasoningStepType::Reflection,
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_imported_sessions_keep_history_and_remap_taken_ids() -> Result<()> {
+        let service = SimpleChatService::in_memory()?;
+        let session = service.create_session("Trip", Some("mock-local"))?;
+        service.append_message(&session.id, Role::User, "Pack for Lisbon?")?;
+        service.sessions.append_reply(
+            &session.id,
+            vec![MessageContent::Text {
+                text: "Sunscreen.".to_string(),
+            }],
+            "mock-local",
+        )?;
+        let original = service.load_messages(&session.id)?;
+        let json = service
+            .export_session(&session.id, ExportFormat::Json)
+            .await?;
+
+        let same = |a: &StoredMessage, b: &StoredMessage| {
+            a.role == b.role
+                && a.parts == b.parts
+                && a.created_at == b.created_at
+                && a.model == b.model
+        };
+
+        // Into another database the ids are free and kept
+        let other = SimpleChatService::in_memory()?;
+        let moved = other.load_messages(&other.import_session(&json)?)?;
+        assert_eq!(moved.len(), 2);
+        assert!(moved
+            .iter()
+            .zip(&original)
+            .all(|(a, b)| same(a, b) && a.id == b.id));
+
+        // Into the database it came from they're taken, so the copy gets new ones
+        let copy_id = service.import_session(&json)?;
+        assert_ne!(copy_id, session.id);
+        let copy = service.load_messages(&copy_id)?;
+        assert!(copy
+            .iter()
+            .zip(&original)
+            .all(|(a, b)| same(a, b) && a.id != b.id));
+        assert_eq!(service.load_messages(&session.id)?, original);
+        assert_eq!(
+            service.sessions.get_session(&copy_id)?.unwrap().title,
+            "Trip"
+        );
+
+        let err = service.import_session("{\"title\": \"Trip\"}").unwrap_err();
+        assert!(err.to_string().starts_with("Not a session export"));
+        Ok(())
+    }
+}
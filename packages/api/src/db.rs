@@ -0,0 +1,95 @@
+// SQLite connection pool shared by the session, usage, memory and reasoning stores
+use anyhow::Result;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+use std::path::Path;
+use std::time::Duration;
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+pub type PooledConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// Connections opened to a database file unless configured otherwise
+pub const DEFAULT_POOL_SIZE: u32 = 8;
+
+/// Environment variable overriding `DEFAULT_POOL_SIZE`
+pub const POOL_SIZE_ENV: &str = "DIOXUS_CHAT_DB_POOL_SIZE";
+
+/// How long a connection waits on a database another connection is writing to
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings every pooled connection starts with. Foreign keys are off by default in SQLite
+/// and the stores rely on their cascades.
+fn init_connection(conn: &mut Connection) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "foreign_keys", true)?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+}
+
+/// Pool size from `POOL_SIZE_ENV`, or `DEFAULT_POOL_SIZE` when it's unset or not a number
+pub fn pool_size_from_env() -> u32 {
+    std::env::var(POOL_SIZE_ENV)
+        .ok()
+        .and_then(|size| size.trim().parse().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or(DEFAULT_POOL_SIZE)
+}
+
+/// A pool of up to `size` connections to the database at `path`. The database runs in WAL
+/// mode so reads go ahead while another connection writes. `:memory:` gives `memory_pool`.
+pub fn open_pool(path: &Path, size: u32) -> Result<DbPool> {
+    if path == Path::new(":memory:") {
+        return memory_pool();
+    }
+    let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+        // Persistent once set, but cheap to repeat
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        init_connection(conn)
+    });
+    r2d2::Pool::builder()
+        .max_size(size.max(1))
+        .build(manager)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+/// A pool over a throwaway in-memory database. Every in-memory connection is a database of
+/// its own, so the pool holds exactly one and never recycles it.
+pub fn memory_pool() -> Result<DbPool> {
+    let manager = SqliteConnectionManager::memory().with_init(init_connection);
+    Ok(r2d2::Pool::builder()
+        .max_size(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .build(manager)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::TransactionBehavior;
+
+    #[test]
+    fn test_reads_proceed_while_another_connection_writes() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("db_pool_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let pool = open_pool(&dir.join("chat.db"), 4)?;
+        pool.get()?
+            .execute_batch("CREATE TABLE notes (text TEXT); INSERT INTO notes VALUES ('kept');")?;
+
+        let mut writer = pool.get()?;
+        let tx = writer.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute("INSERT INTO notes VALUES ('pending')", [])?;
+
+        // The reader sees the last committed state instead of waiting for the writer
+        let reader = pool.get()?;
+        let mode: String = reader.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        assert_eq!(mode, "wal");
+        let count: i64 = reader.query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))?;
+        assert_eq!(count, 1);
+
+        tx.commit()?;
+        let count: i64 = reader.query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))?;
+        assert_eq!(count, 2);
+        drop((reader, writer, pool));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+// Plain text from binary document formats, for uploads and RAG ingestion. PDF and DOCX
+// parsing pulls in extra crates, so it's only built with the `document-extraction` feature.
+use anyhow::Result;
+
+pub const PDF_MIME_TYPE: &str = "application/pdf";
+pub const DOCX_MIME_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+/// Whether `extract_document_text` knows the format, built with the feature or not
+pub fn is_document(mime_type: &str) -> bool {
+    mime_type == PDF_MIME_TYPE || mime_type == DOCX_MIME_TYPE
+}
+
+/// Text of a PDF or DOCX document. Encrypted, corrupt and unsupported files are errors.
+/// Parsing is CPU-bound; call this from `spawn_blocking` in async code.
+#[cfg(feature = "document-extraction")]
+pub fn extract_document_text(data: &[u8], mime_type: &str) -> Result<String> {
+    match mime_type {
+        PDF_MIME_TYPE => extract_pdf(data),
+        DOCX_MIME_TYPE => extract_docx(data),
+        _ => anyhow::bail!("Text extraction isn't supported for {}", mime_type),
+    }
+}
+
+#[cfg(not(feature = "document-extraction"))]
+pub fn extract_document_text(_data: &[u8], mime_type: &str) -> Result<String> {
+    anyhow::bail!(
+        "Text extraction for {} needs the document-extraction feature",
+        mime_type
+    )
+}
+
+#[cfg(feature = "document-extraction")]
+fn extract_pdf(data: &[u8]) -> Result<String> {
+    // pdf-extract panics on some malformed files instead of returning an error
+    let extracted = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data));
+    match extracted {
+        Ok(Ok(text)) => Ok(text),
+        Ok(Err(e)) => anyhow::bail!("Could not read PDF (it may be encrypted or corrupt): {}", e),
+        Err(_) => anyhow::bail!("Could not read PDF: the file is corrupt or unsupported"),
+    }
+}
+
+/// Paragraph text from `word/document.xml`. Formatting, tables and embedded objects are
+/// dropped; each paragraph becomes a line.
+#[cfg(feature = "document-extraction")]
+fn extract_docx(data: &[u8]) -> Result<String> {
+    use std::io::Read;
+
+    // Password-protected DOCX files aren't zip archives at all
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(|e| {
+        anyhow::anyhow!("Could not read DOCX (it may be encrypted or corrupt): {e}")
+    })?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| anyhow::anyhow!("Could not read DOCX: no document body ({})", e))?
+        .read_to_string(&mut xml)?;
+
+    let tokens = regex::Regex::new(r"<w:t(?:\s[^>]*)?>([^<]*)</w:t>|</w:p>|<w:tab/>|<w:br/>")?;
+    let mut text = String::new();
+    for token in tokens.captures_iter(&xml) {
+        match (token.get(1), &token[0]) {
+            (Some(run), _) => text.push_str(&unescape_xml(run.as_str())),
+            (None, "<w:tab/>") => text.push('\t'),
+            (None, _) => text.push('\n'),
+        }
+    }
+    Ok(text.trim_end().to_string())
+}
+
+#[cfg(feature = "document-extraction")]
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(all(test, feature = "document-extraction"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn docx(body: &str) -> Vec<u8> {
+        let options = zip::write::SimpleFileOptions::default();
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer.start_file("word/document.xml", options).unwrap();
+        writer.write_all(body.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_docx_paragraphs_become_lines_and_broken_files_are_errors() {
+        let body = r#"<w:document><w:body>
+            <w:p><w:r><w:t>Fish &amp; chips</w:t></w:r><w:r><w:t xml:space="preserve"> menu</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Price</w:t><w:tab/><w:t>4.50</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        assert_eq!(
+            extract_document_text(&docx(body), DOCX_MIME_TYPE).unwrap(),
+            "Fish & chips menu\nPrice\t4.50"
+        );
+
+        let error = extract_document_text(b"not a zip", DOCX_MIME_TYPE).unwrap_err();
+        assert!(error.to_string().contains("encrypted or corrupt"));
+        let error = extract_document_text(b"%PDF-1.7 garbage", PDF_MIME_TYPE).unwrap_err();
+        assert!(error.to_string().starts_with("Could not read PDF"));
+    }
+}
@@ -0,0 +1,109 @@
+// Text embeddings for semantic search over memory (and later documents)
+use anyhow::Result;
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait EmbeddingService: std::fmt::Debug + Send + Sync {
+    /// A fixed-length vector for `text`. Vectors from the same service are comparable with
+    /// `cosine_similarity`.
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Vectors for several texts, in the same order. The default embeds them one at a time;
+    /// services that can embed many texts in one request should override it.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for text in texts {
+            vectors.push(self.embed(text).await?);
+        }
+        Ok(vectors)
+    }
+}
+
+/// Length of the vectors `MockEmbeddingService` produces
+pub const MOCK_EMBEDDING_DIMENSIONS: usize = 256;
+
+/// Default embedder that needs no model: hashes each word into a bucket, so texts sharing words
+/// score as similar. Good enough for tests and offline use, not for real semantic matching.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockEmbeddingService;
+
+impl MockEmbeddingService {
+    fn vector(text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; MOCK_EMBEDDING_DIMENSIONS];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            vector[fnv1a(&word.to_lowercase()) as usize % MOCK_EMBEDDING_DIMENSIONS] += 1.0;
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingService for MockEmbeddingService {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(Self::vector(text))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| Self::vector(text)).collect())
+    }
+}
+
+/// Stable across builds, unlike `DefaultHasher`, so stored vectors stay valid
+fn fnv1a(word: &str) -> u64 {
+    word.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Cosine of the angle between two vectors; 0.0 when either is all zeros or their lengths differ
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only implements `embed`, so batches go through the default
+    #[derive(Debug)]
+    struct LengthEmbedder;
+
+    #[async_trait]
+    impl EmbeddingService for LengthEmbedder {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_keep_input_length_and_order() -> Result<()> {
+        let texts: Vec<String> = ["a", "rust borrow checker", "", "tea"]
+            .iter()
+            .map(|text| text.to_string())
+            .collect();
+
+        let batch = MockEmbeddingService.embed_batch(&texts).await?;
+        assert_eq!(batch.len(), texts.len());
+        for (text, vector) in texts.iter().zip(&batch) {
+            assert_eq!(vector, &MockEmbeddingService.embed(text).await?);
+        }
+
+        let lengths = LengthEmbedder.embed_batch(&texts).await?;
+        assert_eq!(lengths, vec![vec![1.0], vec![19.0], vec![0.0], vec![3.0]]);
+        assert!(LengthEmbedder.embed_batch(&[]).await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+// Conversation export: a session's transcript as Markdown to read or JSON to re-import
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::chat_service_simple::{MessageContent, Role};
+use crate::session_store::{StoredMessage, StoredSession};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+/// What a JSON export contains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionExport {
+    pub session: StoredSession,
+    pub messages: Vec<StoredMessage>,
+    pub exported_at: DateTime<Utc>,
+}
+
+pub fn to_json(session: StoredSession, messages: Vec<StoredMessage>) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&SessionExport {
+        session,
+        messages,
+        exported_at: Utc::now(),
+    })
+}
+
+/// Render a transcript. `images` maps uploaded file ids to data URIs, so attached images are
+/// embedded rather than linked to files the reader doesn't have.
+pub fn to_markdown(
+    session: &StoredSession,
+    messages: &[StoredMessage],
+    images: &HashMap<String, String>,
+) -> String {
+    let mut out = format!("# {}\n\n", session.title);
+    if let Some(model) = &session.model {
+        out.push_str(&format!("Model: `{}`\n\n", model));
+    }
+
+    // Tool results only carry the call id; name them after the call they answer
+    let tool_names: HashMap<&str, &str> = messages
+        .iter()
+        .flat_map(|message| &message.parts)
+        .filter_map(|part| match part {
+            MessageContent::ToolRequest { id, name, .. } => Some((id.as_str(), name.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    for message in messages {
+        out.push_str(&format!(
+            "## {} · {}\n\n",
+            role_heading(&message.role),
+            message.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+        for part in &message.parts {
+            match part {
+                MessageContent::Text { text } => {
+                    if !text.is_empty() {
+                        out.push_str(text);
+                        out.push_str("\n\n");
+                    }
+                }
+                MessageContent::Reasoning { content } => {
+                    out.push_str(&details("Reasoning", content));
+                }
+                MessageContent::ToolRequest {
+                    name, arguments, ..
+                } => {
+                    let arguments = serde_json::to_string_pretty(arguments).unwrap_or_default();
+                    out.push_str(&details(
+                        &format!("Tool call: {}", name),
+                        &fenced("json", &arguments),
+                    ));
+                }
+                MessageContent::ToolResponse { id, result, .. } => {
+                    let name = tool_names.get(id.as_str()).copied().unwrap_or(id);
+                    let result = match result {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+                    };
+                    out.push_str(&details(
+                        &format!("Tool result: {}", name),
+                        &fenced("", &result),
+                    ));
+                }
+                MessageContent::Image { url, description } => {
+                    let name = description.as_deref().unwrap_or("image");
+                    out.push_str(&format!("![{}]({})\n\n", name, url));
+                }
+                MessageContent::File { file_id, name, .. } => match images.get(file_id) {
+                    Some(uri) => out.push_str(&format!("![{}]({})\n\n", name, uri)),
+                    None => out.push_str(&format!("_Attached file: {}_\n\n", name)),
+                },
+                // Its transcript follows as text
+                MessageContent::Audio { name, .. } => {
+                    out.push_str(&format!("_Audio: {}_\n\n", name));
+                }
+            }
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+fn role_heading(role: &Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::System => "System",
+        Role::Tool => "Tool",
+    }
+}
+
+/// A collapsed block; the blank lines let Markdown inside it render
+fn details(summary: &str, body: &str) -> String {
+    format!(
+        "<details>\n<summary>{}</summary>\n\n{}\n\n</details>\n\n",
+        summary,
+        body.trim_end()
+    )
+}
+
+/// A code block whose fence is longer than any backtick run in `body`, so the body can't
+/// close it early
+fn fenced(language: &str, body: &str) -> String {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, body.trim_end(), fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(seq: i64, role: Role, parts: Vec<MessageContent>) -> StoredMessage {
+        StoredMessage {
+            id: format!("m{}", seq),
+            session_id: "s1".to_string(),
+            role,
+            content: String::new(),
+            parts,
+            created_at: DateTime::from_timestamp(1_700_000_000 + seq, 0).unwrap(),
+            seq,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn test_markdown_export_embeds_images_and_collapses_tool_calls() {
+        let session = StoredSession {
+            id: "s1".to_string(),
+            title: "Cats".to_string(),
+            model: Some("mock-local".to_string()),
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            updated_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            pinned: false,
+        };
+        let messages = vec![
+            message(
+                1,
+                Role::User,
+                vec![
+                    MessageContent::Text {
+                        text: "What's in this picture?".to_string(),
+                    },
+                    MessageContent::File {
+                        file_id: "f1".to_string(),
+                        name: "cat.png".to_string(),
+                        mime_type: "image/png".to_string(),
+                    },
+                ],
+            ),
+            message(
+                2,
+                Role::Assistant,
+                vec![MessageContent::ToolRequest {
+                    id: "call-1".to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({ "command": "echo ```" }),
+                }],
+            ),
+            message(
+                3,
+                Role::Tool,
+                vec![MessageContent::ToolResponse {
+                    id: "call-1".to_string(),
+                    name: "call-1".to_string(),
+                    result: serde_json::json!("```"),
+                }],
+            ),
+        ];
+        let images = HashMap::from([("f1".to_string(), "data:image/png;base64,cG5n".to_string())]);
+
+        let markdown = to_markdown(&session, &messages, &images);
+        assert!(markdown.starts_with("# Cats\n\nModel: `mock-local`\n\n"));
+        assert!(markdown.contains("## User · 2023-11-14 22:13:21 UTC\n\nWhat's in this picture?"));
+        assert!(markdown.contains("![cat.png](data:image/png;base64,cG5n)"));
+        assert!(markdown.contains("<summary>Tool call: shell</summary>\n\n````json\n"));
+        assert!(markdown.contains("<summary>Tool result: shell</summary>\n\n````\n```\n````"));
+
+        let json = to_json(session.clone(), messages.clone()).unwrap();
+        let export: SessionExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(export.session, session);
+        assert_eq!(export.messages, messages);
+    }
+}
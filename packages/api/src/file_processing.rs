@@ -0,0 +1,515 @@
+// Uploaded files are processed as tracked background jobs whose status the UI can poll.
+// The raw bytes live on disk next to a JSON metadata file; only metadata is kept in memory.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::document_text::{extract_document_text, is_document};
+
+/// Text is extracted this many bytes at a time, reporting progress after each chunk
+pub const EXTRACTION_CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileStatus {
+    Pending,
+    Processing {
+        chunks_done: usize,
+        chunks_total: usize,
+    },
+    Completed,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileProcessingResult {
+    pub file_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    #[serde(default)]
+    pub size_bytes: u64,
+    pub status: FileStatus,
+    /// Set once extraction completes
+    pub extracted_text: Option<String>,
+    /// Set for `image/*` uploads once processed
+    #[serde(default)]
+    pub image: Option<ImageMetadata>,
+}
+
+/// What could be read from an image's header. Fields are `None` when the format isn't
+/// supported or the data is truncated.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageMetadata {
+    /// Lowercase format name, e.g. `png`
+    pub format: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub has_transparency: Option<bool>,
+}
+
+#[derive(Debug)]
+struct FileJob {
+    result: FileProcessingResult,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Where the database and uploads live: `$DIOXUS_CHAT_DATA_DIR`, else the per-OS data
+/// directory (e.g. `~/.local/share/dioxus-chat` on Linux)
+pub fn default_data_dir() -> PathBuf {
+    std::env::var_os("DIOXUS_CHAT_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("dioxus-chat")))
+        .unwrap_or_else(|| PathBuf::from(".dioxus-chat"))
+}
+
+/// Where uploads are stored, under `default_data_dir`
+pub fn default_files_dir() -> PathBuf {
+    default_data_dir().join("files")
+}
+
+#[derive(Debug, Clone)]
+pub struct FileJobs {
+    dir: Arc<PathBuf>,
+    jobs: Arc<Mutex<HashMap<String, FileJob>>>,
+}
+
+/// A scratch store under the system temp dir that nothing reopens, e.g. for tests
+impl Default for FileJobs {
+    fn default() -> Self {
+        let dir = std::env::temp_dir().join(format!("dioxus-chat-files-{}", uuid::Uuid::new_v4()));
+        Self {
+            dir: Arc::new(dir),
+            jobs: Arc::default(),
+        }
+    }
+}
+
+impl FileJobs {
+    /// Open the store in `dir`, restoring the files uploaded before the last shutdown
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut jobs = HashMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let mut result = match read_metadata(&path) {
+                Ok(result) => result,
+                Err(e) => {
+                    tracing::warn!("Skipping unreadable upload {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            // The process stopped before extraction finished
+            if matches!(
+                result.status,
+                FileStatus::Pending | FileStatus::Processing { .. }
+            ) {
+                result.status = FileStatus::Failed {
+                    error: "Processing was interrupted by a restart".to_string(),
+                };
+            }
+            jobs.insert(
+                result.file_id.clone(),
+                FileJob {
+                    result,
+                    cancelled: Arc::default(),
+                },
+            );
+        }
+
+        Ok(Self {
+            dir: Arc::new(dir),
+            jobs: Arc::new(Mutex::new(jobs)),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, FileJob>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn data_path(&self, file_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", file_id))
+    }
+
+    fn metadata_path(&self, file_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", file_id))
+    }
+
+    /// Register the file as pending, then write it to disk and extract its text in the background
+    pub fn start(&self, file_name: &str, mime_type: &str, data: Vec<u8>) -> String {
+        let file_id = uuid::Uuid::new_v4().to_string();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.lock().insert(
+            file_id.clone(),
+            FileJob {
+                result: FileProcessingResult {
+                    file_id: file_id.clone(),
+                    file_name: file_name.to_string(),
+                    mime_type: mime_type.to_string(),
+                    size_bytes: data.len() as u64,
+                    status: FileStatus::Pending,
+                    extracted_text: None,
+                    image: None,
+                },
+                cancelled: cancelled.clone(),
+            },
+        );
+
+        let jobs = self.clone();
+        let job_id = file_id.clone();
+        let mime_type = mime_type.to_string();
+        tokio::spawn(async move {
+            let image = mime_type
+                .starts_with("image/")
+                .then(|| process_image(&data));
+            let outcome = match jobs.write_data(&job_id, &data).await {
+                // Images and audio have no text to extract; audio is transcribed when sent
+                Ok(()) if image.is_some() || mime_type.starts_with("audio/") => Ok(None),
+                Ok(()) => extract_text(&data, &mime_type, &cancelled, |done, total| {
+                    jobs.update(&job_id, |result| {
+                        result.status = FileStatus::Processing {
+                            chunks_done: done,
+                            chunks_total: total,
+                        }
+                    })
+                })
+                .await
+                .map(Some),
+                Err(e) => Err(format!("Failed to store file: {}", e)),
+            };
+            let Some(mut result) = jobs.status(&job_id) else {
+                // Deleted while we were working: don't leave the bytes behind
+                jobs.remove_from_disk(&job_id).await;
+                return;
+            };
+            match outcome {
+                Ok(text) => {
+                    result.status = FileStatus::Completed;
+                    result.extracted_text = text;
+                    result.image = image;
+                }
+                Err(error) => result.status = FileStatus::Failed { error },
+            }
+            // Persist before publishing, so a finished file always has its metadata on disk
+            jobs.save_metadata(&result).await;
+            jobs.update(&job_id, |current| *current = result);
+            if cancelled.load(Ordering::Relaxed) {
+                jobs.remove_from_disk(&job_id).await;
+            }
+        });
+
+        file_id
+    }
+
+    pub fn status(&self, file_id: &str) -> Option<FileProcessingResult> {
+        self.lock().get(file_id).map(|job| job.result.clone())
+    }
+
+    /// Read a stored file's bytes from disk
+    pub async fn read(&self, file_id: &str) -> Result<Vec<u8>> {
+        // Only ids we handed out are turned into paths
+        if !self.lock().contains_key(file_id) {
+            anyhow::bail!("File {} not found", file_id);
+        }
+        tokio::fs::read(self.data_path(file_id))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", file_id, e))
+    }
+
+    /// Forget the file and delete it from disk, stopping its extraction if it's still running
+    pub async fn remove(&self, file_id: &str) -> bool {
+        let Some(job) = self.lock().remove(file_id) else {
+            return false;
+        };
+        job.cancelled.store(true, Ordering::Relaxed);
+        self.remove_from_disk(file_id).await;
+        true
+    }
+
+    async fn write_data(&self, file_id: &str, data: &[u8]) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(self.dir.as_path()).await?;
+        tokio::fs::write(self.data_path(file_id), data).await
+    }
+
+    async fn save_metadata(&self, result: &FileProcessingResult) {
+        let saved = match serde_json::to_vec(result) {
+            Ok(json) => tokio::fs::write(self.metadata_path(&result.file_id), json)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            tracing::warn!(
+                "Failed to save metadata for upload {}: {}",
+                result.file_id,
+                e
+            );
+        }
+    }
+
+    async fn remove_from_disk(&self, file_id: &str) {
+        for path in [self.data_path(file_id), self.metadata_path(file_id)] {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to delete {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Apply `change` if the job still exists; a removed job stays removed
+    fn update(&self, file_id: &str, change: impl FnOnce(&mut FileProcessingResult)) {
+        if let Some(job) = self.lock().get_mut(file_id) {
+            change(&mut job.result);
+        }
+    }
+}
+
+/// Read dimensions and transparency from a PNG, JPEG, WebP or GIF header without decoding
+/// the pixels. Unsupported formats and truncated data degrade to `None` fields.
+pub fn process_image(data: &[u8]) -> ImageMetadata {
+    use image::codecs::{gif::GifDecoder, jpeg::JpegDecoder, png::PngDecoder, webp::WebPDecoder};
+    use image::{ImageDecoder, ImageFormat};
+    use std::io::Cursor;
+
+    fn header<'a>(decoder: impl ImageDecoder<'a>) -> (u32, u32, bool) {
+        let (width, height) = decoder.dimensions();
+        (width, height, decoder.color_type().has_alpha())
+    }
+
+    let Ok(format) = image::guess_format(data) else {
+        return ImageMetadata::default();
+    };
+    let unread = ImageMetadata {
+        format: Some(format!("{:?}", format).to_lowercase()),
+        ..ImageMetadata::default()
+    };
+
+    let reader = Cursor::new(data);
+    let decoded = match format {
+        ImageFormat::Png => PngDecoder::new(reader).map(header),
+        ImageFormat::Jpeg => JpegDecoder::new(reader).map(header),
+        ImageFormat::WebP => WebPDecoder::new(reader).map(header),
+        // GIF decoders always report RGBA, so look for a transparent colour in the file instead
+        ImageFormat::Gif => GifDecoder::new(reader).map(|d| {
+            let (width, height) = d.dimensions();
+            (width, height, gif_has_transparency(data))
+        }),
+        _ => return unread,
+    };
+
+    match decoded {
+        Ok((width, height, has_transparency)) => ImageMetadata {
+            width: Some(width),
+            height: Some(height),
+            has_transparency: Some(has_transparency),
+            ..unread
+        },
+        Err(_) => unread,
+    }
+}
+
+/// Whether any frame of a GIF declares a transparent colour in its graphic control extension
+fn gif_has_transparency(data: &[u8]) -> bool {
+    // Skip the signature and logical screen descriptor, then any global colour table
+    let mut pos = 13;
+    let Some(&flags) = data.get(10) else {
+        return false;
+    };
+    if flags & 0x80 != 0 {
+        pos += 3 << ((flags & 0x07) + 1);
+    }
+
+    // Data sub-blocks are length-prefixed and end with an empty block
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let len = *data.get(pos)? as usize;
+            pos += 1 + len;
+            if len == 0 {
+                return Some(pos);
+            }
+        }
+    };
+
+    loop {
+        match data.get(pos) {
+            // Graphic control extension: the low bit of its packed field is the transparency flag
+            Some(0x21) if data.get(pos + 1) == Some(&0xF9) => {
+                if data.get(pos + 3).is_some_and(|packed| packed & 0x01 != 0) {
+                    return true;
+                }
+                let Some(next) = skip_sub_blocks(pos + 2) else {
+                    return false;
+                };
+                pos = next;
+            }
+            Some(0x21) => {
+                let Some(next) = skip_sub_blocks(pos + 2) else {
+                    return false;
+                };
+                pos = next;
+            }
+            Some(0x2C) => {
+                let Some(&flags) = data.get(pos + 9) else {
+                    return false;
+                };
+                pos += 10;
+                if flags & 0x80 != 0 {
+                    pos += 3 << ((flags & 0x07) + 1);
+                }
+                // LZW minimum code size, then the image data
+                let Some(next) = skip_sub_blocks(pos + 1) else {
+                    return false;
+                };
+                pos = next;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn read_metadata(path: &Path) -> Result<FileProcessingResult> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Extract plain text chunk by chunk, yielding between chunks so a cancellation is noticed
+async fn extract_text(
+    data: &[u8],
+    mime_type: &str,
+    cancelled: &AtomicBool,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<String, String> {
+    let document;
+    let text = if is_document(mime_type) {
+        let (data, mime_type) = (data.to_vec(), mime_type.to_string());
+        document = tokio::task::spawn_blocking(move || extract_document_text(&data, &mime_type))
+            .await
+            .map_err(|e| format!("Text extraction failed: {}", e))?
+            .map_err(|e| e.to_string())?;
+        document.as_str()
+    } else if mime_type.starts_with("text/") || mime_type == "application/json" {
+        std::str::from_utf8(data).map_err(|e| format!("File is not valid UTF-8 text: {}", e))?
+    } else {
+        return Err(format!("Text extraction isn't supported for {}", mime_type));
+    };
+
+    let chunks_total = text.len().div_ceil(EXTRACTION_CHUNK_BYTES).max(1);
+    let mut extracted = String::with_capacity(text.len());
+    let mut start = 0;
+    for chunks_done in 1..=chunks_total {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Processing was cancelled".to_string());
+        }
+
+        let mut end = (start + EXTRACTION_CHUNK_BYTES).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        extracted.push_str(&text[start..end]);
+        start = end;
+
+        progress(chunks_done, chunks_total);
+        tokio::task::yield_now().await;
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn wait_until_done(jobs: &FileJobs, file_id: &str) -> FileProcessingResult {
+        for _ in 0..100 {
+            let result = jobs.status(file_id).unwrap();
+            if matches!(
+                result.status,
+                FileStatus::Completed | FileStatus::Failed { .. }
+            ) {
+                return result;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("file {} never finished processing", file_id);
+    }
+
+    #[tokio::test]
+    async fn test_files_are_kept_on_disk_across_reopen_and_deleted_with_the_job() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("dioxus-chat-test-{}", uuid::Uuid::new_v4()));
+        let jobs = FileJobs::open(&dir)?;
+        let bytes = vec![0u8, 159, 146, 150, 255];
+        let file_id = jobs.start("photo.png", "image/png", bytes.clone());
+        wait_until_done(&jobs, &file_id).await;
+
+        // Binary bytes are stored as-is and read back on demand
+        assert_eq!(jobs.read(&file_id).await?, bytes);
+        assert_eq!(std::fs::read(dir.join(format!("{}.bin", file_id)))?, bytes);
+
+        let reopened = FileJobs::open(&dir)?;
+        let restored = reopened.status(&file_id).unwrap();
+        assert_eq!(restored.file_name, "photo.png");
+        assert_eq!(restored.size_bytes, bytes.len() as u64);
+        assert_eq!(reopened.read(&file_id).await?, bytes);
+
+        assert!(reopened.remove(&file_id).await);
+        assert!(!dir.join(format!("{}.bin", file_id)).exists());
+        assert!(!dir.join(format!("{}.json", file_id)).exists());
+        assert!(reopened.read(&file_id).await.is_err());
+        assert!(reopened.read("../../etc/passwd").await.is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_headers_give_dimensions_and_transparency() {
+        use image::{ColorType, ImageOutputFormat, Rgba, RgbaImage};
+        use std::io::Cursor;
+
+        let mut pixels = RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 255]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(pixels.clone())
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        let metadata = process_image(&png);
+        assert_eq!(metadata.format.as_deref(), Some("png"));
+        assert_eq!((metadata.width, metadata.height), (Some(3), Some(2)));
+        assert_eq!(metadata.has_transparency, Some(true));
+
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageRgb8(image::DynamicImage::ImageRgba8(pixels.clone()).to_rgb8())
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        let metadata = process_image(&jpeg);
+        assert_eq!((metadata.width, metadata.height), (Some(3), Some(2)));
+        assert_eq!(metadata.has_transparency, Some(false));
+
+        let gif = |pixels: &RgbaImage| {
+            let mut gif = Vec::new();
+            image::codecs::gif::GifEncoder::new(&mut gif)
+                .encode(pixels.as_raw(), 3, 2, ColorType::Rgba8)
+                .unwrap();
+            gif
+        };
+        assert_eq!(process_image(&gif(&pixels)).has_transparency, Some(false));
+        pixels.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let metadata = process_image(&gif(&pixels));
+        assert_eq!((metadata.width, metadata.height), (Some(3), Some(2)));
+        assert_eq!(metadata.has_transparency, Some(true));
+
+        // Truncated and unknown data degrade instead of panicking
+        let truncated = process_image(&png[..12]);
+        assert_eq!(truncated.format.as_deref(), Some("png"));
+        assert_eq!(truncated.width, None);
+        assert_eq!(process_image(b"not an image"), ImageMetadata::default());
+    }
+}
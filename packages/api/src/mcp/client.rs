@@ -1,16 +1,93 @@
 use super::protocol::*;
-use crate::chat_service::{Tool as ChatTool, ToolCall, ToolResult};
+use super::registry::McpServerConfig;
+use crate::chat_service_simple::{Tool as ChatTool, ToolCall, ToolCategory, ToolResult};
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::pin::Pin;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::mpsc;
-use tracing::{debug, error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+/// Per-server limits for MCP tool calls
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpCallPolicy {
+    /// How long a single tool call may take before it is abandoned
+    pub timeout: Duration,
+    /// Extra attempts for transient transport errors; timeouts are not retried
+    pub max_retries: u32,
+    pub retry_backoff: Duration,
+    /// Consecutive timeouts after which the server is reported as degraded
+    pub degraded_after: u32,
+}
+
+impl Default for McpCallPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(250),
+            degraded_after: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum McpServerStatus {
+    Ready,
+    /// Still connected, but recent calls have been timing out
+    Degraded,
+    /// Not initialized, or the server process has exited
+    Unavailable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum McpTransportError {
+    Timeout {
+        server: String,
+        tool: String,
+        timeout: Duration,
+    },
+    /// The server process exited or closed its pipes
+    Disconnected(String),
+    Io(String),
+}
+
+impl McpTransportError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, McpTransportError::Io(_))
+    }
+}
+
+impl std::fmt::Display for McpTransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            McpTransportError::Timeout {
+                server,
+                tool,
+                timeout,
+            } => write!(
+                f,
+                "Tool '{}' on MCP server '{}' timed out after {}s. The server may be busy or stuck; try again later or continue without this tool.",
+                tool,
+                server,
+                timeout.as_secs_f32()
+            ),
+            McpTransportError::Disconnected(server) => {
+                write!(f, "MCP server '{}' is no longer running", server)
+            }
+            McpTransportError::Io(e) => write!(f, "MCP transport error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for McpTransportError {}
 
 #[async_trait]
 pub trait McpClient: Send + Sync {
@@ -19,13 +96,41 @@ pub trait McpClient: Send + Sync {
     async fn call_tool(&mut self, name: &str, arguments: Option<Value>) -> Result<CallToolResult>;
     fn name(&self) -> &str;
     fn is_ready(&self) -> bool;
+
+    fn call_policy(&self) -> McpCallPolicy {
+        McpCallPolicy::default()
+    }
+
+    /// Stop the server; clients without a process of their own have nothing to do
+    async fn shutdown(&mut self) {}
+}
+
+type PendingRequests = Arc<Mutex<HashMap<i32, oneshot::Sender<Result<Value>>>>>;
+
+/// Removes a pending request if the caller gives up on it (e.g. on timeout)
+struct PendingGuard {
+    pending: PendingRequests,
+    id: i32,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&self.id);
+        }
+    }
 }
 
 pub struct StdioMcpClient {
     name: String,
     command: String,
     args: Vec<String>,
+    env: HashMap<String, String>,
+    policy: McpCallPolicy,
     child: Option<Child>,
+    stdin: Option<ChildStdin>,
+    pending: PendingRequests,
+    exited: Arc<AtomicBool>,
     request_id: i32,
     ready: bool,
 }
@@ -36,18 +141,111 @@ impl StdioMcpClient {
             name,
             command,
             args,
+            env: HashMap::new(),
+            policy: McpCallPolicy::default(),
             child: None,
+            stdin: None,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            exited: Arc::new(AtomicBool::new(false)),
             request_id: 0,
             ready: false,
         }
     }
 
-    async fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
-        let request_id = self.next_id();
-        let child = self
-            .child
+    pub fn with_call_policy(mut self, policy: McpCallPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set extra environment variables for the server process
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Route responses to their waiting requests, and fail everything still
+    /// pending as soon as the server's stdout closes so callers don't hang on a dead process
+    fn spawn_reader(&self, stdout: ChildStdout) {
+        let name = self.name.clone();
+        let pending = self.pending.clone();
+        let exited = self.exited.clone();
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        let response: JsonRpcResponse = match serde_json::from_str(line.trim()) {
+                            Ok(response) => response,
+                            Err(e) => {
+                                debug!("Ignoring non JSON-RPC output from '{}': {}", name, e);
+                                continue;
+                            }
+                        };
+                        let Some(id) = response.id.as_ref().and_then(|id| id.as_i64()) else {
+                            continue;
+                        };
+
+                        let result = match response.error {
+                            Some(error) => Err(anyhow::anyhow!(
+                                "JSON-RPC error: {} - {}",
+                                error.code,
+                                error.message
+                            )),
+                            None => Ok(response.result.unwrap_or(json!(null))),
+                        };
+                        let sender = pending
+                            .lock()
+                            .ok()
+                            .and_then(|mut pending| pending.remove(&(id as i32)));
+                        if let Some(sender) = sender {
+                            let _ = sender.send(result);
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Failed to read from MCP server '{}': {}", name, e);
+                        break;
+                    }
+                }
+            }
+
+            warn!("MCP server '{}' closed its output", name);
+            exited.store(true, Ordering::SeqCst);
+            if let Ok(mut pending) = pending.lock() {
+                for (_, sender) in pending.drain() {
+                    let _ = sender.send(Err(McpTransportError::Disconnected(name.clone()).into()));
+                }
+            }
+        });
+    }
+
+    async fn write_message(&mut self, message: &JsonRpcRequest) -> Result<()> {
+        let stdin = self
+            .stdin
             .as_mut()
             .ok_or_else(|| anyhow::anyhow!("Child process not started"))?;
+
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+
+        let written = async {
+            stdin.write_all(line.as_bytes()).await?;
+            stdin.flush().await
+        }
+        .await;
+
+        written.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::BrokenPipe {
+                McpTransportError::Disconnected(self.name.clone()).into()
+            } else {
+                McpTransportError::Io(e.to_string()).into()
+            }
+        })
+    }
+
+    async fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
+        let request_id = self.next_id();
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
             id: Some(json!(request_id)),
@@ -55,59 +253,42 @@ impl StdioMcpClient {
             params,
         };
 
-        let request_json = serde_json::to_string(&request)?;
+        let (sender, receiver) = oneshot::channel();
+        self.pending
+            .lock()
+            .map_err(|_| anyhow::anyhow!("MCP pending request lock poisoned"))?
+            .insert(request_id, sender);
+        let _guard = PendingGuard {
+            pending: self.pending.clone(),
+            id: request_id,
+        };
 
-        // Send request to stdin
-        if let Some(stdin) = child.stdin.as_mut() {
-            use tokio::io::AsyncWriteExt;
-            stdin.write_all(request_json.as_bytes()).await?;
-            stdin.write_all(b"\n").await?;
-            stdin.flush().await?;
-        } else {
-            return Err(anyhow::anyhow!("Cannot write to child stdin"));
+        // Checked after registering so a concurrent exit can't leave this request waiting forever
+        if self.exited.load(Ordering::SeqCst) {
+            return Err(McpTransportError::Disconnected(self.name.clone()).into());
         }
 
-        // Read response from stdout
-        if let Some(stdout) = child.stdout.as_mut() {
-            let mut reader = BufReader::new(stdout);
-            let mut line = String::new();
-
-            match reader.read_line(&mut line).await {
-                Ok(0) => return Err(anyhow::anyhow!("EOF while reading response")),
-                Ok(_) => {
-                    let response: JsonRpcResponse = serde_json::from_str(&line.trim())
-                        .map_err(|e| anyhow::anyhow!("Failed to parse JSON-RPC response: {}", e))?;
-
-                    if let Some(error) = response.error {
-                        return Err(anyhow::anyhow!(
-                            "JSON-RPC error: {} - {}",
-                            error.code,
-                            error.message
-                        ));
-                    }
+        self.write_message(&request).await?;
 
-                    Ok(response.result.unwrap_or(json!(null)))
-                }
-                Err(e) => return Err(anyhow::anyhow!("Failed to read response: {}", e)),
-            }
-        } else {
-            Err(anyhow::anyhow!("Cannot read from child stdout"))
-        }
+        receiver
+            .await
+            .map_err(|_| McpTransportError::Disconnected(self.name.clone()))?
+    }
+
+    async fn send_notification(&mut self, method: &str, params: Option<Value>) -> Result<()> {
+        let notification = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: method.to_string(),
+            params,
+        };
+        self.write_message(&notification).await
     }
 
     fn next_id(&mut self) -> i32 {
         self.request_id += 1;
         self.request_id
     }
-
-    fn convert_mcp_tool_to_chat_tool(&self, mcp_tool: Tool) -> ChatTool {
-        ChatTool {
-            name: mcp_tool.name,
-            description: mcp_tool.description,
-            input_schema: mcp_tool.input_schema,
-            is_mcp: true,
-        }
-    }
 }
 
 #[async_trait]
@@ -115,14 +296,26 @@ impl McpClient for StdioMcpClient {
     async fn initialize(&mut self) -> Result<()> {
         info!("Initializing MCP client: {}", self.name);
 
+        // stderr is discarded: nobody reads it, and a full pipe would stall the server
         let mut child = Command::new(&self.command)
             .args(&self.args)
+            .envs(&self.env)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| anyhow::anyhow!("Failed to start MCP server '{}': {}", self.command, e))?;
 
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Cannot read from child stdout"))?;
+        self.stdin = child.stdin.take();
+        self.exited.store(false, Ordering::SeqCst);
+        self.spawn_reader(stdout);
+        self.child = Some(child);
+
         // Initialize the MCP connection
         let init_params = InitializeParams {
             protocol_version: "2024-11-05".to_string(),
@@ -136,22 +329,28 @@ impl McpClient for StdioMcpClient {
             },
         };
 
-        let result = self
-            .send_request("initialize", Some(json!(init_params)))
-            .await?;
+        let timeout = self.policy.timeout;
+        let result = tokio::time::timeout(
+            timeout,
+            self.send_request("initialize", Some(json!(init_params))),
+        )
+        .await
+        .map_err(|_| McpTransportError::Timeout {
+            server: self.name.clone(),
+            tool: "initialize".to_string(),
+            timeout,
+        })??;
         debug!("Initialize result: {}", result);
 
         // Send initialized notification
-        self.send_request("notifications/initialized", Some(json!({})))
+        self.send_notification("notifications/initialized", Some(json!({})))
             .await?;
 
-        self.child = Some(child);
         self.ready = true;
 
         info!("MCP client '{}' initialized successfully", self.name);
         Ok(())
     }
-
     async fn list_tools(&mut self) -> Result<Vec<Tool>> {
         if !self.ready {
             return Err(anyhow::anyhow!("MCP client not initialized"));
@@ -186,7 +385,22 @@ impl McpClient for StdioMcpClient {
     }
 
     fn is_ready(&self) -> bool {
-        self.ready
+        self.ready && !self.exited.load(Ordering::SeqCst)
+    }
+
+    fn call_policy(&self) -> McpCallPolicy {
+        self.policy.clone()
+    }
+
+    async fn shutdown(&mut self) {
+        self.ready = false;
+        self.stdin = None;
+        if let Some(mut child) = self.child.take() {
+            info!("Stopping MCP server: {}", self.name);
+            if let Err(e) = child.kill().await {
+                warn!("Failed to stop MCP server '{}': {}", self.name, e);
+            }
+        }
     }
 }
 
@@ -194,19 +408,35 @@ impl Drop for StdioMcpClient {
     fn drop(&mut self) {
         if let Some(mut child) = self.child.take() {
             debug!("Terminating MCP client: {}", self.name);
-            let _ = child.kill();
+            let _ = child.start_kill();
         }
     }
 }
 
 pub struct McpToolExecutor {
     clients: HashMap<String, Box<dyn McpClient>>,
+    consecutive_timeouts: HashMap<String, u32>,
 }
 
 impl McpToolExecutor {
     pub fn new() -> Self {
         Self {
             clients: HashMap::new(),
+            consecutive_timeouts: HashMap::new(),
+        }
+    }
+
+    pub fn server_status(&self, name: &str) -> Option<McpServerStatus> {
+        let client = self.clients.get(name)?;
+        if !client.is_ready() {
+            return Some(McpServerStatus::Unavailable);
+        }
+
+        let timeouts = self.consecutive_timeouts.get(name).copied().unwrap_or(0);
+        if timeouts >= client.call_policy().degraded_after {
+            Some(McpServerStatus::Degraded)
+        } else {
+            Some(McpServerStatus::Ready)
         }
     }
 
@@ -215,6 +445,71 @@ impl McpToolExecutor {
         self.clients.insert(client.name().to_string(), client);
     }
 
+    /// Start a stdio server from its config, run the MCP handshake and list its tools. On
+    /// failure the process is stopped and the server isn't added. A running server with the
+    /// same name is stopped first.
+    pub async fn add_server(
+        &mut self,
+        name: &str,
+        config: &McpServerConfig,
+    ) -> Result<Vec<ChatTool>> {
+        self.remove_server(name).await;
+
+        let mut client: Box<dyn McpClient> = Box::new(
+            StdioMcpClient::new(
+                name.to_string(),
+                config.command.clone(),
+                config.args.clone(),
+            )
+            .with_call_policy(config.call_policy.clone())
+            .with_env(config.env.clone()),
+        );
+        let timeout = config.call_policy.timeout;
+        let started = async {
+            client.initialize().await?;
+            tokio::time::timeout(timeout, client.list_tools())
+                .await
+                .map_err(|_| McpTransportError::Timeout {
+                    server: name.to_string(),
+                    tool: "tools/list".to_string(),
+                    timeout,
+                })?
+        }
+        .await;
+
+        match started {
+            Ok(tools) => {
+                self.add_client(client);
+                Ok(tools
+                    .into_iter()
+                    .map(|tool| ChatTool {
+                        name: format!("{}:{}", name, tool.name),
+                        description: format!("{} - {}", tool.description, name),
+                        input_schema: tool.input_schema,
+                        is_mcp: true,
+                        category: ToolCategory::General,
+                    })
+                    .collect())
+            }
+            Err(e) => {
+                client.shutdown().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Stop a server and forget it. Returns false when no server has that name.
+    pub async fn remove_server(&mut self, name: &str) -> bool {
+        self.consecutive_timeouts.remove(name);
+        match self.clients.remove(name) {
+            Some(mut client) => {
+                client.shutdown().await;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub async fn initialize_all(&mut self) -> Result<()> {
         for (name, client) in &mut self.clients {
             match client.initialize().await {
@@ -233,7 +528,15 @@ impl McpToolExecutor {
                 continue;
             }
 
-            match client.list_tools().await {
+            let timeout = client.call_policy().timeout;
+            match tokio::time::timeout(timeout, client.list_tools())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow::anyhow!(
+                        "Listing tools timed out after {}s",
+                        timeout.as_secs_f32()
+                    ))
+                }) {
                 Ok(tools) => {
                     for tool in tools {
                         all_tools.push(ChatTool {
@@ -241,6 +544,7 @@ impl McpToolExecutor {
                             description: format!("{} - {}", tool.description, client_name),
                             input_schema: tool.input_schema,
                             is_mcp: true,
+                            category: ToolCategory::General,
                         });
                     }
                 }
@@ -276,7 +580,54 @@ impl McpToolExecutor {
             return Err(anyhow::anyhow!("MCP client not ready: {}", client_name));
         }
 
-        let result = client.call_tool(actual_tool_name, arguments).await?;
+        let policy = client.call_policy();
+        let mut attempt = 0;
+        let result = loop {
+            match tokio::time::timeout(
+                policy.timeout,
+                client.call_tool(actual_tool_name, arguments.clone()),
+            )
+            .await
+            {
+                Ok(Ok(result)) => {
+                    self.consecutive_timeouts.remove(client_name);
+                    break result;
+                }
+                Ok(Err(e)) => {
+                    let transient = e
+                        .downcast_ref::<McpTransportError>()
+                        .is_some_and(|e| e.is_transient());
+                    if !transient || attempt >= policy.max_retries {
+                        return Err(e);
+                    }
+                    attempt += 1;
+                    warn!(
+                        "Retrying MCP tool '{}' after transient error (attempt {}): {}",
+                        tool_name, attempt, e
+                    );
+                    tokio::time::sleep(policy.retry_backoff * attempt).await;
+                }
+                Err(_) => {
+                    let timeouts = self
+                        .consecutive_timeouts
+                        .entry(client_name.to_string())
+                        .or_default();
+                    *timeouts += 1;
+                    if *timeouts >= policy.degraded_after {
+                        warn!(
+                            "MCP server '{}' is degraded after {} consecutive timeouts",
+                            client_name, timeouts
+                        );
+                    }
+                    return Err(McpTransportError::Timeout {
+                        server: client_name.to_string(),
+                        tool: actual_tool_name.to_string(),
+                        timeout: policy.timeout,
+                    }
+                    .into());
+                }
+            }
+        };
 
         let mut outputs = Vec::new();
         for content in result.content {
@@ -311,10 +662,7 @@ impl McpToolExecutor {
             .collect()
     }
 
-    pub async fn execute_tool_calls(
-        &mut self,
-        tool_calls: &[crate::chat_service::ToolCall],
-    ) -> Vec<crate::chat_service::ToolResult> {
+    pub async fn execute_tool_calls(&mut self, tool_calls: &[ToolCall]) -> Vec<ToolResult> {
         let mut results = Vec::new();
 
         for tool_call in tool_calls {
@@ -322,14 +670,14 @@ impl McpToolExecutor {
                 .execute_tool(&tool_call.name, Some(tool_call.arguments.clone()))
                 .await
             {
-                Ok(outputs) => crate::chat_service::ToolResult {
+                Ok(outputs) => ToolResult {
                     tool_call_id: tool_call.id.clone(),
                     result: serde_json::to_value(outputs.join("\n")).unwrap_or(
                         serde_json::Value::String("Tool executed successfully".to_string()),
                     ),
                     error: None,
                 },
-                Err(e) => crate::chat_service::ToolResult {
+                Err(e) => ToolResult {
                     tool_call_id: tool_call.id.clone(),
                     result: serde_json::Value::Null,
                     error: Some(e.to_string()),
@@ -341,16 +689,11 @@ impl McpToolExecutor {
         results
     }
 
-    pub async fn list_available_tools(&mut self) -> Vec<crate::chat_service::Tool> {
+    pub async fn list_available_tools(&mut self) -> Vec<ChatTool> {
         match self.list_all_tools().await {
             Ok(chat_tools) => chat_tools
                 .into_iter()
-                .map(|ct| crate::chat_service::Tool {
-                    name: ct.name,
-                    description: ct.description,
-                    input_schema: ct.input_schema,
-                    is_mcp: true,
-                })
+                .map(|ct| ChatTool { is_mcp: true, ..ct })
                 .collect(),
             Err(_) => vec![],
         }
@@ -363,3 +706,136 @@ impl Default for McpToolExecutor {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    struct SlowMcpClient;
+
+    #[async_trait]
+    impl McpClient for SlowMcpClient {
+        async fn initialize(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn list_tools(&mut self) -> Result<Vec<Tool>> {
+            Ok(Vec::new())
+        }
+
+        async fn call_tool(
+            &mut self,
+            _name: &str,
+            _arguments: Option<Value>,
+        ) -> Result<CallToolResult> {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(CallToolResult {
+                content: Vec::new(),
+                is_error: None,
+            })
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn is_ready(&self) -> bool {
+            true
+        }
+
+        fn call_policy(&self) -> McpCallPolicy {
+            McpCallPolicy {
+                timeout: Duration::from_millis(50),
+                ..McpCallPolicy::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_server_times_out_and_is_marked_degraded() {
+        let mut executor = McpToolExecutor::new();
+        executor.add_client(Box::new(SlowMcpClient));
+
+        let started = Instant::now();
+        let results = executor
+            .execute_tool_calls(&[ToolCall {
+                id: "call-1".to_string(),
+                name: "slow:lookup".to_string(),
+                arguments: json!({}),
+            }])
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(results[0].error.as_deref().unwrap().contains("timed out"));
+        assert_eq!(executor.server_status("slow"), Some(McpServerStatus::Ready));
+
+        executor
+            .execute_tool("slow:lookup", None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            executor.server_status("slow"),
+            Some(McpServerStatus::Degraded)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_running_servers_lists_no_tools() {
+        let mut executor = McpToolExecutor::new();
+        assert!(executor.list_all_tools().await.unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_servers_start_from_config_and_stop_on_removal() {
+        // Answers initialize, takes the initialized notification, then lists one tool
+        let script = r#"read line; echo '{"jsonrpc":"2.0","id":1,"result":{}}'; read line; read line; echo "{\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[{\"name\":\"$TOOL\",\"description\":\"Says hi\",\"inputSchema\":{}}]}}"; sleep 30"#;
+        let config = McpServerConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            call_policy: McpCallPolicy::default(),
+            env: HashMap::from([("TOOL".to_string(), "greet".to_string())]),
+        };
+        let mut executor = McpToolExecutor::new();
+
+        let tools = executor.add_server("hello", &config).await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "hello:greet");
+        assert_eq!(
+            executor.server_status("hello"),
+            Some(McpServerStatus::Ready)
+        );
+
+        assert!(executor.remove_server("hello").await);
+        assert_eq!(executor.server_status("hello"), None);
+        assert!(!executor.remove_server("hello").await);
+
+        let missing = McpServerConfig {
+            command: "no-such-mcp-server".to_string(),
+            ..config
+        };
+        assert!(executor.add_server("missing", &missing).await.is_err());
+        assert_eq!(executor.server_status("missing"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_crashed_server_fails_pending_call_fast() {
+        // Answers initialize, then exits on the first tool call without replying
+        let script = r#"read line; echo '{"jsonrpc":"2.0","id":1,"result":{}}'; read line; read line; exit 1"#;
+        let mut client = StdioMcpClient::new(
+            "crashy".to_string(),
+            "sh".to_string(),
+            vec!["-c".to_string(), script.to_string()],
+        );
+        client.initialize().await.unwrap();
+
+        let started = Instant::now();
+        let error = client.call_tool("anything", None).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            error.downcast_ref::<McpTransportError>(),
+            Some(McpTransportError::Disconnected(_))
+        ));
+        assert!(!client.is_ready());
+    }
+}
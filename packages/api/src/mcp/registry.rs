@@ -1,7 +1,7 @@
-use super::client::{McpToolExecutor, StdioMcpClient};
+use super::client::{McpCallPolicy, McpToolExecutor, StdioMcpClient};
 use anyhow::Result;
 use std::collections::HashMap;
-use tracing::{info, warn};
+use tracing::info;
 
 pub fn create_default_mcp_executor() -> Result<McpToolExecutor> {
     let mut executor = McpToolExecutor::new();
@@ -10,7 +10,9 @@ pub fn create_default_mcp_executor() -> Result<McpToolExecutor> {
     let common_servers = get_common_mcp_servers();
 
     for (name, config) in common_servers {
-        let client = StdioMcpClient::new(name.clone(), config.command.clone(), config.args.clone());
+        let client = StdioMcpClient::new(name.clone(), config.command.clone(), config.args.clone())
+            .with_call_policy(config.call_policy.clone())
+            .with_env(config.env.clone());
         executor.add_client(Box::new(client));
         info!("Added MCP server: {}", name);
     }
@@ -31,6 +33,8 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
                 "@modelcontextprotocol/server-filesystem".to_string(),
                 "/tmp".to_string(), // Default to temp directory
             ],
+            call_policy: McpCallPolicy::default(),
+            env: HashMap::new(),
         },
     );
 
@@ -44,6 +48,8 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
                     "-y".to_string(),
                     "@modelcontextprotocol/server-github".to_string(),
                 ],
+                call_policy: McpCallPolicy::default(),
+                env: HashMap::new(),
             },
         );
     }
@@ -57,6 +63,8 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
                 "-y".to_string(),
                 "@modelcontextprotocol/server-sqlite".to_string(),
             ],
+            call_policy: McpCallPolicy::default(),
+            env: HashMap::new(),
         },
     );
 
@@ -70,6 +78,8 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
                     "-y".to_string(),
                     "@modelcontextprotocol/server-brave-search".to_string(),
                 ],
+                call_policy: McpCallPolicy::default(),
+                env: HashMap::new(),
             },
         );
     }
@@ -83,6 +93,8 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
                 "-y".to_string(),
                 "@modelcontextprotocol/server-memory".to_string(),
             ],
+            call_policy: McpCallPolicy::default(),
+            env: HashMap::new(),
         },
     );
 
@@ -93,6 +105,10 @@ pub fn get_common_mcp_servers() -> HashMap<String, McpServerConfig> {
 pub struct McpServerConfig {
     pub command: String,
     pub args: Vec<String>,
+    /// Timeout and retry limits for this server's tool calls
+    pub call_policy: McpCallPolicy,
+    /// Extra environment variables for the server process, e.g. API keys
+    pub env: HashMap<String, String>,
 }
 
 pub fn create_custom_mcp_client(
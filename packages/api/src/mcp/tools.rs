@@ -1,10 +1,168 @@
-use super::client::{McpClient, McpToolExecutor};
-use super::protocol::Tool;
-use crate::chat_service::{Tool as ChatTool, ToolCall};
+use crate::chat_service_simple::{AgentConfig, Tool as ChatTool, ToolCall, ToolCategory};
 use anyhow::Result;
-use serde_json::Value;
-use std::collections::HashMap;
-use tracing::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, info, warn};
+
+/// Characters a shell would treat as more than part of an argument
+const SHELL_OPERATORS: &[char] = &[';', '&', '|', '`', '$', '<', '>', '(', ')', '\n'];
+
+/// What the `shell` tool may run, and where. Nothing, unless the agent config opts in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellPolicy {
+    pub enabled: bool,
+    /// Programs a command may start with; `*` allows any command, run through `sh -c`
+    pub allowlist: Vec<String>,
+    pub timeout: Duration,
+    /// Commands run here, and `working_directory` must stay inside it
+    pub workspace_root: PathBuf,
+}
+
+impl Default for ShellPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowlist: vec![],
+            timeout: Duration::from_secs(30),
+            workspace_root: std::env::current_dir().unwrap_or_default(),
+        }
+    }
+}
+
+impl ShellPolicy {
+    pub fn from_config(config: &AgentConfig) -> Self {
+        Self {
+            enabled: config.shell_tool_enabled,
+            allowlist: config.shell_allowlist.clone(),
+            timeout: Duration::from_secs(config.shell_timeout),
+            ..Self::default()
+        }
+    }
+
+    /// The program and arguments to start for `command`, or a refusal saying why. Allowlisted
+    /// commands are split into words and run without a shell; only a `*` allowlist hands the
+    /// whole string to `sh -c`.
+    pub fn check(&self, command: &str) -> Result<Vec<String>> {
+        anyhow::ensure!(
+            self.enabled,
+            "The shell tool is disabled; set shell_tool_enabled in the agent config to allow it"
+        );
+        if self.allowlist.iter().any(|allowed| allowed == "*") {
+            return Ok(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                command.to_string(),
+            ]);
+        }
+        anyhow::ensure!(
+            !command.contains(SHELL_OPERATORS),
+            "Shell operators are only allowed when the shell allowlist contains \"*\""
+        );
+        let argv = shell_words::split(command)
+            .map_err(|e| anyhow::anyhow!("Couldn't split the command into words: {}", e))?;
+        let program = argv.first().map(String::as_str).unwrap_or_default();
+        anyhow::ensure!(
+            self.allowlist.iter().any(|allowed| allowed == program),
+            "'{}' is not in the shell allowlist",
+            program
+        );
+        Ok(argv)
+    }
+
+    /// Where a command runs: the workspace root, or `dir` relative to it. A `dir` that
+    /// resolves outside the root is refused.
+    pub fn working_directory(&self, dir: Option<&str>) -> Result<PathBuf> {
+        let root = self.workspace_root.canonicalize().map_err(|e| {
+            anyhow::anyhow!(
+                "Workspace root {} is unavailable: {}",
+                self.workspace_root.display(),
+                e
+            )
+        })?;
+        let Some(dir) = dir else {
+            return Ok(root);
+        };
+        let resolved = root
+            .join(dir)
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("Working directory {} is unavailable: {}", dir, e))?;
+        anyhow::ensure!(
+            resolved.starts_with(&root),
+            "Working directory {} is outside the workspace",
+            dir
+        );
+        Ok(resolved)
+    }
+}
+
+/// `file_editor` operations that only read; `view` is accepted as another name for `read`
+const READ_ONLY_FILE_OPERATIONS: &[&str] = &["view", "read", "search", "list"];
+
+/// Refuse a built-in tool call that would write when the agent config lists the tool in
+/// `readonly_tools`. Tools that only ever read pass through.
+pub fn check_readonly(tool_call: &ToolCall, readonly_tools: &[String]) -> Result<()> {
+    if !readonly_tools.contains(&tool_call.name) {
+        return Ok(());
+    }
+    match tool_call.name.as_str() {
+        "shell" => anyhow::bail!(
+            "The shell tool is read-only in the agent config, so it can't run commands"
+        ),
+        "file_editor" => {
+            let operation = tool_call
+                .arguments
+                .get("operation")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            anyhow::ensure!(
+                READ_ONLY_FILE_OPERATIONS.contains(&operation),
+                "file_editor is read-only in the agent config; it can view, search and list files but not '{}'",
+                operation
+            );
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// One `web_search` hit. The tool lists them as `[n] title - url` lines so answers can cite
+/// them by number.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchSource {
+    pub index: usize,
+    pub title: String,
+    pub url: String,
+}
+
+impl SearchSource {
+    pub fn to_line(&self) -> String {
+        format!("[{}] {} - {}", self.index, self.title, self.url)
+    }
+}
+
+/// Sources listed in `web_search` output, in the order given. Other lines are ignored.
+pub fn parse_search_sources(output: &str) -> Vec<SearchSource> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix('[')?;
+            let (index, rest) = rest.split_once(']')?;
+            let (title, url) = rest.rsplit_once(" - ")?;
+            let url = url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return None;
+            }
+            Some(SearchSource {
+                index: index.trim().parse().ok()?,
+                title: title.trim().to_string(),
+                url: url.to_string(),
+            })
+        })
+        .collect()
+}
 
 // Built-in tools that are always available
 pub fn create_builtin_tools() -> Vec<ChatTool> {
@@ -22,12 +180,13 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                     },
                     "working_directory": {
                         "type": "string",
-                        "description": "Optional working directory for the command"
+                        "description": "Optional working directory for the command, inside the workspace"
                     }
                 },
                 "required": ["command"]
             }),
             is_mcp: false,
+            category: ToolCategory::System,
         },
         ChatTool {
             name: "file_editor".to_string(),
@@ -37,7 +196,7 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                 "properties": {
                     "operation": {
                         "type": "string",
-                        "enum": ["read", "write", "edit", "search", "list"],
+                        "enum": ["view", "read", "write", "edit", "str_replace", "search", "list"],
                         "description": "The file operation to perform"
                     },
                     "path": {
@@ -48,6 +207,14 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                         "type": "string",
                         "description": "Content for write/edit operations"
                     },
+                    "old_str": {
+                        "type": "string",
+                        "description": "Exact text to replace for str_replace; must occur exactly once"
+                    },
+                    "new_str": {
+                        "type": "string",
+                        "description": "Replacement text for str_replace"
+                    },
                     "search_term": {
                         "type": "string",
                         "description": "Search term for search operations"
@@ -60,6 +227,7 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                 "required": ["operation", "path"]
             }),
             is_mcp: false,
+            category: ToolCategory::Filesystem,
         },
         ChatTool {
             name: "web_search".to_string(),
@@ -80,6 +248,7 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                 "required": ["query"]
             }),
             is_mcp: false,
+            category: ToolCategory::Network,
         },
         ChatTool {
             name: "analyze_code".to_string(),
@@ -100,6 +269,7 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                 "required": ["file_path"]
             }),
             is_mcp: false,
+            category: ToolCategory::ReadOnly,
         },
         ChatTool {
             name: "system_info".to_string(),
@@ -116,13 +286,17 @@ pub fn create_builtin_tools() -> Vec<ChatTool> {
                 "required": ["info_type"]
             }),
             is_mcp: false,
+            category: ToolCategory::System,
         },
     ]
 }
 
-pub async fn execute_builtin_tool(tool_call: &ToolCall) -> Result<Vec<String>> {
+pub async fn execute_builtin_tool(
+    tool_call: &ToolCall,
+    shell: &ShellPolicy,
+) -> Result<Vec<String>> {
     match tool_call.name.as_str() {
-        "shell" => execute_shell_command(tool_call).await,
+        "shell" => execute_shell_command(tool_call, shell).await,
         "file_editor" => execute_file_operation(tool_call).await,
         "web_search" => execute_web_search(tool_call).await,
         "analyze_code" => execute_code_analysis(tool_call).await,
@@ -131,7 +305,7 @@ pub async fn execute_builtin_tool(tool_call: &ToolCall) -> Result<Vec<String>> {
     }
 }
 
-async fn execute_shell_command(tool_call: &ToolCall) -> Result<Vec<String>> {
+async fn execute_shell_command(tool_call: &ToolCall, shell: &ShellPolicy) -> Result<Vec<String>> {
     let command = tool_call
         .arguments
         .get("command")
@@ -143,41 +317,85 @@ async fn execute_shell_command(tool_call: &ToolCall) -> Result<Vec<String>> {
         .get("working_directory")
         .and_then(|v| v.as_str());
 
+    let argv = shell.check(command)?;
+    let working_dir = shell.working_directory(working_dir)?;
     info!("Executing shell command: {}", command);
 
-    let mut cmd = tokio::process::Command::new("sh");
-    cmd.arg("-c").arg(command);
-
-    if let Some(dir) = working_dir {
-        cmd.current_dir(dir);
-    }
+    let mut cmd = tokio::process::Command::new(&argv[0]);
+    cmd.args(&argv[1..])
+        .current_dir(working_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
 
-    let output = cmd
-        .output()
-        .await
+    let mut child = cmd
+        .spawn()
         .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
 
+    // Read as the command runs, so whatever it printed survives a timeout
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let finished = tokio::time::timeout(shell.timeout, async {
+        let (_, _, status) = tokio::join!(
+            read_into(stdout, &mut out),
+            read_into(stderr, &mut err),
+            child.wait()
+        );
+        status
+    })
+    .await;
+
+    let Ok(status) = finished else {
+        let _ = child.kill().await;
+        warn!("Shell command timed out: {}", command);
+        let mut message = format!(
+            "Command timed out after {}s and was killed",
+            shell.timeout.as_secs_f32()
+        );
+        if !out.is_empty() {
+            message.push_str(&format!(
+                ". Output before the timeout:\n{}",
+                String::from_utf8_lossy(&out)
+            ));
+        }
+        return Err(anyhow::anyhow!(message));
+    };
+    let status = status.map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
+
     let mut results = Vec::new();
 
-    if !output.stdout.is_empty() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        results.push(stdout.to_string());
+    if !out.is_empty() {
+        results.push(String::from_utf8_lossy(&out).to_string());
     }
 
-    if !output.stderr.is_empty() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        results.push(format!("stderr: {}", stderr));
+    if !err.is_empty() {
+        results.push(format!("stderr: {}", String::from_utf8_lossy(&err)));
     }
 
-    results.push(format!("Exit code: {}", output.status));
+    results.push(format!("Exit code: {}", status));
 
-    if !output.status.success() {
+    if !status.success() {
         warn!("Shell command failed: {}", command);
     }
 
     Ok(results)
 }
 
+/// Append everything `reader` yields to `buf`. Reads in chunks so a cancelled read loses
+/// nothing already received.
+async fn read_into(reader: Option<impl AsyncRead + Unpin>, buf: &mut Vec<u8>) {
+    let Some(mut reader) = reader else {
+        return;
+    };
+    let mut chunk = [0u8; 4096];
+    while let Ok(read) = reader.read(&mut chunk).await {
+        if read == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    }
+}
+
 async fn execute_file_operation(tool_call: &ToolCall) -> Result<Vec<String>> {
     let operation = tool_call
         .arguments
@@ -194,7 +412,7 @@ async fn execute_file_operation(tool_call: &ToolCall) -> Result<Vec<String>> {
     debug!("File operation: {} on {}", operation, path);
 
     match operation {
-        "read" => {
+        "view" | "read" => {
             let content = tokio::fs::read_to_string(path)
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to read file '{}': {}", path, e))?;
@@ -228,6 +446,33 @@ async fn execute_file_operation(tool_call: &ToolCall) -> Result<Vec<String>> {
 
             Ok(vec![format!("Successfully edited file: {}", path)])
         }
+        "str_replace" => {
+            let old_str = tool_call
+                .arguments
+                .get("old_str")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing 'old_str' parameter"))?;
+            let new_str = tool_call
+                .arguments
+                .get("new_str")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Missing 'new_str' parameter"))?;
+
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to read file '{}': {}", path, e))?;
+            let updated = replace_once(&content, old_str, new_str)
+                .map_err(|e| anyhow::anyhow!("{} in {}", e, path))?;
+
+            tokio::fs::write(path, updated)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to edit file '{}': {}", path, e))?;
+
+            Ok(vec![format!(
+                "Successfully replaced text in file: {}",
+                path
+            )])
+        }
         "search" => {
             let search_term = tool_call
                 .arguments
@@ -302,6 +547,27 @@ async fn execute_file_operation(tool_call: &ToolCall) -> Result<Vec<String>> {
     }
 }
 
+/// `content` with its single occurrence of `old_str` replaced. Missing or repeated text is an
+/// error, since the edit could land somewhere the caller didn't mean.
+fn replace_once(content: &str, old_str: &str, new_str: &str) -> Result<String> {
+    anyhow::ensure!(!old_str.is_empty(), "'old_str' must not be empty");
+    let first = content
+        .find(old_str)
+        .ok_or_else(|| anyhow::anyhow!("'old_str' was not found"))?;
+    // Search again one character in, so overlapping matches ("aa" in "aaa") count too
+    let next = first + content[first..].chars().next().map_or(1, char::len_utf8);
+    anyhow::ensure!(
+        !content[next..].contains(old_str),
+        "'old_str' occurs more than once; include more surrounding text so it matches once"
+    );
+    Ok(format!(
+        "{}{}{}",
+        &content[..first],
+        new_str,
+        &content[first + old_str.len()..]
+    ))
+}
+
 async fn execute_web_search(tool_call: &ToolCall) -> Result<Vec<String>> {
     let query = tool_call
         .arguments
@@ -316,13 +582,17 @@ async fn execute_web_search(tool_call: &ToolCall) -> Result<Vec<String>> {
         query
     );
 
-    Ok(vec![
-        format!("Web search results for: {}", query),
-        "1. Mock search result 1".to_string(),
-        "2. Mock search result 2".to_string(),
-        "3. Mock search result 3".to_string(),
-        "(Note: Actual web search not implemented - this is a mock result)".to_string(),
-    ])
+    let mut results = vec![format!("Web search results for: {}", query)];
+    results.extend((1..=3).map(|index| {
+        SearchSource {
+            index,
+            title: format!("Mock search result {}", index),
+            url: format!("https://example.com/search/{}", index),
+        }
+        .to_line()
+    }));
+    results.push("(Note: Actual web search not implemented - this is a mock result)".to_string());
+    Ok(results)
 }
 
 async fn execute_code_analysis(tool_call: &ToolCall) -> Result<Vec<String>> {
@@ -431,7 +701,7 @@ async fn execute_system_info(tool_call: &ToolCall) -> Result<Vec<String>> {
         }
         "processes" => {
             if let Ok(output) = tokio::process::Command::new("ps")
-                .args(&["aux"])
+                .args(["aux"])
                 .output()
                 .await
             {
@@ -469,3 +739,156 @@ fn matches_pattern(text: &str, pattern: &str) -> bool {
         .unwrap_or(false)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_call(command: &str) -> ToolCall {
+        ToolCall {
+            id: "call-1".to_string(),
+            name: "shell".to_string(),
+            arguments: serde_json::json!({ "command": command }),
+        }
+    }
+
+    #[test]
+    fn test_shell_policy_is_off_by_default_and_enforces_the_allowlist() {
+        assert!(ShellPolicy::default().check("ls").is_err());
+        let config = AgentConfig {
+            shell_tool_enabled: true,
+            ..AgentConfig::default()
+        };
+        let policy = ShellPolicy::from_config(&config);
+        assert!(policy.check("ls -la src").is_ok());
+        assert!(policy.check("rm -rf /").is_err());
+        assert!(policy.check("ls; rm -rf /").is_err());
+        assert!(policy.check("echo $(rm -rf /)").is_err());
+        assert!(policy.check("find / -delete").is_err());
+        assert_eq!(
+            policy.check("grep -n 'fn main' src/main.rs").unwrap(),
+            vec!["grep", "-n", "fn main", "src/main.rs"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_commands_run_without_a_shell_inside_the_workspace() {
+        let root = std::env::temp_dir().join(format!("shell_workspace_{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(root.join("src")).await.unwrap();
+        let policy = ShellPolicy {
+            enabled: true,
+            allowlist: vec!["echo".to_string(), "pwd".to_string()],
+            workspace_root: root.clone(),
+            ..ShellPolicy::default()
+        };
+        let in_dir = |command: &str, dir: &str| ToolCall {
+            arguments: serde_json::json!({ "command": command, "working_directory": dir }),
+            ..shell_call(command)
+        };
+
+        // Quoted words reach the program as one argument, untouched by a shell
+        let output = execute_builtin_tool(&shell_call("echo 'a  *'"), &policy)
+            .await
+            .unwrap();
+        assert_eq!(output[0], "a  *\n");
+
+        let output = execute_builtin_tool(&in_dir("pwd", "src"), &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            output[0].trim_end(),
+            root.join("src").canonicalize().unwrap().to_string_lossy()
+        );
+        for outside in ["..", "/", "src/../.."] {
+            let error = execute_builtin_tool(&in_dir("pwd", outside), &policy)
+                .await
+                .unwrap_err();
+            assert!(
+                error.to_string().contains("outside the workspace"),
+                "{}",
+                error
+            );
+        }
+        let _ = tokio::fs::remove_dir_all(&root).await;
+    }
+
+    #[test]
+    fn test_readonly_tools_only_allow_reads() {
+        let readonly = vec!["file_editor".to_string(), "shell".to_string()];
+        let file_call = |operation: &str| ToolCall {
+            id: "call-1".to_string(),
+            name: "file_editor".to_string(),
+            arguments: serde_json::json!({ "operation": operation, "path": "notes.txt" }),
+        };
+
+        assert!(check_readonly(&file_call("view"), &readonly).is_ok());
+        assert!(check_readonly(&file_call("read"), &readonly).is_ok());
+        assert!(check_readonly(&file_call("list"), &readonly).is_ok());
+        let error = check_readonly(&file_call("str_replace"), &readonly).unwrap_err();
+        assert!(error.to_string().contains("read-only"));
+        assert!(check_readonly(&file_call("write"), &readonly).is_err());
+        assert!(check_readonly(&shell_call("ls"), &readonly).is_err());
+
+        assert!(check_readonly(&file_call("write"), &[]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_edits_a_single_occurrence() {
+        let path = std::env::temp_dir().join(format!("str_replace_{}.rs", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, "let a = 1;\nlet b = 1;\n")
+            .await
+            .unwrap();
+        let edit = |old_str: &str, new_str: &str| ToolCall {
+            id: "call-1".to_string(),
+            name: "file_editor".to_string(),
+            arguments: serde_json::json!({
+                "operation": "str_replace",
+                "path": path.to_string_lossy(),
+                "old_str": old_str,
+                "new_str": new_str,
+            }),
+        };
+        let policy = ShellPolicy::default();
+
+        let ambiguous = execute_builtin_tool(&edit("= 1;", "= 2;"), &policy).await;
+        assert!(ambiguous
+            .unwrap_err()
+            .to_string()
+            .contains("occurs more than once"));
+        let missing = execute_builtin_tool(&edit("let c", "let d"), &policy).await;
+        assert!(missing.unwrap_err().to_string().contains("not found"));
+
+        execute_builtin_tool(&edit("let b = 1;", "let b = 2;"), &policy)
+            .await
+            .unwrap();
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        assert_eq!(content, "let a = 1;\nlet b = 2;\n");
+        let _ = tokio::fs::remove_file(&path).await;
+
+        // Overlapping matches are ambiguous too
+        assert!(replace_once("aaa", "aa", "b").is_err());
+        assert_eq!(replace_once("aab", "ab", "c").unwrap(), "ac");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_command_is_killed_on_timeout_keeping_partial_output() {
+        let policy = ShellPolicy {
+            enabled: true,
+            allowlist: vec!["*".to_string()],
+            timeout: Duration::from_millis(300),
+            ..ShellPolicy::default()
+        };
+        let error = execute_builtin_tool(&shell_call("echo partial; sleep 5"), &policy)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("timed out"), "{}", error);
+        assert!(error.contains("partial"), "{}", error);
+
+        let output = execute_builtin_tool(&shell_call("echo done"), &policy)
+            .await
+            .unwrap();
+        assert_eq!(output[0], "done\n");
+    }
+}
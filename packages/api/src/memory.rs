@@ -0,0 +1,333 @@
+// Long-term agent memory, stored per session so it survives restarts
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{DbPool, PooledConnection};
+
+/// Most memory entries put in front of the model for one reply
+pub const MAX_RECALLED_MEMORIES: usize = 5;
+
+/// Something the agent was asked to, or decided to, remember
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: String,
+    pub content: String,
+    /// From 0.0 to 1.0; breaks ties between equally relevant entries
+    pub importance: f32,
+    pub access_count: u32,
+    pub last_accessed: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MemoryEntry {
+    pub fn new(content: impl Into<String>, importance: f32) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            content: content.into(),
+            importance: importance.clamp(0.0, 1.0),
+            access_count: 0,
+            last_accessed: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// What happened in an earlier exchange and how it turned out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpisodicMemory {
+    pub id: String,
+    pub summary: String,
+    pub outcome: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A fact the agent learned about a concept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SemanticMemory {
+    pub id: String,
+    pub concept: String,
+    pub knowledge: String,
+    pub confidence: f32,
+}
+
+/// Everything remembered for one session
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionMemory {
+    pub entries: Vec<MemoryEntry>,
+    pub episodes: Vec<EpisodicMemory>,
+    pub facts: Vec<SemanticMemory>,
+}
+
+fn from_millis(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .unwrap_or_default()
+}
+
+/// Lowercase words of a search query worth matching on
+fn query_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    pool: DbPool,
+}
+
+impl MemoryStore {
+    pub fn new(pool: DbPool) -> Result<Self> {
+        let store = Self { pool };
+        store.conn()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS memory_entries (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                content TEXT NOT NULL,
+                importance REAL NOT NULL,
+                access_count INTEGER NOT NULL DEFAULT 0,
+                last_accessed INTEGER,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS memory_episodes (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                summary TEXT NOT NULL,
+                outcome TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS memory_facts (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                concept TEXT NOT NULL,
+                knowledge TEXT NOT NULL,
+                confidence REAL NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS memory_embeddings (
+                entry_id TEXT PRIMARY KEY REFERENCES memory_entries(id) ON DELETE CASCADE,
+                embedding BLOB NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_memory_entries_session ON memory_entries(session_id);
+            CREATE INDEX IF NOT EXISTS idx_memory_episodes_session ON memory_episodes(session_id, created_at);
+            CREATE INDEX IF NOT EXISTS idx_memory_facts_session ON memory_facts(session_id);",
+        )?;
+        Ok(store)
+    }
+
+    fn conn(&self) -> Result<PooledConnection> {
+        Ok(self.pool.get()?)
+    }
+
+    /// Save a session's memory. Items are upserted by id; nothing already stored is removed.
+    /// Saved entries lose their embedding, since their content may have changed.
+    pub fn persist(&self, session_id: &str, memory: &SessionMemory) -> Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for entry in &memory.entries {
+            tx.execute(
+                "DELETE FROM memory_embeddings WHERE entry_id = ?1",
+                params![entry.id],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO memory_entries
+                    (id, session_id, content, importance, access_count, last_accessed, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    entry.id,
+                    session_id,
+                    entry.content,
+                    entry.importance,
+                    entry.access_count,
+                    entry.last_accessed.map(|at| at.timestamp_millis()),
+                    entry.created_at.timestamp_millis()
+                ],
+            )?;
+        }
+        for episode in &memory.episodes {
+            tx.execute(
+                "INSERT OR REPLACE INTO memory_episodes (id, session_id, summary, outcome, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    episode.id,
+                    session_id,
+                    episode.summary,
+                    episode.outcome,
+                    episode.timestamp.timestamp_millis()
+                ],
+            )?;
+        }
+        for fact in &memory.facts {
+            tx.execute(
+                "INSERT OR REPLACE INTO memory_facts (id, session_id, concept, knowledge, confidence)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    fact.id,
+                    session_id,
+                    fact.concept,
+                    fact.knowledge,
+                    fact.confidence
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Everything stored for a session. Loading doesn't count as an access.
+    pub fn load(&self, session_id: &str) -> Result<SessionMemory> {
+        let conn = self.conn()?;
+        let entries = Self::entries(&conn, session_id)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, summary, outcome, created_at FROM memory_episodes
+             WHERE session_id = ?1 ORDER BY created_at, id",
+        )?;
+        let episodes = stmt
+            .query_map(params![session_id], |row| {
+                Ok(EpisodicMemory {
+                    id: row.get(0)?,
+                    summary: row.get(1)?,
+                    outcome: row.get(2)?,
+                    timestamp: from_millis(row.get(3)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, concept, knowledge, confidence FROM memory_facts
+             WHERE session_id = ?1 ORDER BY concept, id",
+        )?;
+        let facts = stmt
+            .query_map(params![session_id], |row| {
+                Ok(SemanticMemory {
+                    id: row.get(0)?,
+                    concept: row.get(1)?,
+                    knowledge: row.get(2)?,
+                    confidence: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(SessionMemory {
+            entries,
+            episodes,
+            facts,
+        })
+    }
+
+    fn entries(conn: &Connection, session_id: &str) -> Result<Vec<MemoryEntry>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, content, importance, access_count, last_accessed, created_at
+             FROM memory_entries WHERE session_id = ?1 ORDER BY created_at, id",
+        )?;
+        let entries = stmt
+            .query_map(params![session_id], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries sharing the most words with `query`, more important ones first on
+    /// a tie. Each returned entry has its `access_count` and `last_accessed` updated.
+    pub fn search(&self, session_id: &str, query: &str, limit: usize) -> Result<Vec<MemoryEntry>> {
+        let terms = query_terms(query);
+        if terms.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut scored: Vec<(usize, MemoryEntry)> = Self::entries(&*self.conn()?, session_id)?
+            .into_iter()
+            .filter_map(|entry| {
+                let content = entry.content.to_lowercase();
+                let matches = terms.iter().filter(|term| content.contains(*term)).count();
+                (matches > 0).then_some((matches, entry))
+            })
+            .collect();
+        scored.sort_by(|(a_matches, a), (b_matches, b)| {
+            b_matches
+                .cmp(a_matches)
+                .then(b.importance.total_cmp(&a.importance))
+        });
+        scored.truncate(limit);
+
+        let mut found: Vec<MemoryEntry> = scored.into_iter().map(|(_, entry)| entry).collect();
+        self.record_access(&mut found)?;
+        Ok(found)
+    }
+
+    /// Count a retrieval of `entries`, in the database and on the values passed in
+    pub fn record_access(&self, entries: &mut [MemoryEntry]) -> Result<()> {
+        let now = Utc::now();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for entry in entries.iter_mut() {
+            tx.execute(
+                "UPDATE memory_entries SET access_count = access_count + 1, last_accessed = ?1
+                 WHERE id = ?2",
+                params![now.timestamp_millis(), entry.id],
+            )?;
+            entry.access_count += 1;
+            entry.last_accessed = Some(now);
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// A session's entries with their stored embedding, if one has been computed
+    pub fn entries_with_embeddings(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<(MemoryEntry, Option<Vec<f32>>)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.content, e.importance, e.access_count, e.last_accessed, e.created_at,
+                    v.embedding
+             FROM memory_entries e LEFT JOIN memory_embeddings v ON v.entry_id = e.id
+             WHERE e.session_id = ?1 ORDER BY e.created_at, e.id",
+        )?;
+        let entries = stmt
+            .query_map(params![session_id], |row| {
+                let embedding: Option<Vec<u8>> = row.get(6)?;
+                Ok((
+                    entry_from_row(row)?,
+                    embedding.map(|bytes| decode_vector(&bytes)),
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    pub fn set_embedding(&self, entry_id: &str, embedding: &[f32]) -> Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO memory_embeddings (entry_id, embedding) VALUES (?1, ?2)",
+            params![entry_id, encode_vector(embedding)],
+        )?;
+        Ok(())
+    }
+}
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<MemoryEntry> {
+    Ok(MemoryEntry {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        importance: row.get(2)?,
+        access_count: row.get(3)?,
+        last_accessed: row.get::<_, Option<i64>>(4)?.map(from_millis),
+        created_at: from_millis(row.get(5)?),
+    })
+}
+
+/// Embeddings are stored as little-endian f32s
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
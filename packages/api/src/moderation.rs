@@ -0,0 +1,206 @@
+// Optional content moderation for outbound user messages and inbound assistant replies
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Which side of the conversation is being checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationDirection {
+    /// A user message about to be sent to the provider
+    Outbound,
+    /// Assistant content received from the provider
+    Inbound,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModerationVerdict {
+    Allow,
+    /// Let the content through but tell the user why it was flagged
+    Warn(String),
+    /// Replace the content with a redacted version
+    Redact(String),
+    /// Refuse the turn
+    Block(String),
+}
+
+#[async_trait]
+pub trait ModerationProvider: Send + Sync {
+    async fn check(
+        &self,
+        content: &str,
+        direction: ModerationDirection,
+    ) -> Result<ModerationVerdict>;
+}
+
+/// Default moderator that allows everything
+pub struct NoopModerator;
+
+#[async_trait]
+impl ModerationProvider for NoopModerator {
+    async fn check(
+        &self,
+        _content: &str,
+        _direction: ModerationDirection,
+    ) -> Result<ModerationVerdict> {
+        Ok(ModerationVerdict::Allow)
+    }
+}
+
+/// Case-insensitive keyword lists for blocking or redacting content
+#[derive(Debug, Clone, Default)]
+pub struct KeywordModerator {
+    pub blocked: Vec<String>,
+    pub redacted: Vec<String>,
+}
+
+impl KeywordModerator {
+    pub fn new(blocked: Vec<String>, redacted: Vec<String>) -> Self {
+        Self {
+            blocked: blocked.into_iter().map(|k| k.to_lowercase()).collect(),
+            redacted: redacted.into_iter().map(|k| k.to_lowercase()).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl ModerationProvider for KeywordModerator {
+    async fn check(
+        &self,
+        content: &str,
+        _direction: ModerationDirection,
+    ) -> Result<ModerationVerdict> {
+        let lower = content.to_lowercase();
+        if let Some(keyword) = self.blocked.iter().find(|k| lower.contains(k.as_str())) {
+            return Ok(ModerationVerdict::Block(format!(
+                "Content contains a blocked phrase: \"{}\"",
+                keyword
+            )));
+        }
+
+        let mut redacted = content.to_string();
+        for keyword in &self.redacted {
+            let pattern = regex::RegexBuilder::new(&regex::escape(keyword))
+                .case_insensitive(true)
+                .build()?;
+            redacted = pattern.replace_all(&redacted, "[redacted]").into_owned();
+        }
+
+        if redacted != content {
+            Ok(ModerationVerdict::Redact(redacted))
+        } else {
+            Ok(ModerationVerdict::Allow)
+        }
+    }
+}
+
+/// A moderation provider together with how long it may take per check
+#[derive(Clone)]
+pub struct Moderation {
+    provider: Arc<dyn ModerationProvider>,
+    timeout: Duration,
+    /// Block the turn when the moderator errors or times out instead of letting it through
+    fail_closed: bool,
+}
+
+impl std::fmt::Debug for Moderation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Moderation")
+            .field("timeout", &self.timeout)
+            .field("fail_closed", &self.fail_closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Moderation {
+    fn default() -> Self {
+        Self::new(Arc::new(NoopModerator), Duration::from_secs(5))
+    }
+}
+
+impl Moderation {
+    pub fn new(provider: Arc<dyn ModerationProvider>, timeout: Duration) -> Self {
+        Self {
+            provider,
+            timeout,
+            fail_closed: false,
+        }
+    }
+
+    pub fn fail_closed(mut self, fail_closed: bool) -> Self {
+        self.fail_closed = fail_closed;
+        self
+    }
+
+    /// Run the moderator, bounded by the configured timeout so a slow moderator can't hang the chat
+    pub async fn check(&self, content: &str, direction: ModerationDirection) -> ModerationVerdict {
+        let failure =
+            match tokio::time::timeout(self.timeout, self.provider.check(content, direction)).await
+            {
+                Ok(Ok(verdict)) => return verdict,
+                Ok(Err(e)) => format!("Moderation failed: {}", e),
+                Err(_) => format!("Moderation timed out after {}s", self.timeout.as_secs_f32()),
+            };
+
+        tracing::warn!("{}", failure);
+        if self.fail_closed {
+            ModerationVerdict::Block(failure)
+        } else {
+            ModerationVerdict::Allow
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SlowModerator;
+
+    #[async_trait]
+    impl ModerationProvider for SlowModerator {
+        async fn check(
+            &self,
+            _content: &str,
+            _direction: ModerationDirection,
+        ) -> Result<ModerationVerdict> {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(ModerationVerdict::Block("too late".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keyword_moderator_blocks_and_redacts() {
+        let moderator =
+            KeywordModerator::new(vec!["Forbidden".to_string()], vec!["secret".to_string()]);
+        let moderation = Moderation::new(Arc::new(moderator), Duration::from_secs(1));
+
+        let verdict = moderation
+            .check("this is FORBIDDEN", ModerationDirection::Outbound)
+            .await;
+        assert!(matches!(verdict, ModerationVerdict::Block(_)));
+
+        let verdict = moderation
+            .check("my Secret code", ModerationDirection::Inbound)
+            .await;
+        assert_eq!(
+            verdict,
+            ModerationVerdict::Redact("my [redacted] code".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_moderator_is_time_bounded() {
+        let moderation = Moderation::new(Arc::new(SlowModerator), Duration::from_millis(50));
+        assert_eq!(
+            moderation.check("hi", ModerationDirection::Outbound).await,
+            ModerationVerdict::Allow
+        );
+
+        let moderation = moderation.fail_closed(true);
+        assert!(matches!(
+            moderation.check("hi", ModerationDirection::Outbound).await,
+            ModerationVerdict::Block(_)
+        ));
+    }
+}
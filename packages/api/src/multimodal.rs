@@ -1,18 +1,20 @@
 // Multimodal Support for Rig Agents
 use anyhow::Result;
 use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
-use rig::{
-    completion::ToolDefinition,
-    tool::Tool,
-};
-use base64::{Engine as _, engine::general_purpose};
-use tokio::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
 
-use crate::{ChatMessage, MessageContent, Role, ToolCall, ToolResult, Tool as ApiTool};
+use crate::document_text;
+use crate::rig_agent_service::CustomTool;
+use crate::{
+    ChatMessage, ChatResponse, MessageContent, Role, Tool as ApiTool, ToolCall, ToolCategory,
+    ToolResult,
+};
 
 /// Supported media types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -118,10 +120,8 @@ impl MultimodalService {
     /// Validate media content
     pub async fn validate_media(&self, content: &MediaContent) -> Result<()> {
         match &content.content {
-            MediaData::Raw(data) => {
-                if data.len() > self.max_file_size {
-                    return Err(anyhow::anyhow!("File size exceeds limit of {} bytes", self.max_file_size));
-                }
+            MediaData::Raw(data) if data.len() > self.max_file_size => {
+                return Err(anyhow::anyhow!("File size exceeds limit of {} bytes", self.max_file_size));
             },
             MediaData::FilePath(path) => {
                 let metadata = fs::metadata(path).await?;
@@ -171,14 +171,33 @@ impl MultimodalService {
             return None;
         }
 
-        match &data[0..4] {
-            [0xFF, 0xD8, 0xFF] => Some((MediaType::Image, "image/jpeg".to_string())),
-            [0x89, 0x50, 0x4E, 0x47] => Some((MediaType::Image, "image/png".to_string())),
-            [0x47, 0x49, 0x46] => Some((MediaType::Image, "image/gif".to_string())),
-            [0x25, 0x50, 0x44, 0x46] => Some((MediaType::Document, "application/pdf".to_string())),
-            [0x49, 0x44, 0x33] => Some((MediaType::Audio, "audio/mp3".to_string())),
-            _ => None,
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some((MediaType::Image, "image/jpeg".to_string()))
+        } else if data.starts_with(&[0x89, 0x50, 0x4E, 0x47]) {
+            Some((MediaType::Image, "image/png".to_string()))
+        } else if data.starts_with(&[0x47, 0x49, 0x46]) {
+            Some((MediaType::Image, "image/gif".to_string()))
+        } else if data.starts_with(&[0x25, 0x50, 0x44, 0x46]) {
+            Some((MediaType::Document, "application/pdf".to_string()))
+        } else if data.starts_with(&[0x49, 0x44, 0x33]) {
+            Some((MediaType::Audio, "audio/mp3".to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Replace file paths and raw bytes with base64 so the request can leave this machine
+    pub async fn inline_media(&self, messages: &mut [MultimodalMessage]) -> Result<()> {
+        for message in messages {
+            for part in &mut message.content {
+                if let MultimodalContent::Media(media) = part {
+                    if matches!(media.content, MediaData::FilePath(_) | MediaData::Raw(_)) {
+                        media.content = MediaData::Base64(self.to_base64(media).await?);
+                    }
+                }
+            }
         }
+        Ok(())
     }
 
     /// Process image with AI vision models
@@ -201,9 +220,12 @@ impl MultimodalService {
             "text/plain" | "text/markdown" => {
                 Ok(String::from_utf8(document_data.to_vec())?)
             },
-            "application/pdf" => {
-                // This would use a PDF extraction library
-                Ok(format!("Extracted text from PDF ({} bytes). PDF processing would be implemented with actual PDF library.", document_data.len()))
+            _ if document_text::is_document(mime_type) => {
+                let (data, mime_type) = (document_data.to_vec(), mime_type.to_string());
+                tokio::task::spawn_blocking(move || {
+                    document_text::extract_document_text(&data, &mime_type)
+                })
+                .await?
             },
             _ => Ok(format!("Document processing for {} not implemented yet.", mime_type))
         }
@@ -216,62 +238,46 @@ impl Default for MultimodalService {
     }
 }
 
+/// Decode a tool argument that is either a file path, a data URL or bare base64
+async fn read_media_argument(value: &str) -> Result<Vec<u8>> {
+    if value.starts_with('/') {
+        return Ok(fs::read(value).await?);
+    }
+    decode_media(value)
+}
+
+/// Bytes of base64 or data URL `value`
+fn decode_media(value: &str) -> Result<Vec<u8>> {
+    let encoded = match value.split_once(',') {
+        Some((header, data)) if header.starts_with("data:") => data,
+        Some(_) => return Err(anyhow::anyhow!("Invalid data URL format")),
+        None => value,
+    };
+    Ok(general_purpose::STANDARD.decode(encoded)?)
+}
+
 /// Vision analysis tool
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct VisionAnalysisTool {
     multimodal_service: MultimodalService,
 }
 
 #[async_trait]
-impl Tool for VisionAnalysisTool {
-    const NAME: &'static str = "vision_analyze";
-    type Error = Box<dyn std::error::Error + Send + Sync>;
-    type Args = VisionAnalysisArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> Result<ToolDefinition, Self::Error> {
-        Ok(ToolDefinition {
-            name: "vision_analyze".to_string(),
-            description: "Analyze images and provide visual descriptions".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "image": {
-                        "type": "string",
-                        "description": "Base64 encoded image data or file path"
-                    },
-                    "prompt": {
-                        "type": "string",
-                        "description": "What to analyze in the image"
-                    }
-                },
-                "required": ["image", "prompt"]
-            }),
-        })
+impl CustomTool for VisionAnalysisTool {
+    fn name(&self) -> &'static str {
+        "vision_analyze"
     }
 
-    async fn call(&self, args: serde_json::Value) -> Result<Self::Output, Self::Error> {
-        let args: VisionAnalysisArgs = serde_json::from_value(args)
-            .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-
-        let image_data = if args.image.starts_with('/') {
-            // File path
-            fs::read(&args.image).await?
-        } else if args.image.contains(',') {
-            // Base64 data URL
-            let parts: Vec<&str> = args.image.split(',').collect();
-            if parts.len() != 2 {
-                return Err("Invalid data URL format".into());
-            }
-            use base64::{Engine as _, engine::general_purpose};
-            general_purpose::STANDARD.decode(parts[1])?
-        } else {
-            // Assume base64 string
-            use base64::{Engine as _, engine::general_purpose};
-            general_purpose::STANDARD.decode(&args.image)?
-        };
+    fn description(&self) -> &'static str {
+        "Analyze images and provide visual descriptions"
+    }
 
-        self.multimodal_service.analyze_image(&image_data, &args.prompt).await.map_err(|e| e.into())
+    async fn call(&self, args: serde_json::Value) -> Result<String> {
+        let args: VisionAnalysisArgs = serde_json::from_value(args)?;
+        let image_data = read_media_argument(&args.image).await?;
+        self.multimodal_service
+            .analyze_image(&image_data, &args.prompt)
+            .await
     }
 }
 
@@ -281,114 +287,132 @@ pub struct VisionAnalysisArgs {
     pub prompt: String,
 }
 
+/// Turns recorded speech into text for models that only read text
+#[async_trait]
+pub trait TranscriptionProvider: std::fmt::Debug + Send + Sync {
+    async fn transcribe(
+        &self,
+        audio: &[u8],
+        mime_type: &str,
+        language: Option<&str>,
+    ) -> Result<String>;
+}
+
+/// Placeholder transcription used until a speech-to-text provider is configured
+#[derive(Debug, Default)]
+pub struct MockTranscriptionProvider;
+
+#[async_trait]
+impl TranscriptionProvider for MockTranscriptionProvider {
+    async fn transcribe(
+        &self,
+        audio: &[u8],
+        _mime_type: &str,
+        _language: Option<&str>,
+    ) -> Result<String> {
+        MultimodalService::new().transcribe_audio(audio).await
+    }
+}
+
 /// Speech-to-text tool
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SpeechToTextTool {
-    multimodal_service: MultimodalService,
+    transcriber: Arc<dyn TranscriptionProvider>,
 }
 
-#[async_trait]
-impl Tool for SpeechToTextTool {
-    const NAME: &'static str = "speech_to_text";
-    type Error = Box<dyn std::error::Error + Send + Sync>;
-    type Args = SpeechToTextArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> Result<ToolDefinition, Self::Error> {
-        Ok(ToolDefinition {
-            name: "speech_to_text".to_string(),
-            description: "Transcribe audio to text".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "audio": {
-                        "type": "string",
-                        "description": "Base64 encoded audio data or file path"
-                    },
-                    "language": {
-                        "type": "string",
-                        "description": "Language code (e.g., en, zh, es)",
-                        "default": "en"
-                    }
-                },
-                "required": ["audio"]
-            }),
-        })
+impl SpeechToTextTool {
+    pub fn new(transcriber: Arc<dyn TranscriptionProvider>) -> Self {
+        Self { transcriber }
+    }
+
+    /// `parts` with a `Text` transcript after every `Audio` part, the audio itself kept
+    pub async fn transcribe_parts(
+        &self,
+        parts: Vec<MessageContent>,
+    ) -> Result<Vec<MessageContent>> {
+        let mut transcribed = Vec::with_capacity(parts.len());
+        for part in parts {
+            let transcript = match &part {
+                MessageContent::Audio {
+                    name,
+                    content,
+                    mime_type,
+                    ..
+                } => {
+                    // Inline only: message parts come from clients, so never a server path
+                    let audio = decode_media(content)?;
+                    let text = self.transcriber.transcribe(&audio, mime_type, None).await?;
+                    Some(format!("[Transcript of {}]\n{}", name, text))
+                }
+                _ => None,
+            };
+            transcribed.push(part);
+            if let Some(text) = transcript {
+                transcribed.push(MessageContent::Text { text });
+            }
+        }
+        Ok(transcribed)
     }
+}
 
-    async fn call(&self, args: serde_json::Value) -> Result<Self::Output, Self::Error> {
-        let args: SpeechToTextArgs = serde_json::from_value(args)
-            .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+impl Default for SpeechToTextTool {
+    fn default() -> Self {
+        Self::new(Arc::new(MockTranscriptionProvider))
+    }
+}
 
-        let audio_data = if args.audio.starts_with('/') {
-            fs::read(&args.audio).await?
-        } else {
-            use base64::{Engine as _, engine::general_purpose};
-            general_purpose::STANDARD.decode(&args.audio)?
-        };
+#[async_trait]
+impl CustomTool for SpeechToTextTool {
+    fn name(&self) -> &'static str {
+        "speech_to_text"
+    }
+
+    fn description(&self) -> &'static str {
+        "Transcribe audio to text"
+    }
 
-        self.multimodal_service.transcribe_audio(&audio_data).await.map_err(|e| e.into())
+    async fn call(&self, args: serde_json::Value) -> Result<String> {
+        let args: SpeechToTextArgs = serde_json::from_value(args)?;
+        let audio_data = read_media_argument(&args.audio).await?;
+        let mime_type = args.mime_type.as_deref().unwrap_or("audio/mpeg");
+        self.transcriber
+            .transcribe(&audio_data, mime_type, args.language.as_deref())
+            .await
     }
 }
 
 #[derive(Debug, serde::Deserialize)]
 pub struct SpeechToTextArgs {
     pub audio: String,
+    pub mime_type: Option<String>,
     pub language: Option<String>,
 }
 
 /// Document processing tool
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct DocumentProcessorTool {
     multimodal_service: MultimodalService,
 }
 
 #[async_trait]
-impl Tool for DocumentProcessorTool {
-    const NAME: &'static str = "document_process";
-    type Error = Box<dyn std::error::Error + Send + Sync>;
-    type Args = DocumentProcessorArgs;
-    type Output = String;
-
-    async fn definition(&self, _prompt: String) -> Result<ToolDefinition, Self::Error> {
-        Ok(ToolDefinition {
-            name: "document_process".to_string(),
-            description: "Extract text and analyze documents".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "document": {
-                        "type": "string",
-                        "description": "Base64 encoded document data or file path"
-                    },
-                    "mime_type": {
-                        "type": "string",
-                        "description": "MIME type of the document"
-                    },
-                    "extract_images": {
-                        "type": "boolean",
-                        "description": "Extract images from documents",
-                        "default": false
-                    }
-                },
-                "required": ["document"]
-            }),
-        })
+impl CustomTool for DocumentProcessorTool {
+    fn name(&self) -> &'static str {
+        "document_process"
     }
 
-    async fn call(&self, args: serde_json::Value) -> Result<Self::Output, Self::Error> {
-        let args: DocumentProcessorArgs = serde_json::from_value(args)
-            .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
+    fn description(&self) -> &'static str {
+        "Extract text and analyze documents"
+    }
 
-        let document_data = if args.document.starts_with('/') {
-            fs::read(&args.document).await?
-        } else {
-            use base64::{Engine as _, engine::general_purpose};
-            general_purpose::STANDARD.decode(&args.document)?
-        };
+    async fn call(&self, args: serde_json::Value) -> Result<String> {
+        let args: DocumentProcessorArgs = serde_json::from_value(args)?;
+        let document_data = read_media_argument(&args.document).await?;
 
         let mime_type = args.mime_type.as_deref().unwrap_or("text/plain");
-        let text = self.multimodal_service.extract_text(&document_data, mime_type).await?;
+        let text = self
+            .multimodal_service
+            .extract_text(&document_data, mime_type)
+            .await?;
 
         if args.extract_images.unwrap_or(false) {
             Ok(format!("Extracted text: {}\n\nImage extraction would be implemented with document processing libraries.", text))
@@ -443,56 +467,100 @@ impl Default for MultimodalConfig {
     }
 }
 
-/// Convert MultimodalMessage to ChatMessage for compatibility
+/// Sends multimodal requests to a vision-capable model
+#[async_trait]
+pub trait VisionProvider: std::fmt::Debug + Send + Sync {
+    /// Complete `request` on `model`, the provider's API id
+    async fn complete(&self, request: &MultimodalChatRequest, model: &str) -> Result<ChatResponse>;
+}
+
+impl MultimodalChatRequest {
+    /// The request for a model without vision: media is reduced to its description
+    pub fn to_text_request(&self) -> crate::ChatRequest {
+        crate::ChatRequest {
+            messages: self
+                .messages
+                .iter()
+                .cloned()
+                .map(ChatMessage::from)
+                .collect(),
+            model: self.model.clone(),
+            system_prompt: self.system_prompt.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            top_p: self.top_p,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            stream: self.stream,
+            agent_config: self.agent_config.clone(),
+            tools: self.tools.clone(),
+        }
+    }
+}
+
+/// URL a provider can fetch the media from: the URL itself, or a base64 data URL. `None` for
+/// file paths, which `MultimodalService::inline_media` turns into base64 first.
+pub fn media_url(media: &MediaContent) -> Option<String> {
+    let mime_type = media
+        .metadata
+        .mime_type
+        .as_deref()
+        .unwrap_or("application/octet-stream");
+    match &media.content {
+        MediaData::Url(url) => Some(url.clone()),
+        MediaData::Base64(data) => Some(format!("data:{};base64,{}", mime_type, data)),
+        MediaData::Raw(raw) => Some(format!(
+            "data:{};base64,{}",
+            mime_type,
+            general_purpose::STANDARD.encode(raw)
+        )),
+        MediaData::FilePath(_) => None,
+    }
+}
+
+/// What a text-only model is told about a piece of media in place of the media itself
+fn media_description(media: &MediaContent) -> String {
+    let kind = match media.media_type {
+        MediaType::Image => "Image",
+        MediaType::Audio => "Audio",
+        MediaType::Video => "Video",
+        MediaType::Document => "Document",
+    };
+    match media.metadata.filename.as_deref() {
+        Some(name) => format!("[{}: {}]", kind, name),
+        None => format!("[{}]", kind),
+    }
+}
+
+/// Convert MultimodalMessage to ChatMessage for compatibility. Media becomes a short
+/// description rather than its data, which would only cost tokens on a text-only model.
 impl From<MultimodalMessage> for ChatMessage {
     fn from(msg: MultimodalMessage) -> Self {
-        let content: Vec<MessageContent> = msg.content.into_iter().map(|c| match c {
-            MultimodalContent::Text(text) => MessageContent::Text { text },
-            MultimodalContent::Media(media) => {
-                MessageContent::Image {
-                    url: match media.content {
-                        MediaData::Url(url) => url,
-                        MediaData::Base64(data) => format!("data:{};base64,{}",
-                            media.metadata.mime_type.as_deref().unwrap_or("application/octet-stream"), data),
-                        MediaData::FilePath(path) => format!("file://{}", path.display()),
-                        MediaData::Raw(_) => "data:application/octet-stream;base64,".to_string(),
-                    },
-                    description: media.metadata.filename,
-                }
-            },
-            MultimodalContent::ToolCall(tc) => MessageContent::ToolRequest {
-                id: tc.id.clone(),
-                name: tc.name.clone(),
-                arguments: tc.arguments.clone(),
-            },
-            MultimodalContent::ToolResult(tr) => MessageContent::ToolResponse {
-                id: tr.tool_call_id.clone(),
-                name: "tool_result".to_string(),
-                result: tr.result.clone(),
-            },
-        }).collect();
-
-        let combined_content = content.into_iter().fold(String::new(), |mut acc, c| {
-            match c {
-                MessageContent::Text { text } => acc.push_str(&text),
-                MessageContent::Image { url, .. } => acc.push_str(&format!("[Image: {}]", url)),
-                MessageContent::ToolRequest { name, arguments, .. } => {
-                    acc.push_str(&format!("[Tool Call: {} with args: {}]", name, arguments));
+        let mut tool_calls = Vec::new();
+        let mut tool_results = Vec::new();
+        let text: Vec<String> = msg
+            .content
+            .into_iter()
+            .filter_map(|part| match part {
+                MultimodalContent::Text(text) => Some(text),
+                MultimodalContent::Media(media) => Some(media_description(&media)),
+                MultimodalContent::ToolCall(call) => {
+                    tool_calls.push(call);
+                    None
                 }
-                MessageContent::ToolResponse { result, .. } => {
-                    acc.push_str(&format!("[Tool Result: {}]", result));
+                MultimodalContent::ToolResult(result) => {
+                    tool_results.push(result);
+                    None
                 }
-                _ => {}
-            }
-            acc
-        });
+            })
+            .collect();
 
         ChatMessage {
             role: msg.role,
-            content: combined_content,
+            content: text.join("\n"),
             timestamp: msg.timestamp,
-            tool_calls: None,
-            tool_results: None,
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            tool_results: (!tool_results.is_empty()).then_some(tool_results),
         }
     }
 }
@@ -513,10 +581,10 @@ impl MultimodalRigAgentService {
 
     /// Process multimodal chat request
     pub async fn process_multimodal_message(&self, request: MultimodalChatRequest) -> Result<crate::ChatResponse> {
-        let multimodal_config = request.multimodal_config.unwrap_or_default();
+        let multimodal_config = request.multimodal_config.clone().unwrap_or_default();
 
         // Add multimodal tools if enabled
-        let mut tools = request.tools.unwrap_or_default();
+        let mut tools = request.tools.clone().unwrap_or_default();
 
         if multimodal_config.enable_vision && !tools.iter().any(|t| t.name == "vision_analyze") {
             tools.push(ApiTool {
@@ -531,6 +599,7 @@ impl MultimodalRigAgentService {
                     "required": ["image", "prompt"]
                 }),
                 is_mcp: false,
+                category: ToolCategory::ReadOnly,
             });
         }
 
@@ -547,6 +616,7 @@ impl MultimodalRigAgentService {
                     "required": ["audio"]
                 }),
                 is_mcp: false,
+                category: ToolCategory::ReadOnly,
             });
         }
 
@@ -563,27 +633,14 @@ impl MultimodalRigAgentService {
                     "required": ["document"]
                 }),
                 is_mcp: false,
+                category: ToolCategory::ReadOnly,
             });
         }
 
         // Convert multimodal messages to standard format
-        let chat_messages: Vec<ChatMessage> = request.messages.into_iter()
-            .map(|msg| msg.into())
-            .collect();
-
-        // Create standard chat request
         let chat_request = crate::ChatRequest {
-            messages: chat_messages,
-            model: request.model,
-            system_prompt: request.system_prompt,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stream: request.stream,
-            agent_config: request.agent_config,
             tools: Some(tools),
+            ..request.to_text_request()
         };
 
         self.base_service.send_message(chat_request).await
@@ -618,4 +675,4 @@ impl std::ops::Deref for MultimodalRigAgentService {
     fn deref(&self) -> &Self::Target {
         &self.base_service
     }
-}
\ No newline at end of file
+}
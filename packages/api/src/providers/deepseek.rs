@@ -0,0 +1,10 @@
+// DeepSeek, served through its OpenAI-compatible chat-completions API
+use super::HostedProvider;
+use crate::chat_service_simple::ProviderError;
+
+pub const BASE_URL: &str = "https://api.deepseek.com/v1";
+pub const DEFAULT_MODEL: &str = "deepseek-chat";
+
+pub fn provider(api_key: &str) -> Result<HostedProvider, ProviderError> {
+    HostedProvider::new("deepseek", BASE_URL, api_key, DEFAULT_MODEL)
+}
@@ -0,0 +1,420 @@
+// In-process inference with llama.cpp, for GGUF models on the user's machine
+//
+// A local model is registered like any other model, with `provider` set to "local" and `model`
+// set to the path of its GGUF file; `LocalProvider::model_config` builds such a config. Models
+// are loaded on first use and stay in memory until the provider is dropped. Only compiled with
+// the `local-inference` feature, since it builds llama.cpp from source.
+use async_trait::async_trait;
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use super::CompletionProvider;
+use crate::chat_service_simple::{
+    ChatMessage, ChatRequest, ChatResponse, ModelConfig, ProviderError, ProviderErrorKind, Role,
+    StreamChunk, TokenUsage,
+};
+use crate::ChatChunkStream;
+
+/// Provider id local models are registered under
+pub const LOCAL_PROVIDER: &str = "local";
+/// Tokens generated when neither the request nor the model config sets `max_tokens`
+pub const DEFAULT_MAX_TOKENS: usize = 512;
+/// Temperature used when neither the request nor the model config sets one
+const DEFAULT_TEMPERATURE: f32 = 0.8;
+
+/// llama.cpp may only be initialised once per process
+static BACKEND: OnceCell<LlamaBackend> = OnceCell::new();
+
+fn backend() -> Result<&'static LlamaBackend, ProviderError> {
+    BACKEND
+        .get_or_try_init(LlamaBackend::init)
+        .map_err(inference_error)
+}
+
+fn inference_error(e: impl std::fmt::Display) -> ProviderError {
+    ProviderError::new(
+        ProviderErrorKind::Other,
+        format!("Local inference failed: {}", e),
+    )
+}
+
+/// Runs GGUF models with llama.cpp. The `model` passed to it is the path of the GGUF file.
+#[derive(Clone, Default)]
+pub struct LocalProvider {
+    /// Loaded models by file path
+    models: Arc<Mutex<HashMap<String, Arc<LlamaModel>>>>,
+}
+
+impl std::fmt::Debug for LocalProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("LocalProvider")
+            .field("loaded", &models.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl LocalProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Config for the GGUF model at `path`, registered under `id`
+    pub fn model_config(id: &str, path: impl AsRef<Path>) -> ModelConfig {
+        ModelConfig {
+            id: id.to_string(),
+            model: path.as_ref().to_string_lossy().into_owned(),
+            name: id.to_string(),
+            provider: LOCAL_PROVIDER.to_string(),
+            description: Some("Runs on this machine".to_string()),
+            context_limit: None,
+            supports_tools: false,
+            supports_streaming: true,
+            supports_vision: false,
+            supports_function_calling: false,
+            pricing: None,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+
+    /// The model at `path`, loading it the first time it's asked for. Blocks while loading.
+    fn load(&self, path: &str) -> Result<Arc<LlamaModel>, ProviderError> {
+        let mut models = self.models.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(model) = models.get(path) {
+            return Ok(model.clone());
+        }
+        if !Path::new(path).is_file() {
+            return Err(ProviderError::new(
+                ProviderErrorKind::InvalidRequest,
+                format!(
+                    "Local model file {} not found; set the model's `model` to the path of a GGUF file",
+                    path
+                ),
+            ));
+        }
+
+        tracing::info!("Loading local model {}", path);
+        let model = LlamaModel::load_from_file(backend()?, path, &LlamaModelParams::default())
+            .map_err(inference_error)?;
+        let model = Arc::new(model);
+        models.insert(path.to_string(), model.clone());
+        Ok(model)
+    }
+
+    /// Load the model and generate on a blocking thread, handing each piece of text to `on_text`
+    async fn generate(
+        &self,
+        request: &ChatRequest,
+        path: &str,
+        on_text: impl FnMut(&str) -> bool + Send + 'static,
+    ) -> Result<Generation, ProviderError> {
+        let provider = self.clone();
+        let path = path.to_string();
+        let messages = request.messages.clone();
+        let sampling = Sampling {
+            temperature: request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            max_tokens: request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        };
+        tokio::task::spawn_blocking(move || {
+            let model = provider.load(&path)?;
+            let prompt = chat_prompt(&model, &messages)?;
+            run_model(&model, &prompt, sampling, on_text)
+        })
+        .await
+        .map_err(inference_error)?
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sampling {
+    temperature: f32,
+    max_tokens: usize,
+}
+
+#[derive(Debug)]
+struct Generation {
+    text: String,
+    usage: TokenUsage,
+    /// "stop" at an end-of-generation token, "length" when `max_tokens` ran out
+    finish_reason: &'static str,
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+        Role::Tool => "tool",
+    }
+}
+
+/// The conversation formatted with the model's own chat template, or as a plain transcript for
+/// models that don't ship one
+fn chat_prompt(model: &LlamaModel, messages: &[ChatMessage]) -> Result<String, ProviderError> {
+    let Ok(template) = model.chat_template(None) else {
+        return Ok(plain_prompt(messages));
+    };
+    let chat = messages
+        .iter()
+        .map(|message| {
+            LlamaChatMessage::new(
+                role_name(&message.role).to_string(),
+                message.content.clone(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(inference_error)?;
+    model
+        .apply_chat_template(&template, &chat, true)
+        .map_err(inference_error)
+}
+
+fn plain_prompt(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let role = role_name(&message.role);
+        let mut speaker = role.chars();
+        if let Some(first) = speaker.next() {
+            prompt.push(first.to_ascii_uppercase());
+            prompt.push_str(speaker.as_str());
+        }
+        prompt.push_str(": ");
+        prompt.push_str(&message.content);
+        prompt.push('\n');
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
+/// Decoded text from the front of `pending`, leaving any incomplete UTF-8 sequence for the next
+/// token to finish. Tokens often split multi-byte characters.
+fn take_text(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => {
+            let text = String::from_utf8_lossy(pending).into_owned();
+            pending.clear();
+            return text;
+        }
+    };
+    let text = String::from_utf8_lossy(&pending[..valid]).into_owned();
+    pending.drain(..valid);
+    text
+}
+
+/// Run the prompt through the model. Stops early when `on_text` returns false.
+fn run_model(
+    model: &LlamaModel,
+    prompt: &str,
+    sampling: Sampling,
+    mut on_text: impl FnMut(&str) -> bool,
+) -> Result<Generation, ProviderError> {
+    let tokens = model
+        .str_to_token(prompt, AddBos::Always)
+        .map_err(inference_error)?;
+    let context_tokens = (tokens.len() + sampling.max_tokens).min(model.n_ctx_train() as usize);
+    if tokens.len() >= context_tokens {
+        return Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            format!(
+                "Prompt of {} tokens doesn't fit the model's {} token context",
+                tokens.len(),
+                context_tokens
+            ),
+        ));
+    }
+
+    let context_params =
+        LlamaContextParams::default().with_n_ctx(NonZeroU32::new(context_tokens as u32));
+    let mut context = model
+        .new_context(backend()?, context_params)
+        .map_err(inference_error)?;
+
+    let mut batch = LlamaBatch::new(tokens.len(), 1);
+    let last = tokens.len() as i32 - 1;
+    for (position, token) in (0_i32..).zip(tokens.iter().copied()) {
+        batch
+            .add(token, position, &[0], position == last)
+            .map_err(inference_error)?;
+    }
+    context.decode(&mut batch).map_err(inference_error)?;
+
+    let mut sampler = if sampling.temperature <= 0.0 {
+        LlamaSampler::greedy()
+    } else {
+        LlamaSampler::chain_simple([
+            LlamaSampler::temp(sampling.temperature),
+            LlamaSampler::dist(rand_seed()),
+        ])
+    };
+
+    let mut text = String::new();
+    let mut pending = Vec::new();
+    let mut position = batch.n_tokens();
+    let mut completion_tokens = 0;
+    let mut finish_reason = "length";
+    while completion_tokens < sampling.max_tokens {
+        let token = sampler.sample(&context, batch.n_tokens() - 1);
+        sampler.accept(token);
+        if model.is_eog_token(token) {
+            finish_reason = "stop";
+            break;
+        }
+        completion_tokens += 1;
+
+        pending.extend(
+            model
+                .token_to_bytes(token, Special::Tokenize)
+                .map_err(inference_error)?,
+        );
+        let piece = take_text(&mut pending);
+        if !piece.is_empty() {
+            text.push_str(&piece);
+            if !on_text(&piece) {
+                // Nobody is listening any more
+                finish_reason = "stop";
+                break;
+            }
+        }
+
+        batch.clear();
+        batch
+            .add(token, position, &[0], true)
+            .map_err(inference_error)?;
+        position += 1;
+        context.decode(&mut batch).map_err(inference_error)?;
+    }
+
+    let prompt_tokens = tokens.len() as u32;
+    let completion_tokens = completion_tokens as u32;
+    Ok(Generation {
+        text,
+        usage: TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        },
+        finish_reason,
+    })
+}
+
+/// Doesn't need to be a good random number, only a different one per request
+fn rand_seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos()
+}
+
+#[async_trait]
+impl CompletionProvider for LocalProvider {
+    async fn complete(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError> {
+        let generation = self.generate(request, model, |_: &str| true).await?;
+        Ok(ChatResponse {
+            message: Some(ChatMessage {
+                role: Role::Assistant,
+                content: generation.text,
+                timestamp: Some(chrono::Utc::now()),
+                tool_calls: None,
+                tool_results: None,
+            }),
+            tool_calls: None,
+            token_usage: Some(generation.usage),
+            model: model.to_string(),
+            finish_reason: Some(generation.finish_reason.to_string()),
+            is_streaming: false,
+            reasoning_content: None,
+            thinking_content: None,
+            notification: None,
+        })
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatChunkStream, ProviderError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let provider = self.clone();
+        let request = request.clone();
+        let model = model.to_string();
+        tokio::spawn(async move {
+            let text_tx = tx.clone();
+            let text_model = model.clone();
+            let on_text = move |piece: &str| {
+                text_tx
+                    .send(Ok(StreamChunk {
+                        content: Some(piece.to_string()),
+                        delta: Some(piece.to_string()),
+                        token_usage: None,
+                        model: text_model.clone(),
+                        finish_reason: None,
+                        is_complete: false,
+                        thinking: None,
+                        tool_calls: None,
+                    }))
+                    .is_ok()
+            };
+            let last = match provider.generate(&request, &model, on_text).await {
+                Ok(generation) => Ok(StreamChunk {
+                    content: None,
+                    delta: None,
+                    token_usage: Some(generation.usage),
+                    model,
+                    finish_reason: Some(generation.finish_reason.to_string()),
+                    is_complete: true,
+                    thinking: None,
+                    tool_calls: None,
+                }),
+                Err(e) => Err(e),
+            };
+            let _ = tx.send(last);
+        });
+        Ok(Box::pin(UnboundedReceiverStream::new(rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        }
+    }
+
+    #[test]
+    fn test_prompt_without_template_and_split_characters() {
+        let prompt = plain_prompt(&[message(Role::System, "Be brief"), message(Role::User, "Hi")]);
+        assert_eq!(prompt, "System: Be brief\nUser: Hi\nAssistant:");
+
+        // "é" arrives split across two tokens
+        let mut pending = vec![b'a', 0xC3];
+        assert_eq!(take_text(&mut pending), "a");
+        pending.push(0xA9);
+        assert_eq!(take_text(&mut pending), "é");
+        assert!(pending.is_empty());
+    }
+}
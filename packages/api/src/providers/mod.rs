@@ -0,0 +1,395 @@
+// HTTP provider clients and the network settings they share
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::chat_service_simple::{
+    ChatRequest, ChatResponse, ModelConfig, ProviderError, ProviderErrorKind, TIMED_OUT,
+};
+use crate::tokenizer::estimate_token_usage;
+use crate::ChatChunkStream;
+
+pub mod deepseek;
+#[cfg(feature = "local-inference")]
+pub mod local;
+pub mod ollama;
+pub mod openai;
+pub mod openai_compatible;
+pub mod openrouter;
+pub mod rate_limit;
+pub mod retry;
+
+#[cfg(feature = "local-inference")]
+pub use local::LocalProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+pub use rate_limit::{RateLimit, RateLimiter};
+pub use retry::{with_retry, RetryConfig};
+
+/// A model backend `ChatService` can send requests to instead of its built-in mock models
+#[async_trait]
+pub trait CompletionProvider: std::fmt::Debug + Send + Sync {
+    async fn complete(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError>;
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatChunkStream, ProviderError>;
+
+    /// Make a minimal authenticated request to check the provider's key and endpoint.
+    /// Providers running in-process have nothing to check.
+    async fn health_check(&self) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Per-minute limits configured for this provider's account, which `ChatService` keeps to
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+}
+
+/// A hosted OpenAI-compatible service with a default model, such as DeepSeek or OpenRouter
+pub struct HostedProvider {
+    /// Provider id, as used in `ModelConfig::provider`
+    name: String,
+    client: OpenAiProvider,
+    model: String,
+    limiter: Option<RateLimiter>,
+}
+
+impl HostedProvider {
+    pub fn new(
+        name: impl Into<String>,
+        base_url: &str,
+        api_key: &str,
+        model: &str,
+    ) -> Result<Self, ProviderError> {
+        Ok(Self {
+            name: name.into(),
+            client: OpenAiProvider::new(base_url, api_key)?,
+            model: model.to_string(),
+            limiter: None,
+        })
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Send `headers` with every request, e.g. for a gateway in front of the provider
+    pub fn with_custom_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        self.client = self.client.with_custom_headers(headers);
+        self
+    }
+
+    /// Hold requests back to stay under the account's per-minute limits
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.client = self.client.with_rate_limit(limit);
+        self.limiter = Some(RateLimiter::new(limit));
+        self
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.client.rate_limit
+    }
+
+    pub async fn health_check(&self) -> Result<(), ProviderError> {
+        self.client.health_check().await
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.client.base_url()
+    }
+
+    pub fn model_config(&self) -> ModelConfig {
+        ModelConfig {
+            id: self.model.clone(),
+            model: self.model.clone(),
+            name: self.model.clone(),
+            provider: self.name.clone(),
+            description: None,
+            context_limit: None,
+            supports_tools: false,
+            supports_streaming: true,
+            supports_vision: false,
+            supports_function_calling: false,
+            pricing: None,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+
+    /// Complete with the request's model, or this provider's model when the request names none
+    pub async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse, ProviderError> {
+        let model = if request.model.is_empty() {
+            self.model.as_str()
+        } else {
+            request.model.as_str()
+        };
+        if let Some(limiter) = &self.limiter {
+            limiter
+                .acquire(estimate_token_usage(&request.messages, None))
+                .await;
+        }
+        self.client.complete(request, model).await
+    }
+}
+
+/// Check the provider listed as `id` by `get_providers`, using its key from the environment
+pub async fn check_provider(id: &str) -> Result<(), ProviderError> {
+    let api_key = |var: &str| {
+        std::env::var(var)
+            .ok()
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| {
+                ProviderError::new(
+                    ProviderErrorKind::Authentication,
+                    format!("{} is not set", var),
+                )
+            })
+    };
+    match id {
+        "local" => Ok(()),
+        "ollama" => OllamaProvider::from_env().health_check().await,
+        "openai" => OpenAiProvider::from_env()?.health_check().await,
+        "deepseek" => {
+            deepseek::provider(api_key("DEEPSEEK_API_KEY")?.trim())?
+                .health_check()
+                .await
+        }
+        "openrouter" => {
+            openrouter::provider(api_key("OPENROUTER_API_KEY")?.trim())?
+                .health_check()
+                .await
+        }
+        other => Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            format!("Checking provider {:?} isn't supported", other),
+        )),
+    }
+}
+
+/// Environment variable holding the API key for the provider `id`, if it needs one
+pub fn api_key_env(id: &str) -> Option<&'static str> {
+    match id {
+        "openai" => Some("OPENAI_API_KEY"),
+        "deepseek" => Some("DEEPSEEK_API_KEY"),
+        "openrouter" => Some("OPENROUTER_API_KEY"),
+        _ => None,
+    }
+}
+
+/// A client for every provider usable with the current environment, by the id models name
+/// in `ModelConfig::provider`: the hosted ones whose API key is set, and Ollama, which
+/// needs none. Rate limits are left to the caller.
+pub fn configured_providers() -> Vec<(String, Arc<dyn CompletionProvider>)> {
+    let mut providers: Vec<(String, Arc<dyn CompletionProvider>)> = Vec::new();
+    let hosted = [
+        ("openai", OpenAiProvider::base_url_from_env()),
+        ("deepseek", deepseek::BASE_URL.to_string()),
+        ("openrouter", openrouter::BASE_URL.to_string()),
+    ];
+    for (id, base_url) in hosted {
+        let Some(api_key) = api_key_env(id)
+            .and_then(|var| std::env::var(var).ok())
+            .filter(|key| !key.trim().is_empty())
+        else {
+            continue;
+        };
+        match OpenAiProvider::new(base_url, api_key.trim()) {
+            Ok(client) => providers.push((id.to_string(), Arc::new(client))),
+            Err(e) => tracing::warn!("Not using {}: {}", id, e),
+        }
+    }
+
+    // Ollama serves the OpenAI protocol under /v1
+    let ollama = format!("{}/v1", OllamaProvider::from_env().base_url());
+    match OpenAiProvider::new(ollama, "") {
+        Ok(client) => providers.push(("ollama".to_string(), Arc::new(client))),
+        Err(e) => tracing::warn!("Not using ollama: {}", e),
+    }
+    providers
+}
+
+/// Network timeouts applied to every provider HTTP client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderTimeouts {
+    /// Time allowed to establish the connection
+    pub connect: Duration,
+    /// Overall limit for a non-streaming request, or for a stream's response headers
+    pub request: Duration,
+    /// Abort a stream when no bytes arrive for this long
+    pub stream_idle: Duration,
+}
+
+impl ProviderTimeouts {
+    /// Derive timeouts from `PerformanceSettings.network_timeout_seconds`
+    pub fn from_network_timeout(seconds: u64) -> Self {
+        let overall = Duration::from_secs(seconds.max(1));
+        Self {
+            connect: overall.min(Duration::from_secs(10)),
+            request: overall,
+            stream_idle: overall,
+        }
+    }
+}
+
+impl Default for ProviderTimeouts {
+    fn default() -> Self {
+        Self::from_network_timeout(30)
+    }
+}
+
+static PROVIDER_TIMEOUTS: Lazy<RwLock<ProviderTimeouts>> =
+    Lazy::new(|| RwLock::new(ProviderTimeouts::default()));
+
+/// Timeouts currently used when building provider clients
+pub fn provider_timeouts() -> ProviderTimeouts {
+    *PROVIDER_TIMEOUTS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Change the provider timeouts at runtime. Clients pick up the new values on their next request.
+pub fn set_provider_timeouts(timeouts: ProviderTimeouts) {
+    *PROVIDER_TIMEOUTS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = timeouts;
+}
+
+pub(crate) fn build_http_client(
+    timeouts: &ProviderTimeouts,
+) -> Result<reqwest::Client, ProviderError> {
+    reqwest::Client::builder()
+        .connect_timeout(timeouts.connect)
+        .build()
+        .map_err(|e| ProviderError::new(ProviderErrorKind::Other, e.to_string()))
+}
+
+/// Headers from a provider's `custom_headers`. Names and values that can't be sent, such as
+/// values with non-ASCII characters, are left out with a warning rather than failing requests.
+pub(crate) fn custom_header_map(headers: &HashMap<String, String>) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        match (
+            HeaderName::from_bytes(name.trim().as_bytes()),
+            HeaderValue::from_str(value.trim()),
+        ) {
+            // `http` accepts non-ASCII bytes, but servers decode them inconsistently
+            (Ok(name), Ok(value)) if value.to_str().is_ok() => {
+                map.insert(name, value);
+            }
+            _ => tracing::warn!("Skipping custom header {:?}: not a valid HTTP header", name),
+        }
+    }
+    map
+}
+
+pub(crate) fn classify_status(status: reqwest::StatusCode) -> ProviderErrorKind {
+    match status.as_u16() {
+        401 | 403 => ProviderErrorKind::Authentication,
+        408 | 504 => ProviderErrorKind::Timeout,
+        429 => ProviderErrorKind::RateLimited,
+        400..=499 => ProviderErrorKind::InvalidRequest,
+        500..=599 => ProviderErrorKind::Server,
+        _ => ProviderErrorKind::Other,
+    }
+}
+
+pub(crate) fn classify_reqwest_error(err: reqwest::Error) -> ProviderError {
+    // Connect timeouts are also connect errors, so check for timeouts first
+    let kind = if err.is_timeout() {
+        ProviderErrorKind::Timeout
+    } else if err.is_connect() || err.is_request() {
+        ProviderErrorKind::Network
+    } else if let Some(status) = err.status() {
+        classify_status(status)
+    } else {
+        ProviderErrorKind::Other
+    };
+    ProviderError::new(kind, err.to_string())
+}
+
+pub(crate) fn timeout_error(what: &str, limit: Duration) -> ProviderError {
+    ProviderError {
+        code: Some(TIMED_OUT.to_string()),
+        ..ProviderError::new(
+            ProviderErrorKind::Timeout,
+            format!("{} timed out after {:.1}s", what, limit.as_secs_f32()),
+        )
+    }
+}
+
+/// `provider.complete`, given up on once it runs past the request timeout. Providers that
+/// don't enforce timeouts themselves can't hang the caller; dropping the request aborts it.
+pub async fn complete_in_time(
+    provider: &dyn CompletionProvider,
+    request: &ChatRequest,
+    model: &str,
+    timeouts: ProviderTimeouts,
+) -> Result<ChatResponse, ProviderError> {
+    tokio::time::timeout(timeouts.request, provider.complete(request, model))
+        .await
+        .map_err(|_| timeout_error("Provider request", timeouts.request))?
+}
+
+/// `provider.stream`, which must open within the request timeout and then produce a chunk
+/// at least every `stream_idle`. Long replies are fine as long as they keep coming.
+pub async fn stream_in_time(
+    provider: &dyn CompletionProvider,
+    request: &ChatRequest,
+    model: &str,
+    timeouts: ProviderTimeouts,
+) -> Result<ChatChunkStream, ProviderError> {
+    let chunks = tokio::time::timeout(timeouts.request, provider.stream(request, model))
+        .await
+        .map_err(|_| timeout_error("Provider request", timeouts.request))??;
+    Ok(Box::pin(with_idle_timeout(chunks, timeouts.stream_idle)))
+}
+
+/// Wrap a stream so it fails with a timeout once nothing arrives for `idle`, and ends after the
+/// first error. This is separate from the overall request timeout, which would cut off long but
+/// healthy streams.
+pub(crate) fn with_idle_timeout<S, T, E>(
+    stream: S,
+    idle: Duration,
+) -> impl Stream<Item = Result<T, ProviderError>> + Send
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Send,
+    E: Into<ProviderError>,
+{
+    futures::stream::unfold(Some(Box::pin(stream)), move |state| async move {
+        let mut stream = state?;
+        match tokio::time::timeout(idle, stream.next()).await {
+            Ok(Some(Ok(item))) => Some((Ok(item), Some(stream))),
+            Ok(Some(Err(e))) => Some((Err(e.into()), None)),
+            Ok(None) => None,
+            Err(_) => Some((Err(timeout_error("Provider stream", idle)), None)),
+        }
+    })
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(err: reqwest::Error) -> Self {
+        classify_reqwest_error(err)
+    }
+}
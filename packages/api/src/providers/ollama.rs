@@ -0,0 +1,167 @@
+// Ollama, a local model server; used to discover the models the user has pulled
+use serde::Deserialize;
+
+use super::{
+    build_http_client, classify_reqwest_error, classify_status, provider_timeouts, ProviderTimeouts,
+};
+use crate::chat_service_simple::{ModelConfig, ProviderError};
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    base_url: String,
+    /// Fixed timeouts; when unset the process-wide provider timeouts are used
+    timeouts: Option<ProviderTimeouts>,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            timeouts: None,
+        }
+    }
+
+    /// The server at `OLLAMA_HOST`, or Ollama's default address when it isn't set
+    pub fn from_env() -> Self {
+        match std::env::var("OLLAMA_HOST") {
+            Ok(host) if host.starts_with("http") => Self::new(host),
+            Ok(host) if !host.trim().is_empty() => Self::new(format!("http://{}", host.trim())),
+            _ => Self::new(DEFAULT_BASE_URL),
+        }
+    }
+
+    /// Pin this provider to specific timeouts instead of following the global settings
+    pub fn with_timeouts(mut self, timeouts: ProviderTimeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Models installed on the server, from `/api/tags`. Empty when the server can't be
+    /// reached, so a stopped Ollama doesn't break listing the other models.
+    pub async fn list_models(&self) -> Vec<ModelConfig> {
+        match self.tags().await {
+            Ok(models) => models,
+            Err(e) => {
+                tracing::debug!("No models from Ollama at {}: {}", self.base_url, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Check that the server answers
+    pub async fn health_check(&self) -> Result<(), ProviderError> {
+        self.tags().await.map(|_| ())
+    }
+
+    async fn tags(&self) -> Result<Vec<ModelConfig>, ProviderError> {
+        let timeouts = self.timeouts.unwrap_or_else(provider_timeouts);
+        let response = build_http_client(&timeouts)?
+            .get(format!("{}/api/tags", self.base_url))
+            .timeout(timeouts.request)
+            .send()
+            .await
+            .map_err(classify_reqwest_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProviderError::new(
+                classify_status(status),
+                format!("Ollama returned {}", status),
+            ));
+        }
+        let tags: TagsResponse = response.json().await.map_err(classify_reqwest_error)?;
+        Ok(tags.models.into_iter().map(model_config).collect())
+    }
+}
+
+fn model_config(model: TagsModel) -> ModelConfig {
+    let details = [
+        model.details.parameter_size,
+        model.details.quantization_level,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    ModelConfig {
+        id: model.name.clone(),
+        model: model.name.clone(),
+        name: model.name,
+        provider: "ollama".to_string(),
+        description: (!details.is_empty()).then(|| details.join(" ")),
+        context_limit: None,
+        supports_tools: false,
+        supports_streaming: true,
+        supports_vision: false,
+        supports_function_calling: false,
+        pricing: None,
+        temperature: None,
+        max_tokens: None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<TagsModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsModel {
+    name: String,
+    #[serde(default)]
+    details: TagsDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagsDetails {
+    parameter_size: Option<String>,
+    quantization_level: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_installed_models_are_listed_and_a_stopped_server_lists_none() {
+        let body = r#"{"models":[{"name":"llama3:latest","details":{"parameter_size":"8.0B","quantization_level":"Q4_0"}},{"name":"qwen2.5-coder:7b"}]}"#;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let _ = socket.read(&mut buf).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+
+        let models = OllamaProvider::new(format!("http://{}", addr))
+            .list_models()
+            .await;
+        let names: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(names, vec!["llama3:latest", "qwen2.5-coder:7b"]);
+        assert_eq!(models[0].provider, "ollama");
+        assert_eq!(models[0].description.as_deref(), Some("8.0B Q4_0"));
+        assert_eq!(models[1].description, None);
+
+        // Nothing listens on the port once the listener is gone
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+        assert!(OllamaProvider::new(format!("http://{}", closed_addr))
+            .list_models()
+            .await
+            .is_empty());
+    }
+}
@@ -0,0 +1,905 @@
+// OpenAI chat-completions client
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use super::CompletionProvider;
+use super::{
+    build_http_client, classify_reqwest_error, classify_status, custom_header_map,
+    provider_timeouts, timeout_error, with_idle_timeout, ProviderTimeouts, RateLimit,
+};
+use crate::chat_service_simple::{
+    ChatMessage, ChatRequest, ChatResponse, ProviderError, ProviderErrorKind, Role, StreamChunk,
+    TokenUsage, ToolCall,
+};
+use crate::multimodal::{media_url, MultimodalChatRequest, MultimodalContent, VisionProvider};
+use crate::ChatChunkStream;
+
+pub struct OpenAiProvider {
+    base_url: String,
+    api_key: String,
+    /// Fixed timeouts; when unset the process-wide provider timeouts are used
+    timeouts: Option<ProviderTimeouts>,
+    client: RwLock<(ProviderTimeouts, reqwest::Client)>,
+    /// Reported to `ChatService`, which holds requests back to stay under it
+    pub(crate) rate_limit: Option<RateLimit>,
+    /// Sent with every request alongside the API key
+    custom_headers: reqwest::header::HeaderMap,
+}
+
+// Leaves out the API key
+impl std::fmt::Debug for OpenAiProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiProvider")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OpenAiProvider {
+    pub const DEFAULT_BASE_URL: &'static str = "https://api.openai.com/v1";
+
+    pub fn new(
+        base_url: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Result<Self, ProviderError> {
+        let timeouts = provider_timeouts();
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: api_key.into(),
+            timeouts: None,
+            client: RwLock::new((timeouts, build_http_client(&timeouts)?)),
+            rate_limit: None,
+            custom_headers: Default::default(),
+        })
+    }
+
+    pub fn from_env() -> Result<Self, ProviderError> {
+        let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| {
+            ProviderError::new(
+                ProviderErrorKind::Authentication,
+                "OPENAI_API_KEY is not set",
+            )
+        })?;
+        Self::new(Self::base_url_from_env(), api_key)
+    }
+
+    /// `OPENAI_BASE_URL`, for proxies and compatible servers, or the OpenAI API
+    pub fn base_url_from_env() -> String {
+        std::env::var("OPENAI_BASE_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .map(|url| url.trim().to_string())
+            .unwrap_or_else(|| Self::DEFAULT_BASE_URL.to_string())
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Send `headers` with every request; invalid ones are skipped with a warning
+    pub fn with_custom_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        self.custom_headers = custom_header_map(headers);
+        self
+    }
+
+    /// A chat-completions POST carrying the API key and custom headers
+    fn completions_request(&self, client: &reqwest::Client) -> reqwest::RequestBuilder {
+        self.authorized(client.post(format!("{}/chat/completions", self.base_url)))
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        // Local OpenAI-compatible servers are often run without a key
+        let request = if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        };
+        request.headers(self.custom_headers.clone())
+    }
+
+    /// Check the key and endpoint by listing models, which costs no tokens. A rejected key
+    /// fails with `Authentication`, an unreachable server with `Network` or `Timeout`.
+    pub async fn health_check(&self) -> Result<(), ProviderError> {
+        let (timeouts, client) = self.client()?;
+        let response = self
+            .authorized(client.get(format!("{}/models", self.base_url)))
+            .timeout(timeouts.request)
+            .send()
+            .await
+            .map_err(classify_reqwest_error)?;
+        check_status(response).await.map(|_| ())
+    }
+
+    /// The account's per-minute limits, for whoever sends requests through this provider
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Pin this provider to specific timeouts instead of following the global settings
+    pub fn with_timeouts(mut self, timeouts: ProviderTimeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// The HTTP client for the current timeouts, rebuilt if they changed since the last request
+    fn client(&self) -> Result<(ProviderTimeouts, reqwest::Client), ProviderError> {
+        let timeouts = self.timeouts.unwrap_or_else(provider_timeouts);
+        {
+            let cached = self.client.read().unwrap_or_else(|p| p.into_inner());
+            if cached.0 == timeouts {
+                return Ok((timeouts, cached.1.clone()));
+            }
+        }
+
+        let client = build_http_client(&timeouts)?;
+        *self.client.write().unwrap_or_else(|p| p.into_inner()) = (timeouts, client.clone());
+        Ok((timeouts, client))
+    }
+
+    pub async fn complete(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError> {
+        self.send_completion(&request_body(request, model, false), model)
+            .await
+    }
+
+    /// `complete` with images sent as `image_url` content parts, for vision models
+    pub async fn complete_multimodal(
+        &self,
+        request: &MultimodalChatRequest,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError> {
+        let mut body = request_body(&request.to_text_request(), model, false);
+        body["messages"] = json!(multimodal_messages(request));
+        self.send_completion(&body, model).await
+    }
+
+    async fn send_completion(
+        &self,
+        body: &serde_json::Value,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError> {
+        let (timeouts, client) = self.client()?;
+        let response = self
+            .completions_request(&client)
+            .timeout(timeouts.request)
+            .json(body)
+            .send()
+            .await
+            .map_err(classify_reqwest_error)?;
+        let response = check_status(response).await?;
+
+        let body: CompletionResponse = response.json().await.map_err(classify_reqwest_error)?;
+        let choice = body.choices.into_iter().next();
+        let finish_reason = choice.as_ref().and_then(|c| c.finish_reason.clone());
+        let (content, reasoning, tool_calls) = choice
+            .map(|c| {
+                let calls: Vec<ToolCall> = c
+                    .message
+                    .tool_calls
+                    .into_iter()
+                    .flatten()
+                    .map(Into::into)
+                    .collect();
+                (c.message.content, c.message.reasoning_content, calls)
+            })
+            .unwrap_or_default();
+        let content = content.unwrap_or_default();
+        let tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+
+        Ok(ChatResponse {
+            message: Some(ChatMessage {
+                role: Role::Assistant,
+                content,
+                timestamp: Some(chrono::Utc::now()),
+                tool_calls: tool_calls.clone(),
+                tool_results: None,
+            }),
+            tool_calls,
+            token_usage: body.usage,
+            model: body.model.unwrap_or_else(|| model.to_string()),
+            finish_reason,
+            is_streaming: false,
+            reasoning_content: reasoning.clone(),
+            thinking_content: reasoning,
+            notification: None,
+        })
+    }
+
+    /// Stream a completion. The response headers must arrive within the request timeout and the
+    /// stream is aborted if it then goes quiet for longer than the idle timeout.
+    pub async fn stream(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatChunkStream, ProviderError> {
+        let (timeouts, client) = self.client()?;
+        let send = self
+            .completions_request(&client)
+            .json(&request_body(request, model, true))
+            .send();
+        let response = tokio::time::timeout(timeouts.request, send)
+            .await
+            .map_err(|_| timeout_error("Provider request", timeouts.request))?
+            .map_err(classify_reqwest_error)?;
+        let response = check_status(response).await?;
+
+        let mut bytes = Box::pin(with_idle_timeout(
+            response.bytes_stream(),
+            timeouts.stream_idle,
+        ));
+        let model = model.to_string();
+
+        Ok(Box::pin(async_stream::stream! {
+            let mut buffer = String::new();
+            let mut tool_calls = StreamedToolCalls::default();
+            while let Some(next) = bytes.next().await {
+                let data = match next {
+                    Ok(data) => data,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                buffer.push_str(&String::from_utf8_lossy(&data));
+
+                // Server-sent events are newline delimited; keep any partial line for the next read
+                while let Some(newline) = buffer.find('\n') {
+                    let line = buffer[..newline].trim().to_string();
+                    buffer.drain(..=newline);
+
+                    let Some(payload) = line.strip_prefix("data:").map(str::trim) else {
+                        continue;
+                    };
+                    if payload == "[DONE]" {
+                        // Calls the server never closed with a finish reason
+                        if let Some(calls) = tool_calls.take() {
+                            yield Ok(StreamChunk {
+                                content: None,
+                                delta: None,
+                                token_usage: None,
+                                model: model.clone(),
+                                is_complete: true,
+                                finish_reason: Some("tool_calls".to_string()),
+                                thinking: None,
+                                tool_calls: Some(calls),
+                            });
+                        }
+                        return;
+                    }
+                    match serde_json::from_str::<StreamResponse>(payload) {
+                        Ok(event) => yield Ok(stream_chunk(event, &model, &mut tool_calls)),
+                        Err(e) => tracing::warn!("Skipping malformed stream event: {}", e),
+                    }
+                }
+            }
+        }))
+    }
+}
+
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ProviderError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let body = response.text().await.unwrap_or_default();
+
+    Err(ProviderError {
+        kind: classify_status(status),
+        message: format!("Provider returned {}: {}", status, body),
+        code: Some(status.as_u16().to_string()),
+        retry_after,
+    })
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+        Role::Tool => "tool",
+    }
+}
+
+/// Build a chat-completions request body
+pub(crate) fn request_body(request: &ChatRequest, model: &str, stream: bool) -> serde_json::Value {
+    let mut messages = Vec::new();
+    if let Some(ref system_prompt) = request.system_prompt {
+        messages.push(json!({ "role": "system", "content": system_prompt }));
+    }
+    for message in &request.messages {
+        messages.push(message_json(message));
+    }
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": stream,
+    });
+    if let Some(tools) = request.tools.as_ref().filter(|tools| !tools.is_empty()) {
+        let tools: Vec<_> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.input_schema,
+                    },
+                })
+            })
+            .collect();
+        body["tools"] = json!(tools);
+    }
+    let options = [
+        ("temperature", request.temperature.map(|v| json!(v))),
+        ("max_tokens", request.max_tokens.map(|v| json!(v))),
+        ("top_p", request.top_p.map(|v| json!(v))),
+        (
+            "frequency_penalty",
+            request.frequency_penalty.map(|v| json!(v)),
+        ),
+        (
+            "presence_penalty",
+            request.presence_penalty.map(|v| json!(v)),
+        ),
+    ];
+    for (key, value) in options {
+        if let Some(value) = value {
+            body[key] = value;
+        }
+    }
+    body
+}
+
+/// One chat-completions message. Assistant turns carry their tool calls, with `null` content
+/// when there is no text, and tool turns carry the id of the call they answer.
+fn message_json(message: &ChatMessage) -> serde_json::Value {
+    let mut value = json!({ "role": role_name(&message.role), "content": message.content });
+    if let Some(calls) = message
+        .tool_calls
+        .as_ref()
+        .filter(|calls| !calls.is_empty())
+    {
+        let calls: Vec<_> = calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": call.arguments.to_string() },
+                })
+            })
+            .collect();
+        value["tool_calls"] = json!(calls);
+        if message.content.is_empty() {
+            value["content"] = serde_json::Value::Null;
+        }
+    }
+    if message.role == Role::Tool {
+        if let Some(result) = message.tool_results.as_ref().and_then(|r| r.first()) {
+            value["tool_call_id"] = json!(result.tool_call_id);
+        }
+    }
+    value
+}
+
+/// Chat-completions messages with content parts: text as `text` parts and media as
+/// `image_url` parts holding the URL or a base64 data URL
+pub(crate) fn multimodal_messages(request: &MultimodalChatRequest) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+    if let Some(ref system_prompt) = request.system_prompt {
+        messages.push(json!({ "role": "system", "content": system_prompt }));
+    }
+    for message in &request.messages {
+        let parts: Vec<serde_json::Value> = message
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                MultimodalContent::Text(text) => Some(json!({ "type": "text", "text": text })),
+                MultimodalContent::Media(media) => media_url(media)
+                    .map(|url| json!({ "type": "image_url", "image_url": { "url": url } })),
+                MultimodalContent::ToolCall(_) | MultimodalContent::ToolResult(_) => None,
+            })
+            .collect();
+        messages.push(json!({ "role": role_name(&message.role), "content": parts }));
+    }
+    messages
+}
+
+#[async_trait::async_trait]
+impl VisionProvider for OpenAiProvider {
+    async fn complete(
+        &self,
+        request: &MultimodalChatRequest,
+        model: &str,
+    ) -> anyhow::Result<ChatResponse> {
+        Ok(self.complete_multimodal(request, model).await?)
+    }
+}
+
+#[async_trait::async_trait]
+impl CompletionProvider for OpenAiProvider {
+    async fn complete(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatResponse, ProviderError> {
+        OpenAiProvider::complete(self, request, model).await
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        model: &str,
+    ) -> Result<ChatChunkStream, ProviderError> {
+        OpenAiProvider::stream(self, request, model).await
+    }
+
+    async fn health_check(&self) -> Result<(), ProviderError> {
+        OpenAiProvider::health_check(self).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+    }
+}
+
+/// Convert one stream event, collecting tool-call fragments into `tool_calls`. The assembled
+/// calls go out on the chunk that finishes the turn.
+fn stream_chunk(
+    event: StreamResponse,
+    model: &str,
+    tool_calls: &mut StreamedToolCalls,
+) -> StreamChunk {
+    let choice = event.choices.into_iter().next();
+    let finish_reason = choice.as_ref().and_then(|c| c.finish_reason.clone());
+    let (delta, thinking) = match choice {
+        Some(choice) => {
+            tool_calls.extend(choice.delta.tool_calls.unwrap_or_default());
+            (choice.delta.content, choice.delta.reasoning_content)
+        }
+        None => Default::default(),
+    };
+    let calls = finish_reason.as_ref().and_then(|_| tool_calls.take());
+
+    StreamChunk {
+        content: delta.clone(),
+        delta,
+        token_usage: event.usage,
+        model: event.model.unwrap_or_else(|| model.to_string()),
+        is_complete: finish_reason.is_some(),
+        finish_reason,
+        thinking,
+        tool_calls: calls,
+    }
+}
+
+/// Tool calls arrive in fragments keyed by index: the id and name first, then the arguments
+/// a piece at a time
+#[derive(Debug, Default)]
+struct StreamedToolCalls {
+    calls: Vec<PartialToolCall>,
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    index: usize,
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl StreamedToolCalls {
+    fn extend(&mut self, deltas: Vec<ToolCallDelta>) {
+        for delta in deltas {
+            let position = match self.calls.iter().position(|c| c.index == delta.index) {
+                Some(position) => position,
+                None => {
+                    self.calls.push(PartialToolCall {
+                        index: delta.index,
+                        ..Default::default()
+                    });
+                    self.calls.len() - 1
+                }
+            };
+            let call = &mut self.calls[position];
+            if let Some(id) = delta.id {
+                call.id = id;
+            }
+            if let Some(function) = delta.function {
+                call.name.push_str(&function.name.unwrap_or_default());
+                call.arguments
+                    .push_str(&function.arguments.unwrap_or_default());
+            }
+        }
+    }
+
+    fn take(&mut self) -> Option<Vec<ToolCall>> {
+        if self.calls.is_empty() {
+            return None;
+        }
+        let calls = std::mem::take(&mut self.calls)
+            .into_iter()
+            .map(|call| parse_tool_call(call.id, call.name, &call.arguments))
+            .collect();
+        Some(calls)
+    }
+}
+
+/// Arguments come as a JSON string; anything that doesn't parse is passed on as the raw string
+fn parse_tool_call(id: String, name: String, arguments: &str) -> ToolCall {
+    let arguments = if arguments.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(arguments).unwrap_or_else(|_| json!(arguments))
+    };
+    ToolCall {
+        id,
+        name,
+        arguments,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompletionResponse {
+    pub model: Option<String>,
+    pub choices: Vec<CompletionChoice>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompletionChoice {
+    pub message: CompletionMessage,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompletionMessage {
+    pub content: Option<String>,
+    /// Reasoning from models that return it separately, such as `deepseek-reasoner`
+    pub reasoning_content: Option<String>,
+    pub tool_calls: Option<Vec<WireToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WireToolCall {
+    pub id: String,
+    pub function: WireFunction,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WireFunction {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+impl From<WireToolCall> for ToolCall {
+    fn from(call: WireToolCall) -> Self {
+        parse_tool_call(call.id, call.function.name, &call.function.arguments)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct StreamResponse {
+    pub model: Option<String>,
+    pub choices: Vec<StreamChoice>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct StreamChoice {
+    #[serde(default)]
+    pub delta: StreamDelta,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct StreamDelta {
+    pub content: Option<String>,
+    pub reasoning_content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A server that writes `response` and then stalls without closing the connection
+    async fn stalling_server(response: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let response = response.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 8192];
+                    let _ = socket.read(&mut buf).await;
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn short_timeouts() -> ProviderTimeouts {
+        ProviderTimeouts {
+            connect: Duration::from_secs(1),
+            request: Duration::from_millis(200),
+            stream_idle: Duration::from_millis(200),
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: "Hello".to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }],
+            model: "gpt-4o".to_string(),
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            agent_config: None,
+            tools: None,
+        }
+    }
+
+    #[test]
+    fn test_custom_headers_are_sent_and_invalid_ones_skipped() {
+        let headers = HashMap::from([
+            ("OpenAI-Organization".to_string(), "org-123".to_string()),
+            ("X-Gateway-Token".to_string(), "caf\u{e9}".to_string()),
+            ("bad header".to_string(), "value".to_string()),
+        ]);
+        let provider = OpenAiProvider::new("http://gateway.test/v1", "test-key")
+            .unwrap()
+            .with_custom_headers(&headers);
+
+        let request = provider
+            .completions_request(&reqwest::Client::new())
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["openai-organization"], "org-123");
+        assert_eq!(request.headers()["authorization"], "Bearer test-key");
+        assert!(request.headers().get("x-gateway-token").is_none());
+        assert_eq!(request.headers().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_tells_rejected_keys_from_unreachable_servers() {
+        let rejected =
+            "HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let base_url = stalling_server(rejected.to_string()).await;
+        let provider = OpenAiProvider::new(base_url, "bad-key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+        let err = provider.health_check().await.unwrap_err();
+        assert_eq!(err.kind, ProviderErrorKind::Authentication);
+
+        // Nothing listens on the discard port
+        let provider = OpenAiProvider::new("http://127.0.0.1:9", "key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+        let err = provider.health_check().await.unwrap_err();
+        assert_eq!(err.kind, ProviderErrorKind::Network);
+    }
+
+    #[tokio::test]
+    async fn test_slow_provider_times_out() {
+        let base_url = stalling_server(String::new()).await;
+        let provider = OpenAiProvider::new(base_url, "test-key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+
+        let err = provider.complete(&request(), "gpt-4o").await.unwrap_err();
+        assert_eq!(err.kind, ProviderErrorKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stream_hits_inactivity_timeout() {
+        let event = r#"data: {"model":"gpt-4o","choices":[{"delta":{"content":"Hi"},"finish_reason":null}]}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n{}\n\n",
+            event
+        );
+        let base_url = stalling_server(response).await;
+        let provider = OpenAiProvider::new(base_url, "test-key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+
+        let mut stream = Box::pin(provider.stream(&request(), "gpt-4o").await.unwrap());
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.delta.as_deref(), Some("Hi"));
+
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind, ProviderErrorKind::Timeout);
+    }
+
+    fn http_ok(content_type: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_request_body_sends_tools_and_tool_turns() {
+        use crate::chat_service_simple::{Tool, ToolResult};
+
+        let call = ToolCall {
+            id: "call-1".to_string(),
+            name: "get_weather".to_string(),
+            arguments: json!({ "city": "Paris" }),
+        };
+        let mut request = request();
+        assert!(request_body(&request, "gpt-4o", false)
+            .get("tools")
+            .is_none());
+        request.tools = Some(vec![Tool {
+            name: "get_weather".to_string(),
+            description: "Current weather".to_string(),
+            input_schema: json!({ "type": "object" }),
+            is_mcp: false,
+            category: Default::default(),
+        }]);
+        request.messages.push(ChatMessage {
+            role: Role::Assistant,
+            content: String::new(),
+            timestamp: None,
+            tool_calls: Some(vec![call]),
+            tool_results: None,
+        });
+        request.messages.push(ChatMessage {
+            role: Role::Tool,
+            content: "Sunny".to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: Some(vec![ToolResult {
+                tool_call_id: "call-1".to_string(),
+                result: json!("Sunny"),
+                error: None,
+            }]),
+        });
+
+        let body = request_body(&request, "gpt-4o", false);
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather",
+                    "parameters": { "type": "object" },
+                },
+            }])
+        );
+        let assistant = &body["messages"][1];
+        assert!(assistant["content"].is_null());
+        assert_eq!(assistant["tool_calls"][0]["id"], "call-1");
+        assert_eq!(assistant["tool_calls"][0]["type"], "function");
+        assert_eq!(
+            assistant["tool_calls"][0]["function"],
+            json!({ "name": "get_weather", "arguments": r#"{"city":"Paris"}"# })
+        );
+        let tool = &body["messages"][2];
+        assert_eq!(tool["role"], "tool");
+        assert_eq!(tool["tool_call_id"], "call-1");
+        assert_eq!(tool["content"], "Sunny");
+    }
+
+    #[tokio::test]
+    async fn test_completion_tool_calls_are_parsed() {
+        let body = json!({
+            "model": "gpt-4o",
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [
+                        {
+                            "id": "call-1",
+                            "type": "function",
+                            "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" },
+                        },
+                        {
+                            "id": "call-2",
+                            "type": "function",
+                            "function": { "name": "get_time", "arguments": "" },
+                        },
+                    ],
+                },
+                "finish_reason": "tool_calls",
+            }],
+        });
+        let base_url = stalling_server(http_ok("application/json", &body.to_string())).await;
+        let provider = OpenAiProvider::new(base_url, "test-key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+
+        let response = provider.complete(&request(), "gpt-4o").await.unwrap();
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call-1");
+        assert_eq!(calls[0].name, "get_weather");
+        assert_eq!(calls[0].arguments, json!({ "city": "Paris" }));
+        assert_eq!(calls[1].arguments, json!({}));
+        assert_eq!(response.message.unwrap().tool_calls.unwrap(), calls);
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_call_deltas_are_assembled() {
+        let events = [
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call-1","type":"function","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call-2","function":{"name":"get_time","arguments":"{}"}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Paris\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+            "[DONE]",
+        ];
+        let body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+        let base_url = stalling_server(http_ok("text/event-stream", &body)).await;
+        let provider = OpenAiProvider::new(base_url, "test-key")
+            .unwrap()
+            .with_timeouts(short_timeouts());
+
+        let chunks: Vec<StreamChunk> = provider
+            .stream(&request(), "gpt-4o")
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 5);
+        assert!(chunks[..4].iter().all(|chunk| chunk.tool_calls.is_none()));
+        let last = chunks.last().unwrap();
+        assert!(last.is_complete);
+        let calls = last.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call-1");
+        assert_eq!(calls[0].arguments, json!({ "city": "Paris" }));
+        assert_eq!(calls[1].name, "get_time");
+        assert_eq!(calls[1].arguments, json!({}));
+    }
+}
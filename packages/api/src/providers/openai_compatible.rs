@@ -0,0 +1,28 @@
+// Any server speaking the OpenAI chat-completions protocol at its own base URL, such as vLLM,
+// LM Studio, Together or Groq
+use super::HostedProvider;
+use crate::chat_service_simple::{ProviderError, ProviderErrorKind};
+
+/// A provider for a custom endpoint. `name` becomes `ModelConfig::provider` for its model. The
+/// API key may be empty for local servers that don't check one.
+pub fn provider(
+    name: &str,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+) -> Result<HostedProvider, ProviderError> {
+    let base_url = base_url.trim();
+    if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+        return Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            format!("{} needs an http(s) base URL, got {:?}", name, base_url),
+        ));
+    }
+    if model.trim().is_empty() {
+        return Err(ProviderError::new(
+            ProviderErrorKind::InvalidRequest,
+            format!("{} needs a model name", name),
+        ));
+    }
+    HostedProvider::new(name, base_url, api_key.trim(), model.trim())
+}
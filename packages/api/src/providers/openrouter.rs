@@ -0,0 +1,10 @@
+// OpenRouter, which proxies many vendors' models behind one OpenAI-compatible API
+use super::HostedProvider;
+use crate::chat_service_simple::ProviderError;
+
+pub const BASE_URL: &str = "https://openrouter.ai/api/v1";
+pub const DEFAULT_MODEL: &str = "anthropic/claude-3.5-sonnet";
+
+pub fn provider(api_key: &str) -> Result<HostedProvider, ProviderError> {
+    HostedProvider::new("openrouter", BASE_URL, api_key, DEFAULT_MODEL)
+}
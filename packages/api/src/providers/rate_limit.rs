@@ -0,0 +1,136 @@
+// Keeping requests to a provider under the account's per-minute limits
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Per-minute limits a provider account is held to; 0 leaves that dimension unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+    pub tokens_per_minute: u32,
+}
+
+/// A bucket holding up to a minute's allowance, refilled continuously
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// Negative once waiting requests have reserved more than is left
+    available: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32, now: Instant) -> Option<Self> {
+        (per_minute > 0).then_some(Self {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+            updated: now,
+        })
+    }
+
+    /// Take `amount` and return how long to wait before it may be used. Amounts above a
+    /// minute's allowance are treated as a full minute's, so they wait rather than never run.
+    fn reserve(&mut self, amount: f64, now: Instant) -> Duration {
+        let per_second = self.capacity / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * per_second).min(self.capacity);
+        self.updated = now;
+
+        self.available -= amount.min(self.capacity);
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / per_second)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+/// Token-bucket limiter shared by everything sending to one provider. Requests over the
+/// limit wait their turn instead of failing; each reserves its share up front, so they go
+/// out in the order they arrived.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            limit,
+            buckets: Arc::new(Mutex::new(Buckets {
+                requests: Bucket::new(limit.requests_per_minute, now),
+                tokens: Bucket::new(limit.tokens_per_minute, now),
+            })),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Reserve one request of about `tokens` tokens and return the wait before sending it
+    fn reserve(&self, tokens: u64, now: Instant) -> Duration {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let requests = buckets
+            .requests
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(1.0, now));
+        let tokens = buckets
+            .tokens
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(tokens as f64, now));
+        requests.max(tokens)
+    }
+
+    /// Wait until a request of about `tokens` tokens fits under the limit
+    pub async fn acquire(&self, tokens: u64) {
+        let wait = self.reserve(tokens, Instant::now());
+        if !wait.is_zero() {
+            tracing::debug!("Delaying provider request by {:?} for its rate limit", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_over_the_limit_wait_for_the_bucket_to_refill() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_minute: 2,
+            tokens_per_minute: 6_000,
+        });
+        let start = Instant::now();
+        let waits = |wait: Duration, secs: f64| (wait.as_secs_f64() - secs).abs() < 1e-6;
+
+        assert_eq!(limiter.reserve(1_000, start), Duration::ZERO);
+        assert_eq!(limiter.reserve(1_000, start), Duration::ZERO);
+        // A third request needs another request slot: one refills every 30s
+        assert!(waits(limiter.reserve(1_000, start), 30.0));
+
+        // Tokens refill at 100/s; 20s later only the request bucket has to catch up
+        let later = start + Duration::from_secs(20);
+        assert!(waits(limiter.reserve(3_000, later), 40.0));
+
+        // Oversized requests wait for a full minute's allowance rather than forever
+        let tokens_only = RateLimiter::new(RateLimit {
+            requests_per_minute: 0,
+            tokens_per_minute: 600,
+        });
+        assert_eq!(tokens_only.reserve(5_000, start), Duration::ZERO);
+        assert!(waits(tokens_only.reserve(300, start), 30.0));
+    }
+}
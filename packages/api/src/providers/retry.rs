@@ -0,0 +1,80 @@
+// Retrying provider requests that failed for reasons that may go away on their own
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+use crate::chat_service_simple::ProviderError;
+
+/// How often and how patiently to retry a provider request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Tries in total, the first one included; 1 turns retrying off
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles for each retry after it
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Fraction of each wait added or taken off at random, so clients don't retry in lockstep
+    pub jitter: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 8_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Wait before retry number `retry` (1 for the first retry). A `retry_after` from the
+    /// provider is used instead of the backoff, still capped at `max_delay_ms`.
+    pub fn delay(&self, retry: u32, error: &ProviderError) -> Duration {
+        let backoff = match error.retry_after {
+            Some(seconds) => seconds.saturating_mul(1_000),
+            None => {
+                let exponential = self
+                    .base_delay_ms
+                    .saturating_mul(1 << retry.saturating_sub(1).min(16));
+                // Doesn't need to be a good random number, only a different one per client
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .subsec_nanos();
+                let unit = (nanos % 1_000) as f64 / 1_000.0;
+                let factor = 1.0 + self.jitter.clamp(0.0, 1.0) * (2.0 * unit - 1.0);
+                (exponential as f64 * factor) as u64
+            }
+        };
+        Duration::from_millis(backoff.min(self.max_delay_ms))
+    }
+}
+
+/// Run `attempt` until it succeeds, fails with an error that isn't retryable, or has been tried
+/// `max_attempts` times. The last error is returned.
+pub async fn with_retry<T, F, Fut>(config: &RetryConfig, mut attempt: F) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut tries = 1;
+    loop {
+        match attempt().await {
+            Err(e) if e.is_retryable() && tries < config.max_attempts => {
+                let delay = config.delay(tries, &e);
+                tracing::warn!(
+                    "Provider request failed ({}), retrying in {:?} (attempt {} of {})",
+                    e,
+                    delay,
+                    tries + 1,
+                    config.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+                tries += 1;
+            }
+            result => return result,
+        }
+    }
+}
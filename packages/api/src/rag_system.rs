@@ -0,0 +1,328 @@
+// Retrieval-augmented generation: chunk documents, embed the chunks and search them by meaning
+use crate::agent_loop::ToolExecutor;
+use crate::chat_service_simple::{Tool, ToolCall, ToolCategory, ToolResult};
+use crate::embeddings::{cosine_similarity, EmbeddingService, MockEmbeddingService};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Name the knowledge-base search tool is offered to the model under
+pub const RAG_TOOL_NAME: &str = "rag_search";
+/// Results returned by the tool when the model doesn't ask for a number
+pub const DEFAULT_TOP_K: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    pub title: String,
+    pub source: Option<String>,
+}
+
+/// A piece of an ingested document, small enough to hand to the model as context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentChunk {
+    pub id: String,
+    pub document_id: String,
+    /// Position of the chunk within its document
+    pub index: usize,
+    pub text: String,
+    pub metadata: DocumentMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub chunk: DocumentChunk,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
+
+#[async_trait]
+pub trait VectorStore: std::fmt::Debug + Send + Sync {
+    async fn add(&self, chunk: DocumentChunk, embedding: Vec<f32>) -> Result<()>;
+
+    /// The `top_k` chunks closest to `embedding`, best first
+    async fn search(&self, embedding: &[f32], top_k: usize) -> Result<Vec<SearchResult>>;
+
+    async fn chunk_count(&self) -> usize;
+}
+
+/// Keeps every vector in memory and compares the query against all of them
+#[derive(Debug, Default)]
+pub struct InMemoryVectorStore {
+    entries: Mutex<Vec<(DocumentChunk, Vec<f32>)>>,
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn add(&self, chunk: DocumentChunk, embedding: Vec<f32>) -> Result<()> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((chunk, embedding));
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut results: Vec<SearchResult> = entries
+            .iter()
+            .map(|(chunk, vector)| SearchResult {
+                chunk: chunk.clone(),
+                score: cosine_similarity(embedding, vector),
+            })
+            .filter(|result| result.score > 0.0)
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(top_k);
+        Ok(results)
+    }
+
+    async fn chunk_count(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// Splits documents into overlapping chunks of whole words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentProcessor {
+    pub chunk_words: usize,
+    /// Words repeated at the start of the next chunk, so a sentence cut in two is still found
+    pub overlap_words: usize,
+}
+
+impl Default for DocumentProcessor {
+    fn default() -> Self {
+        Self {
+            chunk_words: 200,
+            overlap_words: 40,
+        }
+    }
+}
+
+impl DocumentProcessor {
+    pub fn chunk(&self, text: &str) -> Vec<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let size = self.chunk_words.max(1);
+        let step = size.saturating_sub(self.overlap_words).max(1);
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < words.len() {
+            let end = (start + size).min(words.len());
+            chunks.push(words[start..end].join(" "));
+            if end == words.len() {
+                break;
+            }
+            start += step;
+        }
+        chunks
+    }
+}
+
+/// A knowledge base: documents go in through `ingest`, relevant chunks come out of `search`
+#[derive(Debug, Clone)]
+pub struct RAGSystem {
+    store: Arc<dyn VectorStore>,
+    embeddings: Arc<dyn EmbeddingService>,
+    processor: DocumentProcessor,
+}
+
+impl Default for RAGSystem {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(InMemoryVectorStore::default()),
+            Arc::new(MockEmbeddingService),
+        )
+    }
+}
+
+impl RAGSystem {
+    pub fn new(store: Arc<dyn VectorStore>, embeddings: Arc<dyn EmbeddingService>) -> Self {
+        Self {
+            store,
+            embeddings,
+            processor: DocumentProcessor::default(),
+        }
+    }
+
+    pub fn with_processor(mut self, processor: DocumentProcessor) -> Self {
+        self.processor = processor;
+        self
+    }
+
+    /// Chunk and embed a document, all chunks in one batch. Returns the number of chunks stored.
+    pub async fn ingest(&self, content: &str, metadata: DocumentMetadata) -> Result<usize> {
+        let document_id = uuid::Uuid::new_v4().to_string();
+        let chunks = self.processor.chunk(content);
+        let embeddings = self.embeddings.embed_batch(&chunks).await?;
+        anyhow::ensure!(
+            embeddings.len() == chunks.len(),
+            "Embedding service returned {} vectors for {} chunks",
+            embeddings.len(),
+            chunks.len()
+        );
+        for (index, (text, embedding)) in chunks.iter().zip(embeddings).enumerate() {
+            let chunk = DocumentChunk {
+                id: format!("{}-{}", document_id, index),
+                document_id: document_id.clone(),
+                index,
+                text: text.clone(),
+                metadata: metadata.clone(),
+            };
+            self.store.add(chunk, embedding).await?;
+        }
+        Ok(chunks.len())
+    }
+
+    pub async fn search(&self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+        let embedding = self.embeddings.embed(query).await?;
+        self.store.search(&embedding, top_k).await
+    }
+
+    /// Number of chunks stored
+    pub async fn chunk_count(&self) -> usize {
+        self.store.chunk_count().await
+    }
+}
+
+/// The knowledge base as a tool the model can call
+#[derive(Debug, Clone)]
+pub struct RAGTool {
+    rag: RAGSystem,
+}
+
+impl RAGTool {
+    pub fn new(rag: RAGSystem) -> Self {
+        Self { rag }
+    }
+
+    pub fn definition() -> Tool {
+        Tool {
+            name: RAG_TOOL_NAME.to_string(),
+            description: "Search the documents attached to this conversation for passages \
+                relevant to a query"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to look for"
+                    },
+                    "top_k": {
+                        "type": "integer",
+                        "description": "How many passages to return (default 3)"
+                    }
+                },
+                "required": ["query"]
+            }),
+            is_mcp: false,
+            category: ToolCategory::ReadOnly,
+        }
+    }
+
+    pub async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let query = call.arguments["query"].as_str().unwrap_or_default();
+        let top_k = call.arguments["top_k"]
+            .as_u64()
+            .map_or(DEFAULT_TOP_K, |top_k| top_k as usize);
+
+        let outcome = if query.trim().is_empty() {
+            Err(anyhow::anyhow!("query is required"))
+        } else {
+            self.rag.search(query, top_k).await
+        };
+        match outcome {
+            Ok(results) if results.is_empty() => ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::String("No matching passages found".to_string()),
+                error: None,
+            },
+            Ok(results) => {
+                let passages: Vec<String> = results
+                    .iter()
+                    .map(|result| {
+                        format!("[{}] {}", result.chunk.metadata.title, result.chunk.text)
+                    })
+                    .collect();
+                ToolResult {
+                    tool_call_id: call.id.clone(),
+                    result: serde_json::Value::String(passages.join("\n\n")),
+                    error: None,
+                }
+            }
+            Err(e) => ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::Null,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Answers `rag_search` calls from the knowledge base and hands every other call to `inner`
+pub struct KnowledgeBaseExecutor<'a> {
+    pub tool: RAGTool,
+    pub inner: &'a dyn ToolExecutor,
+}
+
+#[async_trait]
+impl ToolExecutor for KnowledgeBaseExecutor<'_> {
+    async fn execute(&self, call: &ToolCall) -> ToolResult {
+        if call.name == RAG_TOOL_NAME {
+            self.tool.execute(call).await
+        } else {
+            self.inner.execute(call).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(title: &str) -> DocumentMetadata {
+        DocumentMetadata {
+            title: title.to_string(),
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingested_documents_are_chunked_and_searchable() -> Result<()> {
+        let processor = DocumentProcessor {
+            chunk_words: 4,
+            overlap_words: 1,
+        };
+        assert_eq!(
+            processor.chunk("one two three four five six seven"),
+            vec!["one two three four", "four five six seven"]
+        );
+
+        let rag = RAGSystem::default().with_processor(processor);
+        rag.ingest("Rust has no garbage collector", metadata("Rust"))
+            .await?;
+        rag.ingest("Tea is brewed with hot water", metadata("Tea"))
+            .await?;
+        assert_eq!(rag.chunk_count().await, 4);
+
+        let results = rag.search("does rust have a garbage collector", 1).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.metadata.title, "Rust");
+
+        let tool = RAGTool::new(rag);
+        let result = tool
+            .execute(&ToolCall {
+                id: "call-1".to_string(),
+                name: RAG_TOOL_NAME.to_string(),
+                arguments: serde_json::json!({ "query": "hot water", "top_k": 1 }),
+            })
+            .await;
+        assert_eq!(result.error, None);
+        assert!(result.result.as_str().unwrap().starts_with("[Tea]"));
+        Ok(())
+    }
+}
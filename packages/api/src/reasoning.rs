@@ -0,0 +1,287 @@
+// Reasoning chain recorded while the agent plans a reply
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::db::{DbPool, PooledConnection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReasoningStepType {
+    Analysis,
+    Planning,
+    ToolSelection,
+    Reflection,
+}
+
+impl ReasoningStepType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningStepType::Analysis => "analysis",
+            ReasoningStepType::Planning => "planning",
+            ReasoningStepType::ToolSelection => "tool_selection",
+            ReasoningStepType::Reflection => "reflection",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "planning" => ReasoningStepType::Planning,
+            "tool_selection" => ReasoningStepType::ToolSelection,
+            "reflection" => ReasoningStepType::Reflection,
+            _ => ReasoningStepType::Analysis,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReasoningStep {
+    pub step_type: ReasoningStepType,
+    pub content: String,
+    /// How sure the agent is about this step, from 0.0 to 1.0
+    pub confidence: f32,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ReasoningStep {
+    pub fn new(step_type: ReasoningStepType, content: impl Into<String>, confidence: f32) -> Self {
+        Self {
+            step_type,
+            content: content.into(),
+            confidence: confidence.clamp(0.0, 1.0),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// One step of an execution plan. `dependencies` are indices of earlier steps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionStep {
+    pub description: String,
+    #[serde(default)]
+    pub tool_name: Option<String>,
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    pub steps: Vec<ExecutionStep>,
+    /// The model's estimate; `None` when the plan came from the keyword fallback
+    pub estimated_duration: Option<u64>,
+}
+
+impl ExecutionPlan {
+    /// One line per step, for the reasoning chain
+    pub fn summary(&self) -> String {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| match &step.tool_name {
+                Some(tool) => format!("{}. {} (using {})", i + 1, step.description, tool),
+                None => format!("{}. {}", i + 1, step.description),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Step indices in an order that runs every step after its dependencies. Among steps that
+    /// are ready at the same time, the one listed first goes first.
+    pub fn execution_order(&self) -> Result<Vec<usize>> {
+        let count = self.steps.len();
+        let mut waiting_on = vec![0; count];
+        let mut dependents = vec![Vec::new(); count];
+        for (i, step) in self.steps.iter().enumerate() {
+            for &dep in &step.dependencies {
+                if dep >= count {
+                    return Err(anyhow::anyhow!(
+                        "Step {} depends on step {}, which doesn't exist",
+                        i + 1,
+                        dep + 1
+                    ));
+                }
+                waiting_on[i] += 1;
+                dependents[dep].push(i);
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..count).filter(|&i| waiting_on[i] == 0).collect();
+        let mut order = Vec::with_capacity(count);
+        while let Some(next) = ready.pop_first() {
+            order.push(next);
+            for &dependent in &dependents[next] {
+                waiting_on[dependent] -= 1;
+                if waiting_on[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        if order.len() < count {
+            let stuck: Vec<String> = (0..count)
+                .filter(|&i| waiting_on[i] > 0)
+                .map(|i| (i + 1).to_string())
+                .collect();
+            return Err(anyhow::anyhow!(
+                "Plan has a dependency cycle involving steps {}",
+                stuck.join(", ")
+            ));
+        }
+        Ok(order)
+    }
+}
+
+pub(crate) const PLANNING_PROMPT: &str = "Plan how to answer the request below using the listed \
+tools. Reply with only a JSON object of the form {\"estimated_duration_seconds\": <number>, \
+\"steps\": [{\"description\": <string>, \"tool_name\": <tool name or null>, \"parameters\": \
+<object>, \"dependencies\": [<indices of earlier steps>]}]}.";
+
+#[derive(Deserialize)]
+struct PlanOutput {
+    steps: Vec<ExecutionStep>,
+    estimated_duration_seconds: Option<f64>,
+}
+
+/// Parse the model's reply to `PLANNING_PROMPT`. Tolerates a code fence or prose around the
+/// JSON, and a bare array of steps. `None` if there are no usable steps, a step names a tool
+/// that isn't available, or the dependencies can't be ordered.
+pub fn parse_plan(output: &str, tools: &[String]) -> Option<ExecutionPlan> {
+    let object = output
+        .find('{')
+        .zip(output.rfind('}'))
+        .and_then(|(start, end)| serde_json::from_str::<PlanOutput>(&output[start..=end]).ok());
+    let plan = match object {
+        Some(plan) => plan,
+        None => {
+            let start = output.find('[')?;
+            let end = output.rfind(']')?;
+            PlanOutput {
+                steps: serde_json::from_str(output.get(start..=end)?).ok()?,
+                estimated_duration_seconds: None,
+            }
+        }
+    };
+
+    let valid = plan.steps.iter().all(|step| {
+        !step.description.trim().is_empty()
+            && step.tool_name.iter().all(|tool| tools.contains(tool))
+    });
+    if plan.steps.is_empty() || !valid {
+        return None;
+    }
+
+    let plan = ExecutionPlan {
+        steps: plan.steps,
+        estimated_duration: plan
+            .estimated_duration_seconds
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(|seconds| seconds.round() as u64),
+    };
+    plan.execution_order().ok()?;
+    Some(plan)
+}
+
+/// Fallback when the model's plan can't be parsed: pick steps by keywords in the request
+pub fn keyword_plan(request: &str, tools: &[String]) -> ExecutionPlan {
+    let lower = request.to_lowercase();
+    let has_tool = |name: &str| tools.iter().any(|tool| tool == name);
+    let mut steps = Vec::new();
+
+    if lower.contains("file") && has_tool("file_editor") {
+        steps.push(ExecutionStep {
+            description: "Read the relevant files".to_string(),
+            tool_name: Some("file_editor".to_string()),
+            parameters: serde_json::Value::Null,
+            dependencies: Vec::new(),
+        });
+    }
+    if lower.contains("search") && has_tool("web_search") {
+        steps.push(ExecutionStep {
+            description: "Search the web".to_string(),
+            tool_name: Some("web_search".to_string()),
+            parameters: serde_json::Value::Null,
+            dependencies: Vec::new(),
+        });
+    }
+    let dependencies = (0..steps.len()).collect();
+    steps.push(ExecutionStep {
+        description: "Answer the request".to_string(),
+        tool_name: None,
+        parameters: serde_json::Value::Null,
+        dependencies,
+    });
+
+    ExecutionPlan {
+        steps,
+        estimated_duration: None,
+    }
+}
+
+/// Per-session reasoning steps, stored alongside the session's messages
+#[derive(Debug, Clone)]
+pub struct ReasoningStore {
+    pool: DbPool,
+}
+
+impl ReasoningStore {
+    pub fn new(pool: DbPool) -> Result<Self> {
+        let store = Self { pool };
+        store.conn()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS reasoning_steps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                step_type TEXT NOT NULL,
+                content TEXT NOT NULL,
+                confidence REAL NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_reasoning_steps_session ON reasoning_steps(session_id, id);",
+        )?;
+        Ok(store)
+    }
+
+    fn conn(&self) -> Result<PooledConnection> {
+        Ok(self.pool.get()?)
+    }
+
+    pub fn add_step(&self, session_id: &str, step: &ReasoningStep) -> Result<()> {
+        self.conn()?.execute(
+            "INSERT INTO reasoning_steps (session_id, step_type, content, confidence, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                session_id,
+                step.step_type.as_str(),
+                step.content,
+                step.confidence,
+                step.timestamp.timestamp_millis()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Steps for a session in the order they were added
+    pub fn chain(&self, session_id: &str) -> Result<Vec<ReasoningStep>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT step_type, content, confidence, created_at FROM reasoning_steps
+             WHERE session_id = ?1 ORDER BY id",
+        )?;
+        let steps = stmt
+            .query_map(params![session_id], |row| {
+                Ok(ReasoningStep {
+                    step_type: ReasoningStepType::parse(&row.get::<_, String>(0)?),
+                    content: row.get(1)?,
+                    confidence: row.get(2)?,
+                    timestamp: Utc
+                        .timestamp_millis_opt(row.get(3)?)
+                        .single()
+                        .unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(steps)
+    }
+}
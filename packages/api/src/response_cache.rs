@@ -0,0 +1,339 @@
+// Replies to repeated deterministic requests, kept so they needn't go to the model again
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::chat_service_simple::{ChatRequest, ChatResponse};
+
+/// Matches `PerformanceSettings.cache_size_mb`'s default
+pub const DEFAULT_CACHE_SIZE_MB: u32 = 512;
+
+/// A request's cache key: the canonical form of everything that can change the reply, and
+/// its hash. Entries keep the canonical form, so two requests whose hashes collide miss
+/// instead of sharing a reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    hash: u64,
+    canonical: String,
+}
+
+#[derive(Debug)]
+struct CachedResponse {
+    canonical: String,
+    response: ChatResponse,
+    /// Approximate bytes held, from the response's JSON size
+    size: usize,
+    /// Position in `State::recency`
+    used: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    capacity: usize,
+    size: usize,
+    entries: HashMap<u64, CachedResponse>,
+    /// Keys by last use, least recent first
+    recency: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl State {
+    fn touch(&mut self, key: u64) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.recency.remove(&entry.used);
+            entry.used = clock;
+            self.recency.insert(clock, key);
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.recency.remove(&entry.used);
+            self.size -= entry.size;
+        }
+    }
+
+    /// Drop least recently used responses until the cache fits its capacity
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.size -= entry.size;
+            }
+        }
+    }
+}
+
+/// LRU cache of non-streaming responses, bounded by their approximate size. A request opts
+/// in by asking for temperature 0, where the model would give the same answer again; any
+/// other request skips the cache.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    state: Arc<Mutex<State>>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                capacity: capacity_bytes,
+                ..State::default()
+            })),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Key for `request`, or `None` when it doesn't opt in to caching. Everything that can
+    /// change the reply goes into the key; message timestamps don't, so the same
+    /// conversation sent again still hits.
+    pub fn key(request: &ChatRequest) -> Option<CacheKey> {
+        if request.stream || request.temperature != Some(0.0) {
+            return None;
+        }
+
+        let messages: Vec<_> = request
+            .messages
+            .iter()
+            .map(|message| {
+                serde_json::json!([
+                    message.role,
+                    message.content,
+                    message.tool_calls,
+                    message.tool_results,
+                ])
+            })
+            .collect();
+        let canonical = serde_json::json!([
+            request.model,
+            request.system_prompt,
+            request.temperature,
+            request.top_p,
+            request.frequency_penalty,
+            request.presence_penalty,
+            request.max_tokens,
+            request.tools,
+            request.agent_config,
+            messages,
+        ])
+        .to_string();
+
+        let mut hasher = DefaultHasher::new();
+        canonical.hash(&mut hasher);
+        Some(CacheKey {
+            hash: hasher.finish(),
+            canonical,
+        })
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<ChatResponse> {
+        let mut state = self.state();
+        let entry = state.entries.get(&key.hash)?;
+        if entry.canonical != key.canonical {
+            return None;
+        }
+        let response = entry.response.clone();
+        state.touch(key.hash);
+        Some(response)
+    }
+
+    /// Store `response`, evicting older ones to make room. Responses larger than the whole
+    /// cache aren't kept.
+    pub fn insert(&self, key: CacheKey, response: &ChatResponse) {
+        // The canonical key is held as well, so it counts towards the size
+        let size = serde_json::to_vec(response).map_or(0, |json| json.len()) + key.canonical.len();
+        let mut state = self.state();
+        state.remove(key.hash);
+        if size > state.capacity {
+            return;
+        }
+
+        state.clock += 1;
+        let used = state.clock;
+        state.entries.insert(
+            key.hash,
+            CachedResponse {
+                canonical: key.canonical,
+                response: response.clone(),
+                size,
+                used,
+            },
+        );
+        state.recency.insert(used, key.hash);
+        state.size += size;
+        state.evict();
+    }
+
+    /// Change the size bound, evicting responses that no longer fit
+    pub fn set_capacity(&self, capacity_bytes: usize) {
+        let mut state = self.state();
+        state.capacity = capacity_bytes;
+        state.evict();
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.entries.clear();
+        state.recency.clear();
+        state.size = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_SIZE_MB as usize * 1024 * 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_service_simple::{ChatMessage, Role, ToolResult};
+
+    fn request(content: &str, temperature: Option<f32>) -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: content.to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }],
+            model: "mock-local".to_string(),
+            system_prompt: None,
+            temperature,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            agent_config: None,
+            tools: None,
+        }
+    }
+
+    fn response(content: &str) -> ChatResponse {
+        ChatResponse {
+            message: Some(ChatMessage {
+                role: Role::Assistant,
+                content: content.to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }),
+            tool_calls: None,
+            token_usage: None,
+            model: "mock-local".to_string(),
+            finish_reason: Some("stop".to_string()),
+            is_streaming: false,
+            reasoning_content: None,
+            thinking_content: None,
+            notification: None,
+        }
+    }
+
+    fn content(response: &ChatResponse) -> &str {
+        &response.message.as_ref().unwrap().content
+    }
+
+    #[test]
+    fn test_identical_deterministic_requests_hit_and_others_skip() {
+        let cache = ResponseCache::default();
+        let key = ResponseCache::key(&request("Hi", Some(0.0))).unwrap();
+        assert!(cache.get(&key).is_none());
+
+        cache.insert(key, &response("Hello"));
+        let again = ResponseCache::key(&request("Hi", Some(0.0))).unwrap();
+        assert_eq!(content(&cache.get(&again).unwrap()), "Hello");
+
+        let other = ResponseCache::key(&request("Bye", Some(0.0))).unwrap();
+        assert!(cache.get(&other).is_none());
+        // A different request whose hash collides misses rather than getting "Hello"
+        let colliding = CacheKey {
+            hash: again.hash,
+            canonical: other.canonical,
+        };
+        assert!(cache.get(&colliding).is_none());
+        // Sampled or streamed requests don't opt in
+        assert!(ResponseCache::key(&request("Hi", Some(0.7))).is_none());
+        assert!(ResponseCache::key(&request("Hi", None)).is_none());
+        let streamed = ChatRequest {
+            stream: true,
+            ..request("Hi", Some(0.0))
+        };
+        assert!(ResponseCache::key(&streamed).is_none());
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_tool_results_and_generation_params_are_part_of_the_key() {
+        let with_result = |result: &str| {
+            let mut request = request("Weather?", Some(0.0));
+            request.messages.push(ChatMessage {
+                role: Role::Tool,
+                content: String::new(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: Some(vec![ToolResult {
+                    tool_call_id: "call-1".to_string(),
+                    result: serde_json::json!(result),
+                    error: None,
+                }]),
+            });
+            ResponseCache::key(&request).unwrap()
+        };
+        assert_eq!(with_result("sunny"), with_result("sunny"));
+        assert_ne!(with_result("sunny"), with_result("raining"));
+
+        let key = ResponseCache::key(&request("Hi", Some(0.0))).unwrap();
+        let capped = ChatRequest {
+            max_tokens: Some(10),
+            ..request("Hi", Some(0.0))
+        };
+        let nucleus = ChatRequest {
+            top_p: Some(0.5),
+            ..request("Hi", Some(0.0))
+        };
+        assert_ne!(ResponseCache::key(&capped).unwrap(), key);
+        assert_ne!(ResponseCache::key(&nucleus).unwrap(), key);
+    }
+
+    #[test]
+    fn test_least_recently_used_response_is_evicted_past_capacity() {
+        let key = |content: &str| ResponseCache::key(&request(content, Some(0.0))).unwrap();
+        let size = serde_json::to_vec(&response("aaaa")).unwrap().len() + key("1").canonical.len();
+        let cache = ResponseCache::new(size * 2);
+        cache.insert(key("1"), &response("aaaa"));
+        cache.insert(key("2"), &response("bbbb"));
+        // Reading 1 makes 2 the least recently used
+        assert!(cache.get(&key("1")).is_some());
+
+        cache.insert(key("3"), &response("cccc"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("2")).is_none());
+        assert_eq!(content(&cache.get(&key("1")).unwrap()), "aaaa");
+        assert_eq!(content(&cache.get(&key("3")).unwrap()), "cccc");
+
+        // Shrinking the cache evicts down to what fits
+        cache.set_capacity(size);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key("3")).is_some());
+    }
+}
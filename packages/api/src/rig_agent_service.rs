@@ -4,7 +4,7 @@ use chrono::Utc;
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -42,7 +42,7 @@ impl MockAgent for MockAgentImpl {
 pub use crate::chat_service_simple::{
     AgentConfig, ChatMessage, ChatRequest, ChatResponse, GooseMode, Message, MessageContent,
     MessageMetadata, ModelConfig, ModelPricing, ProviderError, Role, StreamChunk, TokenUsage, Tool,
-    ToolCall, ToolResult,
+    ToolCall, ToolCategory, ToolResult,
 };
 
 /// Enhanced model configuration with rig provider integration
@@ -56,6 +56,44 @@ pub struct RigModelConfig {
     pub api_key_env: Option<String>,
 }
 
+/// A provider behind one or more registered models, as listed by `/api/providers`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub id: String,
+    pub name: String,
+    /// True when any of the provider's models can stream
+    pub supports_streaming: bool,
+    /// True when any of the provider's models can call tools
+    pub supports_tools: bool,
+    /// False when the provider needs an API key and none is set
+    pub active: bool,
+    /// Environment variable the API key is read from, if the provider needs one
+    pub api_key_env: Option<String>,
+    /// Registry ids of the provider's models
+    pub models: Vec<String>,
+}
+
+/// Set to answer with the mock model when a provider's API key is missing, instead of failing
+pub const MOCK_FALLBACK_ENV: &str = "DIOXUS_CHAT_ALLOW_MOCK_FALLBACK";
+
+/// Model used in place of one whose provider has no key, when falling back is allowed
+const FALLBACK_MODEL: &str = "mock-local";
+
+fn key_present(var: &str) -> bool {
+    std::env::var(var).is_ok_and(|key| !key.trim().is_empty())
+}
+
+pub(crate) fn provider_display_name(id: &str) -> String {
+    match id {
+        "local" => "Local".to_string(),
+        "openai" => "OpenAI".to_string(),
+        "deepseek" => "DeepSeek".to_string(),
+        "anthropic" => "Anthropic".to_string(),
+        "openrouter" => "OpenRouter".to_string(),
+        other => other.to_string(),
+    }
+}
+
 /// Custom tool trait for mock rig integration
 #[async_trait::async_trait]
 pub trait CustomTool: Send + Sync {
@@ -112,6 +150,8 @@ pub struct RigAgentService {
     models: HashMap<String, RigModelConfig>,
     default_model: Option<String>,
     agents: Arc<RwLock<HashMap<String, Box<dyn MockAgent>>>>,
+    /// Answer with `FALLBACK_MODEL` when a provider's key is missing instead of failing
+    allow_mock_fallback: bool,
 }
 
 impl RigAgentService {
@@ -123,6 +163,7 @@ impl RigAgentService {
             RigModelConfig {
                 base: ModelConfig {
                     id: "mock-local".to_string(),
+                    model: "mock-local".to_string(),
                     name: "Mock Local Model".to_string(),
                     provider: "local".to_string(),
                     description: Some("A simple mock model for testing".to_string()),
@@ -132,6 +173,8 @@ impl RigAgentService {
                     supports_vision: false,
                     supports_function_calling: false,
                     pricing: None,
+                    temperature: None,
+                    max_tokens: None,
                 },
                 rig_provider: "mock".to_string(),
                 rig_model_id: "mock-local".to_string(),
@@ -142,6 +185,7 @@ impl RigAgentService {
             RigModelConfig {
                 base: ModelConfig {
                     id: "openai/gpt-4o".to_string(),
+                    model: "gpt-4o".to_string(),
                     name: "GPT-4o".to_string(),
                     provider: "openai".to_string(),
                     description: Some("OpenAI's flagship multimodal model".to_string()),
@@ -155,6 +199,8 @@ impl RigAgentService {
                         output_tokens: 0.015,
                         currency: "USD".to_string(),
                     }),
+                    temperature: None,
+                    max_tokens: None,
                 },
                 rig_provider: "openai".to_string(),
                 rig_model_id: "gpt-4o".to_string(),
@@ -165,6 +211,7 @@ impl RigAgentService {
             RigModelConfig {
                 base: ModelConfig {
                     id: "deepseek-chat".to_string(),
+                    model: "deepseek-chat".to_string(),
                     name: "DeepSeek Chat".to_string(),
                     provider: "deepseek".to_string(),
                     description: Some(
@@ -180,6 +227,8 @@ impl RigAgentService {
                         output_tokens: 0.00028,
                         currency: "USD".to_string(),
                     }),
+                    temperature: None,
+                    max_tokens: None,
                 },
                 rig_provider: "deepseek".to_string(),
                 rig_model_id: "deepseek-chat".to_string(),
@@ -190,6 +239,7 @@ impl RigAgentService {
             RigModelConfig {
                 base: ModelConfig {
                     id: "anthropic/claude-3.5-sonnet".to_string(),
+                    model: "claude-3-5-sonnet-20241022".to_string(),
                     name: "Claude 3.5 Sonnet".to_string(),
                     provider: "anthropic".to_string(),
                     description: Some("Anthropic's most intelligent model".to_string()),
@@ -203,6 +253,8 @@ impl RigAgentService {
                         output_tokens: 0.015,
                         currency: "USD".to_string(),
                     }),
+                    temperature: None,
+                    max_tokens: None,
                 },
                 rig_provider: "anthropic".to_string(),
                 rig_model_id: "claude-3-5-sonnet-20241022".to_string(),
@@ -222,23 +274,105 @@ impl RigAgentService {
             models,
             default_model,
             agents: Arc::new(RwLock::new(HashMap::new())),
+            allow_mock_fallback: std::env::var_os(MOCK_FALLBACK_ENV).is_some(),
         })
     }
 
+    /// Whether models whose provider has no API key fall back to the mock model
+    pub fn with_mock_fallback(mut self, allowed: bool) -> Self {
+        self.allow_mock_fallback = allowed;
+        self
+    }
+
     pub fn get_available_models(&self) -> Vec<ModelConfig> {
         self.models.values().map(|m| m.base.clone()).collect()
     }
 
-    async fn create_or_get_agent(&self, request: &ChatRequest) -> Result<String> {
-        let model_id = if request.model.is_empty() {
+    /// Configured providers with their capabilities, sorted by id. A provider is active when
+    /// it needs no key or its key variable is set.
+    pub fn providers(&self) -> Vec<ProviderMetadata> {
+        self.providers_with(key_present)
+    }
+
+    fn providers_with(&self, key_present: impl Fn(&str) -> bool) -> Vec<ProviderMetadata> {
+        let mut providers: BTreeMap<&str, ProviderMetadata> = BTreeMap::new();
+        for model in self.models.values() {
+            let id = model.base.provider.as_str();
+            let provider = providers.entry(id).or_insert_with(|| ProviderMetadata {
+                id: id.to_string(),
+                name: provider_display_name(id),
+                supports_streaming: false,
+                supports_tools: false,
+                active: true,
+                api_key_env: None,
+                models: Vec::new(),
+            });
+            provider.supports_streaming |= model.supports_streaming;
+            provider.supports_tools |= model.supports_tools;
+            if let Some(ref var) = model.api_key_env {
+                provider.active &= key_present(var);
+                provider.api_key_env = Some(var.clone());
+            }
+            provider.models.push(model.base.id.clone());
+        }
+
+        providers
+            .into_values()
+            .map(|mut provider| {
+                provider.models.sort();
+                provider
+            })
+            .collect()
+    }
+
+    /// Look up a model by its registry id or alias, falling back to the default model
+    pub fn resolve_model(&self, alias: &str) -> Result<&RigModelConfig> {
+        let key = if alias.is_empty() {
             self.default_model
-                .as_ref()
+                .as_deref()
                 .ok_or_else(|| anyhow::anyhow!("No default model configured"))?
-                .clone()
         } else {
-            request.model.clone()
+            alias
+        };
+
+        self.models
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("Model {} not found", key))
+    }
+
+    /// The model to answer `alias` with: the model itself when its provider is usable, else
+    /// the mock model if falling back is allowed, else a `NO_CREDENTIALS` error
+    fn usable_model(
+        &self,
+        alias: &str,
+        key_present: impl Fn(&str) -> bool,
+    ) -> Result<&RigModelConfig> {
+        let model_config = self.resolve_model(alias)?;
+        let Some(var) = model_config
+            .api_key_env
+            .as_deref()
+            .filter(|var| !key_present(var))
+        else {
+            return Ok(model_config);
         };
 
+        if self.allow_mock_fallback {
+            tracing::warn!(
+                "{} is not set; answering {} with {}",
+                var,
+                model_config.base.id,
+                FALLBACK_MODEL
+            );
+            return self.resolve_model(FALLBACK_MODEL);
+        }
+        let provider = provider_display_name(&model_config.base.provider);
+        Err(ProviderError::no_credentials(&provider, var).into())
+    }
+
+    async fn create_or_get_agent(&self, request: &ChatRequest) -> Result<String> {
+        let model_config = self.usable_model(&request.model, key_present)?;
+        let model_id = model_config.base.id.clone();
+
         let agent_key = format!(
             "{}:{}:{}",
             model_id,
@@ -259,11 +393,6 @@ impl RigAgentService {
         }
 
         // Create new agent based on model
-        let model_config = self
-            .models
-            .get(&model_id)
-            .ok_or_else(|| anyhow::anyhow!("Model {} not found", model_id))?;
-
         let agent: Box<dyn MockAgent> = match model_config.rig_provider.as_str() {
             "openai" | "anthropic" | "deepseek" => {
                 // For now, create a mock agent that simulates these providers
@@ -276,7 +405,7 @@ impl RigAgentService {
 
                 // Add tools if supported and requested
                 if model_config.supports_tools
-                    && request.tools.as_ref().map_or(true, |t| !t.is_empty())
+                    && request.tools.as_ref().is_none_or(|t| !t.is_empty())
                 {
                     builder = builder.tool(DateTimeTool).tool(WeatherTool);
                 }
@@ -302,7 +431,7 @@ impl RigAgentService {
             }
             "mock" => {
                 // Create a mock agent for testing
-                MockAgentBuilder::new(&model_id).build()
+                MockAgentBuilder::new(&model_config.base.model).build()
             }
             _ => {
                 return Err(anyhow::anyhow!(
@@ -342,8 +471,8 @@ impl RigAgentService {
         let response = agent.prompt(&user_message).await?;
 
         // Calculate mock token usage
-        let prompt_tokens = (user_message.len() + 3) / 4;
-        let completion_tokens = (response.len() + 3) / 4;
+        let prompt_tokens = user_message.len().div_ceil(4);
+        let completion_tokens = response.len().div_ceil(4);
         let total_tokens = prompt_tokens + completion_tokens;
 
         Ok(ChatResponse {
@@ -365,6 +494,7 @@ impl RigAgentService {
             is_streaming: false,
             reasoning_content: None,
             thinking_content: None,
+            notification: None,
         })
     }
 
@@ -390,15 +520,12 @@ impl RigAgentService {
 
         // Create a streaming response
         let model_id = request.model.clone();
-        let mut response_text = String::new();
 
         // Simulate streaming by breaking the response into chunks
-        match agent.prompt(&user_message).await {
-            Ok(response) => {
-                response_text = response;
-            }
+        let response_text = match agent.prompt(&user_message).await {
+            Ok(response) => response,
             Err(e) => return Err(anyhow::anyhow!("Failed to generate response: {}", e)),
-        }
+        };
 
         let words: Vec<String> = response_text
             .split_whitespace()
@@ -421,6 +548,8 @@ impl RigAgentService {
                         None
                     },
                     is_complete,
+                    thinking: None,
+                    tool_calls: None,
                 }
             }),
         )
@@ -429,7 +558,7 @@ impl RigAgentService {
     pub async fn list_tools(&self, model: &str) -> Vec<Tool> {
         let mut tools = vec![];
 
-        if let Some(model_config) = self.models.get(model) {
+        if let Ok(model_config) = self.resolve_model(model) {
             if model_config.supports_tools {
                 tools.push(Tool {
                     name: "get_current_time".to_string(),
@@ -439,6 +568,7 @@ impl RigAgentService {
                         "properties": {},
                     }),
                     is_mcp: false,
+                    category: ToolCategory::ReadOnly,
                 });
 
                 tools.push(Tool {
@@ -455,6 +585,7 @@ impl RigAgentService {
                         "required": ["location"],
                     }),
                     is_mcp: false,
+                    category: ToolCategory::Network,
                 });
             }
         }
@@ -482,3 +613,49 @@ impl Default for RigAgentService {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_providers_report_capabilities_and_key_status() {
+        let service = RigAgentService::new().unwrap();
+        let providers = service.providers_with(|var| var == "OPENAI_API_KEY");
+
+        let ids: Vec<&str> = providers.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["anthropic", "deepseek", "local", "openai"]);
+
+        let local = &providers[2];
+        assert!(local.active && local.api_key_env.is_none());
+        assert!(!local.supports_streaming && !local.supports_tools);
+
+        let openai = &providers[3];
+        assert_eq!(openai.name, "OpenAI");
+        assert!(openai.active && openai.supports_streaming && openai.supports_tools);
+        assert_eq!(openai.models, vec!["openai/gpt-4o"]);
+
+        let deepseek = &providers[1];
+        assert!(!deepseek.active);
+        assert_eq!(deepseek.api_key_env.as_deref(), Some("DEEPSEEK_API_KEY"));
+    }
+
+    #[test]
+    fn test_missing_key_is_a_typed_error_unless_fallback_is_allowed() {
+        let no_keys = |_: &str| false;
+        let service = RigAgentService::new().unwrap().with_mock_fallback(false);
+
+        let err = service.usable_model("openai/gpt-4o", no_keys).unwrap_err();
+        let err = err.downcast_ref::<ProviderError>().unwrap();
+        assert!(err.is_no_credentials());
+        assert!(err.message.contains("OPENAI_API_KEY"));
+        // Models that need no key are unaffected
+        let model = service.usable_model("mock-local", no_keys).unwrap();
+        assert_eq!(model.base.id, "mock-local");
+
+        let service = service.with_mock_fallback(true);
+        let model = service.usable_model("openai/gpt-4o", no_keys).unwrap();
+        assert_eq!(model.base.id, FALLBACK_MODEL);
+        let model = service.usable_model("openai/gpt-4o", |_| true).unwrap();
+        assert_eq!(model.base.id, "openai/gpt-4o");
+    }
+}
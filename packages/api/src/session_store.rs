@@ -0,0 +1,1188 @@
+// SQLite persistence for chat sessions and their messages
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use crate::chat_service_simple::{concat_text, GooseMode, MessageContent, Role};
+use crate::db::{DbPool, PooledConnection};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredSession {
+    pub id: String,
+    pub title: String,
+    pub model: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Pinned sessions are listed first regardless of recency
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredMessage {
+    pub id: String,
+    pub session_id: String,
+    pub role: Role,
+    /// Text of the message for display; `parts` holds the full structured content
+    pub content: String,
+    pub parts: Vec<MessageContent>,
+    pub created_at: DateTime<Utc>,
+    /// Monotonic insertion order, used to break ties between messages created in the same second
+    pub seq: i64,
+    /// Model that wrote an assistant reply, as the registry id the request named
+    pub model: Option<String>,
+}
+
+/// A session as shown in the sidebar, with its latest message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionPreview {
+    pub session: StoredSession,
+    pub last_message: Option<String>,
+    /// The session's model has been removed; replies fall back to the default model
+    pub model_missing: bool,
+}
+
+/// Position in a session's history: messages strictly older than this come next. The `seq`
+/// tiebreaker keeps pages stable when several messages share a second.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MessageCursor {
+    pub created_at: i64,
+    pub seq: i64,
+}
+
+/// One page of a session's messages, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessagePage {
+    pub messages: Vec<StoredMessage>,
+    /// Where the next (older) page starts; `None` once the start of the session is reached
+    pub next_cursor: Option<MessageCursor>,
+}
+
+/// A message matching a search, with its session and the matched text highlighted in `[...]`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageSearchHit {
+    pub session: StoredSession,
+    pub message: StoredMessage,
+    pub snippet: String,
+}
+
+/// Longest title taken from the start of a message
+pub const TITLE_SNIPPET_CHARS: usize = 50;
+
+/// A session title from the start of `text`: its first line, cut at a word boundary
+pub fn title_snippet(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default().trim();
+    if line.chars().count() <= TITLE_SNIPPET_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(TITLE_SNIPPET_CHARS).collect();
+    match cut.rfind(char::is_whitespace) {
+        Some(end) if end > 0 => format!("{}...", cut[..end].trim_end()),
+        _ => format!("{}...", cut),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    pool: DbPool,
+}
+
+impl SessionStore {
+    pub fn new(pool: DbPool) -> Result<Self> {
+        let store = Self { pool };
+        store.initialize()?;
+        Ok(store)
+    }
+
+    fn conn(&self) -> Result<PooledConnection> {
+        Ok(self.pool.get()?)
+    }
+
+    fn initialize(&self) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                model TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                pinned INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );",
+        )?;
+
+        // Older databases ordered messages by created_at alone; add the tiebreaker and
+        // backfill it from insertion order
+        if !has_column(&conn, "messages", "seq")? {
+            conn.execute_batch(
+                "ALTER TABLE messages ADD COLUMN seq INTEGER;
+                UPDATE messages SET seq = rowid WHERE seq IS NULL;",
+            )?;
+        }
+        if !has_column(&conn, "sessions", "pinned")? {
+            conn.execute_batch(
+                "ALTER TABLE sessions ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;",
+            )?;
+        }
+        // Structured content as JSON; rows without it only have their plain text
+        if !has_column(&conn, "messages", "content_json")? {
+            conn.execute_batch("ALTER TABLE messages ADD COLUMN content_json TEXT;")?;
+        }
+        if !has_column(&conn, "messages", "model")? {
+            conn.execute_batch("ALTER TABLE messages ADD COLUMN model TEXT;")?;
+        }
+        if !has_column(&conn, "sessions", "mode")? {
+            conn.execute_batch("ALTER TABLE sessions ADD COLUMN mode TEXT;")?;
+        }
+
+        // Older databases have no foreign key, so deleting a session left its messages behind.
+        // SQLite can't add one in place; rebuild the table keeping rowids, which the search
+        // index refers to. Enforcement is off for the copy so rows already orphaned survive.
+        let has_foreign_key = conn
+            .prepare("SELECT 1 FROM pragma_foreign_key_list('messages')")?
+            .exists([])?;
+        if !has_foreign_key {
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                BEGIN;
+                CREATE TABLE messages_new (
+                    id TEXT PRIMARY KEY,
+                    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                    role TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    created_at INTEGER NOT NULL,
+                    seq INTEGER,
+                    content_json TEXT,
+                    model TEXT
+                );
+                INSERT INTO messages_new
+                        (rowid, id, session_id, role, content, created_at, seq, content_json, model)
+                    SELECT rowid, id, session_id, role, content, created_at, seq, content_json, model
+                    FROM messages;
+                DROP TABLE messages;
+                ALTER TABLE messages_new RENAME TO messages;
+                COMMIT;",
+            )?;
+        }
+        // Pooled connections turn it on as they open; this one had it off for the copy
+        conn.pragma_update(None, "foreign_keys", true)?;
+
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_messages_session_order
+                ON messages(session_id, created_at, seq);",
+        )?;
+
+        // Full-text index over message text, kept in step with `messages` by triggers and
+        // filled from existing rows the first time it is created
+        let indexed = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE name = 'messages_fts'")?
+            .exists([])?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
+                USING fts5(content, content='messages', content_rowid='rowid');
+            CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                INSERT INTO messages_fts(messages_fts, rowid, content)
+                    VALUES ('delete', old.rowid, old.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+                INSERT INTO messages_fts(messages_fts, rowid, content)
+                    VALUES ('delete', old.rowid, old.content);
+                INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+            END;",
+        )?;
+        if !indexed {
+            conn.execute_batch("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');")?;
+        }
+
+        // Untitled sessions are named after the start of their first user message
+        let untitled: Vec<(String, Option<String>)> = {
+            let mut stmt = conn.prepare(
+                "SELECT s.id,
+                        (SELECT m.content FROM messages m
+                         WHERE m.session_id = s.id AND m.role = 'user'
+                         ORDER BY m.created_at, m.seq LIMIT 1)
+                 FROM sessions s WHERE trim(s.title) = ''",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for (session_id, first_message) in untitled {
+            if let Some(first_message) = first_message {
+                conn.execute(
+                    "UPDATE sessions SET title = ?1 WHERE id = ?2",
+                    params![title_snippet(&first_message), session_id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn create_session(&self, title: &str, model: Option<&str>) -> Result<StoredSession> {
+        let now = Utc::now().timestamp();
+        let session = StoredSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            model: model.map(str::to_string),
+            created_at: from_epoch(now),
+            updated_at: from_epoch(now),
+            pinned: false,
+        };
+
+        self.conn()?.execute(
+            "INSERT INTO sessions (id, title, model, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![session.id, session.title, session.model, now],
+        )?;
+        Ok(session)
+    }
+
+    /// All sessions, pinned first and then most recently updated first
+    pub fn list_sessions(&self) -> Result<Vec<StoredSession>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, model, created_at, updated_at, pinned FROM sessions
+             ORDER BY pinned DESC, updated_at DESC, rowid DESC",
+        )?;
+        let sessions = stmt
+            .query_map([], session_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    /// Sessions in `list_sessions` order, each with the text of its most recent message.
+    /// System messages such as model switch separators are skipped.
+    pub fn list_sessions_with_last_message(&self) -> Result<Vec<(StoredSession, Option<String>)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.title, s.model, s.created_at, s.updated_at, s.pinned,
+                    (SELECT m.content FROM messages m
+                     WHERE m.session_id = s.id AND m.role != 'system'
+                     ORDER BY m.created_at DESC, m.seq DESC LIMIT 1)
+             FROM sessions s
+             ORDER BY s.pinned DESC, s.updated_at DESC, s.rowid DESC",
+        )?;
+        let sessions = stmt
+            .query_map([], |row| Ok((session_from_row(row)?, row.get(6)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    pub fn set_session_model(&self, session_id: &str, model: &str) -> Result<()> {
+        let updated = self.conn()?.execute(
+            "UPDATE sessions SET model = ?1 WHERE id = ?2",
+            params![model, session_id],
+        )?;
+        if updated == 0 {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        Ok(())
+    }
+
+    /// Remember the mode the session's replies run in
+    pub fn set_session_mode(&self, session_id: &str, mode: &GooseMode) -> Result<()> {
+        self.conn()?.execute(
+            "UPDATE sessions SET mode = ?1 WHERE id = ?2",
+            params![mode_to_str(mode), session_id],
+        )?;
+        Ok(())
+    }
+
+    /// The mode of the session's last reply; `None` before its first one
+    pub fn session_mode(&self, session_id: &str) -> Result<Option<GooseMode>> {
+        let mode = self
+            .conn()?
+            .query_row(
+                "SELECT mode FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(mode.as_deref().map(mode_from_str))
+    }
+
+    pub fn get_session(&self, session_id: &str) -> Result<Option<StoredSession>> {
+        let conn = self.conn()?;
+        let session = conn
+            .query_row(
+                "SELECT id, title, model, created_at, updated_at, pinned FROM sessions WHERE id = ?1",
+                params![session_id],
+                session_from_row,
+            )
+            .optional()?;
+        Ok(session)
+    }
+
+    /// Delete a session; its messages go with it
+    pub fn delete_session(&self, session_id: &str) -> Result<()> {
+        let conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM sessions WHERE id = ?1", params![session_id])?;
+        if deleted == 0 {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        Ok(())
+    }
+
+    /// Renaming doesn't touch `updated_at`, so the session keeps its place in the list
+    pub fn rename_session(&self, session_id: &str, title: &str) -> Result<()> {
+        let title = title.trim();
+        anyhow::ensure!(!title.is_empty(), "Session title can't be empty");
+        let updated = self.conn()?.execute(
+            "UPDATE sessions SET title = ?1 WHERE id = ?2",
+            params![title, session_id],
+        )?;
+        if updated == 0 {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        Ok(())
+    }
+
+    /// Pinning doesn't touch `updated_at`, so unpinning returns the session to its recency slot
+    pub fn set_session_pinned(&self, session_id: &str, pinned: bool) -> Result<()> {
+        let updated = self.conn()?.execute(
+            "UPDATE sessions SET pinned = ?1 WHERE id = ?2",
+            params![pinned, session_id],
+        )?;
+        if updated == 0 {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        Ok(())
+    }
+
+    pub fn append_message(
+        &self,
+        session_id: &str,
+        role: Role,
+        content: &str,
+    ) -> Result<StoredMessage> {
+        self.append_message_parts(
+            session_id,
+            role,
+            vec![MessageContent::Text {
+                text: content.to_string(),
+            }],
+        )
+    }
+
+    /// Store an assistant reply, with its full structured content, along with the model that
+    /// wrote it
+    pub fn append_reply(
+        &self,
+        session_id: &str,
+        parts: Vec<MessageContent>,
+        model: &str,
+    ) -> Result<StoredMessage> {
+        self.insert_message(session_id, Role::Assistant, parts, Some(model))
+    }
+
+    /// Store a message with its full structured content so it reloads exactly as it was sent
+    pub fn append_message_parts(
+        &self,
+        session_id: &str,
+        role: Role,
+        parts: Vec<MessageContent>,
+    ) -> Result<StoredMessage> {
+        self.insert_message(session_id, role, parts, None)
+    }
+
+    fn insert_message(
+        &self,
+        session_id: &str,
+        role: Role,
+        parts: Vec<MessageContent>,
+        model: Option<&str>,
+    ) -> Result<StoredMessage> {
+        let mut conn = self.conn()?;
+        // Taking the write lock up front keeps MAX(seq) + 1 strictly increasing across
+        // connections
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let now = Utc::now().timestamp();
+        let seq: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(seq), 0) + 1 FROM messages",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let message = StoredMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            role,
+            content: concat_text(&parts),
+            parts,
+            created_at: from_epoch(now),
+            seq,
+            model: model.map(str::to_string),
+        };
+
+        tx.execute(
+            "INSERT INTO messages (id, session_id, role, content, content_json, created_at, seq, model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                message.id,
+                message.session_id,
+                role_to_str(&message.role),
+                message.content,
+                serde_json::to_string(&message.parts)?,
+                now,
+                seq,
+                message.model
+            ],
+        )?;
+        tx.execute(
+            "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
+            params![now, session_id],
+        )?;
+        tx.commit()?;
+        Ok(message)
+    }
+
+    /// Fold the `sources` sessions into `target` in one transaction. Messages from all of them
+    /// are interleaved in chronological order, moved messages get fresh ids, and the sources
+    /// are deleted. The target keeps its title and model.
+    pub fn merge_sessions(&self, target: &str, sources: &[String]) -> Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        if sources.iter().any(|source| source == target) {
+            return Err(anyhow::anyhow!("Cannot merge session {} into itself", target));
+        }
+        let sessions: Vec<&str> = std::iter::once(target)
+            .chain(sources.iter().map(String::as_str))
+            .collect();
+        for session_id in &sessions {
+            let exists = tx
+                .prepare("SELECT 1 FROM sessions WHERE id = ?1")?
+                .exists(params![session_id])?;
+            if !exists {
+                return Err(anyhow::anyhow!("Session {} not found", session_id));
+            }
+        }
+
+        // Everything in combined order, renumbered so seq alone reproduces that order
+        let merged: Vec<(String, String)> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, session_id FROM messages WHERE session_id IN ({})
+                 ORDER BY created_at, seq",
+                vec!["?"; sessions.len()].join(", ")
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(&sessions), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let base_seq: i64 =
+            tx.query_row("SELECT COALESCE(MAX(seq), 0) FROM messages", [], |row| row.get(0))?;
+        for (offset, (id, session_id)) in merged.iter().enumerate() {
+            let new_id = if session_id == target {
+                id.clone()
+            } else {
+                uuid::Uuid::new_v4().to_string()
+            };
+            tx.execute(
+                "UPDATE messages SET id = ?1, session_id = ?2, seq = ?3 WHERE id = ?4",
+                params![new_id, target, base_seq + offset as i64 + 1, id],
+            )?;
+        }
+
+        for source in sources {
+            tx.execute("DELETE FROM sessions WHERE id = ?1", params![source])?;
+        }
+        tx.execute(
+            "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
+            params![Utc::now().timestamp(), target],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store a copy of `session` and its `messages` under a new session id, in one transaction.
+    /// Timestamps, roles, content and models are kept; a message keeps its id unless that id is
+    /// already taken, in which case it gets a fresh one.
+    pub fn import_session(
+        &self,
+        session: &StoredSession,
+        messages: &[StoredMessage],
+    ) -> Result<StoredSession> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let imported = StoredSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            ..session.clone()
+        };
+        tx.execute(
+            "INSERT INTO sessions (id, title, model, created_at, updated_at, pinned)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                imported.id,
+                imported.title,
+                imported.model,
+                imported.created_at.timestamp(),
+                imported.updated_at.timestamp(),
+                imported.pinned
+            ],
+        )?;
+
+        let mut ordered: Vec<&StoredMessage> = messages.iter().collect();
+        ordered.sort_by_key(|message| (message.created_at, message.seq));
+        let base_seq: i64 =
+            tx.query_row("SELECT COALESCE(MAX(seq), 0) FROM messages", [], |row| row.get(0))?;
+        for (offset, message) in ordered.into_iter().enumerate() {
+            let taken = tx
+                .prepare("SELECT 1 FROM messages WHERE id = ?1")?
+                .exists(params![message.id])?;
+            let id = if taken || message.id.is_empty() {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                message.id.clone()
+            };
+            tx.execute(
+                "INSERT INTO messages (id, session_id, role, content, content_json, created_at, seq, model)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    id,
+                    imported.id,
+                    role_to_str(&message.role),
+                    concat_text(&message.parts),
+                    serde_json::to_string(&message.parts)?,
+                    message.created_at.timestamp(),
+                    base_seq + offset as i64 + 1,
+                    message.model
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    /// Start a new session from `session_id`'s history up to and including `at_message_id`, in
+    /// one transaction. The copied messages keep their timestamps, roles, content and models but
+    /// get fresh ids; the original session is untouched.
+    pub fn fork_session(&self, session_id: &str, at_message_id: &str) -> Result<StoredSession> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let source = tx
+            .query_row(
+                "SELECT id, title, model, created_at, updated_at, pinned FROM sessions WHERE id = ?1",
+                params![session_id],
+                session_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow::anyhow!("Session {} not found", session_id))?;
+        let (created_at, seq): (i64, i64) = tx
+            .query_row(
+                "SELECT created_at, seq FROM messages WHERE id = ?1 AND session_id = ?2",
+                params![at_message_id, session_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Message {} not found in session {}",
+                    at_message_id,
+                    session_id
+                )
+            })?;
+
+        let now = Utc::now().timestamp();
+        let fork = StoredSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: format!("{} (branch)", source.title),
+            model: source.model,
+            created_at: from_epoch(now),
+            updated_at: from_epoch(now),
+            pinned: false,
+        };
+        tx.execute(
+            "INSERT INTO sessions (id, title, model, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![fork.id, fork.title, fork.model, now],
+        )?;
+
+        // Same ordering as load_messages: by time, then insertion order within a second
+        let copied: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM messages WHERE session_id = ?1
+                 AND (created_at < ?2 OR (created_at = ?2 AND seq <= ?3))
+                 ORDER BY created_at, seq",
+            )?;
+            let rows = stmt.query_map(params![session_id, created_at, seq], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        let base_seq: i64 =
+            tx.query_row("SELECT COALESCE(MAX(seq), 0) FROM messages", [], |row| row.get(0))?;
+        for (offset, id) in copied.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO messages (id, session_id, role, content, content_json, created_at, seq, model)
+                 SELECT ?1, ?2, role, content, content_json, created_at, ?3, model
+                 FROM messages WHERE id = ?4",
+                params![
+                    uuid::Uuid::new_v4().to_string(),
+                    fork.id,
+                    base_seq + offset as i64 + 1,
+                    id
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(fork)
+    }
+
+    /// Delete the messages that come after `message_id` in a session, or all of them when it's
+    /// `None`, returning how many were removed. Earlier messages are left as they are.
+    pub fn truncate_after(&self, session_id: &str, message_id: Option<&str>) -> Result<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let exists = tx
+            .prepare("SELECT 1 FROM sessions WHERE id = ?1")?
+            .exists(params![session_id])?;
+        if !exists {
+            return Err(anyhow::anyhow!("Session {} not found", session_id));
+        }
+        let deleted = match message_id {
+            Some(message_id) => {
+                let (created_at, seq): (i64, i64) = tx
+                    .query_row(
+                        "SELECT created_at, seq FROM messages WHERE id = ?1 AND session_id = ?2",
+                        params![message_id, session_id],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Message {} not found in session {}",
+                            message_id,
+                            session_id
+                        )
+                    })?;
+                // Same ordering as load_messages: by time, then insertion order within a second
+                tx.execute(
+                    "DELETE FROM messages WHERE session_id = ?1
+                     AND (created_at > ?2 OR (created_at = ?2 AND seq > ?3))",
+                    params![session_id, created_at, seq],
+                )?
+            }
+            None => tx.execute(
+                "DELETE FROM messages WHERE session_id = ?1",
+                params![session_id],
+            )?,
+        };
+        tx.execute(
+            "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
+            params![Utc::now().timestamp(), session_id],
+        )?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Messages of a session in conversation order
+    pub fn load_messages(&self, session_id: &str) -> Result<Vec<StoredMessage>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, role, content, created_at, seq, content_json, model FROM messages
+             WHERE session_id = ?1
+             ORDER BY created_at, seq",
+        )?;
+        let messages = stmt
+            .query_map(params![session_id], message_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(messages)
+    }
+
+    /// Messages across all sessions that contain every word of `query`, best matches first.
+    /// Words are matched literally, so FTS syntax in the query has no effect.
+    pub fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageSearchHit>> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT m.id, m.session_id, m.role, m.content, m.created_at, m.seq, m.content_json,
+                    m.model, s.id, s.title, s.model, s.created_at, s.updated_at, s.pinned,
+                    snippet(messages_fts, 0, '[', ']', '...', 12)
+             FROM messages_fts
+             JOIN messages m ON m.rowid = messages_fts.rowid
+             JOIN sessions s ON s.id = m.session_id
+             WHERE messages_fts MATCH ?1
+             ORDER BY rank, m.created_at DESC, m.seq DESC
+             LIMIT ?2",
+        )?;
+        let hits = stmt
+            .query_map(params![terms.join(" "), limit as i64], |row| {
+                Ok(MessageSearchHit {
+                    message: message_from_row(row)?,
+                    session: StoredSession {
+                        id: row.get(8)?,
+                        title: row.get(9)?,
+                        model: row.get(10)?,
+                        created_at: from_epoch(row.get(11)?),
+                        updated_at: from_epoch(row.get(12)?),
+                        pinned: row.get(13)?,
+                    },
+                    snippet: row.get(14)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(hits)
+    }
+
+    /// The `limit` most recent messages older than `before` (the newest when `None`), oldest
+    /// first, and the cursor for the page before them
+    pub fn load_messages_page(
+        &self,
+        session_id: &str,
+        limit: usize,
+        before: Option<MessageCursor>,
+    ) -> Result<MessagePage> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, role, content, created_at, seq, content_json, model FROM messages
+             WHERE session_id = ?1
+               AND (?2 IS NULL OR created_at < ?2 OR (created_at = ?2 AND seq < ?3))
+             ORDER BY created_at DESC, seq DESC
+             LIMIT ?4",
+        )?;
+        // One extra row tells whether an older page exists
+        let mut messages = stmt
+            .query_map(
+                params![
+                    session_id,
+                    before.map(|cursor| cursor.created_at),
+                    before.map(|cursor| cursor.seq),
+                    limit as i64 + 1
+                ],
+                message_from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let next_cursor = if messages.len() > limit {
+            messages.truncate(limit);
+            messages.last().map(|oldest| MessageCursor {
+                created_at: oldest.created_at.timestamp(),
+                seq: oldest.seq,
+            })
+        } else {
+            None
+        };
+        messages.reverse();
+        Ok(MessagePage {
+            messages,
+            next_cursor,
+        })
+    }
+}
+
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<StoredMessage> {
+    let content: String = row.get(3)?;
+    let parts = row
+        .get::<_, Option<String>>(6)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| {
+            vec![MessageContent::Text {
+                text: content.clone(),
+            }]
+        });
+    Ok(StoredMessage {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        role: role_from_str(&row.get::<_, String>(2)?),
+        content,
+        parts,
+        created_at: from_epoch(row.get(4)?),
+        seq: row.get(5)?,
+        model: row.get(7)?,
+    })
+}
+
+pub(crate) fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    Ok(conn
+        .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+        .exists(params![table, column])?)
+}
+
+fn session_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<StoredSession> {
+    Ok(StoredSession {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        model: row.get(2)?,
+        created_at: from_epoch(row.get(3)?),
+        updated_at: from_epoch(row.get(4)?),
+        pinned: row.get(5)?,
+    })
+}
+
+fn from_epoch(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0).single().unwrap_or_default()
+}
+
+pub(crate) fn role_to_str(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+        Role::Tool => "tool",
+    }
+}
+
+pub(crate) fn role_from_str(role: &str) -> Role {
+    match role {
+        "assistant" => Role::Assistant,
+        "system" => Role::System,
+        "tool" => Role::Tool,
+        _ => Role::User,
+    }
+}
+
+fn mode_to_str(mode: &GooseMode) -> &'static str {
+    match mode {
+        GooseMode::Chat => "chat",
+        GooseMode::Agent => "agent",
+        GooseMode::Auto => "auto",
+    }
+}
+
+fn mode_from_str(mode: &str) -> GooseMode {
+    match mode {
+        "chat" => GooseMode::Chat,
+        "auto" => GooseMode::Auto,
+        _ => GooseMode::Agent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Result<SessionStore> {
+        SessionStore::new(crate::db::memory_pool()?)
+    }
+
+    #[test]
+    fn test_messages_in_the_same_second_reload_in_insertion_order() -> Result<()> {
+        let store = store()?;
+        let session = store.create_session("Tool loop", None)?;
+
+        let contents: Vec<String> = (0..10).map(|i| format!("step {}", i)).collect();
+        for (i, content) in contents.iter().enumerate() {
+            let role = if i % 2 == 0 {
+                Role::Assistant
+            } else {
+                Role::Tool
+            };
+            store.append_message(&session.id, role, content)?;
+        }
+
+        let loaded = store.load_messages(&session.id)?;
+        let loaded: Vec<_> = loaded.iter().map(|m| m.content.clone()).collect();
+        assert_eq!(loaded, contents);
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_pages_walk_back_without_gaps_or_repeats() -> Result<()> {
+        let store = store()?;
+        let session = store.create_session("Long chat", None)?;
+        // Appended in the same second, so only `seq` orders them
+        for i in 0..5 {
+            store.append_message(&session.id, Role::User, &format!("message {}", i))?;
+        }
+
+        let mut pages = Vec::new();
+        let mut before = None;
+        loop {
+            let page = store.load_messages_page(&session.id, 2, before)?;
+            let contents: Vec<String> = page.messages.iter().map(|m| m.content.clone()).collect();
+            pages.push(contents);
+            before = page.next_cursor;
+            if before.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(
+            pages,
+            vec![
+                vec!["message 3", "message 4"],
+                vec!["message 1", "message 2"],
+                vec!["message 0"],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_finds_messages_across_sessions_and_follows_merges() -> Result<()> {
+        let store = store()?;
+        let rust = store.create_session("Rust", None)?;
+        let travel = store.create_session("Travel", None)?;
+        store.append_message(&rust.id, Role::User, "How do lifetimes work in Rust?")?;
+        store.append_message(&travel.id, Role::User, "Best time to visit Lisbon")?;
+        store.append_message(&travel.id, Role::Assistant, "Lisbon is lovely in spring")?;
+
+        let hits = store.search_messages("lisbon", 10)?;
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.session.id == travel.id));
+        assert!(hits[0].snippet.contains("[Lisbon]"));
+
+        // Every word must match, and FTS operators are treated as plain words
+        assert_eq!(store.search_messages("lisbon spring", 10)?.len(), 1);
+        assert!(store.search_messages("lifetimes OR lisbon", 10)?.is_empty());
+        assert!(store.search_messages("\"unbalanced", 10)?.is_empty());
+        assert!(store.search_messages("   ", 10)?.is_empty());
+
+        store.merge_sessions(&rust.id, std::slice::from_ref(&travel.id))?;
+        let hits = store.search_messages("lisbon", 1)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session.id, rust.id);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merged_sessions_interleave_in_chronological_order() -> Result<()> {
+        let store = store()?;
+        let target = store.create_session("Trip plans", Some("gpt-4"))?;
+        let flights = store.create_session("Flights", None)?;
+        let hotels = store.create_session("Hotels", None)?;
+
+        store.append_message(&target.id, Role::User, "where to?")?;
+        let moved = store.append_message(&flights.id, Role::User, "cheap flights")?;
+        store.append_message(&hotels.id, Role::User, "hotel near the beach")?;
+        store.append_message(&target.id, Role::Assistant, "Lisbon")?;
+        store.append_message(&flights.id, Role::Assistant, "TAP on Tuesday")?;
+
+        store.merge_sessions(&target.id, &[flights.id.clone(), hotels.id.clone()])?;
+
+        let merged = store.load_messages(&target.id)?;
+        let contents: Vec<&str> = merged.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["where to?", "cheap flights", "hotel near the beach", "Lisbon", "TAP on Tuesday"]
+        );
+        assert!(merged.iter().all(|m| m.id != moved.id));
+
+        let sessions = store.list_sessions()?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].model.as_deref(), Some("gpt-4"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deleting_a_session_deletes_its_messages() -> Result<()> {
+        let store = store()?;
+        let doomed = store.create_session("Doomed", None)?;
+        let kept = store.create_session("Kept", None)?;
+        store.append_message(&doomed.id, Role::User, "goodbye")?;
+        store.append_message(&doomed.id, Role::Assistant, "farewell")?;
+        store.append_message(&kept.id, Role::User, "still here")?;
+
+        store.delete_session(&doomed.id)?;
+        let orphaned: i64 = store.conn()?.query_row(
+            "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+            params![doomed.id],
+            |row| row.get(0),
+        )?;
+        assert_eq!(orphaned, 0);
+        assert_eq!(store.load_messages(&kept.id)?.len(), 1);
+        assert!(store.search_messages("farewell", 10)?.is_empty());
+        assert!(store.delete_session(&doomed.id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_existing_rows_are_backfilled_by_rowid() -> Result<()> {
+        let pool = crate::db::memory_pool()?;
+        pool.get()?.execute_batch(
+            "CREATE TABLE messages (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            INSERT INTO messages VALUES ('c', 's', 'user', 'first', 100);
+            INSERT INTO messages VALUES ('a', 's', 'assistant', 'second', 100);
+            INSERT INTO messages VALUES ('b', 's', 'user', 'third', 100);",
+        )?;
+
+        let store = SessionStore::new(pool)?;
+        let loaded: Vec<_> = store
+            .load_messages("s")?
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(loaded, vec!["first", "second", "third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_sessions_sort_ahead_of_recent_ones() -> Result<()> {
+        let store = store()?;
+        let old = store.create_session("Old but important", None)?;
+        store.create_session("Recent", None)?;
+        store.conn()?.execute(
+            "UPDATE sessions SET updated_at = updated_at - 3600 WHERE id = ?1",
+            params![old.id],
+        )?;
+
+        let titles = |store: &SessionStore| -> Result<Vec<String>> {
+            Ok(store
+                .list_sessions()?
+                .into_iter()
+                .map(|s| s.title)
+                .collect())
+        };
+        assert_eq!(titles(&store)?, vec!["Recent", "Old but important"]);
+
+        store.set_session_pinned(&old.id, true)?;
+        assert_eq!(titles(&store)?, vec!["Old but important", "Recent"]);
+        assert!(store.get_session(&old.id)?.unwrap().pinned);
+
+        store.set_session_pinned(&old.id, false)?;
+        assert_eq!(titles(&store)?, vec!["Recent", "Old but important"]);
+        assert!(store.set_session_pinned("missing", true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_structured_content_round_trips_exactly() -> Result<()> {
+        let store = store()?;
+        let session = store.create_session("Code", None)?;
+
+        let text = "Here's the fix:\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n  Indented trailing line\t\n";
+        let parts = vec![
+            MessageContent::Text {
+                text: text.to_string(),
+            },
+            MessageContent::ToolRequest {
+                id: "call_1".to_string(),
+                name: "shell".to_string(),
+                arguments: serde_json::json!({ "command": "cargo build" }),
+            },
+            MessageContent::Image {
+                url: "data:image/png;base64,AAAA".to_string(),
+                description: None,
+            },
+            MessageContent::File {
+                file_id: "file-1".to_string(),
+                name: "notes.pdf".to_string(),
+                mime_type: "application/pdf".to_string(),
+            },
+        ];
+        store.append_message_parts(&session.id, Role::Assistant, parts.clone())?;
+
+        let loaded = store.load_messages(&session.id)?;
+        assert_eq!(loaded[0].parts, parts);
+        assert_eq!(loaded[0].content, text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forking_copies_history_up_to_the_message_with_fresh_ids() -> Result<()> {
+        let store = store()?;
+        let session = store.create_session("Ideas", Some("gpt-4"))?;
+        let question = store.append_message(&session.id, Role::User, "first question")?;
+        let answer = store.append_reply(
+            &session.id,
+            vec![MessageContent::Text {
+                text: "first answer".to_string(),
+            }],
+            "gpt-4",
+        )?;
+        store.append_message(&session.id, Role::User, "second question")?;
+
+        let fork = store.fork_session(&session.id, &answer.id)?;
+        assert_eq!(fork.title, "Ideas (branch)");
+        assert_eq!(fork.model.as_deref(), Some("gpt-4"));
+
+        let copied = store.load_messages(&fork.id)?;
+        assert_eq!(copied.len(), 2);
+        assert!(copied
+            .iter()
+            .zip([&question, &answer])
+            .all(|(copy, original)| copy.id != original.id
+                && copy.content == original.content
+                && copy.parts == original.parts
+                && copy.model == original.model
+                && copy.created_at == original.created_at));
+        assert_eq!(store.load_messages(&session.id)?.len(), 3);
+
+        // The cut point has to belong to the session being forked
+        assert!(store.fork_session(&fork.id, &answer.id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_untitled_sessions_are_backfilled_and_can_be_renamed() -> Result<()> {
+        let store = store()?;
+        let untitled = store.create_session("", None)?;
+        store.append_message(&untitled.id, Role::Assistant, "Welcome!")?;
+        store.append_message(
+            &untitled.id,
+            Role::User,
+            "Plan a weekend in Lisbon\nwith kids",
+        )?;
+        let titled = store.create_session("Kept", None)?;
+        store.append_message(&titled.id, Role::User, "Something else")?;
+
+        // Reopening runs the backfill
+        let store = SessionStore::new(store.pool.clone())?;
+        let title = |id: &str| -> Result<String> { Ok(store.get_session(id)?.unwrap().title) };
+        assert_eq!(title(&untitled.id)?, "Plan a weekend in Lisbon");
+        assert_eq!(title(&titled.id)?, "Kept");
+
+        store.rename_session(&untitled.id, "  Lisbon trip ")?;
+        assert_eq!(title(&untitled.id)?, "Lisbon trip");
+        assert!(store.rename_session(&untitled.id, " ").is_err());
+        assert!(store.rename_session("missing", "Title").is_err());
+
+        assert_eq!(
+            title_snippet("How do I keep a long first message from becoming an unreadable title?"),
+            "How do I keep a long first message from becoming..."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncating_keeps_earlier_messages_and_other_sessions() -> Result<()> {
+        let store = store()?;
+        let session = store.create_session("Edits", None)?;
+        let other = store.create_session("Other", None)?;
+        let question = store.append_message(&session.id, Role::User, "first question")?;
+        let answer = store.append_message(&session.id, Role::Assistant, "first answer")?;
+        store.append_message(&session.id, Role::User, "second question")?;
+        store.append_message(&session.id, Role::Assistant, "second answer")?;
+        store.append_message(&other.id, Role::User, "untouched")?;
+
+        assert_eq!(store.truncate_after(&session.id, Some(&answer.id))?, 2);
+        let kept: Vec<String> = store
+            .load_messages(&session.id)?
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(kept, vec![question.id.clone(), answer.id]);
+        assert!(store.search_messages("second", 10)?.is_empty());
+
+        // A message from another session doesn't count as a cut point
+        assert!(store.truncate_after(&other.id, Some(&question.id)).is_err());
+        assert_eq!(store.truncate_after(&session.id, None)?, 2);
+        assert!(store.load_messages(&session.id)?.is_empty());
+        assert_eq!(store.load_messages(&other.id)?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+// Long-lived services shared by every server function instead of being rebuilt per request
+use anyhow::Result;
+use std::future::Future;
+use tokio::sync::OnceCell;
+
+use crate::chat_service_simple::SimpleChatService;
+use crate::mcp::McpToolExecutor;
+use crate::rig_agent_service::RigAgentService;
+
+/// A service built on first use and reused for the life of the process. Concurrent first
+/// callers wait for a single construction; if it fails, the next caller tries again.
+pub struct SharedService<T> {
+    cell: OnceCell<T>,
+}
+
+impl<T> SharedService<T> {
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceCell::const_new(),
+        }
+    }
+
+    pub async fn get_or_init<F, Fut>(&self, init: F) -> Result<&T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.cell.get_or_try_init(init).await
+    }
+}
+
+impl<T> Default for SharedService<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static CHAT_SERVICE: SharedService<SimpleChatService> = SharedService::new();
+static RIG_AGENT_SERVICE: SharedService<RigAgentService> = SharedService::new();
+static MCP_EXECUTOR: SharedService<tokio::sync::Mutex<McpToolExecutor>> = SharedService::new();
+
+pub(crate) async fn chat_service() -> Result<&'static SimpleChatService> {
+    CHAT_SERVICE
+        .get_or_init(|| async {
+            let mut service = SimpleChatService::new()?;
+            service.initialize().await?;
+            Ok(service)
+        })
+        .await
+}
+
+pub(crate) async fn rig_agent_service() -> Result<&'static RigAgentService> {
+    RIG_AGENT_SERVICE
+        .get_or_init(|| async { RigAgentService::new() })
+        .await
+}
+
+/// The MCP servers started for this process; empty until one is added
+pub(crate) async fn mcp_executor() -> Result<&'static tokio::sync::Mutex<McpToolExecutor>> {
+    MCP_EXECUTOR
+        .get_or_init(|| async { Ok(tokio::sync::Mutex::new(McpToolExecutor::new())) })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_service_is_constructed_once_across_concurrent_requests() -> Result<()> {
+        let shared = SharedService::new();
+        let constructed = AtomicUsize::new(0);
+
+        let requests = (0..16).map(|_| {
+            shared.get_or_init(|| async {
+                constructed.fetch_add(1, Ordering::Relaxed);
+                // Widen the window for racing first callers
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(String::from("service"))
+            })
+        });
+        let services = futures::future::try_join_all(requests).await?;
+
+        assert_eq!(constructed.load(Ordering::Relaxed), 1);
+        assert!(services
+            .iter()
+            .all(|service| std::ptr::eq(*service, services[0])));
+        Ok(())
+    }
+}
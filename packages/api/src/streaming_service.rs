@@ -2,13 +2,15 @@
 use anyhow::Result;
 use chrono::Utc;
 use futures::{Stream, StreamExt};
-use serde_json::json;
+use once_cell::sync::Lazy;
 use std::pin::Pin;
+use std::sync::RwLock;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{sleep, sleep_until, Instant};
 
 use crate::chat_service_simple::{
-    ChatMessage, ChatRequest, ChatResponse, Role, StreamChunk, TokenUsage,
+    ChatRequest, StreamChunk, SystemNotification, SystemNotificationType, TokenUsage, ToolCall,
+    ToolResult,
 };
 use crate::rig_agent_service::RigAgentService;
 
@@ -32,6 +34,50 @@ impl Default for StreamingConfig {
     }
 }
 
+/// How `buffered` coalesces streamed text before sending it on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamBuffer {
+    /// Characters collected before a combined chunk goes out
+    pub size: usize,
+    /// Longest a partial buffer is held before it goes out anyway
+    pub flush_interval: Duration,
+}
+
+impl StreamBuffer {
+    pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Buffer from `PerformanceSettings.streaming_buffer_size`
+    pub fn from_buffer_size(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            flush_interval: Self::DEFAULT_FLUSH_INTERVAL,
+        }
+    }
+}
+
+impl Default for StreamBuffer {
+    fn default() -> Self {
+        Self::from_buffer_size(8192)
+    }
+}
+
+static STREAM_BUFFER: Lazy<RwLock<StreamBuffer>> =
+    Lazy::new(|| RwLock::new(StreamBuffer::default()));
+
+/// Buffering currently applied to streamed replies
+pub fn stream_buffer() -> StreamBuffer {
+    *STREAM_BUFFER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Change the buffering of streamed replies. Streams already running keep theirs.
+pub fn set_stream_buffer(buffer: StreamBuffer) {
+    *STREAM_BUFFER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = buffer;
+}
+
 /// Enhanced streaming chunk with more metadata
 #[derive(Debug, Clone)]
 pub struct EnhancedStreamChunk {
@@ -58,6 +104,164 @@ pub struct StreamMetadata {
     pub agent_mode: String,
 }
 
+/// How far a tool call got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolEventStatus {
+    /// Called, no result yet
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// One tool call and its outcome, as a client renders it in a tool card
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolEvent {
+    pub tool_call_id: String,
+    pub tool_name: String,
+    pub arguments: serde_json::Value,
+    /// The tool's output, or its error message when it failed
+    pub result: Option<serde_json::Value>,
+    pub status: ToolEventStatus,
+}
+
+/// Pair the `ToolCall` and `ToolResult` chunks of a stream into one event per call, in call
+/// order. Tool chunks carry the serialized `ToolCall` or `ToolResult` as their content.
+pub fn tool_events<'a>(
+    chunks: impl IntoIterator<Item = &'a EnhancedStreamChunk>,
+) -> Vec<ToolEvent> {
+    let mut events: Vec<ToolEvent> = Vec::new();
+    for chunk in chunks {
+        let Some(content) = chunk.base.content.as_deref() else {
+            continue;
+        };
+        match chunk.chunk_type {
+            ChunkType::ToolCall => {
+                if let Ok(call) = serde_json::from_str::<ToolCall>(content) {
+                    events.push(ToolEvent {
+                        tool_call_id: call.id,
+                        tool_name: call.name,
+                        arguments: call.arguments,
+                        result: None,
+                        status: ToolEventStatus::Running,
+                    });
+                }
+            }
+            ChunkType::ToolResult => {
+                let Ok(result) = serde_json::from_str::<ToolResult>(content) else {
+                    continue;
+                };
+                let Some(event) = events
+                    .iter_mut()
+                    .find(|event| event.tool_call_id == result.tool_call_id)
+                else {
+                    continue;
+                };
+                match result.error {
+                    Some(error) => {
+                        event.result = Some(serde_json::Value::String(error));
+                        event.status = ToolEventStatus::Failed;
+                    }
+                    None => {
+                        event.result = Some(result.result);
+                        event.status = ToolEventStatus::Succeeded;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// One step of an agent reply as it happens. Serialized as a JSON object tagged by `type`
+/// (`thinking`, `content`, `tool_call`, `tool_confirmation_request`, `tool_result`,
+/// `system_notification`, `done`), one event per stream frame, e.g.
+/// `{"type":"content","text":"Hello"}`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    Thinking {
+        text: String,
+    },
+    Content {
+        text: String,
+    },
+    ToolCall {
+        tool_call: ToolCall,
+    },
+    /// The agent is paused until `confirm_tool` approves or declines this call
+    ToolConfirmationRequest {
+        tool_call: ToolCall,
+    },
+    ToolResult {
+        tool_result: ToolResult,
+    },
+    SystemNotification {
+        notification: SystemNotification,
+    },
+    /// Always the last event, carrying the turn's token usage and finish reason
+    Done {
+        model: String,
+        token_usage: Option<TokenUsage>,
+        finish_reason: Option<String>,
+    },
+}
+
+/// Turn enhanced chunks into agent events as they arrive, ending with `AgentEvent::Done`.
+/// Error chunks become error notifications; metadata chunks are left out.
+pub fn agent_events<S>(chunks: S, model: String) -> Pin<Box<dyn Stream<Item = AgentEvent> + Send>>
+where
+    S: Stream<Item = EnhancedStreamChunk> + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut chunks = Box::pin(chunks);
+        let mut token_usage = None;
+        let mut finish_reason = None;
+
+        while let Some(chunk) = chunks.next().await {
+            let base = chunk.base;
+            if base.token_usage.is_some() {
+                token_usage = base.token_usage;
+            }
+            if base.finish_reason.is_some() {
+                finish_reason = base.finish_reason;
+            }
+            let Some(text) = base.content.filter(|content| !content.is_empty()) else {
+                continue;
+            };
+            let event = match chunk.chunk_type {
+                ChunkType::Thinking => AgentEvent::Thinking { text },
+                ChunkType::Content => AgentEvent::Content { text },
+                // Tool chunks carry the serialized call or result
+                ChunkType::ToolCall => match serde_json::from_str(&text) {
+                    Ok(tool_call) => AgentEvent::ToolCall { tool_call },
+                    Err(_) => continue,
+                },
+                ChunkType::ToolResult => match serde_json::from_str(&text) {
+                    Ok(tool_result) => AgentEvent::ToolResult { tool_result },
+                    Err(_) => continue,
+                },
+                ChunkType::Error => AgentEvent::SystemNotification {
+                    notification: SystemNotification {
+                        notification_type: SystemNotificationType::ErrorMessage,
+                        message: text,
+                        code: None,
+                    },
+                },
+                ChunkType::Metadata => continue,
+            };
+            yield event;
+        }
+
+        yield AgentEvent::Done {
+            model,
+            token_usage,
+            finish_reason,
+        };
+    })
+}
+
 /// Streaming Agent Service
 pub struct StreamingAgentService {
     agent_service: RigAgentService,
@@ -99,6 +303,8 @@ impl StreamingAgentService {
                             model: model_id,
                             finish_reason: Some("error".to_string()),
                             is_complete: true,
+                            thinking: None,
+                            tool_calls: None,
                         },
                         chunk_type: ChunkType::Error,
                         metadata: StreamMetadata {
@@ -120,14 +326,29 @@ impl StreamingAgentService {
             .unwrap_or_default();
 
         let thinking_content = full_response.thinking_content.clone();
+        let tool_calls = full_response
+            .tool_calls
+            .clone()
+            .or_else(|| {
+                full_response
+                    .message
+                    .as_ref()
+                    .and_then(|msg| msg.tool_calls.clone())
+            })
+            .unwrap_or_default();
+        let tool_results = full_response
+            .message
+            .as_ref()
+            .and_then(|msg| msg.tool_results.clone())
+            .unwrap_or_default();
 
         // Create streaming chunks
         let chunks = self
             .create_enhanced_chunks(
                 content,
                 thinking_content,
-                agent_name,
-                agent_mode,
+                (&tool_calls, &tool_results),
+                (agent_name, agent_mode),
                 model_id,
                 full_response.token_usage,
             )
@@ -233,6 +454,8 @@ impl StreamingAgentService {
                 model: request.model.clone(),
                 finish_reason: None,
                 is_complete: false,
+                thinking: None,
+                tool_calls: None,
             },
             chunk_type: ChunkType::Metadata,
             metadata: StreamMetadata {
@@ -244,7 +467,7 @@ impl StreamingAgentService {
         });
 
         // If tools are available, add tool information
-        if request.tools.as_ref().map_or(false, |t| !t.is_empty()) {
+        if request.tools.as_ref().is_some_and(|t| !t.is_empty()) {
             let tool_names: Vec<String> = request
                 .tools
                 .as_ref()
@@ -261,6 +484,8 @@ impl StreamingAgentService {
                     model: request.model.clone(),
                     finish_reason: None,
                     is_complete: false,
+                    thinking: None,
+                    tool_calls: None,
                 },
                 chunk_type: ChunkType::Metadata,
                 metadata: StreamMetadata {
@@ -285,8 +510,8 @@ impl StreamingAgentService {
         &self,
         content: String,
         thinking_content: Option<String>,
-        agent_name: String,
-        agent_mode: String,
+        (tool_calls, tool_results): (&[ToolCall], &[ToolResult]),
+        (agent_name, agent_mode): (String, String),
         model_id: String,
         token_usage: Option<TokenUsage>,
     ) -> Vec<EnhancedStreamChunk> {
@@ -299,8 +524,7 @@ impl StreamingAgentService {
                 let thinking_words: Vec<String> =
                     thinking.split_whitespace().map(|s| s.to_string()).collect();
 
-                for (i, word) in thinking_words.iter().enumerate() {
-                    let is_complete_thinking = i == thinking_words.len() - 1;
+                for word in &thinking_words {
                     chunks.push(EnhancedStreamChunk {
                         base: StreamChunk {
                             content: Some(format!("{} ", word)),
@@ -309,6 +533,8 @@ impl StreamingAgentService {
                             model: model_id.clone(),
                             finish_reason: None,
                             is_complete: false,
+                            thinking: None,
+                            tool_calls: None,
                         },
                         chunk_type: ChunkType::Thinking,
                         metadata: StreamMetadata {
@@ -329,6 +555,8 @@ impl StreamingAgentService {
                         model: model_id.clone(),
                         finish_reason: None,
                         is_complete: false,
+                        thinking: None,
+                        tool_calls: None,
                     },
                     chunk_type: ChunkType::Metadata,
                     metadata: StreamMetadata {
@@ -341,6 +569,40 @@ impl StreamingAgentService {
             }
         }
 
+        // Each tool call, followed by its result once there is one
+        if self.config.enable_tool_call_stream {
+            for call in tool_calls {
+                let mut tool_chunks = vec![(ChunkType::ToolCall, serde_json::to_string(call))];
+                if let Some(result) = tool_results
+                    .iter()
+                    .find(|result| result.tool_call_id == call.id)
+                {
+                    tool_chunks.push((ChunkType::ToolResult, serde_json::to_string(result)));
+                }
+                for (chunk_type, content) in tool_chunks {
+                    chunks.push(EnhancedStreamChunk {
+                        base: StreamChunk {
+                            content: content.ok(),
+                            delta: None,
+                            token_usage: None,
+                            model: model_id.clone(),
+                            finish_reason: None,
+                            is_complete: false,
+                            thinking: None,
+                            tool_calls: None,
+                        },
+                        chunk_type,
+                        metadata: StreamMetadata {
+                            agent_name: agent_name.clone(),
+                            iteration,
+                            timestamp: Utc::now(),
+                            agent_mode: agent_mode.clone(),
+                        },
+                    });
+                }
+            }
+        }
+
         // Split content into chunks for streaming
         let words: Vec<String> = content.split_whitespace().map(|s| s.to_string()).collect();
         let words_len = words.len();
@@ -365,6 +627,8 @@ impl StreamingAgentService {
                         None
                     },
                     is_complete,
+                    thinking: None,
+                    tool_calls: None,
                 },
                 chunk_type: ChunkType::Content,
                 metadata: StreamMetadata {
@@ -421,8 +685,8 @@ impl StreamingAgentService {
             .create_enhanced_chunks(
                 "Response will be streamed with delay".to_string(),
                 None,
-                self.get_agent_name(&request),
-                self.get_agent_mode(&request),
+                (&[], &[]),
+                (self.get_agent_name(&request), self.get_agent_mode(&request)),
                 request.model.clone(),
                 None,
             )
@@ -443,3 +707,388 @@ impl Clone for StreamingAgentService {
     }
 }
 
+/// Reduce enhanced chunks to what a client renders: each chunk's content as soon as it
+/// arrives, then one terminal chunk carrying the turn's token usage and finish reason.
+/// Thinking goes out in `thinking` rather than `content`; tool and metadata chunks are left
+/// out. Dropping the returned stream stops pulling from `chunks`.
+pub fn content_stream<S>(
+    chunks: S,
+    model: String,
+) -> Pin<Box<dyn Stream<Item = StreamChunk> + Send>>
+where
+    S: Stream<Item = EnhancedStreamChunk> + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut chunks = Box::pin(chunks);
+        let mut token_usage = None;
+        let mut finish_reason = None;
+
+        while let Some(chunk) = chunks.next().await {
+            let base = chunk.base;
+            if base.token_usage.is_some() {
+                token_usage = base.token_usage;
+            }
+            if base.finish_reason.is_some() {
+                finish_reason = base.finish_reason;
+            }
+            let Some(text) = base.content.filter(|content| !content.is_empty()) else {
+                continue;
+            };
+            match chunk.chunk_type {
+                ChunkType::Thinking => {
+                    yield StreamChunk {
+                        content: None,
+                        delta: None,
+                        token_usage: None,
+                        model: base.model,
+                        finish_reason: None,
+                        is_complete: false,
+                        thinking: Some(text),
+                        tool_calls: None,
+                    };
+                }
+                ChunkType::Content | ChunkType::Error => {
+                    yield StreamChunk {
+                        delta: Some(text.clone()),
+                        content: Some(text),
+                        token_usage: None,
+                        model: base.model,
+                        finish_reason: None,
+                        is_complete: false,
+                        thinking: None,
+                        tool_calls: None,
+                    };
+                }
+                ChunkType::ToolCall | ChunkType::ToolResult | ChunkType::Metadata => {}
+            }
+        }
+
+        yield StreamChunk {
+            content: None,
+            delta: None,
+            token_usage,
+            model,
+            finish_reason,
+            is_complete: true,
+            thinking: None,
+            tool_calls: None,
+        };
+    })
+}
+
+/// Add `chunk`'s text to the one being held
+fn append_chunk(held: &mut StreamChunk, chunk: StreamChunk) {
+    for (into, text) in [
+        (&mut held.content, chunk.content),
+        (&mut held.delta, chunk.delta),
+        (&mut held.thinking, chunk.thinking),
+    ] {
+        if let Some(text) = text {
+            into.get_or_insert_with(String::new).push_str(&text);
+        }
+    }
+}
+
+/// Coalesce consecutive content chunks, and consecutive thinking chunks, so token-by-token
+/// models don't re-render the reply for every token. Text is held until `buffer.size`
+/// characters have collected or `buffer.flush_interval` has passed since the first of them;
+/// whatever is held goes out before the terminal chunk and when the stream ends.
+pub fn buffered<S>(
+    chunks: S,
+    buffer: StreamBuffer,
+) -> Pin<Box<dyn Stream<Item = StreamChunk> + Send>>
+where
+    S: Stream<Item = StreamChunk> + Send + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let mut chunks = Box::pin(chunks);
+        let mut held: Option<StreamChunk> = None;
+        let mut held_chars = 0;
+        let mut deadline = Instant::now();
+
+        loop {
+            let next = if held.is_some() {
+                tokio::select! {
+                    next = chunks.next() => Some(next),
+                    _ = sleep_until(deadline) => None,
+                }
+            } else {
+                Some(chunks.next().await)
+            };
+            let Some(next) = next else {
+                // Nothing new within the interval: send what there is
+                if let Some(chunk) = held.take() {
+                    yield chunk;
+                }
+                continue;
+            };
+            let Some(chunk) = next else {
+                break;
+            };
+
+            let chars = chunk
+                .content
+                .as_deref()
+                .or(chunk.thinking.as_deref())
+                .map_or(0, |text| text.chars().count());
+            let text_only = !chunk.is_complete
+                && chunk.token_usage.is_none()
+                && chunk.finish_reason.is_none()
+                && chars > 0;
+            match held.as_mut() {
+                Some(pending)
+                    if text_only && pending.thinking.is_some() == chunk.thinking.is_some() =>
+                {
+                    append_chunk(pending, chunk);
+                    held_chars += chars;
+                }
+                _ => {
+                    if let Some(pending) = held.take() {
+                        yield pending;
+                    }
+                    if !text_only {
+                        yield chunk;
+                        continue;
+                    }
+                    held = Some(chunk);
+                    held_chars = chars;
+                    deadline = Instant::now() + buffer.flush_interval;
+                }
+            }
+            if held_chars >= buffer.size {
+                if let Some(pending) = held.take() {
+                    yield pending;
+                }
+            }
+        }
+
+        if let Some(chunk) = held.take() {
+            yield chunk;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chunk(
+        content: &str,
+        usage: Option<TokenUsage>,
+        finish: Option<&str>,
+    ) -> EnhancedStreamChunk {
+        EnhancedStreamChunk {
+            base: StreamChunk {
+                content: Some(content.to_string()),
+                delta: Some(content.to_string()),
+                token_usage: usage,
+                model: "mock-local".to_string(),
+                finish_reason: finish.map(str::to_string),
+                is_complete: finish.is_some(),
+                thinking: None,
+                tool_calls: None,
+            },
+            chunk_type: ChunkType::Content,
+            metadata: StreamMetadata {
+                agent_name: "Assistant".to_string(),
+                iteration: 0,
+                timestamp: Utc::now(),
+                agent_mode: "chat".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_is_forwarded_per_chunk_with_a_terminal_usage_chunk() {
+        let usage = TokenUsage {
+            prompt_tokens: 3,
+            completion_tokens: 2,
+            total_tokens: 5,
+        };
+        let chunks = futures::stream::iter(vec![
+            chunk("Hello ", None, None),
+            chunk("world ", Some(usage.clone()), Some("stop")),
+        ]);
+
+        let out: Vec<StreamChunk> = content_stream(chunks, "mock-local".to_string())
+            .collect()
+            .await;
+        let contents: Vec<_> = out.iter().filter_map(|c| c.content.as_deref()).collect();
+        assert_eq!(contents, vec!["Hello ", "world "]);
+        assert!(out[..2]
+            .iter()
+            .all(|c| !c.is_complete && c.token_usage.is_none()));
+
+        let last = out.last().unwrap();
+        assert!(last.is_complete);
+        assert_eq!(last.token_usage, Some(usage));
+        assert_eq!(last.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_agent_events_are_tagged_per_frame_and_end_with_done() {
+        let typed = |chunk_type: ChunkType, content: &str| EnhancedStreamChunk {
+            chunk_type,
+            ..chunk(content, None, None)
+        };
+        let call = ToolCall {
+            id: "call-1".to_string(),
+            name: "weather".to_string(),
+            arguments: json!({ "city": "Oslo" }),
+        };
+        let chunks = futures::stream::iter(vec![
+            typed(ChunkType::Metadata, "Starting agent"),
+            typed(ChunkType::Thinking, "Checking"),
+            typed(ChunkType::ToolCall, &serde_json::to_string(&call).unwrap()),
+            typed(ChunkType::Error, "Tool failed"),
+            chunk("Sunny", None, Some("stop")),
+        ]);
+
+        let events: Vec<AgentEvent> = agent_events(chunks, "mock-local".to_string())
+            .collect()
+            .await;
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[1], AgentEvent::ToolCall { tool_call: call });
+        assert!(matches!(events[2], AgentEvent::SystemNotification { .. }));
+        assert_eq!(
+            serde_json::to_value(&events[3]).unwrap(),
+            json!({ "type": "content", "text": "Sunny" })
+        );
+        assert_eq!(
+            events[4],
+            AgentEvent::Done {
+                model: "mock-local".to_string(),
+                token_usage: None,
+                finish_reason: Some("stop".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_thinking_is_streamed_apart_from_content() {
+        let typed = |chunk_type: ChunkType, content: &str| EnhancedStreamChunk {
+            chunk_type,
+            ..chunk(content, None, None)
+        };
+        let chunks = futures::stream::iter(vec![
+            typed(ChunkType::Thinking, "Considering "),
+            typed(ChunkType::Metadata, "\n\n--- Response ---\n\n"),
+            chunk("Answer ", None, Some("stop")),
+        ]);
+
+        let out: Vec<StreamChunk> = content_stream(chunks, "mock-local".to_string())
+            .collect()
+            .await;
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].thinking.as_deref(), Some("Considering "));
+        assert_eq!(out[0].content, None);
+        assert_eq!(out[1].content.as_deref(), Some("Answer "));
+        assert_eq!(out[1].thinking, None);
+        assert!(out[2].is_complete);
+    }
+
+    #[test]
+    fn test_tool_chunks_pair_into_events_by_call_id() {
+        let tool_chunk = |chunk_type: ChunkType, content: serde_json::Value| EnhancedStreamChunk {
+            chunk_type,
+            ..chunk(&content.to_string(), None, None)
+        };
+        let chunks = vec![
+            tool_chunk(
+                ChunkType::ToolCall,
+                json!({ "id": "call-1", "name": "read_file", "arguments": { "path": "a.txt" } }),
+            ),
+            tool_chunk(
+                ChunkType::ToolCall,
+                json!({ "id": "call-2", "name": "shell", "arguments": { "command": "ls" } }),
+            ),
+            chunk("Reading ", None, None),
+            tool_chunk(
+                ChunkType::ToolResult,
+                json!({ "tool_call_id": "call-1", "result": "hello", "error": null }),
+            ),
+            tool_chunk(
+                ChunkType::ToolCall,
+                json!({ "id": "call-3", "name": "web_search", "arguments": {} }),
+            ),
+            tool_chunk(
+                ChunkType::ToolResult,
+                json!({ "tool_call_id": "call-2", "result": null, "error": "denied" }),
+            ),
+        ];
+
+        let events = tool_events(&chunks);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| (event.tool_name.as_str(), event.status, event.result.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "read_file",
+                    ToolEventStatus::Succeeded,
+                    Some(json!("hello"))
+                ),
+                ("shell", ToolEventStatus::Failed, Some(json!("denied"))),
+                ("web_search", ToolEventStatus::Running, None),
+            ]
+        );
+        assert_eq!(events[0].arguments, json!({ "path": "a.txt" }));
+    }
+
+    #[tokio::test]
+    async fn test_buffered_coalesces_text_and_flushes_the_rest() {
+        let text = |content: &str, thinking: bool| StreamChunk {
+            content: (!thinking).then(|| content.to_string()),
+            delta: (!thinking).then(|| content.to_string()),
+            thinking: thinking.then(|| content.to_string()),
+            ..chunk("", None, None).base
+        };
+        let done = StreamChunk {
+            content: None,
+            delta: None,
+            is_complete: true,
+            finish_reason: Some("stop".to_string()),
+            ..chunk("", None, None).base
+        };
+        let chunks = futures::stream::iter(vec![
+            text("Let ", true),
+            text("me see", true),
+            text("Hello ", false),
+            text("world, ", false),
+            text("again", false),
+            done,
+        ]);
+        let buffer = StreamBuffer {
+            size: 10,
+            flush_interval: Duration::from_secs(60),
+        };
+
+        let out: Vec<StreamChunk> = buffered(chunks, buffer).collect().await;
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0].thinking.as_deref(), Some("Let me see"));
+        assert_eq!(out[1].content.as_deref(), Some("Hello world, "));
+        assert_eq!(out[1].delta.as_deref(), Some("Hello world, "));
+        // The partial buffer goes out ahead of the terminal chunk
+        assert_eq!(out[2].content.as_deref(), Some("again"));
+        assert!(out[3].is_complete);
+
+        // A quiet stream doesn't hold text back past the flush interval
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let buffer = StreamBuffer {
+            size: 1_000,
+            flush_interval: Duration::from_millis(20),
+        };
+        let mut out = buffered(rx, buffer);
+        tx.unbounded_send(text("Hi", false)).unwrap();
+        let first = tokio::time::timeout(Duration::from_secs(5), out.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.content.as_deref(), Some("Hi"));
+        drop(tx);
+        assert!(out.next().await.is_none());
+    }
+}
@@ -0,0 +1,146 @@
+// Token counting per model family, for context-window and session-ceiling estimates
+use once_cell::sync::Lazy;
+use tiktoken_rs::CoreBPE;
+
+use crate::chat_service_simple::{ChatMessage, ModelConfig};
+
+/// Tokens each chat message adds on top of its content for the role and separators
+pub const TOKENS_PER_MESSAGE: u64 = 4;
+
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// About four bytes per token. Only used when no encoding is known for the model.
+pub struct CharHeuristic;
+
+impl Tokenizer for CharHeuristic {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.len().div_ceil(4)
+    }
+}
+
+struct Bpe(CoreBPE);
+
+impl Tokenizer for Bpe {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.0.encode_with_special_tokens(text).len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// GPT-4o and the o-series
+    O200kBase,
+    /// GPT-4 and GPT-3.5; also the closest public match for other hosted families
+    Cl100kBase,
+}
+
+// Loading an encoding parses its whole vocabulary, so each is built once on first use
+static O200K_BASE: Lazy<Option<Bpe>> = Lazy::new(|| tiktoken_rs::o200k_base().ok().map(Bpe));
+static CL100K_BASE: Lazy<Option<Bpe>> = Lazy::new(|| tiktoken_rs::cl100k_base().ok().map(Bpe));
+
+/// The encoding for a model, from its provider and API id. OpenRouter ids carry the vendor,
+/// e.g. `openai/gpt-4o`. Local and unknown models have none.
+pub fn encoding_for(provider: &str, model: &str) -> Option<Encoding> {
+    let (provider, model) = match (provider, model.split_once('/')) {
+        ("openrouter", Some((vendor, model))) => (vendor, model),
+        _ => (provider, model),
+    };
+
+    match provider {
+        "openai" => {
+            let o200k = ["gpt-4o", "gpt-4.1", "o1", "o3", "o4"];
+            if o200k.iter().any(|prefix| model.starts_with(prefix)) {
+                Some(Encoding::O200kBase)
+            } else {
+                Some(Encoding::Cl100kBase)
+            }
+        }
+        "anthropic" | "deepseek" | "google" => Some(Encoding::Cl100kBase),
+        _ => None,
+    }
+}
+
+pub fn tokenizer_for(model: &ModelConfig) -> &'static dyn Tokenizer {
+    let loaded = match encoding_for(&model.provider, &model.model) {
+        Some(Encoding::O200kBase) => O200K_BASE.as_ref(),
+        Some(Encoding::Cl100kBase) => CL100K_BASE.as_ref(),
+        None => None,
+    };
+    match loaded {
+        Some(bpe) => bpe,
+        None => &CharHeuristic,
+    }
+}
+
+/// Tokens `messages` take up in the model's context window. Without a model this falls back
+/// to the character heuristic.
+pub fn estimate_token_usage(messages: &[ChatMessage], model: Option<&ModelConfig>) -> u64 {
+    let tokenizer: &dyn Tokenizer = match model {
+        Some(model) => tokenizer_for(model),
+        None => &CharHeuristic,
+    };
+    messages
+        .iter()
+        .map(|msg| tokenizer.count_tokens(&msg.content) as u64 + TOKENS_PER_MESSAGE)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_service_simple::Role;
+
+    fn model(provider: &str, model: &str) -> ModelConfig {
+        ModelConfig {
+            id: model.to_string(),
+            model: model.to_string(),
+            name: model.to_string(),
+            provider: provider.to_string(),
+            description: None,
+            context_limit: None,
+            supports_tools: false,
+            supports_streaming: false,
+            supports_vision: false,
+            supports_function_calling: false,
+            pricing: None,
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_tokens_are_counted_with_the_model_familys_encoding() {
+        assert_eq!(encoding_for("openai", "gpt-4o"), Some(Encoding::O200kBase));
+        assert_eq!(encoding_for("openai", "gpt-4"), Some(Encoding::Cl100kBase));
+        assert_eq!(
+            encoding_for("openrouter", "openai/gpt-4o"),
+            Some(Encoding::O200kBase)
+        );
+        assert_eq!(
+            encoding_for("deepseek", "deepseek-chat"),
+            Some(Encoding::Cl100kBase)
+        );
+        assert_eq!(encoding_for("local", "mock-local"), None);
+
+        let gpt4 = model("openai", "gpt-4");
+        assert_eq!(tokenizer_for(&gpt4).count_tokens("hello world"), 2);
+
+        let messages = vec![ChatMessage {
+            role: Role::User,
+            content: "hello world".to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        }];
+        assert_eq!(
+            estimate_token_usage(&messages, Some(&gpt4)),
+            2 + TOKENS_PER_MESSAGE
+        );
+        assert_eq!(
+            estimate_token_usage(&messages, Some(&model("local", "mock-local"))),
+            3 + TOKENS_PER_MESSAGE
+        );
+    }
+}
@@ -0,0 +1,72 @@
+// Per-session decision trace: a timestamped timeline of what the agent did and why
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Oldest entries are dropped once a session's trace grows past this
+pub const MAX_TRACE_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    TurnStart,
+    ToolSelection,
+    ToolCall,
+    ToolResult,
+    Compaction,
+    Finish,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub kind: TraceKind,
+    pub detail: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// In-memory traces for every session. Recording is off until enabled, so normal chats pay
+/// nothing for it.
+#[derive(Debug, Clone, Default)]
+pub struct TraceLog {
+    enabled: Arc<AtomicBool>,
+    sessions: Arc<Mutex<HashMap<String, VecDeque<TraceEntry>>>>,
+}
+
+impl TraceLog {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, session_id: &str, kind: TraceKind, detail: impl Into<String>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = sessions.entry(session_id.to_string()).or_default();
+        if entries.len() == MAX_TRACE_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(TraceEntry {
+            kind,
+            detail: detail.into(),
+            timestamp: Utc::now(),
+        });
+    }
+
+    /// A session's trace, oldest first
+    pub fn entries(&self, session_id: &str) -> Vec<TraceEntry> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(session_id)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
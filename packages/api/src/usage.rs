@@ -0,0 +1,299 @@
+// Usage ledger for cost tracking across models, providers and days
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::chat_service_simple::{ModelPricing, TokenUsage};
+use crate::db::{DbPool, PooledConnection};
+use crate::session_store::has_column;
+
+/// A single completion recorded in the `usage_log` table
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageRecord {
+    pub model: String,
+    pub provider: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub estimated_cost: f64,
+    /// True when the provider did not report usage and the counts are estimates
+    pub is_estimated: bool,
+    pub timestamp: DateTime<Utc>,
+    /// Stored session the completion was part of, if any
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Time window for a usage summary. Open ends are unbounded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// Aggregated totals for one model, provider or day
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageBucket {
+    pub key: String,
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated_cost: f64,
+    /// Number of requests in this bucket whose usage was estimated
+    pub estimated_requests: u32,
+}
+
+impl UsageBucket {
+    fn add(&mut self, other: &UsageBucket) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.estimated_cost += other.estimated_cost;
+        self.estimated_requests += other.estimated_requests;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageSummary {
+    pub range: UsageRange,
+    pub totals: UsageBucket,
+    pub by_model: Vec<UsageBucket>,
+    pub by_provider: Vec<UsageBucket>,
+    /// Buckets keyed by `YYYY-MM-DD` (UTC), oldest first
+    pub by_day: Vec<UsageBucket>,
+}
+
+/// Estimate the cost of a completion. Pricing is expressed per 1K tokens.
+pub fn estimate_cost(usage: &TokenUsage, pricing: Option<&ModelPricing>) -> f64 {
+    pricing.map_or(0.0, |pricing| usage.estimated_cost(pricing))
+}
+
+/// SQLite-backed ledger of completion usage
+#[derive(Debug, Clone)]
+pub struct UsageLedger {
+    pool: DbPool,
+}
+
+impl UsageLedger {
+    pub fn new(pool: DbPool) -> Result<Self> {
+        let ledger = Self { pool };
+        ledger.initialize()?;
+        Ok(ledger)
+    }
+
+    fn conn(&self) -> Result<PooledConnection> {
+        Ok(self.pool.get()?)
+    }
+
+    fn initialize(&self) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                model TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                estimated_cost REAL NOT NULL,
+                is_estimated INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_usage_log_created_at ON usage_log(created_at);",
+        )?;
+        if !has_column(&conn, "usage_log", "session_id")? {
+            conn.execute_batch("ALTER TABLE usage_log ADD COLUMN session_id TEXT;")?;
+        }
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_usage_log_session ON usage_log(session_id);",
+        )?;
+        Ok(())
+    }
+
+    pub fn record(&self, record: &UsageRecord) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO usage_log
+                (model, provider, prompt_tokens, completion_tokens, estimated_cost, is_estimated, created_at, session_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.model,
+                record.provider,
+                record.prompt_tokens,
+                record.completion_tokens,
+                record.estimated_cost,
+                record.is_estimated,
+                record.timestamp,
+                record.session_id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Record usage on a blocking thread so the caller's request is never delayed
+    /// or failed by ledger errors.
+    pub fn record_in_background(&self, record: UsageRecord) {
+        let ledger = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = ledger.record(&record) {
+                tracing::warn!("Failed to record usage for {}: {}", record.model, e);
+            }
+        });
+    }
+
+    /// Running totals for one session, keyed by the session id
+    pub fn session_totals(&self, session_id: &str) -> Result<UsageBucket> {
+        let conn = self.conn()?;
+        let bucket = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0),
+                    COALESCE(SUM(estimated_cost), 0.0), COALESCE(SUM(is_estimated), 0)
+             FROM usage_log WHERE session_id = ?1",
+            params![session_id],
+            |row| {
+                Ok(UsageBucket {
+                    key: session_id.to_string(),
+                    requests: row.get(0)?,
+                    prompt_tokens: row.get::<_, i64>(1)? as u64,
+                    completion_tokens: row.get::<_, i64>(2)? as u64,
+                    estimated_cost: row.get(3)?,
+                    estimated_requests: row.get(4)?,
+                })
+            },
+        )?;
+        Ok(bucket)
+    }
+
+    pub fn summary(&self, range: &UsageRange) -> Result<UsageSummary> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT model, provider, substr(created_at, 1, 10) AS day,
+                    COUNT(*), SUM(prompt_tokens), SUM(completion_tokens),
+                    SUM(estimated_cost), SUM(is_estimated)
+             FROM usage_log
+             WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+             GROUP BY model, provider, day",
+        )?;
+
+        let rows = stmt.query_map(params![range.start, range.end], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                UsageBucket {
+                    key: String::new(),
+                    requests: row.get(3)?,
+                    prompt_tokens: row.get::<_, i64>(4)? as u64,
+                    completion_tokens: row.get::<_, i64>(5)? as u64,
+                    estimated_cost: row.get(6)?,
+                    estimated_requests: row.get(7)?,
+                },
+            ))
+        })?;
+
+        let mut totals = UsageBucket {
+            key: "total".to_string(),
+            ..Default::default()
+        };
+        let mut by_model: BTreeMap<String, UsageBucket> = BTreeMap::new();
+        let mut by_provider: BTreeMap<String, UsageBucket> = BTreeMap::new();
+        let mut by_day: BTreeMap<String, UsageBucket> = BTreeMap::new();
+
+        for row in rows {
+            let (model, provider, day, bucket) = row?;
+            totals.add(&bucket);
+            for (map, key) in [
+                (&mut by_model, model),
+                (&mut by_provider, provider),
+                (&mut by_day, day),
+            ] {
+                map.entry(key.clone())
+                    .or_insert_with(|| UsageBucket {
+                        key,
+                        ..Default::default()
+                    })
+                    .add(&bucket);
+            }
+        }
+
+        Ok(UsageSummary {
+            range: range.clone(),
+            totals,
+            by_model: by_model.into_values().collect(),
+            by_provider: by_provider.into_values().collect(),
+            by_day: by_day.into_values().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(model: &str, provider: &str, day: u32, prompt: u32, completion: u32) -> UsageRecord {
+        let usage = TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        };
+        let pricing = ModelPricing {
+            input_tokens: 0.001,
+            output_tokens: 0.002,
+            currency: "USD".to_string(),
+        };
+        UsageRecord {
+            model: model.to_string(),
+            provider: provider.to_string(),
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            estimated_cost: estimate_cost(&usage, Some(&pricing)),
+            is_estimated: provider == "local",
+            timestamp: Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_usage_summary_aggregates_by_model_provider_and_day() -> Result<()> {
+        let ledger = UsageLedger::new(crate::db::memory_pool()?)?;
+
+        ledger.record(&record("deepseek-chat", "deepseek", 1, 1000, 500))?;
+        ledger.record(&record("deepseek-chat", "deepseek", 2, 2000, 1000))?;
+        ledger.record(&record("openai/gpt-4o", "openrouter", 2, 1000, 1000))?;
+        ledger.record(&record("mock-local", "local", 3, 100, 100))?;
+
+        let summary = ledger.summary(&UsageRange::default())?;
+        assert_eq!(summary.totals.requests, 4);
+        assert_eq!(summary.totals.prompt_tokens, 4100);
+        assert_eq!(summary.totals.completion_tokens, 2600);
+        assert!((summary.totals.estimated_cost - 0.0093).abs() < 1e-9);
+        assert_eq!(summary.totals.estimated_requests, 1);
+
+        let deepseek = summary
+            .by_model
+            .iter()
+            .find(|b| b.key == "deepseek-chat")
+            .unwrap();
+        assert_eq!(deepseek.requests, 2);
+        assert_eq!(deepseek.total_tokens(), 4500);
+
+        let days: Vec<_> = summary.by_day.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(days, vec!["2024-05-01", "2024-05-02", "2024-05-03"]);
+        assert_eq!(summary.by_day[1].requests, 2);
+        assert_eq!(summary.by_provider.len(), 3);
+
+        let ranged = ledger.summary(&UsageRange {
+            start: Some(Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 0).unwrap()),
+            end: Some(Utc.with_ymd_and_hms(2024, 5, 3, 0, 0, 0).unwrap()),
+        })?;
+        assert_eq!(ranged.totals.requests, 2);
+        assert_eq!(ranged.totals.prompt_tokens, 3000);
+
+        Ok(())
+    }
+}
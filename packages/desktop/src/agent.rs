@@ -1,7 +1,8 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -10,8 +11,12 @@ pub use api::{
     AgentConfig, AgentFactory, ChatMessage, ChatRequest, ChatResponse, ChunkType,
     EnhancedStreamChunk, GooseMode, MessageMetadata, ModelConfig, RigAgentService, Role,
     StreamMetadata, StreamingAgentService, TokenUsage, Tool, ToolCall, ToolResult,
+    model_change_notice,
 };
 
+use api::agent_loop::run_tool_loop;
+use api::{BuiltinToolExecutor, ExecutionPlan, ToolExecutor};
+
 // Simplified MessageContent for UI usage
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MessageContent {
@@ -37,10 +42,22 @@ pub enum AgentEvent {
     Token(TokenUsage),
     ToolCall(ToolCall),
     ToolResult(ToolResult),
+    /// The conversation switched model or mode since its previous reply
+    ModelChange { model: String, mode: GooseMode },
     Error(String),
     Done,
 }
 
+impl AgentEvent {
+    /// Inline separator text for events that mark a change in the transcript
+    pub fn separator(&self) -> Option<String> {
+        match self {
+            AgentEvent::ModelChange { model, mode } => Some(model_change_notice(model, mode)),
+            _ => None,
+        }
+    }
+}
+
 /// Simplified ChatMessage for UI usage
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct UiChatMessage {
@@ -133,6 +150,84 @@ impl Conversation {
     }
 }
 
+/// Ask `next_turn` for replies, running the tool calls each one carries with `executor` and
+/// feeding the results back, until a reply comes without tool calls. Returns that final reply
+/// and the tool call and result events from the turns before it, in order.
+async fn run_agent_turns<F, Fut>(
+    messages: Vec<ChatMessage>,
+    max_iterations: usize,
+    executor: &dyn ToolExecutor,
+    next_turn: F,
+) -> Result<(ChatResponse, Vec<AgentEvent>)>
+where
+    F: FnMut(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<ChatResponse>>,
+{
+    let sent = messages.len();
+    let (response, conversation) =
+        run_tool_loop(messages, max_iterations, executor, next_turn).await?;
+
+    let events = conversation[sent..]
+        .iter()
+        .flat_map(|msg| {
+            let calls = msg
+                .tool_calls
+                .iter()
+                .flatten()
+                .cloned()
+                .map(AgentEvent::ToolCall);
+            let results = msg
+                .tool_results
+                .iter()
+                .flatten()
+                .cloned()
+                .map(AgentEvent::ToolResult);
+            calls.chain(results)
+        })
+        .collect();
+    Ok((response, events))
+}
+
+/// Run the tool steps of `plan` in dependency order, whatever order they are listed in. A step
+/// is skipped when a step it depends on failed; a plan with a cycle runs nothing.
+pub async fn execute_plan(plan: &ExecutionPlan, executor: &dyn ToolExecutor) -> Vec<AgentEvent> {
+    let order = match plan.execution_order() {
+        Ok(order) => order,
+        Err(e) => return vec![AgentEvent::Error(e.to_string())],
+    };
+
+    let mut events = Vec::new();
+    let mut failed = HashSet::new();
+    for index in order {
+        let step = &plan.steps[index];
+        if let Some(&dep) = step.dependencies.iter().find(|dep| failed.contains(*dep)) {
+            events.push(AgentEvent::Error(format!(
+                "Skipped step {} because step {} failed",
+                index + 1,
+                dep + 1
+            )));
+            failed.insert(index);
+            continue;
+        }
+        let Some(tool_name) = &step.tool_name else {
+            continue;
+        };
+
+        let call = ToolCall {
+            id: format!("plan-step-{}", index + 1),
+            name: tool_name.clone(),
+            arguments: step.parameters.clone(),
+        };
+        let result = executor.execute(&call).await;
+        if result.error.is_some() {
+            failed.insert(index);
+        }
+        events.push(AgentEvent::ToolCall(call));
+        events.push(AgentEvent::ToolResult(result));
+    }
+    events
+}
+
 /// Core Agent trait for extensibility with rig integration
 #[async_trait]
 pub trait Agent: Send + Sync {
@@ -216,20 +311,33 @@ pub struct GooseAgent {
     streaming_service: StreamingAgentService,
     extensions: Arc<RwLock<HashMap<String, Box<dyn AgentExtension>>>>,
     conversation_history: Arc<RwLock<HashMap<String, Conversation>>>,
+    /// Model and mode each conversation last replied with, to detect switches
+    last_model: Arc<RwLock<HashMap<String, (String, GooseMode)>>>,
+    /// Runs the tool calls the model asks for between turns
+    tool_executor: Arc<dyn ToolExecutor>,
 }
 
 impl GooseAgent {
     pub fn new(config: AgentConfig, rig_service: RigAgentService) -> Self {
         let streaming_service = StreamingAgentService::new(rig_service.clone());
+        let tool_executor = Arc::new(BuiltinToolExecutor::new(&config));
         Self {
             config,
             rig_service,
             streaming_service,
             extensions: Arc::new(RwLock::new(HashMap::new())),
             conversation_history: Arc::new(RwLock::new(HashMap::new())),
+            last_model: Arc::new(RwLock::new(HashMap::new())),
+            tool_executor,
         }
     }
 
+    /// Run tool calls with `executor` instead of the built-in tools
+    pub fn with_tool_executor(mut self, executor: Arc<dyn ToolExecutor>) -> Self {
+        self.tool_executor = executor;
+        self
+    }
+
     pub async fn load_conversation(&self, id: &str) -> Option<Conversation> {
         self.conversation_history.read().await.get(id).cloned()
     }
@@ -327,25 +435,38 @@ impl Agent for GooseAgent {
             presence_penalty: None,
             agent_config: Some(AgentConfig {
                 goose_mode: conversation.metadata.agent_mode.unwrap_or(GooseMode::Chat),
-                max_iterations: self.config.max_iterations,
-                require_confirmation: self.config.require_confirmation,
-                readonly_tools: self.config.readonly_tools.clone(),
-                enable_tool_inspection: self.config.enable_tool_inspection,
-                enable_auto_compact: self.config.enable_auto_compact,
-                compact_threshold: self.config.compact_threshold,
-                max_turns_without_tools: self.config.max_turns_without_tools,
-                enable_autopilot: self.config.enable_autopilot,
-                enable_extensions: self.config.enable_extensions,
-                extension_timeout: self.config.extension_timeout,
+                ..self.config.clone()
             }),
             tools: tools.map(|t| t.to_vec()),
             system_prompt: system_prompt.map(|s| s.to_string()),
         };
 
         let rig_service = self.rig_service.clone();
+        let tool_executor = self.tool_executor.clone();
         let conversation_id = conversation.id.clone();
 
+        let mode = request
+            .agent_config
+            .as_ref()
+            .map(|config| config.goose_mode.clone())
+            .unwrap_or(GooseMode::Chat);
+        let previous = self
+            .last_model
+            .write()
+            .await
+            .insert(conversation_id.clone(), (request.model.clone(), mode.clone()));
+        let model_change = previous
+            .filter(|previous| *previous != (request.model.clone(), mode.clone()))
+            .map(|_| AgentEvent::ModelChange {
+                model: request.model.clone(),
+                mode,
+            });
+
         let stream = async_stream::stream! {
+            if let Some(event) = model_change {
+                yield Ok(event);
+            }
+
             // Send the user message event
             if let Some(msg) = request.messages.last() {
                 if matches!(msg.role, Role::User) {
@@ -361,9 +482,31 @@ impl Agent for GooseAgent {
                 }
             }
 
-            // Stream the response using rig service
-            match rig_service.send_message(request).await {
-                Ok(response) => {
+            // Run the agent loop, executing tool calls until the model answers without any
+            let max_iterations = request
+                .agent_config
+                .as_ref()
+                .map_or(1, |config| config.max_iterations);
+            let turns = run_agent_turns(
+                request.messages.clone(),
+                max_iterations,
+                tool_executor.as_ref(),
+                |messages| {
+                    let turn = ChatRequest {
+                        messages,
+                        ..request.clone()
+                    };
+                    let rig_service = rig_service.clone();
+                    async move { rig_service.send_message(turn).await }
+                },
+            )
+            .await;
+            match turns {
+                Ok((response, tool_events)) => {
+                    for event in tool_events {
+                        yield Ok(event);
+                    }
+
                     // Send thinking content if present
                     if let Some(ref thinking) = response.thinking_content {
                         let thinking_msg = UiChatMessage {
@@ -492,16 +635,7 @@ impl Agent for GooseAgent {
             presence_penalty: None,
             agent_config: Some(AgentConfig {
                 goose_mode: conversation.metadata.agent_mode.unwrap_or(GooseMode::Chat),
-                max_iterations: self.config.max_iterations,
-                require_confirmation: self.config.require_confirmation,
-                readonly_tools: self.config.readonly_tools.clone(),
-                enable_tool_inspection: self.config.enable_tool_inspection,
-                enable_auto_compact: self.config.enable_auto_compact,
-                compact_threshold: self.config.compact_threshold,
-                max_turns_without_tools: self.config.max_turns_without_tools,
-                enable_autopilot: self.config.enable_autopilot,
-                enable_extensions: self.config.enable_extensions,
-                extension_timeout: self.config.extension_timeout,
+                ..self.config.clone()
             }),
             tools: None,
             system_prompt: system_prompt.map(|s| s.to_string()),
@@ -532,6 +666,7 @@ impl AgentFactory {
             enable_autopilot: false,
             enable_extensions: true,
             extension_timeout: 30,
+            ..AgentConfig::default()
         };
 
         Ok(Box::new(GooseAgent::new(config, rig_service)))
@@ -551,6 +686,7 @@ impl AgentFactory {
             enable_autopilot: false,
             enable_extensions: true,
             extension_timeout: 60,
+            ..AgentConfig::default()
         };
 
         Ok(Box::new(GooseAgent::new(config, rig_service)))
@@ -570,6 +706,7 @@ impl AgentFactory {
             enable_autopilot: false,
             enable_extensions: true,
             extension_timeout: 90,
+            ..AgentConfig::default()
         };
 
         Ok(Box::new(GooseAgent::new(config, rig_service)))
@@ -589,6 +726,7 @@ impl AgentFactory {
             enable_autopilot: true,
             enable_extensions: true,
             extension_timeout: 120,
+            ..AgentConfig::default()
         };
 
         Ok(Box::new(GooseAgent::new(config, rig_service)))
@@ -619,9 +757,135 @@ impl AgentFactory {
             enable_autopilot: matches!(mode, GooseMode::Auto),
             enable_extensions: true,
             extension_timeout: 60,
+            ..AgentConfig::default()
         };
 
         Ok(Box::new(GooseAgent::new(config, rig_service)))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::ExecutionStep;
+    use std::sync::Mutex;
+
+    /// Records the calls it runs and answers each with the tool's name
+    #[derive(Default)]
+    struct RecordingExecutor {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ToolExecutor for RecordingExecutor {
+        async fn execute(&self, call: &ToolCall) -> ToolResult {
+            self.calls.lock().unwrap().push(call.name.clone());
+            ToolResult {
+                tool_call_id: call.id.clone(),
+                result: serde_json::Value::String(format!("{} ran", call.name)),
+                error: None,
+            }
+        }
+    }
+
+    fn reply(content: &str, tool_calls: Option<Vec<ToolCall>>) -> ChatResponse {
+        ChatResponse {
+            message: Some(ChatMessage {
+                role: Role::Assistant,
+                content: content.to_string(),
+                timestamp: None,
+                tool_calls: None,
+                tool_results: None,
+            }),
+            tool_calls,
+            token_usage: None,
+            model: "mock".to_string(),
+            finish_reason: None,
+            is_streaming: false,
+            reasoning_content: None,
+            thinking_content: None,
+            notification: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_request_from_the_provider_is_executed_and_fed_back() -> Result<()> {
+        let executor = RecordingExecutor::default();
+        let turns = Mutex::new(Vec::new());
+        let user = ChatMessage {
+            role: Role::User,
+            content: "List the files".to_string(),
+            timestamp: None,
+            tool_calls: None,
+            tool_results: None,
+        };
+
+        let (response, events) = run_agent_turns(vec![user], 5, &executor, |messages| {
+            turns.lock().unwrap().push(messages.clone());
+            async move {
+                let last = messages.last().unwrap();
+                Ok(match last.role {
+                    Role::Tool => reply(&format!("Done: {}", last.content), None),
+                    _ => reply(
+                        "",
+                        Some(vec![ToolCall {
+                            id: "call-1".to_string(),
+                            name: "list_files".to_string(),
+                            arguments: serde_json::json!({ "path": "." }),
+                        }]),
+                    ),
+                })
+            }
+        })
+        .await?;
+
+        assert_eq!(*executor.calls.lock().unwrap(), vec!["list_files"]);
+        assert_eq!(turns.lock().unwrap().len(), 2);
+        assert_eq!(response.message.unwrap().content, "Done: list_files ran");
+        let [AgentEvent::ToolCall(call), AgentEvent::ToolResult(result)] = &events[..] else {
+            panic!("expected a tool call then its result, got {:?}", events);
+        };
+        assert_eq!(call.id, "call-1");
+        assert_eq!(result.tool_call_id, "call-1");
+        Ok(())
+    }
+
+    fn step(tool: &str, dependencies: Vec<usize>) -> ExecutionStep {
+        ExecutionStep {
+            description: format!("Run {}", tool),
+            tool_name: Some(tool.to_string()),
+            parameters: serde_json::Value::Null,
+            dependencies,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_steps_listed_out_of_order_run_after_their_dependencies() {
+        let executor = RecordingExecutor::default();
+        let plan = ExecutionPlan {
+            steps: vec![
+                step("summarize", vec![2, 1]),
+                step("read_file", vec![2]),
+                step("list_files", vec![]),
+            ],
+            estimated_duration: None,
+        };
+
+        let events = execute_plan(&plan, &executor).await;
+        assert_eq!(
+            *executor.calls.lock().unwrap(),
+            vec!["list_files", "read_file", "summarize"]
+        );
+        assert_eq!(events.len(), 6);
+
+        let cyclic = ExecutionPlan {
+            steps: vec![step("a", vec![1]), step("b", vec![0]), step("c", vec![])],
+            estimated_duration: None,
+        };
+        let events = execute_plan(&cyclic, &RecordingExecutor::default()).await;
+        let [AgentEvent::Error(message)] = &events[..] else {
+            panic!("expected a cycle error, got {:?}", events);
+        };
+        assert_eq!(message, "Plan has a dependency cycle involving steps 1, 2");
+    }
+}
@@ -34,6 +34,8 @@ fn main() {
 #[component]
 fn App() -> Element {
     // Build cool things ✌️
+    ui::use_appearance_provider();
+    ui::use_settings_provider();
 
     rsx! {
         // Global app resources
@@ -48,6 +50,9 @@ fn App() -> Element {
 /// which allows us to use the desktop-specific `Route` enum.
 #[component]
 fn DesktopNavbar() -> Element {
+    let provider_status = ui::use_provider_status(None);
+    let mut settings_open = use_signal(|| false);
+
     rsx! {
         nav {
             class: "bg-white dark:bg-gray-800 border-b border-gray-200 dark:border-gray-700 px-6 py-4",
@@ -78,9 +83,23 @@ fn DesktopNavbar() -> Element {
                     class: "text-gray-700 dark:text-gray-200 hover:text-blue-600 dark:hover:text-blue-400 font-medium bg-blue-100 dark:bg-blue-900 px-2 py-1 rounded",
                     "🚀 Rig Demo"
                 }
+                div {
+                    class: "ml-auto flex items-center gap-4",
+                    ui::ProviderStatusBadge { status: provider_status.status() }
+                    button {
+                        class: "text-gray-700 dark:text-gray-200 hover:text-blue-600 dark:hover:text-blue-400 font-medium",
+                        onclick: move |_| settings_open.set(true),
+                        "Settings"
+                    }
+                }
             }
         }
 
+        ui::SettingsDialog {
+            open: settings_open(),
+            on_open_change: move |open| settings_open.set(open),
+        }
+
         Outlet::<Route> {}
     }
 }
@@ -1,5 +1,6 @@
 use dioxus::prelude::*;
-use api::{ChatService, ChatRequest, ChatMessage as ApiMessage, Role, ChatResponse};
+use futures::StreamExt;
+use api::{ChatService, ChatRequest, ChatMessage as ApiMessage, Role};
 use std::collections::HashMap;
 
 #[derive(Clone, PartialEq)]
@@ -252,62 +253,45 @@ pub fn Chat() -> Element {
                 tools: None,
             };
 
-            // Call the real streaming API
+            // Append each chunk to the reply as the server streams it
             match api::send_message_stream(api_request).await {
-                Ok(response_json) => {
-                    // Parse the response
-                    match serde_json::from_str::<ChatResponse>(&response_json) {
-                        Ok(response) => {
-                            if let Some(msg) = response.message {
-                                // Simulate streaming by gradually updating the message
-                                let words: Vec<String> = msg.content.split_whitespace().map(|s| s.to_string()).collect();
-                                let words_len = words.len();
-                                let mut accumulated_content = String::new();
-
-                                for (i, word) in words.into_iter().enumerate() {
-                                    // Add word with space
-                                    accumulated_content.push_str(&word);
-                                    if i < words_len - 1 {
-                                        accumulated_content.push(' ');
-                                    }
-
-                                    // Update UI with partial content
-                                    conversations.with_mut(|convs| {
-                                        if let Some(conv) = convs.get_mut(&conv_id) {
-                                            // Find and update the last AI message
-                                            if let Some(last_msg) = conv.messages.last_mut() {
-                                                if !last_msg.is_user {
-                                                    last_msg.content = accumulated_content.clone();
-                                                }
-                                            }
-                                        }
-                                    });
-
-                                    // Small delay to simulate streaming effect
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(30)).await;
-                                }
+                Ok(mut stream) => {
+                    let mut accumulated_content = String::new();
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = match chunk {
+                            Ok(chunk) => chunk,
+                            Err(e) => {
+                                error.set(Some(format!("Stream interrupted: {}", e)));
+                                break;
+                            }
+                        };
 
-                                // Final update with token usage if provided
-                                if let Some(usage) = response.token_usage {
-                                    conversations.with_mut(|convs| {
-                                        if let Some(conv) = convs.get_mut(&conv_id) {
-                                            conv.token_usage.prompt_tokens = usage.prompt_tokens;
-                                            conv.token_usage.completion_tokens = usage.completion_tokens;
-                                            conv.token_usage.total_tokens = usage.total_tokens;
+                        if let Some(content) = chunk.content {
+                            accumulated_content.push_str(&content);
+                            conversations.with_mut(|convs| {
+                                if let Some(conv) = convs.get_mut(&conv_id) {
+                                    if let Some(last_msg) = conv.messages.last_mut() {
+                                        if !last_msg.is_user {
+                                            last_msg.content = accumulated_content.clone();
                                         }
-                                    });
+                                    }
                                 }
-                            }
+                            });
                         }
-                        Err(e) => {
-                            error.set(Some(format!("Failed to parse response: {}", e)));
-                            // Remove the empty AI message on error
+
+                        // The terminal chunk carries the token usage for the whole reply
+                        if let Some(usage) = chunk.token_usage {
                             conversations.with_mut(|convs| {
                                 if let Some(conv) = convs.get_mut(&conv_id) {
-                                    conv.messages.pop();
+                                    conv.token_usage.prompt_tokens = usage.prompt_tokens;
+                                    conv.token_usage.completion_tokens = usage.completion_tokens;
+                                    conv.token_usage.total_tokens = usage.total_tokens;
                                 }
                             });
                         }
+                        if chunk.is_complete {
+                            break;
+                        }
                     }
                 }
                 Err(e) => {
@@ -1,5 +1,6 @@
 use dioxus::prelude::*;
-use api::{ChatRequest, ChatMessage as ApiMessage, Role, ChatResponse};
+use futures::StreamExt;
+use api::{ChatRequest, ChatMessage as ApiMessage, Role};
 use std::collections::HashMap;
 
 #[derive(Clone, PartialEq)]
@@ -18,6 +19,8 @@ pub struct SimpleChatMessage {
     pub thinking_content: Option<String>,
     pub tool_calls: Option<Vec<SimpleToolCall>>,
     pub tool_results: Option<Vec<SimpleToolResult>>,
+    /// Why the model stopped, as reported on the reply's last chunk
+    pub finish_reason: Option<String>,
 }
 
 #[derive(Clone, PartialEq)]
@@ -128,6 +131,25 @@ pub fn SimpleChat() -> Element {
     let mut error = use_signal(|| Option::<String>::None);
     let mut models_loaded = use_signal(|| false);
 
+    // Conversation the reply being streamed belongs to
+    let mut streaming_conversation = use_signal(|| Option::<String>::None);
+
+    // Streamed text reaches the reply at most once per frame
+    let reply_stream = ui::use_coalesced_stream(Callback::new(move |text: String| {
+        let Some(conv_id) = streaming_conversation.peek().clone() else {
+            return;
+        };
+        conversations.with_mut(|convs| {
+            if let Some(conv) = convs.get_mut(&conv_id) {
+                if let Some(last_msg) = conv.messages.last_mut() {
+                    if !last_msg.is_user {
+                        last_msg.content.push_str(&text);
+                    }
+                }
+            }
+        });
+    }));
+
     // Load models from the real API
     use_effect(move || {
         spawn(async move {
@@ -179,6 +201,14 @@ pub fn SimpleChat() -> Element {
     let current_messages = current_conversation_id().and_then(|id| {
         conversations().get(&id).map(|conv| conv.messages.clone())
     }).unwrap_or_default();
+    // Only the latest reply can be regenerated
+    let last_reply_id = current_messages
+        .last()
+        .filter(|msg| !msg.is_user)
+        .map(|msg| msg.id.clone());
+
+    let density = ui::use_appearance().density;
+    let padding = density.bubble_padding();
 
     // Get conversation items for sidebar
     let conversation_items: Vec<SimpleConversationItem> = conversations()
@@ -186,41 +216,8 @@ pub fn SimpleChat() -> Element {
         .map(|conv| conv.as_conversation_item())
         .collect();
 
-    let mut handle_send_message = move |content: String| {
-        // Clear any previous errors
-        error.set(None);
-
-        // Get or create current conversation
-        let conv_id = if let Some(id) = current_conversation_id() {
-            id.clone()
-        } else {
-            // Create new conversation if none exists
-            let new_id = format!("conv_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos());
-            let new_conv = ConversationState::new(new_id.clone(), "New Chat".to_string());
-            conversations.with_mut(|convs| { convs.insert(new_id.clone(), new_conv); });
-            current_conversation_id.set(Some(new_id.clone()));
-            new_id
-        };
-
-        // Create user message
-        let user_message = SimpleChatMessage {
-            id: format!("msg_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()),
-            content: content.clone(),
-            is_user: true,
-            timestamp: Some(format!("{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs())),
-            thinking_content: None,
-            tool_calls: None,
-            tool_results: None,
-        };
-
-        // Add user message to conversation
-        conversations.with_mut(|convs| {
-            if let Some(conv) = convs.get_mut(&conv_id) {
-                conv.add_message(user_message);
-                conv.model = selected_model();
-            }
-        });
-
+    // Stream a reply to the conversation as it stands, sending all of it as history
+    let mut stream_reply = move |conv_id: String| {
         // Get conversation history for API request
         let conversation_history = conversations()
             .get(&conv_id)
@@ -251,6 +248,7 @@ pub fn SimpleChat() -> Element {
             thinking_content: None,
             tool_calls: None,
             tool_results: None,
+            finish_reason: None,
         };
 
         conversations.with_mut(|convs| {
@@ -259,6 +257,7 @@ pub fn SimpleChat() -> Element {
             }
         });
 
+        streaming_conversation.set(Some(conv_id.clone()));
         spawn(async move {
             // Prepare API request
             let api_request = ChatRequest {
@@ -275,94 +274,64 @@ pub fn SimpleChat() -> Element {
                 tools: None,
             };
 
-            // Call the real streaming API
+            // Append each chunk to the reply as the server streams it
             match api::send_message_stream(api_request).await {
-                Ok(response_json) => {
-                    // Parse the response
-                    match serde_json::from_str::<ChatResponse>(&response_json) {
-                        Ok(response) => {
-                            if let Some(msg) = response.message {
-                                // Convert tool calls if present
-                                let tool_calls = msg.tool_calls.map(|calls| {
-                                    calls.into_iter().map(|call| SimpleToolCall {
-                                        id: call.id,
-                                        name: call.name,
-                                        arguments: call.arguments.to_string(),
-                                    }).collect()
-                                });
-
-                                // Convert tool results if present
-                                let tool_results = msg.tool_results.map(|results| {
-                                    results.into_iter().map(|result| SimpleToolResult {
-                                        tool_call_id: result.tool_call_id,
-                                        result: result.result.to_string(),
-                                        error: result.error,
-                                    }).collect()
-                                });
-
-                                // Update the final AI message with all content
-                                conversations.with_mut(|convs| {
-                                    if let Some(conv) = convs.get_mut(&conv_id) {
-                                        if let Some(last_msg) = conv.messages.last_mut() {
-                                            if !last_msg.is_user {
-                                                last_msg.content = msg.content.clone();
-                                                last_msg.thinking_content = response.thinking_content.clone();
-                                                last_msg.tool_calls = tool_calls;
-                                                last_msg.tool_results = tool_results;
-                                            }
-                                        }
-                                    }
-                                });
-
-                                // Simulate streaming for the main content only
-                                let words: Vec<String> = msg.content.split_whitespace().map(|s| s.to_string()).collect();
-                                let words_len = words.len();
-                                let mut accumulated_content = String::new();
-
-                                for (i, word) in words.into_iter().enumerate() {
-                                    // Add word with space
-                                    accumulated_content.push_str(&word);
-                                    if i < words_len - 1 {
-                                        accumulated_content.push(' ');
-                                    }
+                Ok(mut stream) => {
+                    let mut accumulated_thinking = String::new();
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = match chunk {
+                            Ok(chunk) => chunk,
+                            Err(e) => {
+                                error.set(Some(format!("Stream interrupted: {}", e)));
+                                break;
+                            }
+                        };
 
-                                    // Update UI with partial content (thinking content stays complete)
-                                    conversations.with_mut(|convs| {
-                                        if let Some(conv) = convs.get_mut(&conv_id) {
-                                            if let Some(last_msg) = conv.messages.last_mut() {
-                                                if !last_msg.is_user {
-                                                    last_msg.content = accumulated_content.clone();
-                                                }
-                                            }
-                                        }
-                                    });
+                        if let Some(content) = chunk.content {
+                            reply_stream.push(&content);
+                        }
 
-                                    // Small delay to simulate streaming effect
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(30)).await;
+                        // Reasoning streams separately and is shown apart from the reply
+                        if let Some(thinking) = chunk.thinking {
+                            accumulated_thinking.push_str(&thinking);
+                            conversations.with_mut(|convs| {
+                                if let Some(conv) = convs.get_mut(&conv_id) {
+                                    if let Some(last_msg) = conv.messages.last_mut() {
+                                        if !last_msg.is_user {
+                                            last_msg.thinking_content = Some(accumulated_thinking.clone());
+                                        }
+                                    }
                                 }
+                            });
+                        }
 
-                                // Final update with token usage if provided
-                                if let Some(usage) = response.token_usage {
-                                    conversations.with_mut(|convs| {
-                                        if let Some(conv) = convs.get_mut(&conv_id) {
-                                            conv.token_usage.prompt_tokens = usage.prompt_tokens;
-                                            conv.token_usage.completion_tokens = usage.completion_tokens;
-                                            conv.token_usage.total_tokens = usage.total_tokens;
-                                        }
-                                    });
+                        // The terminal chunk carries the token usage for the whole reply
+                        if let Some(usage) = chunk.token_usage {
+                            conversations.with_mut(|convs| {
+                                if let Some(conv) = convs.get_mut(&conv_id) {
+                                    conv.token_usage.prompt_tokens = usage.prompt_tokens;
+                                    conv.token_usage.completion_tokens = usage.completion_tokens;
+                                    conv.token_usage.total_tokens = usage.total_tokens;
                                 }
-                            }
+                            });
                         }
-                        Err(e) => {
-                            error.set(Some(format!("Failed to parse response: {}", e)));
-                            // Remove the empty AI message on error
+                        if chunk.finish_reason.is_some() {
                             conversations.with_mut(|convs| {
                                 if let Some(conv) = convs.get_mut(&conv_id) {
-                                    conv.messages.pop();
+                                    if let Some(last_msg) = conv.messages.last_mut() {
+                                        if !last_msg.is_user {
+                                            last_msg.finish_reason = chunk.finish_reason.clone();
+                                        }
+                                    }
                                 }
                             });
                         }
+                        if chunk.is_complete {
+                            break;
+                        }
                     }
+                    // Whatever is still buffered belongs to this reply
+                    reply_stream.finish();
                 }
                 Err(e) => {
                     error.set(Some(format!("Failed to send message: {}", e)));
@@ -375,11 +344,67 @@ pub fn SimpleChat() -> Element {
                 }
             }
 
+            streaming_conversation.set(None);
             loading.set(false);
             streaming.set(false);
         });
     };
 
+    let mut handle_send_message = move |content: String| {
+        // Clear any previous errors
+        error.set(None);
+
+        // Get or create current conversation
+        let conv_id = if let Some(id) = current_conversation_id() {
+            id.clone()
+        } else {
+            // Create new conversation if none exists
+            let new_id = format!("conv_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos());
+            let new_conv = ConversationState::new(new_id.clone(), "New Chat".to_string());
+            conversations.with_mut(|convs| { convs.insert(new_id.clone(), new_conv); });
+            current_conversation_id.set(Some(new_id.clone()));
+            new_id
+        };
+
+        // Create user message
+        let user_message = SimpleChatMessage {
+            id: format!("msg_{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()),
+            content: content.clone(),
+            is_user: true,
+            timestamp: Some(format!("{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs())),
+            thinking_content: None,
+            tool_calls: None,
+            tool_results: None,
+            finish_reason: None,
+        };
+
+        // Add user message to conversation
+        conversations.with_mut(|convs| {
+            if let Some(conv) = convs.get_mut(&conv_id) {
+                conv.add_message(user_message);
+                conv.model = selected_model();
+            }
+        });
+
+        stream_reply(conv_id);
+    };
+
+    // Answer the last user message again, replacing the reply to it
+    let mut handle_regenerate = move || {
+        let Some(conv_id) = current_conversation_id() else {
+            return;
+        };
+        error.set(None);
+        conversations.with_mut(|convs| {
+            if let Some(conv) = convs.get_mut(&conv_id) {
+                while conv.messages.last().is_some_and(|msg| !msg.is_user) {
+                    conv.messages.pop();
+                }
+            }
+        });
+        stream_reply(conv_id);
+    };
+
     rsx! {
         div {
             class: "flex h-screen bg-gray-100 dark:bg-gray-900",
@@ -559,7 +584,7 @@ pub fn SimpleChat() -> Element {
 
                 // Chat messages
                 div {
-                    class: "flex-1 overflow-y-auto p-4",
+                    class: density.list_class(),
 
                     if current_messages.is_empty() {
                         div {
@@ -583,21 +608,17 @@ pub fn SimpleChat() -> Element {
                         }
                     } else {
                         div {
-                            class: "space-y-4",
+                            class: density.stack_class(),
                             for message in current_messages.clone() {
                                 div {
                                     key: "{message.id}",
-                                    class: if message.is_user {
-                                        "flex justify-end mb-4"
-                                    } else {
-                                        "flex justify-start mb-4"
-                                    },
+                                    class: density.row_class(message.is_user),
 
                                     div {
                                         class: if message.is_user {
-                                            "max-w-xs lg:max-w-md bg-blue-500 text-white rounded-lg p-3"
+                                            "max-w-xs lg:max-w-md bg-blue-500 text-white rounded-lg {padding}"
                                         } else {
-                                            "max-w-xs lg:max-w-md bg-gray-100 text-gray-800 rounded-lg p-3"
+                                            "max-w-xs lg:max-w-md bg-gray-100 text-gray-800 rounded-lg {padding}"
                                         },
 
                                         if message.is_user {
@@ -617,12 +638,12 @@ pub fn SimpleChat() -> Element {
                                                 // Thinking content (if present)
                                                 if let Some(thinking) = &message.thinking_content {
                                                     if !thinking.is_empty() {
-                                                        div { class: "mb-3 p-2 bg-purple-50 border border-purple-200 rounded-md",
-                                                            div { class: "flex items-center mb-1",
+                                                        details { class: "mb-3 p-2 bg-purple-50 border border-purple-200 rounded-md",
+                                                            summary { class: "flex items-center cursor-pointer",
                                                                 span { class: "text-purple-600 mr-1", "🧠" }
-                                                                span { class: "font-semibold text-sm text-purple-700", "Thinking:" }
+                                                                span { class: "font-semibold text-sm text-purple-700", "Reasoning" }
                                                             }
-                                                            pre { class: "text-xs text-purple-600 whitespace-pre-wrap font-mono", "{thinking}" }
+                                                            pre { class: "mt-1 text-xs text-purple-600 whitespace-pre-wrap font-mono", "{thinking}" }
                                                         }
                                                     }
                                                 }
@@ -686,6 +707,20 @@ pub fn SimpleChat() -> Element {
                                                     div { class: "text-xs text-gray-500 mt-2", "{ts}" }
                                                 }
 
+                                                if api::is_truncated(message.finish_reason.as_deref()) {
+                                                    div { class: "text-xs text-amber-600 mt-2",
+                                                        "⚠️ Response truncated: the model reached its token limit"
+                                                    }
+                                                }
+
+                                                if last_reply_id.as_deref() == Some(message.id.as_str()) && !loading() {
+                                                    button {
+                                                        class: "mt-2 px-2 py-1 text-xs rounded bg-gray-200 hover:bg-gray-300 text-gray-700",
+                                                        onclick: move |_| handle_regenerate(),
+                                                        "🔄 Regenerate"
+                                                    }
+                                                }
+
                                                 // Show streaming indicator
                                                 if streaming() && message.content.is_empty() {
                                                     span {
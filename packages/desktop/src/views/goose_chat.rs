@@ -520,6 +520,20 @@ async fn send_message(
                 current_messages.push(result_msg);
                 messages.set(current_messages.clone());
             }
+            event @ AgentEvent::ModelChange { .. } => {
+                // Inline separator so the switch stays visible in the transcript
+                let separator = UiChatMessage {
+                    role: Role::System,
+                    content: MessageContent::Text(event.separator().unwrap_or_default()),
+                    timestamp: Some(chrono::Utc::now()),
+                    tool_calls: None,
+                    tool_results: None,
+                    metadata: None,
+                };
+                updated_conversation.add_message(separator.clone());
+                current_messages.push(separator);
+                messages.set(current_messages.clone());
+            }
             AgentEvent::Token(_usage) => {
                 // Update token usage display (could be added to UI)
             }
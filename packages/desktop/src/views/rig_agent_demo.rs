@@ -546,6 +546,17 @@ async fn handle_send_message(
                                 current_messages.push(result_msg);
                                 messages.set(current_messages.clone());
                             },
+                            event @ crate::agent::AgentEvent::ModelChange { .. } => {
+                                let separator = StreamingMessage {
+                                    content: event.separator().unwrap_or_default(),
+                                    chunk_type: ChunkType::Metadata,
+                                    metadata: None,
+                                    timestamp: chrono::Utc::now(),
+                                    is_complete: true,
+                                };
+                                current_messages.push(separator);
+                                messages.set(current_messages.clone());
+                            },
                             crate::agent::AgentEvent::Done => {
                                 // Mark the last message as complete
                                 if let Some(last_msg) = current_messages.last_mut() {
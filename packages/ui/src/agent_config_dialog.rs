@@ -3,6 +3,11 @@ use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 use api::{AgentConfig, GooseMode};
 use crate::ui_components::*;
+use crate::parameter_manager::ParameterManager;
+use crate::settings_validation::{
+    validate_numeric, NumericBounds, EXTENSION_TIMEOUT_SECONDS, MAX_ITERATIONS,
+    MAX_TURNS_WITHOUT_TOOLS,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AgentParameter {
@@ -15,7 +20,11 @@ pub struct AgentParameter {
 pub enum ParameterType {
     String,
     Number,
+    /// A number within inclusive bounds
+    Range { min: f64, max: f64 },
     Boolean,
+    /// One of a fixed set of strings
+    Enum(Vec<String>),
     Json,
 }
 
@@ -30,11 +39,97 @@ pub struct AgentData {
     pub parameters: Vec<AgentParameter>,
 }
 
+impl AgentData {
+    /// The agent's effective configuration: `config` with any parameter named after a config
+    /// field applied on top. Other parameters (sampling settings and the like) are left for the
+    /// request. The error names the first parameter that is out of range or of the wrong type.
+    pub fn to_agent_config(&self) -> Result<AgentConfig, String> {
+        let mut config = self.config.clone();
+        for parameter in &self.parameters {
+            let value = &parameter.value;
+            match parameter.key.as_str() {
+                "max_iterations" => {
+                    config.max_iterations = whole_number(parameter, MAX_ITERATIONS)? as usize
+                }
+                "max_turns_without_tools" => {
+                    config.max_turns_without_tools =
+                        whole_number(parameter, MAX_TURNS_WITHOUT_TOOLS)? as usize
+                }
+                "extension_timeout" => {
+                    config.extension_timeout = whole_number(parameter, EXTENSION_TIMEOUT_SECONDS)?
+                }
+                "compact_threshold" => {
+                    config.compact_threshold = value
+                        .as_f64()
+                        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+                        .filter(|threshold| *threshold > 0.0 && *threshold <= 1.0)
+                        .ok_or("compact_threshold must be a number above 0 and at most 1")?
+                        as f32
+                }
+                "goose_mode" | "mode" => {
+                    let mode = value.as_str().map(str::to_ascii_lowercase);
+                    config.goose_mode = match mode.as_deref() {
+                        Some("chat") => GooseMode::Chat,
+                        Some("agent") => GooseMode::Agent,
+                        Some("auto") => GooseMode::Auto,
+                        _ => {
+                            return Err(format!(
+                                "{} must be one of chat, agent or auto",
+                                parameter.key
+                            ))
+                        }
+                    }
+                }
+                "require_confirmation" => config.require_confirmation = flag(parameter)?,
+                "enable_tool_inspection" => config.enable_tool_inspection = flag(parameter)?,
+                "enable_auto_compact" => config.enable_auto_compact = flag(parameter)?,
+                "enable_extensions" => config.enable_extensions = flag(parameter)?,
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Edit an existing configuration as a new, unnamed agent
+impl From<AgentConfig> for AgentData {
+    fn from(config: AgentConfig) -> Self {
+        Self {
+            config,
+            ..AgentConfigDialogState::default().agent_data
+        }
+    }
+}
+
+fn whole_number(parameter: &AgentParameter, bounds: NumericBounds) -> Result<u64, String> {
+    let input = match &parameter.value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    validate_numeric(&input, bounds).map_err(|error| format!("{}: {}", parameter.key, error))
+}
+
+fn flag(parameter: &AgentParameter) -> Result<bool, String> {
+    match &parameter.value {
+        serde_json::Value::Bool(value) => Ok(*value),
+        serde_json::Value::String(text) => text
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} must be true or false", parameter.key)),
+        _ => Err(format!("{} must be true or false", parameter.key)),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct AgentConfigDialogState {
     pub agent_data: AgentData,
     pub show_emoji_picker: bool,
     pub name_error: Option<String>,
+    pub max_iterations_error: Option<String>,
+    /// Why the parameters don't make a valid configuration; blocks saving
+    pub config_error: Option<String>,
+    /// Whether every parameter row holds a value of its type; saving is disabled otherwise
+    pub parameters_valid: bool,
     pub selected_emoji: String,
 }
 
@@ -59,11 +154,15 @@ impl Default for AgentConfigDialogState {
                     enable_autopilot: false,
                     enable_extensions: true,
                     extension_timeout: 30,
+                    ..AgentConfig::default()
                 },
                 parameters: vec![],
             },
             show_emoji_picker: false,
             name_error: None,
+            max_iterations_error: None,
+            config_error: None,
+            parameters_valid: true,
             selected_emoji: "🤖".to_string(),
         }
     }
@@ -89,6 +188,9 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
                     agent_data: agent.clone(),
                     show_emoji_picker: false,
                     name_error: None,
+                    max_iterations_error: None,
+                    config_error: None,
+                    parameters_valid: true,
                     selected_emoji: agent.avatar.clone().unwrap_or("🤖".to_string()),
                 });
             } else {
@@ -106,6 +208,14 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
 
         let mut agent_data = current_state.agent_data.clone();
         agent_data.avatar = Some(current_state.selected_emoji.clone());
+        match agent_data.to_agent_config() {
+            Ok(config) => agent_data.config = config,
+            Err(error) => {
+                drop(current_state);
+                state.write().config_error = Some(error);
+                return;
+            }
+        }
 
         props.on_save.call(agent_data);
         props.on_open_change.call(false);
@@ -274,13 +384,41 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
                             Input {
                                 value: state.read().agent_data.config.max_iterations.to_string(),
                                 r#type: "number".to_string(),
-                                oninput: move |value| {
-                                    if let Ok(num) = value.parse() {
-                                        state.write().agent_data.config.max_iterations = num;
+                                oninput: move |value: String| {
+                                    match validate_numeric(&value, MAX_ITERATIONS) {
+                                        Ok(num) => {
+                                            state.write().agent_data.config.max_iterations = num as usize;
+                                            state.write().max_iterations_error = None;
+                                        }
+                                        Err(error) => state.write().max_iterations_error = Some(error),
                                     }
                                 },
                                 class: "w-24",
                             }
+                            if let Some(ref error) = state.read().max_iterations_error {
+                                p { class: "text-xs text-red-500 mt-1", "{error}" }
+                            }
+                        }
+
+                        // Compact Threshold
+                        if state.read().agent_data.config.enable_auto_compact {
+                            div {
+                                label { class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
+                                    "Compact Threshold"
+                                }
+                                Slider {
+                                    min: 0.1,
+                                    max: 1.0,
+                                    step: 0.05,
+                                    value: state.read().agent_data.config.compact_threshold,
+                                    on_change: move |threshold| {
+                                        state.write().agent_data.config.compact_threshold = threshold;
+                                    },
+                                }
+                                p { class: "text-xs text-gray-500 dark:text-gray-400 mt-1",
+                                    "Fraction of the context limit at which history is compacted"
+                                }
+                            }
                         }
 
                         // Checkboxes for boolean settings
@@ -335,6 +473,20 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
                         }
                     }
                 }
+
+                // Parameters
+                ParameterManager {
+                    parameters: state.read().agent_data.parameters.clone(),
+                    on_parameters_change: move |parameters| {
+                        state.write().agent_data.parameters = parameters;
+                        state.write().config_error = None;
+                    },
+                    on_validity_change: move |valid| state.write().parameters_valid = valid,
+                }
+            }
+
+            if let Some(ref error) = state.read().config_error {
+                p { class: "text-sm text-red-500 mt-4", role: "alert", "{error}" }
             }
 
             DialogFooter {
@@ -346,6 +498,7 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
                 Button {
                     onclick: handle_save,
                     variant: ButtonVariant::Primary,
+                    disabled: !state.read().parameters_valid,
                     {
                         if props.editing_agent.is_some() {
                             "Save Changes"
@@ -357,4 +510,43 @@ pub fn AgentConfigDialog(props: AgentConfigDialogProps) -> Element {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(key: &str, value: serde_json::Value) -> AgentParameter {
+        AgentParameter {
+            key: key.to_string(),
+            value,
+            param_type: ParameterType::Json,
+        }
+    }
+
+    #[test]
+    fn test_parameters_apply_to_config_and_are_range_checked() {
+        let mut agent = AgentData::from(AgentConfig::default());
+        agent.parameters = vec![
+            parameter("max_iterations", serde_json::json!(25)),
+            parameter("mode", serde_json::json!("Auto")),
+            parameter("require_confirmation", serde_json::json!("true")),
+            parameter("temperature", serde_json::json!(0.2)),
+        ];
+        let config = agent.to_agent_config().unwrap();
+        assert_eq!(config.max_iterations, 25);
+        assert_eq!(config.goose_mode, GooseMode::Auto);
+        assert!(config.require_confirmation);
+        assert_eq!(config.compact_threshold, AgentConfig::default().compact_threshold);
+
+        agent.parameters = vec![parameter("max_iterations", serde_json::json!(0))];
+        assert_eq!(
+            agent.to_agent_config(),
+            Err("max_iterations: Must be at least 1".to_string())
+        );
+        agent.parameters = vec![parameter("compact_threshold", serde_json::json!(1.5))];
+        assert!(agent.to_agent_config().is_err());
+        agent.parameters = vec![parameter("mode", serde_json::json!("turbo"))];
+        assert!(agent.to_agent_config().is_err());
+    }
+}
@@ -0,0 +1,172 @@
+// Appearance settings shared by the chat components
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Local storage key the appearance settings are persisted under
+const APPEARANCE_STORAGE_KEY: &str = "dioxus-chat:appearance";
+
+/// How streamed assistant text is revealed in the message list
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RevealMode {
+    /// Show tokens as soon as they arrive
+    Instant,
+    /// Reveal text at a steady rate, catching up if the stream gets too far ahead
+    Typewriter { chars_per_sec: u32 },
+}
+
+impl Default for RevealMode {
+    fn default() -> Self {
+        RevealMode::Typewriter { chars_per_sec: 240 }
+    }
+}
+
+/// Vertical density of the message list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum MessageDensity {
+    #[default]
+    Comfortable,
+    /// Less padding, tighter spacing and no avatars
+    Compact,
+}
+
+impl MessageDensity {
+    /// Scrollable container holding the messages
+    pub fn list_class(self) -> &'static str {
+        match self {
+            MessageDensity::Comfortable => "flex-1 overflow-y-auto p-4",
+            MessageDensity::Compact => "flex-1 overflow-y-auto px-3 py-2",
+        }
+    }
+
+    /// Spacing between consecutive messages
+    pub fn stack_class(self) -> &'static str {
+        match self {
+            MessageDensity::Comfortable => "space-y-4",
+            MessageDensity::Compact => "space-y-1",
+        }
+    }
+
+    /// Row aligning a single message bubble
+    pub fn row_class(self, is_user: bool) -> &'static str {
+        match (self, is_user) {
+            (MessageDensity::Comfortable, true) => "flex justify-end mb-4",
+            (MessageDensity::Comfortable, false) => "flex justify-start mb-4",
+            (MessageDensity::Compact, true) => "flex justify-end mb-1",
+            (MessageDensity::Compact, false) => "flex justify-start mb-1",
+        }
+    }
+
+    pub fn bubble_padding(self) -> &'static str {
+        match self {
+            MessageDensity::Comfortable => "p-3",
+            MessageDensity::Compact => "px-2 py-1",
+        }
+    }
+
+    pub fn show_avatars(self) -> bool {
+        self == MessageDensity::Comfortable
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    pub reveal_mode: RevealMode,
+    pub auto_scroll: bool,
+    /// Disable animations regardless of the reveal mode
+    pub reduce_motion: bool,
+    pub density: MessageDensity,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        Self {
+            reveal_mode: RevealMode::default(),
+            auto_scroll: true,
+            reduce_motion: false,
+            density: MessageDensity::default(),
+        }
+    }
+}
+
+impl AppearanceSettings {
+    /// The reveal mode to actually use, taking reduced motion into account
+    pub fn effective_reveal_mode(&self) -> RevealMode {
+        effective_reveal_mode(self.reveal_mode, self.reduce_motion)
+    }
+}
+
+pub fn effective_reveal_mode(mode: RevealMode, reduce_motion: bool) -> RevealMode {
+    if reduce_motion {
+        RevealMode::Instant
+    } else {
+        mode
+    }
+}
+
+/// Provide appearance settings to the component tree. Call once at the app root; the
+/// settings are loaded from local storage on mount and saved whenever they change.
+pub fn use_appearance_provider() -> Signal<AppearanceSettings> {
+    let mut settings = use_context_provider(|| Signal::new(AppearanceSettings::default()));
+    let mut loaded = use_signal(|| false);
+
+    use_effect(move || {
+        spawn(async move {
+            let stored = document::eval(&format!(
+                "return localStorage.getItem('{}');",
+                APPEARANCE_STORAGE_KEY
+            ))
+            .join::<Option<String>>()
+            .await
+            .ok()
+            .flatten();
+            if let Some(stored) = stored.and_then(|json| serde_json::from_str(&json).ok()) {
+                settings.set(stored);
+            }
+            loaded.set(true);
+        });
+    });
+
+    use_effect(move || {
+        let current = settings.read().clone();
+        // Don't overwrite the stored settings with defaults before they've been loaded
+        if !loaded() {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(&current) {
+            let _ = document::eval(&format!(
+                "localStorage.setItem('{}', {});",
+                APPEARANCE_STORAGE_KEY,
+                serde_json::Value::String(json)
+            ));
+        }
+    });
+
+    settings
+}
+
+/// Current appearance settings, or the defaults when no provider is mounted
+pub fn use_appearance() -> AppearanceSettings {
+    try_use_context::<Signal<AppearanceSettings>>()
+        .map(|settings| settings.read().clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_density_renders_tighter_classes() {
+        let comfortable = MessageDensity::Comfortable;
+        let compact = MessageDensity::Compact;
+
+        assert_ne!(comfortable.list_class(), compact.list_class());
+        assert_ne!(comfortable.stack_class(), compact.stack_class());
+        assert_ne!(comfortable.row_class(true), compact.row_class(true));
+        assert_ne!(comfortable.row_class(false), compact.row_class(false));
+        assert_ne!(comfortable.bubble_padding(), compact.bubble_padding());
+        assert!(comfortable.show_avatars());
+        assert!(!compact.show_avatars());
+    }
+}
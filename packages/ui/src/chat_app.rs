@@ -299,6 +299,7 @@ Would you like me to dive deeper into any specific aspect of building the chat a
         enable_extensions: true,
         extension_timeout: 30,
         goose_mode: GooseMode::Agent,
+        ..AgentConfig::default()
     };
 
     rsx! {
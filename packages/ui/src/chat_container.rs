@@ -18,6 +18,8 @@ pub struct ChatContainerProps {
     pub loading: Option<bool>,
     pub streaming: Option<bool>,
     pub on_stop_streaming: Option<EventHandler>,
+    /// Regenerate the latest reply; called with its message id
+    pub on_regenerate: Option<EventHandler<String>>,
     pub placeholder: Option<String>,
 }
 
@@ -26,6 +28,12 @@ pub fn ChatContainer(props: ChatContainerProps) -> Element {
     let loading = props.loading.unwrap_or(false);
     let streaming = props.streaming.unwrap_or(false);
     let _messages_end = use_signal(|| 0);
+    // Only the latest reply can be regenerated, and not while another is on its way
+    let regenerate_id = props
+        .messages
+        .last()
+        .filter(|message| !message.is_user && !loading && !streaming)
+        .map(|message| message.id.clone());
 
     // Auto scroll to bottom when new messages arrive
     use_effect(move || {
@@ -65,6 +73,13 @@ pub fn ChatContainer(props: ChatContainerProps) -> Element {
                                 is_user: message.is_user,
                                 timestamp: message.timestamp.clone(),
                                 avatar: message.avatar.clone(),
+                                on_regenerate: props
+                                    .on_regenerate
+                                    .filter(|_| regenerate_id.as_ref() == Some(&message.id))
+                                    .map(|handler| {
+                                        let message_id = message.id.clone();
+                                        EventHandler::new(move |_| handler.call(message_id.clone()))
+                                    }),
                             }
                         }
                         if loading || streaming {
@@ -0,0 +1,149 @@
+// Inline [n] citations in assistant replies, linked to the web_search results they cite
+use api::{parse_search_sources, SearchSource, ToolResult};
+use dioxus::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CitationSegment {
+    Text(String),
+    Citation(SearchSource),
+}
+
+/// Sources from the turn's `web_search` results, in the order they were returned
+pub fn collect_sources(results: &[ToolResult]) -> Vec<SearchSource> {
+    results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .filter_map(|result| result.result.as_str())
+        .flat_map(parse_search_sources)
+        .collect()
+}
+
+/// Split reply content into text and `[n]` markers. A marker becomes a citation only when
+/// a source with that number was collected; anything else stays as plain text.
+pub fn parse_citations(content: &str, sources: &[SearchSource]) -> Vec<CitationSegment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = content;
+
+    while let Some(open) = rest.find('[') {
+        text.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let source = after.find(']').and_then(|close| {
+            let marker = &after[..close];
+            let index: usize = marker
+                .chars()
+                .all(|c| c.is_ascii_digit())
+                .then(|| marker.parse().ok())
+                .flatten()?;
+            let source = sources.iter().find(|source| source.index == index)?;
+            Some((close, source))
+        });
+
+        match source {
+            Some((close, source)) => {
+                if !text.is_empty() {
+                    segments.push(CitationSegment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(CitationSegment::Citation(source.clone()));
+                rest = &after[close + 1..];
+            }
+            None => {
+                text.push('[');
+                rest = after;
+            }
+        }
+    }
+
+    text.push_str(rest);
+    if !text.is_empty() {
+        segments.push(CitationSegment::Text(text));
+    }
+    segments
+}
+
+/// Reply text with clickable citation markers and a sources footer
+#[component]
+pub fn CitedContent(content: String, sources: Vec<SearchSource>) -> Element {
+    let segments = parse_citations(&content, &sources);
+
+    rsx! {
+        span {
+            for segment in segments {
+                match segment {
+                    CitationSegment::Text(text) => rsx! { "{text}" },
+                    CitationSegment::Citation(source) => rsx! {
+                        a {
+                            class: "text-blue-600 dark:text-blue-400 hover:underline align-super text-xs",
+                            href: "{source.url}",
+                            target: "_blank",
+                            rel: "noopener noreferrer",
+                            title: "{source.title}",
+                            "[{source.index}]"
+                        }
+                    },
+                }
+            }
+        }
+        if !sources.is_empty() {
+            div { class: "mt-3 pt-2 border-t border-gray-200 dark:border-gray-700 text-xs whitespace-normal",
+                div { class: "font-medium mb-1 opacity-70", "Sources" }
+                ol { class: "space-y-1",
+                    for source in sources.iter() {
+                        li { key: "{source.index}",
+                            span { class: "opacity-70", "[{source.index}] " }
+                            a {
+                                class: "text-blue-600 dark:text-blue-400 hover:underline break-all",
+                                href: "{source.url}",
+                                target: "_blank",
+                                rel: "noopener noreferrer",
+                                "{source.title}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_map_to_collected_source_urls() {
+        let output = "Web search results for: rust\n\
+            [1] The Rust Book - https://doc.rust-lang.org/book/\n\
+            [2] Rust by Example - https://doc.rust-lang.org/rust-by-example/";
+        let sources = collect_sources(&[ToolResult {
+            tool_call_id: "call-1".to_string(),
+            result: serde_json::Value::String(output.to_string()),
+            error: None,
+        }]);
+        assert_eq!(sources.len(), 2);
+
+        let segments = parse_citations(
+            "Start with the book [1], then [2]. See [7] and [a].",
+            &sources,
+        );
+        let urls: Vec<&str> = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                CitationSegment::Citation(source) => Some(source.url.as_str()),
+                CitationSegment::Text(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://doc.rust-lang.org/book/",
+                "https://doc.rust-lang.org/rust-by-example/"
+            ]
+        );
+        // Unknown indices are left as plain text
+        assert_eq!(
+            segments.last(),
+            Some(&CitationSegment::Text(". See [7] and [a].".to_string()))
+        );
+    }
+}
@@ -1,9 +1,19 @@
 // Enhanced Chat Interface with agent configuration and improved UI
 use dioxus::prelude::*;
-use api::{AgentConfig, GooseMode, ChatRequest, ChatMessage, Role};
+use api::{AgentConfig, GooseMode, ChatRequest, ChatMessage, ModelPricing, ProviderError, Role, SearchSource, TokenUsage, Tool};
 use crate::ui_components::*;
 use crate::agent_config_dialog::{AgentConfigDialog, AgentData};
 use crate::parameter_manager::ParameterManager;
+use crate::reasoning_panel::ReasoningPanel;
+use crate::citations::CitedContent;
+use crate::markdown::Markdown;
+use crate::appearance::{effective_reveal_mode, use_appearance, AppearanceSettings, RevealMode};
+
+/// Interval between typewriter reveal ticks
+const REVEAL_TICK_MS: u64 = 33;
+
+/// How long a message's copy button shows "Copied!"
+const COPIED_NOTICE_MS: u64 = 1500;
 
 #[derive(Debug, Clone, PartialEq, Props)]
 pub struct EnhancedChatMessage {
@@ -15,6 +25,17 @@ pub struct EnhancedChatMessage {
     pub agent_mode: Option<GooseMode>,
     pub is_thinking: bool,
     pub token_usage: Option<u32>,
+    /// Model that wrote an assistant reply; labelled on the bubble
+    pub model: Option<String>,
+    /// Search results the reply can cite as `[n]`
+    pub sources: Vec<SearchSource>,
+    /// The user stopped this reply before it finished; `content` is what had arrived
+    #[props(default)]
+    pub interrupted: bool,
+    /// Another alternative of this reply was chosen. It stays on screen for comparison but
+    /// is left out of the history sent to the model.
+    #[props(default)]
+    pub superseded: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,14 +43,27 @@ pub struct EnhancedChatState {
     pub messages: Vec<EnhancedChatMessage>,
     pub agent_config: AgentConfig,
     pub is_streaming: bool,
+    /// A reply has been requested and nothing has arrived yet; the typing indicator shows
+    /// until the first text does
+    pub awaiting_first_token: bool,
     pub current_model: String,
     pub agent_name: String,
     pub show_config_dialog: bool,
     pub editing_agent: Option<AgentData>,
+    pub reveal_mode: RevealMode,
+    pub auto_scroll: bool,
+    pub reduce_motion: bool,
+    /// Why the last send failed; shown under the messages until the next send
+    pub send_error: Option<ProviderError>,
+    /// Pricing of `current_model`; `None` when the model has no published prices
+    pub pricing: Option<ModelPricing>,
+    /// Estimated cost of the replies so far this session, in `pricing`'s currency
+    pub session_cost: f64,
 }
 
 impl Default for EnhancedChatState {
     fn default() -> Self {
+        let appearance = AppearanceSettings::default();
         Self {
             messages: Vec::new(),
             agent_config: AgentConfig {
@@ -44,13 +78,256 @@ impl Default for EnhancedChatState {
                 enable_autopilot: false,
                 enable_extensions: true,
                 extension_timeout: 30,
+                ..AgentConfig::default()
             },
             is_streaming: false,
+            awaiting_first_token: false,
             current_model: "gpt-3.5-turbo".to_string(),
             agent_name: "Assistant".to_string(),
             show_config_dialog: false,
             editing_agent: None,
+            reveal_mode: appearance.reveal_mode,
+            auto_scroll: appearance.auto_scroll,
+            reduce_motion: appearance.reduce_motion,
+            send_error: None,
+            pricing: None,
+            session_cost: 0.0,
+        }
+    }
+}
+
+impl EnhancedChatState {
+    pub fn apply_appearance(&mut self, appearance: &AppearanceSettings) {
+        self.reveal_mode = appearance.reveal_mode;
+        self.auto_scroll = appearance.auto_scroll;
+        self.reduce_motion = appearance.reduce_motion;
+    }
+
+    /// A reply has been requested: streaming, with the typing indicator up until text arrives
+    pub fn start_reply(&mut self) {
+        self.is_streaming = true;
+        self.awaiting_first_token = true;
+        self.send_error = None;
+    }
+
+    /// The reply stream has ended, with the error that ended it if it failed
+    pub fn finish_reply(&mut self, error: Option<ProviderError>) {
+        self.is_streaming = false;
+        self.awaiting_first_token = false;
+        self.send_error = error;
+    }
+
+    /// Record a finished reply's token usage on the latest assistant message and add its cost
+    /// to the session total
+    pub fn record_usage(&mut self, usage: &TokenUsage) {
+        if let Some(message) = self.messages.iter_mut().rev().find(|m| !m.is_user) {
+            message.token_usage = Some(usage.total_tokens);
+        }
+        if let Some(pricing) = &self.pricing {
+            self.session_cost += usage.estimated_cost(pricing);
+        }
+    }
+
+    /// The session cost for the header, or "—" when the model is unpriced
+    pub fn session_cost_label(&self) -> String {
+        match &self.pricing {
+            Some(pricing) if pricing.currency == "USD" => format!("${:.4}", self.session_cost),
+            Some(pricing) => format!("{:.4} {}", self.session_cost, pricing.currency),
+            None => "—".to_string(),
+        }
+    }
+
+    /// Append streamed text to the assistant message being written. Feed this from
+    /// `use_coalesced_stream` rather than per token to keep re-renders to one per frame.
+    pub fn append_streaming_text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.awaiting_first_token = false;
+        }
+        // Chunks already in flight when a reply is stopped are dropped
+        if let Some(message) = self
+            .messages
+            .last_mut()
+            .filter(|m| !m.is_user && !m.interrupted)
+        {
+            message.content.push_str(text);
+        }
+    }
+
+    /// Stop waiting on the reply being streamed. What has arrived so far is kept, marked as
+    /// interrupted. Returns false when nothing was streaming.
+    pub fn interrupt_stream(&mut self) -> bool {
+        if !self.is_streaming {
+            return false;
+        }
+        self.is_streaming = false;
+        self.awaiting_first_token = false;
+        if let Some(message) = self.messages.last_mut().filter(|m| !m.is_user) {
+            message.interrupted = true;
+        }
+        true
+    }
+
+    /// The conversation a reply was generated from: everything before it, with only the
+    /// chosen alternative of each earlier reply
+    pub fn history_before(&self, message_id: &str) -> Vec<EnhancedChatMessage> {
+        self.messages
+            .iter()
+            .take_while(|message| message.id != message_id)
+            .filter(|message| !message.superseded)
+            .cloned()
+            .collect()
+    }
+
+    /// Cut the conversation back to just before `message_id`, dropping it and everything
+    /// after it. Returns the id of the message now last (`None` when nothing is left), which is
+    /// where the stored session should be truncated too; `None` as well if the id isn't here.
+    pub fn rewind_before(&mut self, message_id: &str) -> Option<Option<String>> {
+        let index = self.messages.iter().position(|message| message.id == message_id)?;
+        self.messages.truncate(index);
+        Some(self.messages.last().map(|message| message.id.clone()))
+    }
+
+    /// Place a regenerated reply right after the one it replaces, keeping the original
+    /// (and any earlier alternatives) for comparison. The new reply becomes the chosen one.
+    pub fn insert_alternative(&mut self, original_id: &str, mut reply: EnhancedChatMessage) {
+        let Some(index) = self
+            .messages
+            .iter()
+            .rposition(|message| message.id == original_id)
+        else {
+            self.messages.push(reply);
+            return;
+        };
+
+        let run = self.alternatives_of(index);
+        for message in &mut self.messages[run.clone()] {
+            message.superseded = true;
+        }
+        reply.superseded = false;
+        self.messages.insert(run.end, reply);
+    }
+
+    /// Continue the conversation from `message_id` instead of its other alternatives
+    pub fn select_alternative(&mut self, message_id: &str) {
+        let Some(index) = self
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)
+        else {
+            return;
+        };
+        let run = self.alternatives_of(index);
+        for message in &mut self.messages[run] {
+            message.superseded = message.id != message_id;
+        }
+    }
+
+    /// The run of consecutive assistant replies the message at `index` belongs to
+    fn alternatives_of(&self, index: usize) -> std::ops::Range<usize> {
+        let start = self.messages[..index]
+            .iter()
+            .rposition(|message| message.is_user)
+            .map_or(0, |user| user + 1);
+        let end = index
+            + self.messages[index..]
+                .iter()
+                .take_while(|message| !message.is_user)
+                .count();
+        start..end
+    }
+}
+
+impl EnhancedChatMessage {
+    /// This message on its own as Markdown, for copying out of the chat
+    pub fn to_markdown(&self) -> String {
+        let author = if self.is_user {
+            "You".to_string()
+        } else {
+            self.agent_name.clone().unwrap_or_else(|| "Assistant".to_string())
+        };
+        let mut header = format!("**{}**", author);
+        if let Some(ref model) = self.model {
+            header.push_str(&format!(" (via {})", model));
+        }
+        if let Some(ref timestamp) = self.timestamp {
+            header.push_str(&format!(" - {}", timestamp));
+        }
+        format!("{}\n\n{}\n", header, self.content.trim_end())
+    }
+}
+
+/// `text` as a Markdown blockquote. Every line is prefixed, fences included, so quoted code
+/// blocks stay code blocks.
+pub fn quote_block(text: &str) -> String {
+    text.trim_end()
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                ">".to_string()
+            } else {
+                format!("> {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Composer text after quote-replying to `quoted`: the quote goes after any draft, followed
+/// by a blank line to type the reply on
+pub fn insert_quote(draft: &str, quoted: &str) -> String {
+    let draft = draft.trim_end();
+    if draft.is_empty() {
+        format!("{}\n\n", quote_block(quoted))
+    } else {
+        format!("{}\n\n{}\n\n", draft, quote_block(quoted))
+    }
+}
+
+/// Tracks how much of the streaming assistant message has been revealed in typewriter mode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypewriterReveal {
+    pub message_id: Option<String>,
+    pub revealed: usize,
+    carry: f32,
+}
+
+impl TypewriterReveal {
+    /// Maximum lag, in seconds of reveal time, before the reveal jumps ahead to catch up
+    pub const MAX_LAG_SECS: f32 = 1.5;
+
+    /// Start revealing a new message from the beginning
+    pub fn reset(&mut self, message_id: Option<String>) {
+        self.message_id = message_id;
+        self.revealed = 0;
+        self.carry = 0.0;
+    }
+
+    /// Advance the reveal by `elapsed_secs` worth of characters, never revealing more than
+    /// `available` and never lagging more than `MAX_LAG_SECS` behind it.
+    pub fn advance(&mut self, available: usize, chars_per_sec: u32, elapsed_secs: f32) -> usize {
+        if self.revealed >= available {
+            self.revealed = available;
+            self.carry = 0.0;
+            return self.revealed;
+        }
+
+        let exact = chars_per_sec as f32 * elapsed_secs + self.carry;
+        let mut step = exact.floor() as usize;
+        self.carry = exact - step as f32;
+
+        let backlog = available - self.revealed;
+        let max_lag = (chars_per_sec as f32 * Self::MAX_LAG_SECS) as usize;
+        if backlog.saturating_sub(step) > max_lag {
+            step = backlog - max_lag;
+            self.carry = 0.0;
         }
+
+        self.revealed = (self.revealed + step).min(available);
+        self.revealed
+    }
+
+    pub fn is_caught_up(&self, available: usize) -> bool {
+        self.revealed >= available
     }
 }
 
@@ -60,12 +337,139 @@ pub struct EnhancedChatContainerProps {
     pub on_send_message: EventHandler<String>,
     pub on_agent_config_change: Option<EventHandler<AgentConfig>>,
     pub available_models: Vec<String>,
+    /// Overrides `EnhancedChatState::reveal_mode` when set
+    pub reveal_mode: Option<RevealMode>,
+    /// Overrides `EnhancedChatState::auto_scroll` when set
+    pub auto_scroll: Option<bool>,
+    /// Stored session backing this chat; enables the reasoning panel
+    pub session_id: Option<String>,
+    /// Regenerate an assistant reply: called with its message id and, for a one-off model
+    /// override, the model to use instead of the current one
+    pub on_regenerate: Option<EventHandler<(String, Option<String>)>>,
+    /// Resend after a failed send; without it the error has no retry button
+    pub on_retry: Option<EventHandler>,
+    /// Resend an edited prompt. The conversation has already been cut back to before the
+    /// edited message; called with the id of the message now last (pass it to
+    /// `truncate_session_after` so the stored session matches) and the new text to send.
+    pub on_edit: Option<EventHandler<(Option<String>, String)>>,
+    /// Stop the reply being streamed. Without it, stopping cancels `session_id`'s reply on
+    /// the server.
+    pub on_cancel: Option<EventHandler>,
 }
 
 #[component]
-pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
+pub fn EnhancedChatContainer(mut props: EnhancedChatContainerProps) -> Element {
     let mut message_input = use_signal(String::new);
+    let mut reveal = use_signal(TypewriterReveal::default);
+
+    let reveal_mode = effective_reveal_mode(
+        props.reveal_mode.unwrap_or(props.state.read().reveal_mode),
+        props.state.read().reduce_motion,
+    );
+    let auto_scroll = props.auto_scroll.unwrap_or(props.state.read().auto_scroll);
+
+    // Props are plain values, so mirror them into signals the ticker can read
+    let mut active_reveal_mode = use_signal(|| reveal_mode);
+    use_effect(use_reactive!(|reveal_mode| active_reveal_mode.set(reveal_mode)));
+
+    // Honour the OS-level reduced motion preference
+    use_effect(move || {
+        spawn(async move {
+            let prefers_reduced = document::eval(
+                "return !!(window.matchMedia && window.matchMedia('(prefers-reduced-motion: reduce)').matches);",
+            )
+            .join::<bool>()
+            .await
+            .unwrap_or(false);
+            if prefers_reduced {
+                props.state.write().reduce_motion = true;
+            }
+        });
+    });
+
+    // Typewriter ticker: reveals the streaming assistant message at the configured rate
+    use_future(move || async move {
+        loop {
+            let _ = document::eval(&format!(
+                "await new Promise(r => setTimeout(r, {})); return true;",
+                REVEAL_TICK_MS
+            ))
+            .join::<bool>()
+            .await;
+
+            let RevealMode::Typewriter { chars_per_sec } = *active_reveal_mode.peek() else {
+                continue;
+            };
+
+            let (last_id, available, is_streaming) = {
+                let state = props.state.peek();
+                match state.messages.last().filter(|m| !m.is_user) {
+                    Some(message) => (
+                        Some(message.id.clone()),
+                        message.content.chars().count(),
+                        state.is_streaming,
+                    ),
+                    None => (None, 0, state.is_streaming),
+                }
+            };
+
+            if reveal.peek().message_id != last_id {
+                if is_streaming {
+                    reveal.write().reset(last_id);
+                } else {
+                    continue;
+                }
+            }
+
+            if !reveal.peek().is_caught_up(available) {
+                reveal
+                    .write()
+                    .advance(available, chars_per_sec, REVEAL_TICK_MS as f32 / 1000.0);
+            }
+        }
+    });
+
     let messages = props.state.read().messages.clone();
+    let reduce_motion = props.state.read().reduce_motion;
+    let density = use_appearance().density;
+    let padding = density.bubble_padding();
+
+    // Keep the newest content in view while messages arrive
+    use_effect(move || {
+        let _ = props.state.read().messages.len();
+        let _ = reveal.read().revealed;
+        if !auto_scroll {
+            return;
+        }
+        let behavior = if props.state.peek().reduce_motion {
+            "auto"
+        } else {
+            "smooth"
+        };
+        let _ = document::eval(&format!(
+            "const el = document.getElementById('enhanced-chat-messages'); if (el) {{ el.scrollTo({{ top: el.scrollHeight, behavior: '{}' }}); }}",
+            behavior
+        ));
+    });
+
+    // Trim the message currently being revealed by the typewriter
+    let messages: Vec<EnhancedChatMessage> = match reveal_mode {
+        RevealMode::Instant => messages,
+        RevealMode::Typewriter { .. } => {
+            let reveal = reveal.read();
+            messages
+                .into_iter()
+                .map(|mut message| {
+                    if reveal.message_id.as_deref() == Some(message.id.as_str())
+                        && !reveal.is_caught_up(message.content.chars().count())
+                    {
+                        message.content = message.content.chars().take(reveal.revealed).collect();
+                    }
+                    message
+                })
+                .collect()
+        }
+    };
 
     rsx! {
         div { class: "flex h-full bg-gray-50 dark:bg-gray-900",
@@ -99,6 +503,12 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
                                             format!("{:?}", props.state.read().agent_config.goose_mode)
                                         }
                                     }
+                                    Badge {
+                                        variant: BadgeVariant::Outline,
+                                        {
+                                            format!("Cost: {}", props.state.read().session_cost_label())
+                                        }
+                                    }
                                     if props.state.read().is_streaming {
                                         Badge {
                                             variant: BadgeVariant::Default,
@@ -123,7 +533,13 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
                 }
 
                 // Messages Area
-                div { class: "flex-1 overflow-y-auto p-4",
+                // Announced politely as a log; aria-busy holds announcements until a streamed reply settles
+                div {
+                    id: "enhanced-chat-messages",
+                    class: density.list_class(),
+                    role: "log",
+                    "aria-live": "polite",
+                    "aria-busy": props.state.read().is_streaming,
                     if messages.is_empty() {
                         div { class: "flex flex-col items-center justify-center h-full text-gray-500 dark:text-gray-400",
                             div { class: "text-6xl mb-4", "💬" }
@@ -142,21 +558,81 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
                             EnhancedMessageBubble {
                                 key: "{message.id}-{index}",
                                 message: message.clone(),
+                                regenerate_models: props.available_models.clone(),
+                                can_regenerate: !props.state.read().is_streaming,
+                                on_select: message.superseded.then(|| {
+                                    let message_id = message.id.clone();
+                                    EventHandler::new(move |_| {
+                                        props.state.write().select_alternative(&message_id)
+                                    })
+                                }),
+                                on_regenerate: props.on_regenerate.map(|handler| {
+                                    let message_id = message.id.clone();
+                                    EventHandler::new(move |model: Option<String>| {
+                                        handler.call((message_id.clone(), model))
+                                    })
+                                }),
+                                on_edit: props.on_edit.map(|handler| {
+                                    let message_id = message.id.clone();
+                                    EventHandler::new(move |text: String| {
+                                        let rewound = props.state.write().rewind_before(&message_id);
+                                        if let Some(last_kept) = rewound {
+                                            handler.call((last_kept, text));
+                                        }
+                                    })
+                                }),
+                                on_quote: move |quoted: String| {
+                                    let draft = message_input.read().clone();
+                                    message_input.set(insert_quote(&draft, &quoted));
+                                    let _ = document::eval(
+                                        "const el = document.getElementById('enhanced-chat-input'); if (el) { el.focus(); el.setSelectionRange(el.value.length, el.value.length); }",
+                                    );
+                                },
                             }
                         }
 
-                        if props.state.read().is_streaming {
-                            div { class: "flex justify-start",
-                                div { class: "bg-gray-200 dark:bg-gray-700 rounded-lg p-3",
+                        // Until the first text arrives; after that the reply itself shows progress
+                        if props.state.read().awaiting_first_token {
+                            div { class: density.row_class(false),
+                                div {
+                                    class: "bg-gray-200 dark:bg-gray-700 rounded-lg {padding}",
+                                    role: "status",
+                                    "aria-label": "Assistant is typing",
                                     div { class: "flex space-x-1",
-                                        div { class: "w-2 h-2 bg-gray-400 rounded-full animate-bounce" }
-                                        div { class: "w-2 h-2 bg-gray-400 rounded-full animate-bounce", style: "animation-delay: 0.1s" }
-                                        div { class: "w-2 h-2 bg-gray-400 rounded-full animate-bounce", style: "animation-delay: 0.2s" }
+                                        for delay in ["0s", "0.1s", "0.2s"] {
+                                            div {
+                                                class: if reduce_motion {
+                                                    "w-2 h-2 bg-gray-400 rounded-full"
+                                                } else {
+                                                    "w-2 h-2 bg-gray-400 rounded-full animate-bounce"
+                                                },
+                                                style: "animation-delay: {delay}",
+                                            }
+                                        }
                                     }
                                 }
                             }
                         }
                     }
+
+                    if let Some(error) = props.state.read().send_error.clone() {
+                        if !props.state.read().is_streaming {
+                            div { class: "px-4 py-2",
+                                ErrorState {
+                                    kind: error.kind,
+                                    message: error.message,
+                                    code: error.code,
+                                    on_retry: props.on_retry,
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if let Some(session_id) = props.session_id.clone() {
+                    div { class: "px-4 pb-2",
+                        ReasoningPanel { session_id }
+                    }
                 }
 
                 // Input Area with Enhanced Features
@@ -206,6 +682,7 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
                             // Message Input
                             div { class: "flex-1",
                                 textarea {
+                                    id: "enhanced-chat-input",
                                     r#type: "text",
                                     class: "w-full px-4 py-3 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 focus:outline-none focus:ring-2 focus:ring-blue-500 resize-none",
                                     placeholder: "Type your message here...",
@@ -217,27 +694,42 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
                                 }
                             }
 
-                            // Send Button
-                            button {
-                                class: if props.state.read().is_streaming || message_input.read().trim().is_empty() {
-                                    "px-6 py-3 bg-gray-400 text-white rounded-lg cursor-not-allowed"
-                                } else {
-                                    "px-6 py-3 bg-blue-500 hover:bg-blue-600 text-white rounded-lg transition-colors"
-                                },
-                                onclick: move |_| {
-                                    let content = message_input.read().clone();
-                                    if !content.trim().is_empty() && !props.state.read().is_streaming {
-                                        message_input.set(String::new());
-                                        props.on_send_message.call(content);
-                                    }
-                                },
-                                disabled: props.state.read().is_streaming || message_input.read().trim().is_empty(),
-                                {
-                                    if props.state.read().is_streaming {
-                                        "⏳ Processing..."
+                            // Stop while a reply streams, Send otherwise
+                            if props.state.read().is_streaming {
+                                button {
+                                    class: "px-6 py-3 bg-red-500 hover:bg-red-600 text-white rounded-lg transition-colors",
+                                    "aria-label": "Stop generating",
+                                    onclick: move |_| {
+                                        if !props.state.write().interrupt_stream() {
+                                            return;
+                                        }
+                                        if let Some(on_cancel) = props.on_cancel {
+                                            on_cancel.call(());
+                                        } else if let Some(session_id) = props.session_id.clone() {
+                                            spawn(async move {
+                                                let _ = api::cancel_session(session_id).await;
+                                            });
+                                        }
+                                    },
+                                    "⏹ Stop"
+                                }
+                            } else {
+                                button {
+                                    class: if message_input.read().trim().is_empty() {
+                                        "px-6 py-3 bg-gray-400 text-white rounded-lg cursor-not-allowed"
                                     } else {
-                                        "Send Message"
-                                    }
+                                        "px-6 py-3 bg-blue-500 hover:bg-blue-600 text-white rounded-lg transition-colors"
+                                    },
+                                    onclick: move |_| {
+                                        let content = message_input.read().clone();
+                                        if !content.trim().is_empty() && !props.state.read().is_streaming {
+                                            message_input.set(String::new());
+                                            props.state.write().send_error = None;
+                                            props.on_send_message.call(content);
+                                        }
+                                    },
+                                    disabled: message_input.read().trim().is_empty(),
+                                    "Send Message"
                                 }
                             }
                         }
@@ -299,34 +791,94 @@ pub fn EnhancedChatContainer(props: EnhancedChatContainerProps) -> Element {
 #[derive(Clone, PartialEq, Props)]
 pub struct EnhancedMessageBubbleProps {
     pub message: EnhancedChatMessage,
+    /// Models offered for "regenerate with"
+    #[props(default)]
+    pub regenerate_models: Vec<String>,
+    /// False while a reply is streaming, so regenerating or editing can't start a second stream
+    #[props(default = true)]
+    pub can_regenerate: bool,
+    /// Called with a one-off model override, or `None` to regenerate with the current model
+    pub on_regenerate: Option<EventHandler<Option<String>>>,
+    /// Continue the conversation from this superseded alternative
+    pub on_select: Option<EventHandler>,
+    /// Quote-reply: called with the selected text in this message, or all of it
+    pub on_quote: Option<EventHandler<String>>,
+    /// Edit a user prompt: called with the new text, which replaces this message and
+    /// everything after it
+    pub on_edit: Option<EventHandler<String>>,
 }
 
 #[component]
 pub fn EnhancedMessageBubble(props: EnhancedMessageBubbleProps) -> Element {
+    let density = use_appearance().density;
+    let padding = density.bubble_padding();
+    let mut copied = use_signal(|| false);
+    // Draft of the prompt while it is being edited
+    let mut editing = use_signal(|| None::<String>);
+
     rsx! {
         div {
-            class: if props.message.is_user {
-                "flex justify-end mb-4"
-            } else {
-                "flex justify-start mb-4"
-            },
+            class: density.row_class(props.message.is_user),
 
             div {
                 class: if props.message.is_user {
-                    "max-w-xs lg:max-w-2xl bg-blue-500 text-white rounded-lg p-3 shadow-md"
+                    "relative group max-w-xs lg:max-w-2xl bg-blue-500 text-white rounded-lg shadow-md {padding}"
                 } else {
-                    "max-w-xs lg:max-w-2xl bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 rounded-lg p-3 shadow-md border border-gray-200 dark:border-gray-700"
+                    "relative group max-w-xs lg:max-w-2xl bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 rounded-lg shadow-md border border-gray-200 dark:border-gray-700 {padding}"
                 },
 
+                // Copies the raw content, so code comes out as written rather than as rendered.
+                // Hidden until hover, but shown whenever it has keyboard focus.
+                button {
+                    r#type: "button",
+                    class: if copied() {
+                        "absolute -top-3 right-2 px-2 py-0.5 rounded text-xs shadow bg-green-600 text-white opacity-100"
+                    } else {
+                        "absolute -top-3 right-2 px-2 py-0.5 rounded text-xs shadow bg-white dark:bg-gray-700 text-gray-700 dark:text-gray-200 border border-gray-200 dark:border-gray-600 opacity-0 group-hover:opacity-100 focus-visible:opacity-100 focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-blue-500 transition-opacity"
+                    },
+                    title: "Copy message text",
+                    "aria-label": if copied() { "Copied" } else { "Copy message text" },
+                    "aria-live": "polite",
+                    onclick: {
+                        let content = props.message.content.clone();
+                        move |_| {
+                            let text = serde_json::to_string(&content).unwrap_or_default();
+                            spawn(async move {
+                                let written = document::eval(&format!(
+                                    "await navigator.clipboard.writeText({}); return true;",
+                                    text
+                                ))
+                                .join::<bool>()
+                                .await
+                                .unwrap_or(false);
+                                if !written {
+                                    return;
+                                }
+                                copied.set(true);
+                                let _ = document::eval(&format!(
+                                    "await new Promise(r => setTimeout(r, {})); return true;",
+                                    COPIED_NOTICE_MS
+                                ))
+                                .join::<bool>()
+                                .await;
+                                copied.set(false);
+                            });
+                        }
+                    },
+                    if copied() { "Copied!" } else { "⧉ Copy" }
+                }
+
                 // Message Header with Agent Info
                 if !props.message.is_user {
-                    div { class: "flex items-center justify-between mb-2",
+                    div { class: if density.show_avatars() { "flex items-center justify-between mb-2" } else { "flex items-center justify-between mb-1" },
                         div { class: "flex items-center gap-2",
                             if let Some(ref agent_name) = props.message.agent_name {
-                                Avatar {
-                                    src: None,
-                                    fallback: agent_name.chars().next().unwrap_or('A').to_string(),
-                                    size: AvatarSize::Sm,
+                                if density.show_avatars() {
+                                    Avatar {
+                                        src: None,
+                                        fallback: agent_name.chars().next().unwrap_or('A').to_string(),
+                                        size: AvatarSize::Sm,
+                                    }
                                 }
                                 span { class: "text-xs font-medium", "{agent_name}" }
                             }
@@ -338,24 +890,73 @@ pub fn EnhancedMessageBubble(props: EnhancedMessageBubbleProps) -> Element {
                                 }
                             }
                         }
-                        if let Some(timestamp) = props.message.timestamp {
-                            span { class: "text-xs opacity-70", "{timestamp}" }
+                        div { class: "flex items-center gap-2",
+                            if let Some(ref model) = props.message.model {
+                                span { class: "text-xs opacity-70", "(via {model})" }
+                            }
+                            if let Some(ref timestamp) = props.message.timestamp {
+                                span { class: "text-xs opacity-70", "{timestamp}" }
+                            }
                         }
                     }
                 }
 
                 // Message Content
-                div {
-                    class: "text-sm leading-relaxed",
-                    if props.message.is_thinking {
-                        span { class: "italic opacity-75", "🧠 Thinking: " }
+                // Keep code blocks scrollable and images contained in either density
+                if let (Some(draft), Some(on_edit)) = (editing(), props.on_edit) {
+                    div { class: "space-y-2",
+                        Textarea {
+                            value: draft.clone(),
+                            oninput: move |text| editing.set(Some(text)),
+                            rows: 3,
+                            class: "text-gray-900 dark:text-gray-100",
+                        }
+                        div { class: "flex justify-end gap-2",
+                            Button {
+                                onclick: move |_| editing.set(None),
+                                variant: ButtonVariant::Ghost,
+                                size: ButtonSize::Sm,
+                                "Cancel"
+                            }
+                            Button {
+                                onclick: move |_| {
+                                    let text = editing().unwrap_or_default();
+                                    editing.set(None);
+                                    if !text.trim().is_empty() {
+                                        on_edit.call(text);
+                                    }
+                                },
+                                variant: ButtonVariant::Secondary,
+                                size: ButtonSize::Sm,
+                                disabled: draft.trim().is_empty() || !props.can_regenerate,
+                                "Save & resend"
+                            }
+                        }
                     }
-                    "{props.message.content}"
+                } else {
+                    div {
+                        class: "text-sm leading-relaxed whitespace-pre-wrap break-words [&_pre]:overflow-x-auto [&_img]:max-w-full",
+                        if props.message.is_thinking {
+                            span { class: "italic opacity-75", "🧠 Thinking: " }
+                        }
+                        if props.message.sources.is_empty() {
+                            Markdown { content: props.message.content.clone() }
+                        } else {
+                            CitedContent {
+                                content: props.message.content.clone(),
+                                sources: props.message.sources.clone(),
+                            }
+                        }
+                    }
+                }
+
+                if props.message.interrupted {
+                    p { class: "mt-1 text-xs italic opacity-70", "⏹ Stopped before the reply finished" }
                 }
 
                 // Message Footer with Token Usage
                 if let Some(token_usage) = props.message.token_usage {
-                    div { class: "mt-2 text-xs opacity-60 flex justify-between",
+                    div { class: if density.show_avatars() { "mt-2 text-xs opacity-60 flex justify-between" } else { "mt-1 text-xs opacity-60 flex justify-between" },
                         span {
                             {
                                 format!("Tokens: {}", token_usage)
@@ -368,21 +969,116 @@ pub fn EnhancedMessageBubble(props: EnhancedMessageBubbleProps) -> Element {
                         }
                     }
                 }
+
+                div { class: "mt-2 flex items-center gap-2 text-xs opacity-70",
+                    button {
+                        class: "px-2 py-1 rounded hover:bg-gray-100 dark:hover:bg-gray-700",
+                        title: "Copy this message as Markdown",
+                        onclick: {
+                            let markdown = props.message.to_markdown();
+                            move |_| {
+                                let text = serde_json::to_string(&markdown).unwrap_or_default();
+                                let _ = document::eval(&format!("navigator.clipboard.writeText({});", text));
+                            }
+                        },
+                        "📋 Copy as Markdown"
+                    }
+                    if let (true, Some(_)) = (props.message.is_user, props.on_edit) {
+                        button {
+                            class: "px-2 py-1 rounded hover:bg-gray-100 dark:hover:bg-gray-700 disabled:opacity-50",
+                            title: "Edit this prompt and resend it; later messages are removed",
+                            disabled: !props.can_regenerate || editing().is_some(),
+                            onclick: {
+                                let content = props.message.content.clone();
+                                move |_| editing.set(Some(content.clone()))
+                            },
+                            "✏️ Edit"
+                        }
+                    }
+                    if let Some(on_quote) = props.on_quote {
+                        button {
+                            class: "px-2 py-1 rounded hover:bg-gray-100 dark:hover:bg-gray-700",
+                            title: "Quote in your reply (select text to quote part of it)",
+                            onclick: {
+                                let content = props.message.content.clone();
+                                move |_| {
+                                    let content = content.clone();
+                                    spawn(async move {
+                                        let selection = document::eval(
+                                            "return window.getSelection ? window.getSelection().toString() : '';",
+                                        )
+                                        .join::<String>()
+                                        .await
+                                        .unwrap_or_default();
+                                        // Only a selection inside this message counts
+                                        let quoted = if !selection.trim().is_empty() && content.contains(selection.trim()) {
+                                            selection.trim().to_string()
+                                        } else {
+                                            content
+                                        };
+                                        on_quote.call(quoted);
+                                    });
+                                }
+                            },
+                            "❝ Quote"
+                        }
+                    }
+                }
+
+                if let (false, Some(on_regenerate)) = (props.message.is_user, props.on_regenerate) {
+                    div { class: "mt-2 flex items-center gap-2 text-xs",
+                        if let Some(on_select) = props.on_select {
+                            button {
+                                class: "px-2 py-1 rounded bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600 disabled:opacity-50",
+                                disabled: !props.can_regenerate,
+                                onclick: move |_| on_select.call(()),
+                                "✓ Use this reply"
+                            }
+                        }
+                        button {
+                            class: "px-2 py-1 rounded bg-gray-100 dark:bg-gray-700 hover:bg-gray-200 dark:hover:bg-gray-600 disabled:opacity-50",
+                            disabled: !props.can_regenerate,
+                            onclick: move |_| on_regenerate.call(None),
+                            "🔄 Regenerate"
+                        }
+                        select {
+                            class: "px-2 py-1 rounded border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-800 disabled:opacity-50",
+                            "aria-label": "Regenerate with a different model",
+                            disabled: !props.can_regenerate,
+                            value: "",
+                            onchange: move |evt| {
+                                let model = evt.value();
+                                if !model.is_empty() {
+                                    on_regenerate.call(Some(model));
+                                }
+                            },
+                            option { value: "", "Try another model..." }
+                            for model in props.regenerate_models.iter() {
+                                option { value: "{model}", "{model}" }
+                            }
+                        }
+                    }
+                }
             }
         }
     }
 }
 
 // Utility function to create a chat request
+/// `system_prompt` is the agent's instructions; without them (or when blank) the agent gets
+/// a one-line persona from its name and mode. `tools` are the tools the model may call.
 pub fn create_enhanced_chat_request(
     content: String,
     config: &AgentConfig,
     model: String,
     conversation_history: Vec<EnhancedChatMessage>,
     agent_name: &str,
+    system_prompt: Option<String>,
+    tools: Option<Vec<Tool>>,
 ) -> ChatRequest {
     let mut messages: Vec<ChatMessage> = conversation_history
         .into_iter()
+        .filter(|msg| !msg.superseded)
         .map(|msg| ChatMessage {
             role: if msg.is_user { Role::User } else { Role::Assistant },
             content: msg.content,
@@ -404,7 +1100,11 @@ pub fn create_enhanced_chat_request(
     ChatRequest {
         messages,
         model,
-        system_prompt: Some(format!("You are {}. Act in {:?} mode.", agent_name, config.goose_mode)),
+        system_prompt: Some(
+            system_prompt
+                .filter(|prompt| !prompt.trim().is_empty())
+                .unwrap_or_else(|| format!("You are {}. Act in {:?} mode.", agent_name, config.goose_mode)),
+        ),
         temperature: Some(0.7),
         max_tokens: None,
         top_p: None,
@@ -412,6 +1112,258 @@ pub fn create_enhanced_chat_request(
         presence_penalty: None,
         stream: true,
         agent_config: Some(config.clone()),
-        tools: None,
+        tools,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::ProviderErrorKind;
+
+    fn message(id: &str, is_user: bool) -> EnhancedChatMessage {
+        EnhancedChatMessage {
+            id: id.to_string(),
+            content: id.to_string(),
+            is_user,
+            timestamp: None,
+            agent_name: None,
+            agent_mode: None,
+            is_thinking: false,
+            token_usage: None,
+            model: None,
+            sources: vec![],
+            interrupted: false,
+            superseded: false,
+        }
+    }
+
+    #[test]
+    fn test_single_message_markdown_and_quote_insertion() {
+        let mut reply = message("a1", false);
+        reply.agent_name = Some("Goose".to_string());
+        reply.model = Some("gpt-4".to_string());
+        reply.content = "Use this:\n\n```rust\nlet x = 1;\n```".to_string();
+        assert_eq!(
+            reply.to_markdown(),
+            "**Goose** (via gpt-4)\n\nUse this:\n\n```rust\nlet x = 1;\n```\n"
+        );
+
+        assert_eq!(
+            insert_quote("", &reply.content),
+            "> Use this:\n>\n> ```rust\n> let x = 1;\n> ```\n\n"
+        );
+        // A draft in progress is kept ahead of the quote
+        assert_eq!(
+            insert_quote("Two things. ", "let x = 1;"),
+            "Two things.\n\n> let x = 1;\n\n"
+        );
+    }
+
+    #[test]
+    fn test_regenerated_reply_is_kept_next_to_the_original() {
+        let mut state = EnhancedChatState {
+            messages: vec![message("q1", true), message("a1", false), message("q2", true)],
+            ..EnhancedChatState::default()
+        };
+
+        let ids = |messages: &[EnhancedChatMessage]| -> Vec<String> {
+            messages.iter().map(|m| m.id.clone()).collect()
+        };
+        assert_eq!(ids(&state.history_before("a1")), vec!["q1"]);
+
+        state.insert_alternative("a1", message("a1-gpt4", false));
+        state.insert_alternative("a1", message("a1-claude", false));
+        assert_eq!(ids(&state.messages), vec!["q1", "a1", "a1-gpt4", "a1-claude", "q2"]);
+
+        // Only the chosen alternative goes back to the model
+        assert_eq!(ids(&state.history_before("q2")), vec!["q1", "a1-claude"]);
+        state.select_alternative("a1-gpt4");
+        assert_eq!(ids(&state.history_before("q2")), vec!["q1", "a1-gpt4"]);
+        let request = create_enhanced_chat_request(
+            "Next".to_string(),
+            &AgentConfig::default(),
+            "mock-local".to_string(),
+            state.messages.clone(),
+            "Scout",
+            None,
+            None,
+        );
+        let roles: Vec<Role> = request.messages.iter().map(|m| m.role.clone()).collect();
+        assert_eq!(roles, vec![Role::User, Role::Assistant, Role::User, Role::User]);
+    }
+
+    #[test]
+    fn test_stopping_keeps_partial_reply_and_drops_late_chunks() {
+        let mut state = EnhancedChatState {
+            messages: vec![message("q1", true), message("a1", false)],
+            is_streaming: true,
+            ..EnhancedChatState::default()
+        };
+        state.messages[1].content = "Half an ans".to_string();
+
+        assert!(state.interrupt_stream());
+        assert!(!state.is_streaming);
+        assert!(state.messages[1].interrupted);
+        state.append_streaming_text("wer");
+        assert_eq!(state.messages[1].content, "Half an ans");
+        assert!(!state.interrupt_stream());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_session_cost_accumulates_from_each_reply_usage() {
+        let usage = |prompt: u32, completion: u32| TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        };
+        let mut state = EnhancedChatState {
+            messages: vec![message("q1", true), message("a1", false)],
+            ..EnhancedChatState::default()
+        };
+        state.record_usage(&usage(1000, 500));
+        assert_eq!(state.messages[1].token_usage, Some(1500));
+        assert_eq!(state.session_cost_label(), "—");
+
+        // $0.01 per 1K prompt tokens and $0.03 per 1K completion tokens
+        state.pricing = Some(ModelPricing {
+            input_tokens: 0.01,
+            output_tokens: 0.03,
+            currency: "USD".to_string(),
+        });
+        state.record_usage(&usage(1200, 400));
+        state.record_usage(&usage(3000, 2000));
+        // 0.012 + 0.012, then 0.03 + 0.06
+        assert!((state.session_cost - 0.114).abs() < 1e-9);
+        assert_eq!(state.session_cost_label(), "$0.1140");
+    }
+
+    #[test]
+    fn test_typing_indicator_lasts_until_the_first_text_or_the_end() {
+        let mut state = EnhancedChatState::default();
+        state.messages.push(message("q1", true));
+        state.start_reply();
+        assert!(state.is_streaming && state.awaiting_first_token);
+
+        state.messages.push(message("a1", false));
+        state.append_streaming_text("");
+        assert!(state.awaiting_first_token);
+        state.append_streaming_text("Hel");
+        assert!(state.is_streaming && !state.awaiting_first_token);
+
+        // A failure before any text still clears the indicator
+        state.start_reply();
+        let timeout = ProviderError::new(ProviderErrorKind::Timeout, "timed out");
+        state.finish_reply(Some(timeout));
+        assert!(!state.is_streaming && !state.awaiting_first_token);
+        assert!(state.send_error.is_some());
+    }
+
+    #[test]
+    fn test_chat_request_carries_instructions_and_tools() {
+        let tool = Tool {
+            name: "web_search".to_string(),
+            description: "Search the web".to_string(),
+            input_schema: serde_json::json!({ "type": "object" }),
+            is_mcp: false,
+            category: api::ToolCategory::Network,
+        };
+        let request = create_enhanced_chat_request(
+            "What's new?".to_string(),
+            &AgentConfig::default(),
+            "mock-local".to_string(),
+            vec![message("q1", true), message("a1", false)],
+            "Scout",
+            Some("You research things carefully.".to_string()),
+            Some(vec![tool]),
+        );
+        assert_eq!(request.system_prompt.as_deref(), Some("You research things carefully."));
+        let tools: Vec<String> = request.tools.unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, vec!["web_search"]);
+        assert_eq!(request.messages.len(), 3);
+
+        let request = create_enhanced_chat_request(
+            "Hi".to_string(),
+            &AgentConfig::default(),
+            "mock-local".to_string(),
+            vec![],
+            "Scout",
+            Some("  ".to_string()),
+            None,
+        );
+        assert!(request.system_prompt.unwrap().starts_with("You are Scout."));
+        assert!(request.tools.is_none());
+    }
+
+    #[test]
+    fn test_editing_a_prompt_rewinds_to_just_before_it() {
+        let mut state = EnhancedChatState {
+            messages: vec![
+                message("q1", true),
+                message("a1", false),
+                message("q2", true),
+                message("a2", false),
+            ],
+            ..EnhancedChatState::default()
+        };
+
+        assert_eq!(state.rewind_before("missing"), None);
+        assert_eq!(state.messages.len(), 4);
+        assert_eq!(state.rewind_before("q2"), Some(Some("a1".to_string())));
+        let ids: Vec<&str> = state.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["q1", "a1"]);
+        // Editing the opening prompt clears the whole conversation
+        assert_eq!(state.rewind_before("q1"), Some(None));
+        assert!(state.messages.is_empty());
+    }
+
+    #[test]
+    fn test_typewriter_reveals_at_configured_rate() {
+        let mut reveal = TypewriterReveal::default();
+        reveal.reset(Some("m1".to_string()));
+
+        assert_eq!(reveal.advance(1000, 100, 0.1), 10);
+        assert_eq!(reveal.advance(1000, 100, 0.1), 20);
+        // Never reveals past what has arrived
+        assert_eq!(reveal.advance(25, 100, 1.0), 25);
+        assert!(reveal.is_caught_up(25));
+    }
+
+    #[test]
+    fn test_typewriter_catches_up_when_stream_outpaces_reveal() {
+        let chars_per_sec = 100;
+        let max_lag = (chars_per_sec as f32 * TypewriterReveal::MAX_LAG_SECS) as usize;
+        let mut reveal = TypewriterReveal::default();
+        reveal.reset(Some("m1".to_string()));
+
+        // The stream delivers 1000 chars/sec, ten times faster than the reveal rate
+        let mut available = 0;
+        for _ in 0..50 {
+            available += 100;
+            let revealed = reveal.advance(available, chars_per_sec, 0.1);
+            assert!(available - revealed <= max_lag);
+        }
+
+        // Once the stream stops, the reveal drains the remaining backlog
+        for _ in 0..100 {
+            reveal.advance(available, chars_per_sec, 0.1);
+        }
+        assert!(reveal.is_caught_up(available));
+    }
+
+    #[test]
+    fn test_message_bubble_has_a_focusable_copy_button() {
+        let mut dom = VirtualDom::new(|| {
+            let mut reply = message("a1", false);
+            reply.content = "```rust\nlet x = 1;\n```".to_string();
+            rsx! { EnhancedMessageBubble { message: reply } }
+        });
+        dom.rebuild_in_place();
+        let html = dioxus_ssr::render(&dom);
+
+        assert!(html.contains(r#"<button type="button""#));
+        assert!(html.contains(r#"aria-label="Copy message text""#));
+        assert!(html.contains("focus-visible:opacity-100"));
+        assert!(html.contains("⧉ Copy</button>"));
+    }
+}
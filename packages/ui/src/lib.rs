@@ -6,17 +6,68 @@ pub use ui_components::{
     Dialog, DialogHeader, DialogTitle, DialogContent, DialogFooter,
     Card, CardItem,
     Button, ButtonVariant, ButtonSize,
-    Input, Textarea, Switch,
+    Input, Textarea, Switch, Slider,
     Avatar, AvatarSize, Badge, BadgeVariant,
+    EmptyState, ErrorState,
+};
+
+// Appearance settings
+mod appearance;
+pub use appearance::{
+    use_appearance, use_appearance_provider, AppearanceSettings, MessageDensity, RevealMode,
 };
 
 // Enhanced Chat Interface
 mod enhanced_chat;
 pub use enhanced_chat::{
     EnhancedChatContainer, EnhancedChatMessage, EnhancedChatState,
-    EnhancedMessageBubble, create_enhanced_chat_request,
+    EnhancedMessageBubble, TypewriterReveal, create_enhanced_chat_request, insert_quote,
+    quote_block,
+};
+
+// Persisted settings shared by the settings panels
+mod settings_store;
+pub use settings_store::{
+    use_settings, use_settings_provider, AppSettings, DataSource, DataSourceType, LogLevel,
+    PerformanceSettings, Provider, RateLimit, SettingsStore, SyncFrequency, Theme,
+    SETTINGS_SCHEMA_VERSION,
 };
 
+// Settings dialog backed by the settings store
+mod settings_dialog;
+pub use settings_dialog::SettingsDialog;
+
+// Bounds for numeric settings fields
+mod settings_validation;
+pub use settings_validation::{validate_numeric, NumericBounds};
+
+// Frame-rate batching for streamed text
+mod stream_coalescer;
+pub use stream_coalescer::{use_coalesced_stream, CoalescedStream, StreamCoalescer};
+
+// Inline citations for web_search answers
+mod citations;
+pub use citations::{collect_sources, parse_citations, CitationSegment, CitedContent};
+
+// Markdown rendering for message content
+mod markdown;
+pub use markdown::{render_markdown, Markdown};
+
+// Provider reachability indicator
+mod provider_status;
+pub use provider_status::{
+    use_provider_status, ProviderStatus, ProviderStatusBadge, ProviderStatusHandle,
+    ProviderStatusTracker,
+};
+
+// Reasoning chain panel
+mod reasoning_panel;
+pub use reasoning_panel::ReasoningPanel;
+
+// Side-by-side model comparison
+mod model_compare;
+pub use model_compare::ModelCompare;
+
 // Agent Configuration Dialog
 mod agent_config_dialog;
 pub use agent_config_dialog::{
@@ -34,9 +85,9 @@ pub use parameter_manager::{
 mod rig_chat;
 pub use rig_chat::{RigChatContainer, RigChatSidebar, RigChatMessage, RigChatState, create_chat_request};
 
-// Re-export Model from model_selector for SimpleModelSelector
+// Keyboard-accessible model listbox; Model is also used by SimpleModelSelector
 mod model_selector;
-pub use model_selector::Model;
+pub use model_selector::{filter_models, Capability, Model, ModelSelector};
 
 // Basic components that should work
 mod hero;
@@ -0,0 +1,218 @@
+// Markdown rendering for chat messages; model output is untrusted, so raw HTML is never passed through
+use dioxus::prelude::*;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::sync::OnceLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+use crate::settings_store::{AppSettings, Theme};
+
+/// Styles for the rendered elements; code blocks are monospace and scroll sideways, with their
+/// colours coming from the highlighting theme
+const MARKDOWN_CLASS: &str = "whitespace-normal break-words space-y-2 [&_ul]:list-disc [&_ul]:pl-5 [&_ol]:list-decimal [&_ol]:pl-5 [&_a]:underline [&_code]:font-mono [&_code]:text-[0.85em] [&_pre]:font-mono [&_pre]:text-xs [&_pre]:rounded-md [&_pre]:p-3 [&_pre]:overflow-x-auto [&_blockquote]:border-l-4 [&_blockquote]:pl-3 [&_blockquote]:opacity-80 [&_img]:max-w-full";
+
+const COPY_BUTTON_CLASS: &str = "absolute right-2 top-2 rounded px-2 py-0.5 text-xs font-sans bg-gray-700/80 text-gray-100 opacity-0 group-hover:opacity-100 focus:opacity-100 transition-opacity";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Highlighting theme for the app theme. Only an explicitly light app gets light code blocks;
+/// dark, automatic and custom themes get dark ones, which read well on either background.
+fn code_theme_name(theme: &Theme) -> &'static str {
+    match theme {
+        Theme::Light => "InspiredGitHub",
+        Theme::Dark | Theme::Auto | Theme::Custom(_) => "base16-ocean.dark",
+    }
+}
+
+/// Render Markdown to HTML that is safe to inject. HTML in the source is shown as text and
+/// links or images with a script-capable URL are stripped of it. A fence left open by a reply
+/// that is still streaming renders as a code block up to the end of what has arrived.
+pub fn render_markdown(content: &str, theme: &Theme) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut events = Vec::new();
+    // Info string and source of the code block being read
+    let mut code: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(content, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((info, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((info, source)) = code.take() {
+                    events.push(Event::Html(code_block_html(&source, &info, theme).into()));
+                }
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, source)) = code.as_mut() {
+                    source.push_str(&text);
+                }
+            }
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => events.push(Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            })),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => events.push(Event::Start(Tag::Image {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            })),
+            other => events.push(other),
+        }
+    }
+
+    let mut out = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut out, events.into_iter());
+    out
+}
+
+/// A highlighted code block with a copy button. The language is the first word of the fence's
+/// info string; unknown or missing languages are shown as plain text.
+fn code_block_html(source: &str, info: &str, theme: &Theme) -> String {
+    let syntaxes = syntax_set();
+    let syntax = info
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+        .filter(|language| !language.is_empty())
+        .and_then(|language| syntaxes.find_syntax_by_token(language))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let highlighted = highlighted_html_for_string(
+        source,
+        syntaxes,
+        syntax,
+        &theme_set().themes[code_theme_name(theme)],
+    )
+    .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", escape_html(source)));
+
+    // Injected HTML can't carry Dioxus handlers, so the button copies its own data attribute
+    format!(
+        r#"<div class="relative group"><button type="button" class="{}" title="Copy code" data-code="{}" onclick="navigator.clipboard.writeText(this.dataset.code)">Copy</button>{}</div>"#,
+        COPY_BUTTON_CLASS,
+        escape_html(source),
+        highlighted.trim_end()
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Relative URLs and web, mail and image-data URLs are kept; anything else (`javascript:`,
+/// `vbscript:`, other `data:`) becomes an empty link
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let lower = url.trim_start().to_ascii_lowercase();
+    let scheme = lower
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+    match scheme {
+        None | Some("http" | "https" | "mailto") => url,
+        Some("data") if lower.starts_with("data:image/") => url,
+        Some(_) => CowStr::Borrowed(""),
+    }
+}
+
+/// Message content rendered as Markdown, with code highlighted for the app's theme
+#[component]
+pub fn Markdown(content: String, class: Option<String>) -> Element {
+    let theme = try_use_context::<Signal<AppSettings>>()
+        .map(|settings| settings.read().theme.clone())
+        .unwrap_or_default();
+    let html = render_markdown(&content, &theme);
+
+    rsx! {
+        div {
+            class: format!("{} {}", MARKDOWN_CLASS, class.unwrap_or_default()),
+            dangerous_inner_html: "{html}",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_html_is_escaped_and_script_links_dropped() {
+        let html = render_markdown(
+            "Hi <script>alert(1)</script>\n\n<img src=x onerror=alert(1)>\n\n[click](javascript:alert(1)) [docs](https://docs.rs)",
+            &Theme::Auto,
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains(r#"<a href="">click</a>"#));
+        assert!(html.contains(r#"<a href="https://docs.rs">docs</a>"#));
+    }
+
+    #[test]
+    fn test_fences_render_as_code_even_while_still_open() {
+        let html = render_markdown(
+            "Try:\n\n```rust\nfn main() {}\n```\n\n- one\n- two",
+            &Theme::Dark,
+        );
+        assert!(html.contains("data-code=\"fn main() {}\n\""));
+        assert!(html.contains("<li>one</li>"));
+
+        let partial = render_markdown(
+            "Try:\n\n```rust\nfn main() {\n    let x = 1 < 2;",
+            &Theme::Dark,
+        );
+        assert!(partial.contains("data-code=\"fn main() {\n    let x = 1 &lt; 2;"));
+        assert!(partial.trim_end().ends_with("</pre></div>"));
+    }
+
+    #[test]
+    fn test_code_is_highlighted_for_the_theme_with_plain_text_fallback() {
+        let source = "```rust\nlet answer = 42;\n```";
+        let dark = render_markdown(source, &Theme::Dark);
+        let light = render_markdown(source, &Theme::Light);
+        assert!(dark.contains("<span style="));
+        assert_ne!(dark, light);
+        assert!(dark.contains(">Copy</button>"));
+
+        // Unknown languages still render, as a single unhighlighted run
+        let unknown = render_markdown("```nosuchlang\nlet answer = 42;\n```", &Theme::Dark);
+        assert!(unknown.contains("let answer = 42;"));
+        assert!(unknown.contains("<pre"));
+    }
+}
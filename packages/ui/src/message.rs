@@ -12,6 +12,8 @@ pub struct MessageProps {
     pub avatar: Option<String>,
     pub on_edit: Option<EventHandler<String>>,
     pub on_delete: Option<EventHandler>,
+    /// Answer the prompt again; offered on an assistant reply when set
+    pub on_regenerate: Option<EventHandler>,
     pub is_last_message: Option<bool>,
 }
 
@@ -166,8 +168,15 @@ pub fn Message(props: MessageProps) -> Element {
                 // Message metadata for AI messages
                 if !props.is_user {
                     div {
-                        class: "mt-2 text-xs text-gray-500 dark:text-gray-400",
+                        class: "mt-2 flex items-center gap-2 text-xs text-gray-500 dark:text-gray-400",
                         "AI response generated"
+                        if let Some(on_regenerate) = props.on_regenerate {
+                            button {
+                                class: "px-2 py-0.5 rounded hover:bg-gray-200 dark:hover:bg-gray-700",
+                                onclick: move |_| on_regenerate.call(()),
+                                "🔄 Regenerate"
+                            }
+                        }
                     }
                 }
             }
@@ -0,0 +1,135 @@
+// Side-by-side view of several models answering the same prompt
+use api::{ChatMessage, ModelComparison, ProviderErrorKind, Role, SystemNotification};
+use dioxus::prelude::*;
+
+use crate::ui_components::ErrorState;
+
+#[component]
+pub fn ModelCompare(models: Vec<String>) -> Element {
+    let mut prompt = use_signal(String::new);
+    let mut results = use_signal(Vec::<ModelComparison>::new);
+    let mut is_running = use_signal(|| false);
+    let mut error = use_signal(|| None::<String>);
+
+    // Dynamic Tailwind grid classes aren't generated, so size the columns inline
+    let grid_style = format!(
+        "grid-template-columns: repeat({}, minmax(0, 1fr));",
+        models.len().max(1)
+    );
+    let models_to_send = models.clone();
+
+    rsx! {
+        div { class: "flex flex-col gap-4 h-full",
+            div { class: "flex gap-2",
+                textarea {
+                    class: "flex-1 px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 resize-none",
+                    rows: "3",
+                    placeholder: "Ask every selected model the same question...",
+                    value: "{prompt}",
+                    oninput: move |e| prompt.set(e.value()),
+                }
+                button {
+                    class: "px-4 py-2 bg-blue-600 hover:bg-blue-700 text-white rounded-lg disabled:opacity-50",
+                    disabled: is_running() || models.is_empty(),
+                    onclick: move |_| {
+                        let text = prompt.read().trim().to_string();
+                        if text.is_empty() {
+                            return;
+                        }
+                        let messages = vec![ChatMessage {
+                            role: Role::User,
+                            content: text,
+                            timestamp: None,
+                            tool_calls: None,
+                            tool_results: None,
+                        }];
+                        let models = models_to_send.clone();
+                        is_running.set(true);
+                        error.set(None);
+                        spawn(async move {
+                            match api::compare_models(messages, models).await {
+                                Ok(comparisons) => results.set(comparisons),
+                                Err(e) => error.set(Some(e.to_string())),
+                            }
+                            is_running.set(false);
+                        });
+                    },
+                    if is_running() { "Comparing..." } else { "Compare" }
+                }
+            }
+
+            if let Some(message) = error() {
+                p { class: "text-sm text-red-600 dark:text-red-400", "{message}" }
+            }
+
+            div { class: "grid gap-4 flex-1 min-h-0", style: "{grid_style}",
+                if is_running() {
+                    for model in models.iter() {
+                        div {
+                            key: "{model}",
+                            class: "border border-gray-200 dark:border-gray-700 rounded-lg p-3 text-sm text-gray-500 dark:text-gray-400",
+                            div { class: "font-medium text-gray-900 dark:text-gray-100 mb-2", "{model}" }
+                            "Waiting for a reply..."
+                        }
+                    }
+                } else {
+                    for comparison in results.read().iter() {
+                        ComparisonColumn { key: "{comparison.model}", comparison: comparison.clone() }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ComparisonColumn(comparison: ModelComparison) -> Element {
+    let content = comparison.response.as_ref().and_then(|response| {
+        response
+            .message
+            .as_ref()
+            .map(|message| message.content.clone())
+            .or_else(|| response.notification.as_ref().map(|n| n.message.clone()))
+    });
+    let missing_key = comparison
+        .response
+        .as_ref()
+        .and_then(|response| response.notification.clone())
+        .filter(SystemNotification::is_no_credentials);
+    let tokens = comparison
+        .response
+        .as_ref()
+        .and_then(|response| response.token_usage.as_ref())
+        .map(|usage| usage.total_tokens.to_string())
+        .unwrap_or_else(|| "–".to_string());
+    let cost = comparison
+        .estimated_cost
+        .map(|cost| format!("${:.4}", cost))
+        .unwrap_or_else(|| "–".to_string());
+
+    rsx! {
+        div { class: "flex flex-col border border-gray-200 dark:border-gray-700 rounded-lg bg-white dark:bg-gray-800 min-h-0",
+            div { class: "px-3 py-2 border-b border-gray-200 dark:border-gray-700 font-medium text-sm text-gray-900 dark:text-gray-100 truncate",
+                "{comparison.model}"
+            }
+            div { class: "flex-1 overflow-y-auto px-3 py-2 text-sm leading-relaxed whitespace-pre-wrap break-words [&_pre]:overflow-x-auto",
+                if let Some(error) = comparison.error.as_ref() {
+                    span { class: "text-red-600 dark:text-red-400", "{error}" }
+                } else if let Some(notification) = missing_key {
+                    ErrorState {
+                        kind: ProviderErrorKind::Authentication,
+                        message: notification.message,
+                        code: notification.code,
+                    }
+                } else if let Some(content) = content {
+                    span { class: "text-gray-800 dark:text-gray-200", "{content}" }
+                }
+            }
+            div { class: "px-3 py-2 border-t border-gray-200 dark:border-gray-700 flex justify-between text-xs text-gray-500 dark:text-gray-400",
+                span { "Tokens: {tokens}" }
+                span { "Cost: {cost}" }
+                span { "{comparison.latency_ms} ms" }
+            }
+        }
+    }
+}
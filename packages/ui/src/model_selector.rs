@@ -6,6 +6,9 @@ pub struct ModelSelectorProps {
     pub selected_model: Option<String>,
     pub on_select_model: EventHandler<String>,
     pub loading: Option<bool>,
+    /// Capability filters switched on when the selector is first shown, e.g. vision while an
+    /// image is attached
+    pub filters: Option<Vec<Capability>>,
 }
 
 #[derive(Clone, PartialEq)]
@@ -15,34 +18,269 @@ pub struct Model {
     pub provider: String,
     pub description: Option<String>,
     pub capabilities: Vec<String>,
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+    pub supports_streaming: bool,
+}
+
+/// A capability the selector can filter models by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    Tools,
+    Vision,
+    Streaming,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [Capability::Tools, Capability::Vision, Capability::Streaming];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Capability::Tools => "Tools",
+            Capability::Vision => "Vision",
+            Capability::Streaming => "Streaming",
+        }
+    }
+}
+
+impl Model {
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Tools => self.supports_tools,
+            Capability::Vision => self.supports_vision,
+            Capability::Streaming => self.supports_streaming,
+        }
+    }
+}
+
+/// The models having every capability in `filters`, with each provider's models together.
+/// Providers keep the order in which they first appear, and models keep their order within one.
+pub fn filter_models(models: &[Model], filters: &[Capability]) -> Vec<Model> {
+    let matching: Vec<&Model> = models
+        .iter()
+        .filter(|model| filters.iter().all(|&capability| model.supports(capability)))
+        .collect();
+    let mut providers: Vec<&str> = Vec::new();
+    for model in &matching {
+        if !providers.contains(&model.provider.as_str()) {
+            providers.push(&model.provider);
+        }
+    }
+    providers
+        .iter()
+        .flat_map(|provider| matching.iter().filter(move |model| model.provider == *provider))
+        .map(|model| (*model).clone())
+        .collect()
+}
+
+/// Where the active option moves for a key press in an open listbox, or `None` if the key
+/// doesn't navigate. Arrow keys wrap around at either end.
+pub(crate) fn move_active_index(current: Option<usize>, len: usize, key: &Key) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+
+    match key {
+        Key::ArrowDown => Some(current.map_or(0, |index| (index + 1) % len)),
+        Key::ArrowUp => Some(current.map_or(len - 1, |index| (index + len - 1) % len)),
+        Key::Home => Some(0),
+        Key::End => Some(len - 1),
+        _ => None,
+    }
+}
+
+/// Close the listbox and hand focus back to its trigger button
+fn close_listbox(mut open: Signal<bool>, mut active_index: Signal<Option<usize>>, trigger_id: &str) {
+    open.set(false);
+    active_index.set(None);
+    document::eval(&format!(
+        "document.getElementById('{}')?.focus();",
+        trigger_id
+    ));
 }
 
 #[component]
 pub fn ModelSelector(props: ModelSelectorProps) -> Element {
     let loading = props.loading.unwrap_or(false);
+    let mut open = use_signal(|| false);
+    let mut active_index = use_signal(|| None::<usize>);
+    let mut filters = use_signal(|| props.filters.clone().unwrap_or_default());
+    let listbox_id = use_hook(|| format!("model-selector-{}", uuid::Uuid::new_v4().simple()));
+    let trigger_id = format!("{}-trigger", listbox_id);
+
+    let visible_models = filter_models(&props.models, &filters());
+    let selected_model = props
+        .selected_model
+        .as_ref()
+        .and_then(|id| props.models.iter().find(|model| &model.id == id));
+    let selected_index = props
+        .selected_model
+        .as_ref()
+        .and_then(|id| visible_models.iter().position(|model| &model.id == id));
+    let trigger_label = match selected_model {
+        Some(model) => format!("{} - {}", model.name, model.provider),
+        None if loading => "Loading models...".to_string(),
+        None => "Select model".to_string(),
+    };
+    // Capabilities the chosen model lacks among the active filters
+    let missing: Vec<&str> = selected_model
+        .map(|model| {
+            filters()
+                .into_iter()
+                .filter(|&capability| !model.supports(capability))
+                .map(Capability::label)
+                .collect()
+        })
+        .unwrap_or_default();
+    let missing = missing.join(", ");
+
+    // Roving tabindex: keep DOM focus on whichever option is active
+    let focus_prefix = listbox_id.clone();
+    use_effect(move || {
+        if let (true, Some(index)) = (open(), active_index()) {
+            document::eval(&format!(
+                "document.getElementById('{}-option-{}')?.focus();",
+                focus_prefix, index
+            ));
+        }
+    });
+
+    let model_count = visible_models.len();
+    let models = visible_models.clone();
+    let on_select_model = props.on_select_model;
+    let listbox_trigger_id = trigger_id.clone();
 
     rsx! {
         div {
             class: "relative",
-            select {
-                class: "px-4 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 hover:bg-gray-50 dark:hover:bg-gray-700 focus:outline-none focus:ring-2 focus:ring-blue-500 transition-colors min-w-0",
+            button {
+                id: "{trigger_id}",
+                r#type: "button",
+                class: "px-4 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 hover:bg-gray-50 dark:hover:bg-gray-700 focus:outline-none focus:ring-2 focus:ring-blue-500 transition-colors min-w-0 text-left",
                 disabled: loading,
-                onchange: move |event| {
-                    props.on_select_model.call(event.value());
+                "aria-haspopup": "listbox",
+                "aria-expanded": open(),
+                "aria-controls": "{listbox_id}",
+                onclick: move |_| {
+                    let opening = !open();
+                    open.set(opening);
+                    active_index.set(if opening { selected_index.or(Some(0)) } else { None });
                 },
+                onkeydown: move |evt| {
+                    if matches!(evt.key(), Key::ArrowDown | Key::ArrowUp) {
+                        evt.prevent_default();
+                        open.set(true);
+                        active_index.set(
+                            selected_index.or_else(|| move_active_index(None, model_count, &evt.key())),
+                        );
+                    }
+                },
+                "{trigger_label}"
+            }
 
-                option {
-                    value: "",
-                    disabled: true,
-                    selected: props.selected_model.is_none(),
-                    if loading { "Loading models..." } else { "Select model" }
+            if !missing.is_empty() {
+                if let Some(model) = selected_model {
+                    p {
+                        role: "status",
+                        class: "mt-1 text-xs text-amber-600 dark:text-amber-400",
+                        "⚠️ {model.name} doesn't support {missing}"
+                    }
+                }
+            }
+
+            div {
+                hidden: !open(),
+                class: "absolute z-10 mt-1 w-full rounded-lg border border-gray-200 dark:border-gray-700 bg-white dark:bg-gray-800 shadow-lg",
+                div {
+                    class: "flex gap-1 p-2 border-b border-gray-200 dark:border-gray-700",
+                    for capability in Capability::ALL {
+                        button {
+                            r#type: "button",
+                            "aria-pressed": filters().contains(&capability),
+                            class: if filters().contains(&capability) {
+                                "px-2 py-0.5 text-xs rounded-full border border-blue-500 bg-blue-50 dark:bg-blue-900/30 text-blue-700 dark:text-blue-300"
+                            } else {
+                                "px-2 py-0.5 text-xs rounded-full border border-gray-300 dark:border-gray-600 text-gray-600 dark:text-gray-400"
+                            },
+                            onclick: move |_| {
+                                let mut active = filters.write();
+                                if let Some(position) = active.iter().position(|&c| c == capability) {
+                                    active.remove(position);
+                                } else {
+                                    active.push(capability);
+                                }
+                                active_index.set(None);
+                            },
+                            "{capability.label()}"
+                        }
+                    }
                 }
 
-                for model in &props.models {
-                    option {
-                        value: "{model.id}",
-                        selected: props.selected_model.as_ref() == Some(&model.id),
-                        "{model.name} - {model.provider}"
+                ul {
+                    id: "{listbox_id}",
+                    role: "listbox",
+                    "aria-labelledby": "{trigger_id}",
+                    class: "max-h-64 overflow-y-auto py-1",
+                    onkeydown: move |evt| {
+                        let key = evt.key();
+                        if let Some(index) = move_active_index(active_index(), model_count, &key) {
+                            evt.prevent_default();
+                            active_index.set(Some(index));
+                            return;
+                        }
+                        let is_space = matches!(&key, Key::Character(c) if c == " ");
+                        if key == Key::Enter || is_space {
+                            evt.prevent_default();
+                            if let Some(model) = active_index().and_then(|index| models.get(index)) {
+                                on_select_model.call(model.id.clone());
+                            }
+                            close_listbox(open, active_index, &listbox_trigger_id);
+                        } else if key == Key::Escape {
+                            evt.prevent_default();
+                            close_listbox(open, active_index, &listbox_trigger_id);
+                        } else if key == Key::Tab {
+                            open.set(false);
+                            active_index.set(None);
+                        }
+                    },
+
+                    if visible_models.is_empty() {
+                        li {
+                            role: "presentation",
+                            class: "px-4 py-2 text-sm text-gray-500 dark:text-gray-400",
+                            "No models have all the selected capabilities"
+                        }
+                    }
+                    for (index, model) in visible_models.iter().enumerate() {
+                        if index == 0 || visible_models[index - 1].provider != model.provider {
+                            li {
+                                key: "provider-{model.provider}",
+                                role: "presentation",
+                                class: "px-4 pt-2 pb-1 text-xs font-semibold uppercase tracking-wide text-gray-500 dark:text-gray-400",
+                                "{model.provider}"
+                            }
+                        }
+                        li {
+                            key: "{model.id}",
+                            id: "{listbox_id}-option-{index}",
+                            role: "option",
+                            "aria-selected": selected_index == Some(index),
+                            tabindex: if active_index() == Some(index) { "0" } else { "-1" },
+                            class: if active_index() == Some(index) {
+                                "px-4 py-2 cursor-pointer bg-blue-50 dark:bg-blue-900/30 text-gray-900 dark:text-gray-100"
+                            } else {
+                                "px-4 py-2 cursor-pointer text-gray-700 dark:text-gray-300 hover:bg-gray-50 dark:hover:bg-gray-700"
+                            },
+                            onclick: {
+                                let model_id = model.id.clone();
+                                let trigger_id = trigger_id.clone();
+                                move |_| {
+                                    on_select_model.call(model_id.clone());
+                                    close_listbox(open, active_index, &trigger_id);
+                                }
+                            },
+                            "{model.name} - {model.provider}"
+                        }
                     }
                 }
             }
@@ -50,3 +288,97 @@ pub fn ModelSelector(props: ModelSelectorProps) -> Element {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_models() -> Vec<Model> {
+        ["gpt-4", "claude", "gemini"]
+            .iter()
+            .map(|id| Model {
+                id: id.to_string(),
+                name: id.to_string(),
+                provider: "test".to_string(),
+                description: None,
+                capabilities: Vec::new(),
+                supports_tools: true,
+                supports_vision: false,
+                supports_streaming: true,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_listbox_exposes_roles_and_selection() {
+        fn app() -> Element {
+            rsx! {
+                ModelSelector {
+                    models: sample_models(),
+                    selected_model: Some("claude".to_string()),
+                    on_select_model: move |_| {},
+                }
+            }
+        }
+
+        let mut dom = VirtualDom::new(app);
+        dom.rebuild_in_place();
+        let html = dioxus_ssr::render(&dom);
+
+        assert!(html.contains(r#"aria-haspopup="listbox""#));
+        assert!(html.contains(r#"aria-expanded="false""#));
+        assert!(html.contains(r#"role="listbox""#));
+        assert_eq!(html.matches(r#"role="option""#).count(), 3);
+        assert_eq!(html.matches(r#"aria-selected="true""#).count(), 1);
+    }
+
+    #[test]
+    fn test_arrow_keys_move_active_index() {
+        assert_eq!(move_active_index(None, 3, &Key::ArrowDown), Some(0));
+        assert_eq!(move_active_index(Some(0), 3, &Key::ArrowDown), Some(1));
+        assert_eq!(move_active_index(Some(2), 3, &Key::ArrowDown), Some(0));
+        assert_eq!(move_active_index(Some(0), 3, &Key::ArrowUp), Some(2));
+        assert_eq!(move_active_index(Some(1), 3, &Key::End), Some(2));
+        assert_eq!(move_active_index(Some(1), 3, &Key::Enter), None);
+        assert_eq!(move_active_index(None, 0, &Key::ArrowDown), None);
+    }
+
+    #[test]
+    fn test_filters_hide_incapable_models_and_group_by_provider() {
+        let mut models = sample_models();
+        models[0].provider = "openai".to_string();
+        models[2].provider = "openai".to_string();
+        models[2].supports_vision = true;
+        models[0].supports_vision = true;
+
+        let all: Vec<String> = filter_models(&models, &[]).into_iter().map(|m| m.id).collect();
+        assert_eq!(all, ["gpt-4", "gemini", "claude"]);
+        let vision: Vec<String> = filter_models(&models, &[Capability::Vision, Capability::Tools])
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(vision, ["gpt-4", "gemini"]);
+    }
+
+    #[test]
+    fn test_warns_when_filters_exclude_the_selected_model() {
+        fn app() -> Element {
+            rsx! {
+                ModelSelector {
+                    models: sample_models(),
+                    selected_model: Some("claude".to_string()),
+                    on_select_model: move |_| {},
+                    filters: vec![Capability::Vision],
+                }
+            }
+        }
+
+        let mut dom = VirtualDom::new(app);
+        dom.rebuild_in_place();
+        let html = dioxus_ssr::render(&dom);
+
+        assert!(html.contains("claude - test"));
+        assert!(html.contains("support Vision"));
+        assert_eq!(html.matches(r#"role="option""#).count(), 0);
+        assert!(html.contains(r#"aria-pressed="true""#));
+    }
+}
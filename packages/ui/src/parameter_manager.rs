@@ -1,14 +1,89 @@
 // Parameter Management Interface for Agent Configuration
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use crate::ui_components::*;
 use crate::agent_config_dialog::{AgentParameter, ParameterType};
 
+impl ParameterType {
+    /// Check a value as typed into the editor; the error is shown under the field
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match self {
+            ParameterType::String if value.is_empty() => Err("Enter a value".to_string()),
+            ParameterType::String => Ok(()),
+            ParameterType::Number => parse_number(value).map(|_| ()),
+            ParameterType::Range { min, max } => {
+                let number = parse_number(value)?;
+                if number < *min || number > *max {
+                    Err(format!("Must be between {} and {}", min, max))
+                } else {
+                    Ok(())
+                }
+            }
+            ParameterType::Boolean => match value {
+                "true" | "false" => Ok(()),
+                _ => Err("Must be true or false".to_string()),
+            },
+            ParameterType::Enum(options) if options.iter().any(|option| option == value) => Ok(()),
+            ParameterType::Enum(options) => Err(format!("Must be one of: {}", options.join(", "))),
+            ParameterType::Json => serde_json::from_str::<serde_json::Value>(value)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid JSON: {}", e)),
+        }
+    }
+
+    /// Name shown on the type selector
+    pub fn label(&self) -> &'static str {
+        match self {
+            ParameterType::String => "String",
+            ParameterType::Number | ParameterType::Range { .. } => "Number",
+            ParameterType::Boolean => "Boolean",
+            ParameterType::Enum(_) => "Choice",
+            ParameterType::Json => "JSON",
+        }
+    }
+
+    /// Parse a value that has passed `validate`
+    fn parse(&self, value: &str) -> serde_json::Value {
+        let value = value.trim();
+        match self {
+            ParameterType::Number | ParameterType::Range { .. } => parse_number(value)
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            ParameterType::Boolean => serde_json::Value::Bool(value == "true"),
+            ParameterType::Json => serde_json::from_str(value).unwrap_or(serde_json::Value::Null),
+            ParameterType::String | ParameterType::Enum(_) => {
+                serde_json::Value::String(value.to_string())
+            }
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| "Enter a number".to_string())
+}
+
+/// Text for a stored value in the editor
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PredefinedParameter {
     pub key: String,
     pub title: String,
     pub value: serde_json::Value,
+    pub param_type: ParameterType,
     pub description: Option<String>,
 }
 
@@ -19,48 +94,56 @@ pub fn get_predefined_parameters() -> Vec<PredefinedParameter> {
             key: "temperature".to_string(),
             title: "Temperature".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from_f64(0.7).unwrap()),
+            param_type: ParameterType::Range { min: 0.0, max: 2.0 },
             description: Some("Controls randomness in responses (0.0-2.0)".to_string()),
         },
         PredefinedParameter {
             key: "max_tokens".to_string(),
             title: "Max Tokens".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from(1000)),
+            param_type: ParameterType::Range { min: 1.0, max: 200_000.0 },
             description: Some("Maximum number of tokens in response".to_string()),
         },
         PredefinedParameter {
             key: "top_p".to_string(),
             title: "Top P".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from_f64(0.9).unwrap()),
+            param_type: ParameterType::Range { min: 0.0, max: 1.0 },
             description: Some("Controls diversity via nucleus sampling".to_string()),
         },
         PredefinedParameter {
             key: "frequency_penalty".to_string(),
             title: "Frequency Penalty".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from_f64(0.0).unwrap()),
+            param_type: ParameterType::Range { min: -2.0, max: 2.0 },
             description: Some("Reduces repetition of frequent words".to_string()),
         },
         PredefinedParameter {
             key: "presence_penalty".to_string(),
             title: "Presence Penalty".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from_f64(0.0).unwrap()),
+            param_type: ParameterType::Range { min: -2.0, max: 2.0 },
             description: Some("Encourages talking about new topics".to_string()),
         },
         PredefinedParameter {
             key: "stream".to_string(),
             title: "Stream".to_string(),
             value: serde_json::Value::Bool(true),
+            param_type: ParameterType::Boolean,
             description: Some("Enable streaming responses".to_string()),
         },
         PredefinedParameter {
             key: "timeout".to_string(),
             title: "Timeout".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from(30)),
+            param_type: ParameterType::Range { min: 1.0, max: 600.0 },
             description: Some("Request timeout in seconds".to_string()),
         },
         PredefinedParameter {
             key: "retry_attempts".to_string(),
             title: "Retry Attempts".to_string(),
             value: serde_json::Value::Number(serde_json::Number::from(3)),
+            param_type: ParameterType::Range { min: 0.0, max: 10.0 },
             description: Some("Number of retry attempts on failure".to_string()),
         },
     ]
@@ -70,11 +153,26 @@ pub fn get_predefined_parameters() -> Vec<PredefinedParameter> {
 pub struct ParameterManagerProps {
     pub parameters: Vec<AgentParameter>,
     pub on_parameters_change: EventHandler<Vec<AgentParameter>>,
+    /// Called with `false` while any row holds a value its type rejects, and `true` once none do
+    pub on_validity_change: Option<EventHandler<bool>>,
     pub class: Option<String>,
 }
 
 #[component]
 pub fn ParameterManager(props: ParameterManagerProps) -> Element {
+    // Rows whose last edit failed validation
+    let mut invalid_rows = use_signal(BTreeSet::<usize>::new);
+    let mut report_validity = move |rows: BTreeSet<usize>| {
+        let was_valid = invalid_rows.read().is_empty();
+        let valid = rows.is_empty();
+        invalid_rows.set(rows);
+        if valid != was_valid {
+            if let Some(handler) = props.on_validity_change {
+                handler.call(valid);
+            }
+        }
+    };
+
     rsx! {
         div { class: format!("space-y-4 {}", props.class.unwrap_or_default()),
             // Header with Add Parameter Button
@@ -114,7 +212,7 @@ pub fn ParameterManager(props: ParameterManagerProps) -> Element {
                                     new_params.push(AgentParameter {
                                         key: param.key.clone(),
                                         value: param.value.clone(),
-                                        param_type: determine_parameter_type(&param.value),
+                                        param_type: param.param_type.clone(),
                                     });
                                     props.on_parameters_change.call(new_params);
                                 }
@@ -155,6 +253,22 @@ pub fn ParameterManager(props: ParameterManagerProps) -> Element {
                                 let mut new_params = props.parameters.clone();
                                 new_params.remove(index);
                                 props.on_parameters_change.call(new_params);
+                                let rows = invalid_rows
+                                    .read()
+                                    .iter()
+                                    .filter(|&&row| row != index)
+                                    .map(|&row| if row > index { row - 1 } else { row })
+                                    .collect();
+                                report_validity(rows);
+                            },
+                            on_validation_change: move |(index, error): (usize, Option<String>)| {
+                                let mut rows = invalid_rows.read().clone();
+                                if error.is_some() {
+                                    rows.insert(index);
+                                } else {
+                                    rows.remove(&index);
+                                }
+                                report_validity(rows);
                             },
                         }
                     }
@@ -170,12 +284,25 @@ pub struct ParameterRowProps {
     pub index: usize,
     pub on_parameter_change: EventHandler<(usize, AgentParameter)>,
     pub on_remove: EventHandler<usize>,
+    /// Reports the row's validation error, or `None` once its value is valid again
+    pub on_validation_change: Option<EventHandler<(usize, Option<String>)>>,
 }
 
 #[component]
 pub fn ParameterRow(props: ParameterRowProps) -> Element {
     let param_type = props.parameter.param_type.clone();
     let mut type_dropdown_open = use_signal(|| false);
+    // Text that failed validation is kept here rather than written to the parameter
+    let mut draft = use_signal(|| None::<String>);
+    let mut error = use_signal(|| None::<String>);
+    let mut set_error = move |message: Option<String>| {
+        if *error.peek() != message {
+            error.set(message.clone());
+            if let Some(handler) = props.on_validation_change {
+                handler.call((props.index, message));
+            }
+        }
+    };
 
     let handle_type_change = move |new_type: ParameterType| {
         let mut new_param = props.parameter.clone();
@@ -184,13 +311,15 @@ pub fn ParameterRow(props: ParameterRowProps) -> Element {
         // Convert value to new type
         new_param.value = match new_type {
             ParameterType::String => serde_json::Value::String(format!("{:?}", new_param.value)),
-            ParameterType::Number => serde_json::Value::Number(
+            ParameterType::Number | ParameterType::Range { .. } => serde_json::Value::Number(
                 serde_json::Number::from_f64(new_param.value.as_f64().unwrap_or(0.0)).unwrap_or_else(|| serde_json::Number::from(0))
             ),
             ParameterType::Boolean => serde_json::Value::Bool(new_param.value.as_bool().unwrap_or(false)),
-            ParameterType::Json => new_param.value,
+            ParameterType::Enum(_) | ParameterType::Json => new_param.value,
         };
 
+        draft.set(None);
+        set_error(new_type.validate(&value_text(&new_param.value)).err());
         props.on_parameter_change.call((props.index, new_param));
         type_dropdown_open.set(false);
     };
@@ -201,30 +330,20 @@ pub fn ParameterRow(props: ParameterRowProps) -> Element {
         props.on_parameter_change.call((props.index, new_param));
     };
 
-    let handle_value_change = move |value_str: String| {
+    let value_type = param_type.clone();
+    let mut handle_value_change = move |value_str: String| {
+        if let Err(message) = value_type.validate(&value_str) {
+            draft.set(Some(value_str));
+            set_error(Some(message));
+            return;
+        }
+        draft.set(None);
+        set_error(None);
         let mut new_param = props.parameter.clone();
-        new_param.value = match param_type {
-            ParameterType::String => serde_json::Value::String(value_str),
-            ParameterType::Number => {
-                if let Ok(num) = value_str.parse::<f64>() {
-                    serde_json::Value::Number(serde_json::Number::from_f64(num).unwrap_or_else(|| serde_json::Number::from(0)))
-                } else {
-                    serde_json::Value::String(value_str)
-                }
-            },
-            ParameterType::Boolean => {
-                serde_json::Value::Bool(value_str.parse().unwrap_or(false))
-            },
-            ParameterType::Json => {
-                if let Ok(json_val) = serde_json::from_str(&value_str) {
-                    json_val
-                } else {
-                    serde_json::Value::String(value_str)
-                }
-            },
-        };
+        new_param.value = value_type.parse(&value_str);
         props.on_parameter_change.call((props.index, new_param));
     };
+    let value = draft().unwrap_or_else(|| value_text(&props.parameter.value));
 
     rsx! {
         div { class: "flex items-center gap-3 p-3 bg-gray-50 dark:bg-gray-800 rounded-lg border border-gray-200 dark:border-gray-700",
@@ -244,7 +363,7 @@ pub fn ParameterRow(props: ParameterRowProps) -> Element {
                     class: "flex items-center gap-1 px-3 py-2 text-sm border border-gray-300 dark:border-gray-600 rounded-md bg-white dark:bg-gray-800 hover:bg-gray-50 dark:hover:bg-gray-700",
                     onclick: move |_| type_dropdown_open.set(!type_dropdown_open()),
                     span {
-                        "{param_type:?}"
+                        "{param_type.label()}"
                     }
                     span { class: "text-xs", "▼" }
                 }
@@ -289,33 +408,46 @@ pub fn ParameterRow(props: ParameterRowProps) -> Element {
                             },
                         }
                     },
-                    ParameterType::Number => {
+                    ParameterType::Number | ParameterType::Range { .. } => {
                         Input {
-                            value: props.parameter.value.as_f64().unwrap_or(0.0).to_string(),
+                            value: value.clone(),
                             r#type: "number".to_string(),
-                            oninput: handle_value_change,
+                            oninput: move |text| handle_value_change(text),
                             placeholder: "Number value...",
-                            class: "text-sm",
+                            class: if error().is_some() { "text-sm border-red-500" } else { "text-sm" },
+                        }
+                    },
+                    ParameterType::Enum(ref options) => {
+                        select {
+                            class: "px-3 py-2 text-sm border border-gray-300 dark:border-gray-600 rounded-md bg-white dark:bg-gray-800",
+                            value: "{value}",
+                            onchange: move |evt| handle_value_change(evt.value()),
+                            for option in options.iter().cloned() {
+                                option { value: "{option}", "{option}" }
+                            }
                         }
                     },
                     ParameterType::Json => {
                         Textarea {
-                            value: props.parameter.value.to_string(),
-                            oninput: handle_value_change,
+                            value: value.clone(),
+                            oninput: move |text| handle_value_change(text),
                             placeholder: "JSON value...",
                             rows: 2,
-                            class: "text-sm font-mono",
+                            class: if error().is_some() { "text-sm font-mono border-red-500" } else { "text-sm font-mono" },
                         }
                     },
-                    _ => {
+                    ParameterType::String => {
                         Input {
-                            value: props.parameter.value.as_str().unwrap_or("").to_string(),
-                            oninput: handle_value_change,
+                            value: value.clone(),
+                            oninput: move |text| handle_value_change(text),
                             placeholder: "String value...",
-                            class: "text-sm",
+                            class: if error().is_some() { "text-sm border-red-500" } else { "text-sm" },
                         }
                     },
                 }
+                if let Some(message) = error() {
+                    p { class: "text-xs text-red-500 mt-1", role: "alert", "{message}" }
+                }
             }
 
             // Remove Button
@@ -329,12 +461,54 @@ pub fn ParameterRow(props: ParameterRowProps) -> Element {
     }
 }
 
-fn determine_parameter_type(value: &serde_json::Value) -> ParameterType {
-    match value {
-        serde_json::Value::String(_) => ParameterType::String,
-        serde_json::Value::Number(_) => ParameterType::Number,
-        serde_json::Value::Bool(_) => ParameterType::Boolean,
-        serde_json::Value::Object(_) | serde_json::Value::Array(_) => ParameterType::Json,
-        serde_json::Value::Null => ParameterType::String,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strings_must_not_be_blank() {
+        assert!(ParameterType::String.validate("gpt-4o").is_ok());
+        assert_eq!(ParameterType::String.validate("  "), Err("Enter a value".to_string()));
+    }
+
+    #[test]
+    fn test_numbers_must_parse_and_stay_in_range() {
+        assert!(ParameterType::Number.validate("-3.5").is_ok());
+        assert_eq!(ParameterType::Number.validate("abc"), Err("Enter a number".to_string()));
+        assert!(ParameterType::Number.validate("NaN").is_err());
+
+        let temperature = ParameterType::Range { min: 0.0, max: 2.0 };
+        assert!(temperature.validate("0").is_ok());
+        assert!(temperature.validate("2").is_ok());
+        assert_eq!(temperature.validate("2.5"), Err("Must be between 0 and 2".to_string()));
+        assert!(temperature.validate("warm").is_err());
+    }
+
+    #[test]
+    fn test_booleans_and_enums_accept_only_their_values() {
+        assert!(ParameterType::Boolean.validate("true").is_ok());
+        assert!(ParameterType::Boolean.validate("yes").is_err());
+
+        let mode = ParameterType::Enum(vec!["chat".to_string(), "agent".to_string()]);
+        assert!(mode.validate("agent").is_ok());
+        assert_eq!(mode.validate("auto"), Err("Must be one of: chat, agent".to_string()));
+    }
+
+    #[test]
+    fn test_json_must_parse() {
+        assert!(ParameterType::Json.validate(r#"{"stop": ["\n"]}"#).is_ok());
+        assert!(ParameterType::Json.validate("{stop").unwrap_err().starts_with("Invalid JSON"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_templates_hold_values_their_types_accept() {
+        for template in get_predefined_parameters() {
+            assert_eq!(
+                template.param_type.validate(&value_text(&template.value)),
+                Ok(()),
+                "{}",
+                template.key
+            );
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Global provider reachability, polled from the health-check endpoint
+use api::ProviderHealth;
+use dioxus::prelude::*;
+
+/// Replies slower than this count as degraded
+pub const DEGRADED_LATENCY_MS: u64 = 2_000;
+pub const ONLINE_POLL_MS: i64 = 30_000;
+pub const DEGRADED_POLL_MS: i64 = 10_000;
+/// First retry delay once checks fail; doubles per failure up to `MAX_POLL_MS`
+pub const OFFLINE_BASE_POLL_MS: i64 = 5_000;
+pub const MAX_POLL_MS: i64 = 120_000;
+/// Consecutive failed checks before a provider is shown as offline rather than degraded
+pub const OFFLINE_AFTER_FAILURES: u32 = 2;
+
+/// How often the poll loop wakes to see whether a check is due
+const TICK_MS: u64 = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProviderStatus {
+    /// No check has finished yet
+    #[default]
+    Unknown,
+    Online,
+    Degraded,
+    Offline,
+}
+
+impl ProviderStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProviderStatus::Unknown => "Checking...",
+            ProviderStatus::Online => "Online",
+            ProviderStatus::Degraded => "Degraded",
+            ProviderStatus::Offline => "Offline",
+        }
+    }
+
+    fn dot_class(&self) -> &'static str {
+        match self {
+            ProviderStatus::Unknown => "bg-gray-400",
+            ProviderStatus::Online => "bg-green-500",
+            ProviderStatus::Degraded => "bg-yellow-500",
+            ProviderStatus::Offline => "bg-red-500",
+        }
+    }
+}
+
+/// Status plus the polling schedule that follows from it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderStatusTracker {
+    pub status: ProviderStatus,
+    consecutive_failures: u32,
+    next_check_ms: i64,
+}
+
+impl ProviderStatusTracker {
+    pub fn is_due(&self, now_ms: i64) -> bool {
+        now_ms >= self.next_check_ms
+    }
+
+    /// Apply a health-check result. `Err` means the endpoint itself couldn't be reached.
+    pub fn observe(&mut self, result: &Result<ProviderHealth, String>, now_ms: i64) {
+        match result {
+            Ok(health) if health.reachable => {
+                self.consecutive_failures = 0;
+                if health.latency_ms > DEGRADED_LATENCY_MS {
+                    self.status = ProviderStatus::Degraded;
+                    self.next_check_ms = now_ms + DEGRADED_POLL_MS;
+                } else {
+                    self.status = ProviderStatus::Online;
+                    self.next_check_ms = now_ms + ONLINE_POLL_MS;
+                }
+            }
+            _ => {
+                self.consecutive_failures += 1;
+                self.status = if self.consecutive_failures < OFFLINE_AFTER_FAILURES {
+                    ProviderStatus::Degraded
+                } else {
+                    ProviderStatus::Offline
+                };
+                let backoff = OFFLINE_BASE_POLL_MS
+                    .saturating_mul(1 << (self.consecutive_failures - 1).min(16))
+                    .min(MAX_POLL_MS);
+                self.next_check_ms = now_ms + backoff;
+            }
+        }
+    }
+
+    /// A real request just went through: trust it over a stale failed check and confirm now
+    pub fn request_succeeded(&mut self, now_ms: i64) {
+        if self.status != ProviderStatus::Online {
+            self.status = ProviderStatus::Online;
+            self.consecutive_failures = 0;
+            self.next_check_ms = now_ms;
+        }
+    }
+}
+
+/// Handle returned by `use_provider_status`. It is also provided as context, so views below
+/// can call `request_succeeded` after a send.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ProviderStatusHandle {
+    tracker: Signal<ProviderStatusTracker>,
+}
+
+impl ProviderStatusHandle {
+    pub fn status(&self) -> ProviderStatus {
+        self.tracker.read().status
+    }
+
+    pub fn request_succeeded(&mut self) {
+        self.tracker
+            .write()
+            .request_succeeded(chrono::Utc::now().timestamp_millis());
+    }
+}
+
+/// Poll the health-check endpoint for `model` (the default model when `None`). Goes through
+/// the server function and a JS timer, so web and desktop behave the same.
+pub fn use_provider_status(model: Option<String>) -> ProviderStatusHandle {
+    let mut tracker = use_signal(ProviderStatusTracker::default);
+    let mut active_model = use_signal(|| model.clone().unwrap_or_default());
+
+    // Switching models starts over with an immediate check
+    use_effect(use_reactive!(|model| {
+        let model = model.unwrap_or_default();
+        if *active_model.peek() != model {
+            active_model.set(model);
+            tracker.set(ProviderStatusTracker::default());
+        }
+    }));
+
+    use_future(move || async move {
+        loop {
+            if tracker.peek().is_due(chrono::Utc::now().timestamp_millis()) {
+                let model = active_model.peek().clone();
+                let result = api::check_provider_health(model.clone())
+                    .await
+                    .map_err(|e| e.to_string());
+                // Drop results for a model the user has already switched away from
+                if *active_model.peek() == model {
+                    tracker
+                        .write()
+                        .observe(&result, chrono::Utc::now().timestamp_millis());
+                }
+            }
+
+            let _ = document::eval(&format!(
+                "await new Promise(r => setTimeout(r, {})); return true;",
+                TICK_MS
+            ))
+            .join::<bool>()
+            .await;
+        }
+    });
+
+    use_context_provider(|| ProviderStatusHandle { tracker })
+}
+
+/// Dot and label for the navbar
+#[component]
+pub fn ProviderStatusBadge(status: ProviderStatus) -> Element {
+    rsx! {
+        span {
+            class: "inline-flex items-center gap-1.5 text-xs text-gray-600 dark:text-gray-300",
+            role: "status",
+            title: "Provider connection: {status.label()}",
+            span { class: "w-2 h-2 rounded-full {status.dot_class()}" }
+            "{status.label()}"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(reachable: bool, latency_ms: u64) -> Result<ProviderHealth, String> {
+        Ok(ProviderHealth {
+            model: "mock-local".to_string(),
+            reachable,
+            latency_ms,
+            error: None,
+        })
+    }
+
+    #[test]
+    fn test_status_follows_health_responses_and_backs_off_when_offline() {
+        let mut tracker = ProviderStatusTracker::default();
+        assert!(tracker.is_due(0));
+
+        tracker.observe(&health(true, 50), 0);
+        assert_eq!(tracker.status, ProviderStatus::Online);
+        assert!(!tracker.is_due(ONLINE_POLL_MS - 1));
+
+        tracker.observe(&health(true, DEGRADED_LATENCY_MS + 1), 0);
+        assert_eq!(tracker.status, ProviderStatus::Degraded);
+
+        // One failure is degraded, the next is offline, and each retry waits longer
+        tracker.observe(&Err("connection refused".to_string()), 0);
+        assert_eq!(tracker.status, ProviderStatus::Degraded);
+        tracker.observe(&health(false, 0), 0);
+        assert_eq!(tracker.status, ProviderStatus::Offline);
+        assert!(!tracker.is_due(OFFLINE_BASE_POLL_MS * 2 - 1));
+        tracker.observe(&Err("connection refused".to_string()), 0);
+        assert!(!tracker.is_due(OFFLINE_BASE_POLL_MS * 4 - 1));
+
+        // A successful send brings it back and asks for a confirming check straight away
+        tracker.request_succeeded(1_000);
+        assert_eq!(tracker.status, ProviderStatus::Online);
+        assert!(tracker.is_due(1_000));
+    }
+}
@@ -0,0 +1,86 @@
+// Collapsible panel showing the agent's reasoning chain for a session
+use api::{ReasoningStep, ReasoningStepType};
+use dioxus::prelude::*;
+
+/// How often the panel refreshes while a reply is being planned
+const REASONING_POLL_MS: u64 = 1000;
+
+#[component]
+pub fn ReasoningPanel(session_id: String) -> Element {
+    let mut expanded = use_signal(|| false);
+    let mut steps = use_signal(Vec::<ReasoningStep>::new);
+
+    let mut active_session = use_signal(|| session_id.clone());
+    use_effect(use_reactive!(|session_id| {
+        active_session.set(session_id);
+        steps.set(Vec::new());
+    }));
+
+    // Poll so steps show up live while the agent is still working
+    use_future(move || async move {
+        loop {
+            let session_id = active_session.peek().clone();
+            if let Ok(chain) = api::get_reasoning_chain(session_id).await {
+                if *steps.peek() != chain {
+                    steps.set(chain);
+                }
+            }
+
+            let _ = document::eval(&format!(
+                "await new Promise(r => setTimeout(r, {})); return true;",
+                REASONING_POLL_MS
+            ))
+            .join::<bool>()
+            .await;
+        }
+    });
+
+    let step_count = steps.read().len();
+
+    rsx! {
+        div { class: "border border-gray-200 dark:border-gray-700 rounded-lg bg-white dark:bg-gray-800 text-sm",
+            button {
+                class: "w-full flex items-center justify-between px-3 py-2 text-gray-700 dark:text-gray-300",
+                onclick: move |_| expanded.toggle(),
+                span { class: "font-medium", "🧭 Reasoning" }
+                span { class: "text-xs text-gray-500 dark:text-gray-400",
+                    if expanded() { "▾ {step_count} steps" } else { "▸ {step_count} steps" }
+                }
+            }
+
+            if expanded() {
+                div { class: "border-t border-gray-200 dark:border-gray-700 px-3 py-2 space-y-2",
+                    if step_count == 0 {
+                        p { class: "text-xs text-gray-500 dark:text-gray-400", "No reasoning recorded yet" }
+                    }
+                    for (index, step) in steps.read().iter().enumerate() {
+                        ReasoningStepRow { key: "{index}", step: step.clone() }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ReasoningStepRow(step: ReasoningStep) -> Element {
+    let label = match step.step_type {
+        ReasoningStepType::Analysis => "Analysis",
+        ReasoningStepType::Planning => "Planning",
+        ReasoningStepType::ToolSelection => "Tool selection",
+        ReasoningStepType::Reflection => "Reflection",
+    };
+    let percent = (step.confidence * 100.0).round() as u32;
+
+    rsx! {
+        div { class: "flex items-start gap-3",
+            div { class: "w-24 shrink-0 text-xs font-medium text-gray-500 dark:text-gray-400", "{label}" }
+            div { class: "flex-1 min-w-0 text-gray-800 dark:text-gray-200 whitespace-pre-wrap break-words", "{step.content}" }
+            div {
+                class: "w-16 shrink-0 h-1.5 mt-1.5 rounded-full bg-gray-200 dark:bg-gray-700 overflow-hidden",
+                title: "Confidence {percent}%",
+                div { class: "h-full bg-blue-500", style: "width: {percent}%" }
+            }
+        }
+    }
+}
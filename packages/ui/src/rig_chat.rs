@@ -1,6 +1,8 @@
 // Rig-Integrated Chat Components
 use dioxus::prelude::*;
 use api::{AgentConfig, GooseMode, ChatRequest, ChatMessage, Role};
+use crate::markdown::Markdown;
+use crate::ui_components::EmptyState;
 
 #[derive(Clone, PartialEq, Props)]
 pub struct SimpleChatMessage {
@@ -67,6 +69,7 @@ impl Default for RigChatState {
                 enable_autopilot: false,
                 enable_extensions: true,
                 extension_timeout: 30,
+                ..AgentConfig::default()
             },
             is_streaming: false,
             current_model: "gpt-3.5-turbo".to_string(),
@@ -119,7 +122,7 @@ pub fn RigChatContainer(
                                         "max-w-xs lg:max-w-2xl bg-gray-200 dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-lg p-3"
                                     },
 
-                                    p { class: "text-sm", "{message.content}" }
+                                    Markdown { content: message.content.clone(), class: "text-sm" }
 
                                     if let Some(timestamp) = message.timestamp {
                                         p { class: "text-xs mt-1 opacity-70", "{timestamp}" }
@@ -331,10 +334,12 @@ pub fn RigChatSidebar(
             // Conversation List
             div { class: "flex-1 overflow-y-auto p-2",
                 if conversations.is_empty() {
-                    div { class: "text-center text-gray-500 dark:text-gray-400 py-8",
-                        div { class: "text-4xl mb-2", "💭" }
-                        p { "No conversations yet" }
-                        p { class: "text-sm mt-1", "Start a new conversation to see it here" }
+                    EmptyState {
+                        icon: "💭",
+                        title: "No conversations yet",
+                        description: "Start a new conversation to see it here",
+                        action_label: "New conversation",
+                        on_action: move |_| on_new_conversation.call(()),
                     }
                 } else {
                     for conversation in conversations {
@@ -280,6 +280,7 @@ pub fn SettingsCoreExample() -> Element {
         enable_extensions: true,
         extension_timeout: 30,
         goose_mode: GooseMode::Agent,
+        ..AgentConfig::default()
     };
 
     rsx! {
@@ -0,0 +1,242 @@
+// Settings dialog editing the persisted app settings from `use_settings`
+use crate::settings_store::{use_settings, Theme};
+use crate::settings_validation::{
+    validate_numeric, NumericBounds, CACHE_SIZE_MB, MAX_CONCURRENT_REQUESTS, MAX_ITERATIONS,
+    MEMORY_LIMIT_MB, NETWORK_TIMEOUT_SECONDS,
+};
+use crate::ui_components::*;
+use api::GooseMode;
+use dioxus::prelude::*;
+
+#[derive(Clone, PartialEq, Props)]
+pub struct SettingsDialogProps {
+    pub open: bool,
+    pub on_open_change: EventHandler<bool>,
+}
+
+/// Changes apply immediately; the settings provider saves them once they stop changing
+#[component]
+pub fn SettingsDialog(props: SettingsDialogProps) -> Element {
+    let mut settings = use_settings();
+
+    let mut handle_theme_change = move |theme: String| {
+        settings.write().theme = match theme.as_str() {
+            "Light" => Theme::Light,
+            "Dark" => Theme::Dark,
+            _ => Theme::Auto,
+        };
+    };
+
+    let mut handle_mode_change = move |mode: String| {
+        settings.write().agent_config.goose_mode = match mode.as_str() {
+            "Agent" => GooseMode::Agent,
+            "Auto" => GooseMode::Auto,
+            _ => GooseMode::Chat,
+        };
+    };
+
+    let mut cache_status = use_signal(|| None::<String>);
+    let handle_clear_cache = move |_| {
+        spawn(async move {
+            let status = match api::clear_cache().await {
+                Ok(()) => "Cache cleared".to_string(),
+                Err(e) => format!("Couldn't clear the cache: {}", e),
+            };
+            cache_status.set(Some(status));
+        });
+    };
+
+    let theme = match settings.read().theme {
+        Theme::Light => "Light",
+        Theme::Dark => "Dark",
+        Theme::Auto | Theme::Custom(_) => "Auto",
+    };
+
+    rsx! {
+        Dialog {
+            open: props.open,
+            on_open_change: props.on_open_change,
+            max_width: Some("max-w-xl".to_string()),
+            aria_label: Some("Settings".to_string()),
+            DialogHeader {
+                DialogTitle { "Settings" }
+            }
+
+            DialogContent {
+                // General
+                div { class: "space-y-4",
+                    h3 { class: "text-lg font-medium text-gray-900 dark:text-gray-100",
+                        "General"
+                    }
+                    div {
+                        label { class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
+                            "Theme"
+                        }
+                        select {
+                            class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 focus:outline-none focus:ring-2 focus:ring-blue-500",
+                            value: theme,
+                            onchange: move |evt| handle_theme_change(evt.value()),
+                            option { value: "Auto", "System" }
+                            option { value: "Light", "Light" }
+                            option { value: "Dark", "Dark" }
+                        }
+                    }
+                    div {
+                        label { class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
+                            "Language"
+                        }
+                        Input {
+                            value: settings.read().language.clone(),
+                            oninput: move |language| settings.write().language = language,
+                            placeholder: "en",
+                            class: "w-24",
+                        }
+                    }
+                }
+
+                // Agent
+                div { class: "space-y-4",
+                    h3 { class: "text-lg font-medium text-gray-900 dark:text-gray-100",
+                        "Agent"
+                    }
+                    div {
+                        label { class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
+                            "Default Mode"
+                        }
+                        select {
+                            class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100 focus:outline-none focus:ring-2 focus:ring-blue-500",
+                            value: "{settings.read().agent_config.goose_mode:?}",
+                            onchange: move |evt| handle_mode_change(evt.value()),
+                            option { value: "Chat", "💬 Chat" }
+                            option { value: "Agent", "🔧 Agent" }
+                            option { value: "Auto", "🤖 Auto" }
+                        }
+                    }
+                    NumericSetting {
+                        label: "Max Iterations",
+                        value: settings.read().agent_config.max_iterations as u64,
+                        bounds: MAX_ITERATIONS,
+                        on_change: move |value| {
+                            settings.write().agent_config.max_iterations = value as usize;
+                        },
+                    }
+                    div { class: "flex items-center justify-between",
+                        label { class: "text-sm font-medium text-gray-700 dark:text-gray-300",
+                            "Require Confirmation"
+                        }
+                        Switch {
+                            checked: settings.read().agent_config.require_confirmation,
+                            on_checked_change: move |checked| {
+                                settings.write().agent_config.require_confirmation = checked;
+                            },
+                        }
+                    }
+                }
+
+                // Performance
+                div { class: "space-y-4",
+                    h3 { class: "text-lg font-medium text-gray-900 dark:text-gray-100",
+                        "Performance"
+                    }
+                    NumericSetting {
+                        label: "Max Concurrent Requests",
+                        value: settings.read().performance.max_concurrent_requests as u64,
+                        bounds: MAX_CONCURRENT_REQUESTS,
+                        on_change: move |value| {
+                            settings.write().performance.max_concurrent_requests = value as u32;
+                        },
+                    }
+                    NumericSetting {
+                        label: "Network Timeout (seconds)",
+                        value: settings.read().performance.network_timeout_seconds,
+                        bounds: NETWORK_TIMEOUT_SECONDS,
+                        on_change: move |value| {
+                            settings.write().performance.network_timeout_seconds = value;
+                        },
+                    }
+                    NumericSetting {
+                        label: "Cache Size (MB)",
+                        value: settings.read().performance.cache_size_mb as u64,
+                        bounds: CACHE_SIZE_MB,
+                        on_change: move |value| {
+                            settings.write().performance.cache_size_mb = value as u32;
+                            spawn(async move {
+                                let _ = api::set_cache_size(value as u32).await;
+                            });
+                        },
+                    }
+                    NumericSetting {
+                        label: "Memory Limit (MB)",
+                        value: settings.read().performance.memory_limit_mb as u64,
+                        bounds: MEMORY_LIMIT_MB,
+                        on_change: move |value| {
+                            settings.write().performance.memory_limit_mb = value as u32;
+                        },
+                    }
+                    div { class: "flex items-center justify-between",
+                        div {
+                            p { class: "text-sm font-medium text-gray-700 dark:text-gray-300",
+                                "Response Cache"
+                            }
+                            p { class: "text-xs text-gray-500 dark:text-gray-400",
+                                {cache_status().unwrap_or("Replies to repeated temperature-0 requests".to_string())}
+                            }
+                        }
+                        Button {
+                            onclick: handle_clear_cache,
+                            variant: ButtonVariant::Ghost,
+                            "Clear Cache"
+                        }
+                    }
+                }
+            }
+
+            DialogFooter {
+                Button {
+                    onclick: move |_| props.on_open_change.call(false),
+                    variant: ButtonVariant::Primary,
+                    "Done"
+                }
+            }
+        }
+    }
+}
+
+/// A whole-number field that only writes values within `bounds`. Invalid input stays in the
+/// field with the reason shown under it.
+#[component]
+fn NumericSetting(
+    label: String,
+    value: u64,
+    bounds: NumericBounds,
+    on_change: EventHandler<u64>,
+) -> Element {
+    let mut input = use_signal(|| value.to_string());
+    let mut error = use_signal(|| None::<String>);
+
+    rsx! {
+        div {
+            label { class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
+                "{label}"
+            }
+            Input {
+                value: input(),
+                r#type: "number".to_string(),
+                oninput: move |text: String| {
+                    match validate_numeric(&text, bounds) {
+                        Ok(value) => {
+                            error.set(None);
+                            on_change.call(value);
+                        }
+                        Err(message) => error.set(Some(message)),
+                    }
+                    input.set(text);
+                },
+                class: if error().is_some() { "w-32 border-red-500" } else { "w-32" },
+            }
+            if let Some(message) = error() {
+                p { class: "text-xs text-red-500 mt-1", role: "alert", "{message}" }
+            }
+        }
+    }
+}
@@ -187,6 +187,7 @@ pub fn SettingsExample() -> Element {
         enable_extensions: true,
         extension_timeout: 30,
         goose_mode: GooseMode::Agent,
+        ..AgentConfig::default()
     };
 
     rsx! {
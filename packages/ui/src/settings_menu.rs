@@ -1,6 +1,7 @@
 use crate::components::button::{Button, ButtonVariant};
 use crate::components::input::Input;
 use crate::components::switch::Switch;
+use crate::ui_components::Slider;
 use api::{AgentConfig, GooseMode};
 use dioxus::prelude::*;
 
@@ -277,18 +278,17 @@ pub fn SettingsMenu(props: SettingsMenuProps) -> Element {
                                             class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
                                             "Compact Threshold (%)"
                                         }
-                                        Input {
-                                            value: (agent_config_signal.read().compact_threshold * 100.0).to_string(),
-                                            placeholder: "80",
-                                            onchange: move |event: Event<FormData>| {
-                                                if let Ok(threshold) = event.value().parse::<f32>() {
-                                                    let mut config = agent_config_signal.read().clone();
-                                                    config.compact_threshold = threshold / 100.0;
-                                                    agent_config_signal.set(config.clone());
-                                                    props.on_agent_config_change.call(config);
-                                                }
+                                        Slider {
+                                            min: 10.0,
+                                            max: 100.0,
+                                            step: 5.0,
+                                            value: agent_config_signal.read().compact_threshold * 100.0,
+                                            on_change: move |threshold: f32| {
+                                                let mut config = agent_config_signal.read().clone();
+                                                config.compact_threshold = threshold / 100.0;
+                                                agent_config_signal.set(config.clone());
+                                                props.on_agent_config_change.call(config);
                                             },
-                                            class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 focus:outline-none focus:ring-2 focus:ring-blue-500"
                                         }
                                         p {
                                             class: "mt-1 text-xs text-gray-500 dark:text-gray-400",
@@ -9,7 +9,19 @@ use crate::components::{
     dialog::{Dialog, DialogContent, DialogHeader, DialogTitle},
 };
 use dioxus::prelude::*;
+use std::collections::HashMap;
 use api::{AgentConfig, GooseMode};
+use crate::appearance::MessageDensity;
+use crate::ui_components::{EmptyState, Slider};
+use crate::settings_validation::{
+    validate_numeric, CACHE_SIZE_MB, EXTENSION_TIMEOUT_SECONDS, MAX_CONCURRENT_REQUESTS,
+    MAX_ITERATIONS, MAX_TURNS_WITHOUT_TOOLS, MEMORY_LIMIT_MB, NETWORK_TIMEOUT_SECONDS,
+    STREAMING_BUFFER_SIZE,
+};
+pub use crate::settings_store::{
+    DataSource, DataSourceType, LogLevel, Model, ModelPricing, PerformanceSettings, Provider,
+    RateLimit, SyncFrequency, Theme,
+};
 
 #[derive(Clone, PartialEq, Props)]
 pub struct SettingsPanelProps {
@@ -49,77 +61,6 @@ pub enum SettingsTab {
     Advanced,
 }
 
-#[derive(Clone, PartialEq)]
-pub struct Model {
-    pub id: String,
-    pub name: String,
-    pub provider: String,
-    pub description: Option<String>,
-    pub capabilities: Vec<String>,
-    pub context_limit: Option<usize>,
-    pub supports_tools: bool,
-    pub supports_streaming: bool,
-    pub supports_vision: bool,
-    pub supports_function_calling: bool,
-    pub pricing: Option<ModelPricing>,
-}
-
-#[derive(Clone, PartialEq)]
-pub struct ModelPricing {
-    pub input_tokens: f64,
-    pub output_tokens: f64,
-    pub currency: String,
-}
-
-#[derive(Clone, PartialEq)]
-pub struct Provider {
-    pub id: String,
-    pub name: String,
-    pub api_key: Option<String>,
-    pub base_url: Option<String>,
-    pub models: Vec<Model>,
-    pub active: bool,
-    pub rate_limit: Option<RateLimit>,
-    pub custom_headers: std::collections::HashMap<String, String>,
-}
-
-#[derive(Clone, PartialEq)]
-pub struct RateLimit {
-    pub requests_per_minute: u32,
-    pub tokens_per_minute: u32,
-}
-
-#[derive(Clone, PartialEq)]
-pub struct DataSource {
-    pub id: String,
-    pub name: String,
-    pub type_: DataSourceType,
-    pub url: Option<String>,
-    pub api_key: Option<String>,
-    pub enabled: bool,
-    pub last_sync: Option<String>,
-    pub sync_frequency: SyncFrequency,
-}
-
-#[derive(Clone, PartialEq, Debug)]
-pub enum DataSourceType {
-    LocalFile,
-    GitHub,
-    Confluence,
-    Notion,
-    WebScraping,
-    Database,
-}
-
-#[derive(Clone, PartialEq, Debug)]
-pub enum SyncFrequency {
-    RealTime,
-    Hourly,
-    Daily,
-    Weekly,
-    Manual,
-}
-
 #[derive(Clone, PartialEq)]
 pub struct Shortcut {
     pub id: String,
@@ -139,35 +80,6 @@ pub enum ShortcutCategory {
     System,
 }
 
-#[derive(Clone, PartialEq)]
-pub struct PerformanceSettings {
-    pub max_concurrent_requests: u32,
-    pub cache_size_mb: u32,
-    pub streaming_buffer_size: usize,
-    pub enable_gpu_acceleration: bool,
-    pub memory_limit_mb: u32,
-    pub network_timeout_seconds: u64,
-    pub enable_compression: bool,
-    pub log_level: LogLevel,
-}
-
-#[derive(Clone, PartialEq, Debug)]
-pub enum LogLevel {
-    Error,
-    Warn,
-    Info,
-    Debug,
-    Trace,
-}
-
-#[derive(Clone, PartialEq)]
-pub enum Theme {
-    Light,
-    Dark,
-    Auto,
-    Custom(String),
-}
-
 #[component]
 pub fn SettingsPanelComplete(props: SettingsPanelProps) -> Element {
     if !props.open {
@@ -671,6 +583,11 @@ fn AppearanceSettings(
     theme: Option<Theme>,
     on_theme_change: EventHandler<Theme>,
 ) -> Element {
+    let appearance = try_use_context::<Signal<crate::appearance::AppearanceSettings>>();
+    let compact = appearance
+        .map(|settings| settings.read().density == MessageDensity::Compact)
+        .unwrap_or(false);
+
     rsx! {
         div {
             class: "space-y-8 p-4",
@@ -833,9 +750,15 @@ fn AppearanceSettings(
                             "Compact mode"
                         }
                         Switch {
-                            checked: false,
-                            on_checked_change: move |_checked| {
-                                // TODO: Implement compact mode
+                            checked: compact,
+                            on_checked_change: move |checked| {
+                                if let Some(mut settings) = appearance {
+                                    settings.write().density = if checked {
+                                        MessageDensity::Compact
+                                    } else {
+                                        MessageDensity::Comfortable
+                                    };
+                                }
                             },
                         }
                     }
@@ -1043,6 +966,15 @@ fn ModelsSettingsComplete(
                     "+ Add Provider"
                 }
             }
+            if providers.is_empty() {
+                EmptyState {
+                    icon: "🔌",
+                    title: "No providers configured",
+                    description: "Add a provider and API key to start chatting",
+                    action_label: "+ Add Provider",
+                    on_action: move |_| show_add_provider.set(true),
+                }
+            }
             div {
                 class: "space-y-3",
                 {providers.iter().map(|provider| {
@@ -1354,6 +1286,7 @@ fn AgentSettingsComplete(
 ) -> Element {
     let config = agent_config.unwrap_or_default();
     let mut config_signal = use_signal(|| config);
+    let mut field_errors = use_signal(HashMap::<&'static str, String>::new);
 
     rsx! {
         div {
@@ -1413,15 +1346,28 @@ fn AgentSettingsComplete(
                     Input {
                         value: config_signal.read().max_iterations.to_string(),
                         oninput: move |event| {
-                            if let Ok(iterations) = event.value().parse::<usize>() {
-                                let mut config = config_signal.read().clone();
-                                config.max_iterations = iterations;
-                                config_signal.set(config.clone());
-                                on_agent_config_change.call(config);
+                            match validate_numeric(&event.value(), MAX_ITERATIONS) {
+                                Ok(iterations) => {
+                                    let iterations = iterations as usize;
+                                    field_errors.write().remove("max_iterations");
+                                    let mut config = config_signal.read().clone();
+                                    config.max_iterations = iterations;
+                                    config_signal.set(config.clone());
+                                    on_agent_config_change.call(config);
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("max_iterations", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("max_iterations") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                     p {
                         class: "mt-1 text-xs text-gray-500 dark:text-gray-400",
                         "Maximum number of agent iterations before stopping"
@@ -1483,17 +1429,17 @@ fn AgentSettingsComplete(
                             class: "block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1",
                             "Compact Threshold (%)"
                         }
-                        Input {
-                            value: (config_signal.read().compact_threshold * 100.0).to_string(),
-                            oninput: move |event| {
-                                if let Ok(threshold) = event.value().parse::<f32>() {
-                                    let mut config = config_signal.read().clone();
-                                    config.compact_threshold = threshold / 100.0;
-                                    config_signal.set(config.clone());
-                                    on_agent_config_change.call(config);
-                                }
+                        Slider {
+                            min: 10.0,
+                            max: 100.0,
+                            step: 5.0,
+                            value: config_signal.read().compact_threshold * 100.0,
+                            on_change: move |threshold: f32| {
+                                let mut config = config_signal.read().clone();
+                                config.compact_threshold = threshold / 100.0;
+                                config_signal.set(config.clone());
+                                on_agent_config_change.call(config);
                             },
-                            class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                         }
                         p {
                             class: "mt-1 text-xs text-gray-500 dark:text-gray-400",
@@ -1511,15 +1457,28 @@ fn AgentSettingsComplete(
                     Input {
                         value: config_signal.read().max_turns_without_tools.to_string(),
                         oninput: move |event| {
-                            if let Ok(turns) = event.value().parse::<usize>() {
-                                let mut config = config_signal.read().clone();
-                                config.max_turns_without_tools = turns;
-                                config_signal.set(config.clone());
-                                on_agent_config_change.call(config);
+                            match validate_numeric(&event.value(), MAX_TURNS_WITHOUT_TOOLS) {
+                                Ok(turns) => {
+                                    let turns = turns as usize;
+                                    field_errors.write().remove("max_turns_without_tools");
+                                    let mut config = config_signal.read().clone();
+                                    config.max_turns_without_tools = turns;
+                                    config_signal.set(config.clone());
+                                    on_agent_config_change.call(config);
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("max_turns_without_tools", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("max_turns_without_tools") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                     p {
                         class: "mt-1 text-xs text-gray-500 dark:text-gray-400",
                         "Maximum number of consecutive turns without tool usage before stopping"
@@ -1584,15 +1543,27 @@ fn AgentSettingsComplete(
                         Input {
                             value: config_signal.read().extension_timeout.to_string(),
                             oninput: move |event| {
-                                if let Ok(timeout) = event.value().parse::<u64>() {
-                                    let mut config = config_signal.read().clone();
-                                    config.extension_timeout = timeout;
-                                    config_signal.set(config.clone());
-                                    on_agent_config_change.call(config);
+                                match validate_numeric(&event.value(), EXTENSION_TIMEOUT_SECONDS) {
+                                    Ok(timeout) => {
+                                        field_errors.write().remove("extension_timeout");
+                                        let mut config = config_signal.read().clone();
+                                        config.extension_timeout = timeout;
+                                        config_signal.set(config.clone());
+                                        on_agent_config_change.call(config);
+                                    }
+                                    Err(error) => {
+                                        field_errors.write().insert("extension_timeout", error);
+                                    }
                                 }
                             },
                             class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                         }
+                        if let Some(error) = field_errors.read().get("extension_timeout") {
+                            p {
+                                class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                                "{error}"
+                            }
+                        }
                         p {
                             class: "mt-1 text-xs text-gray-500 dark:text-gray-400",
                             "Maximum time to wait for extension responses"
@@ -2064,6 +2035,7 @@ fn PerformanceSettings(
     on_change: EventHandler<PerformanceSettings>,
 ) -> Element {
     let mut settings_signal: Signal<PerformanceSettings> = use_signal(|| settings);
+    let mut field_errors = use_signal(HashMap::<&'static str, String>::new);
 
     rsx! {
         div {
@@ -2085,15 +2057,28 @@ fn PerformanceSettings(
                     Input {
                         value: settings_signal.read().max_concurrent_requests.to_string(),
                         oninput: move |evt: dioxus::prelude::Event<FormEvent>| {
-                            if let Ok(value) = evt.value().parse::<u32>() {
-                                let mut settings = settings_signal.read().clone();
-                                settings.max_concurrent_requests = value;
-                                settings_signal.set(settings.clone());
-                                on_change.call(settings);
+                            match validate_numeric(&evt.value(), MAX_CONCURRENT_REQUESTS) {
+                                Ok(value) => {
+                                    let value = value as u32;
+                                    field_errors.write().remove("max_concurrent_requests");
+                                    let mut settings = settings_signal.read().clone();
+                                    settings.max_concurrent_requests = value;
+                                    settings_signal.set(settings.clone());
+                                    on_change.call(settings);
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("max_concurrent_requests", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("max_concurrent_requests") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                 }
 
                 // Cache Size
@@ -2105,15 +2090,31 @@ fn PerformanceSettings(
                     Input {
                         value: settings_signal.read().cache_size_mb.to_string(),
                         oninput: move |evt| {
-                            if let Ok(value) = evt.value().parse::<u32>() {
-                                let mut settings = settings_signal.read().clone();
-                                settings.cache_size_mb = value;
-                                settings_signal.set(settings.clone());
-                                on_change.call(settings);
+                            match validate_numeric(&evt.value(), CACHE_SIZE_MB) {
+                                Ok(value) => {
+                                    let value = value as u32;
+                                    field_errors.write().remove("cache_size_mb");
+                                    let mut settings = settings_signal.read().clone();
+                                    settings.cache_size_mb = value;
+                                    settings_signal.set(settings.clone());
+                                    on_change.call(settings);
+                                    spawn(async move {
+                                        let _ = api::set_cache_size(value).await;
+                                    });
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("cache_size_mb", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("cache_size_mb") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                 }
 
                 // Streaming Buffer Size
@@ -2125,15 +2126,32 @@ fn PerformanceSettings(
                     Input {
                         value: settings_signal.read().streaming_buffer_size.to_string(),
                         oninput: move |evt| {
-                            if let Ok(value) = evt.value().parse::<usize>() {
-                                let mut settings = settings_signal.read().clone();
-                                settings.streaming_buffer_size = value;
-                                settings_signal.set(settings.clone());
-                                on_change.call(settings);
+                            match validate_numeric(&evt.value(), STREAMING_BUFFER_SIZE) {
+                                Ok(value) => {
+                                    let value = value as usize;
+                                    field_errors.write().remove("streaming_buffer_size");
+                                    let mut settings = settings_signal.read().clone();
+                                    settings.streaming_buffer_size = value;
+                                    settings_signal.set(settings.clone());
+                                    on_change.call(settings);
+                                    // Replies streamed from now on are buffered to this size
+                                    spawn(async move {
+                                        let _ = api::set_streaming_buffer(value).await;
+                                    });
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("streaming_buffer_size", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("streaming_buffer_size") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                 }
 
                 // Memory Limit
@@ -2145,15 +2163,28 @@ fn PerformanceSettings(
                     Input {
                         value: settings_signal.read().memory_limit_mb.to_string(),
                         oninput: move |evt| {
-                            if let Ok(value) = evt.value().parse::<u32>() {
-                                let mut settings = settings_signal.read().clone();
-                                settings.memory_limit_mb = value;
-                                settings_signal.set(settings.clone());
-                                on_change.call(settings);
+                            match validate_numeric(&evt.value(), MEMORY_LIMIT_MB) {
+                                Ok(value) => {
+                                    let value = value as u32;
+                                    field_errors.write().remove("memory_limit_mb");
+                                    let mut settings = settings_signal.read().clone();
+                                    settings.memory_limit_mb = value;
+                                    settings_signal.set(settings.clone());
+                                    on_change.call(settings);
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("memory_limit_mb", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("memory_limit_mb") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                 }
 
                 // Network Timeout
@@ -2165,15 +2196,31 @@ fn PerformanceSettings(
                     Input {
                         value: settings_signal.read().network_timeout_seconds.to_string(),
                         oninput: move |evt| {
-                            if let Ok(value) = evt.value().parse::<u64>() {
-                                let mut settings = settings_signal.read().clone();
-                                settings.network_timeout_seconds = value;
-                                settings_signal.set(settings.clone());
-                                on_change.call(settings);
+                            match validate_numeric(&evt.value(), NETWORK_TIMEOUT_SECONDS) {
+                                Ok(value) => {
+                                    field_errors.write().remove("network_timeout_seconds");
+                                    let mut settings = settings_signal.read().clone();
+                                    settings.network_timeout_seconds = value;
+                                    settings_signal.set(settings.clone());
+                                    on_change.call(settings);
+                                    // Provider clients pick this up on their next request
+                                    spawn(async move {
+                                        let _ = api::set_network_timeout(value).await;
+                                    });
+                                }
+                                Err(error) => {
+                                    field_errors.write().insert("network_timeout_seconds", error);
+                                }
                             }
                         },
                         class: "w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800"
                     }
+                    if let Some(error) = field_errors.read().get("network_timeout_seconds") {
+                        p {
+                            class: "mt-1 text-xs text-red-600 dark:text-red-400",
+                            "{error}"
+                        }
+                    }
                 }
 
                 // Log Level
@@ -2283,6 +2330,8 @@ fn PerformanceSettings(
 
 #[component]
 fn AdvancedSettings() -> Element {
+    let mut show_debug_info = use_signal(|| false);
+
     rsx! {
         div {
             class: "space-y-6 p-4",
@@ -2327,9 +2376,13 @@ fn AdvancedSettings() -> Element {
                                 "Show Debug Information"
                             }
                             Switch {
-                                checked: false,
-                                on_checked_change: move |_checked| {
-                                    // TODO: Implement debug info
+                                checked: show_debug_info(),
+                                on_checked_change: move |checked| {
+                                    show_debug_info.set(checked);
+                                    // Records a per-session decision trace while enabled
+                                    spawn(async move {
+                                        let _ = api::set_trace_enabled(checked).await;
+                                    });
                                 },
                             }
                         }
@@ -2359,7 +2412,9 @@ fn AdvancedSettings() -> Element {
                         }
                         Button {
                             onclick: move |_| {
-                                // TODO: Implement cache clearing
+                                spawn(async move {
+                                    let _ = api::clear_cache().await;
+                                });
                             },
                             variant: ButtonVariant::Ghost,
                             "Clear"
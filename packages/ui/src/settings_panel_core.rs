@@ -506,7 +506,10 @@ fn ProviderCard(
     on_remove: EventHandler<String>,
 ) -> Element {
     let mut show_edit = use_signal(|| false);
+    // Last connection test: `None` until one finishes, then the error if it failed
+    let mut check = use_signal(|| None::<Option<api::ProviderError>>);
     let provider_id = provider.id.clone();
+    let check_id = provider.id.clone();
 
     rsx! {
         div {
@@ -536,7 +539,19 @@ fn ProviderCard(
                     class: "flex items-center gap-2",
                     div {
                         class: "w-2 h-2 rounded-full",
-                        class: if provider.enabled { "bg-green-500" } else { "bg-gray-400" }
+                        class: match check() {
+                            Some(None) => "bg-green-500",
+                            Some(Some(_)) => "bg-red-500",
+                            None => "bg-gray-400",
+                        },
+                        title: match check() {
+                            Some(None) => "Connected".to_string(),
+                            Some(Some(error)) if error.kind == api::ProviderErrorKind::Authentication => {
+                                format!("API key rejected: {}", error.message)
+                            }
+                            Some(Some(error)) => format!("Unreachable: {}", error.message),
+                            None => "Not tested".to_string(),
+                        },
                     }
                     DropdownMenu {
                         DropdownMenuTrigger {
@@ -554,8 +569,22 @@ fn ProviderCard(
                                 "Edit"
                             }
                             DropdownMenuItem::<String> {
-                                value: "remove".to_string(),
+                                value: "test".to_string(),
                                 index: 1usize,
+                                on_select: move |_: String| {
+                                    let id = check_id.clone();
+                                    spawn(async move {
+                                        let result = api::test_provider(id).await.unwrap_or_else(|e| {
+                                            Some(api::ProviderError::new(api::ProviderErrorKind::Network, e.to_string()))
+                                        });
+                                        check.set(Some(result));
+                                    });
+                                },
+                                "Test connection"
+                            }
+                            DropdownMenuItem::<String> {
+                                value: "remove".to_string(),
+                                index: 2usize,
                                 on_select: move |_: String| on_remove.call(provider_id.clone()),
                                 class: "text-red-600 dark:text-red-400",
                                 "Remove"
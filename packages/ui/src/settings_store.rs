@@ -0,0 +1,443 @@
+// Persisted application settings: a JSON file on desktop, local storage on web (without API keys)
+use api::AgentConfig;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bump when the stored layout changes, and teach `migrate` how to upgrade older data
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
+/// Local storage key used when there is no settings file
+const SETTINGS_STORAGE_KEY: &str = "dioxus-chat:settings";
+
+/// How long settings must stay unchanged before they're written out
+const SETTINGS_SAVE_DEBOUNCE_MS: u64 = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    Light,
+    Dark,
+    #[default]
+    Auto,
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Model {
+    pub id: String,
+    pub name: String,
+    pub provider: String,
+    pub description: Option<String>,
+    pub capabilities: Vec<String>,
+    pub context_limit: Option<usize>,
+    pub supports_tools: bool,
+    pub supports_streaming: bool,
+    pub supports_vision: bool,
+    pub supports_function_calling: bool,
+    pub pricing: Option<ModelPricing>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_tokens: f64,
+    pub output_tokens: f64,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provider {
+    pub id: String,
+    pub name: String,
+    pub api_key: Option<String>,
+    pub base_url: Option<String>,
+    pub models: Vec<Model>,
+    pub active: bool,
+    pub rate_limit: Option<RateLimit>,
+    pub custom_headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+    pub tokens_per_minute: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataSource {
+    pub id: String,
+    pub name: String,
+    pub type_: DataSourceType,
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+    pub enabled: bool,
+    pub last_sync: Option<String>,
+    pub sync_frequency: SyncFrequency,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DataSourceType {
+    LocalFile,
+    GitHub,
+    Confluence,
+    Notion,
+    WebScraping,
+    Database,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncFrequency {
+    RealTime,
+    Hourly,
+    Daily,
+    Weekly,
+    Manual,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerformanceSettings {
+    pub max_concurrent_requests: u32,
+    pub cache_size_mb: u32,
+    pub streaming_buffer_size: usize,
+    pub enable_gpu_acceleration: bool,
+    pub memory_limit_mb: u32,
+    pub network_timeout_seconds: u64,
+    pub enable_compression: bool,
+    pub log_level: LogLevel,
+}
+
+impl Default for PerformanceSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: 5,
+            cache_size_mb: 512,
+            streaming_buffer_size: 8192,
+            enable_gpu_acceleration: false,
+            memory_limit_mb: 2048,
+            network_timeout_seconds: 30,
+            enable_compression: true,
+            log_level: LogLevel::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Everything the settings panels let the user change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    pub theme: Theme,
+    pub language: String,
+    pub performance: PerformanceSettings,
+    pub agent_config: AgentConfig,
+    pub providers: Vec<Provider>,
+    pub data_sources: Vec<DataSource>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_SCHEMA_VERSION,
+            theme: Theme::default(),
+            language: "en".to_string(),
+            performance: PerformanceSettings::default(),
+            agent_config: AgentConfig::default(),
+            providers: Vec::new(),
+            data_sources: Vec::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    /// Parse stored settings, upgrading them from older schema versions first
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let mut settings: Self = serde_json::from_value(migrate(value))?;
+        settings.clamp_numeric();
+        Ok(settings)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// A copy with every provider and data source API key removed
+    pub fn without_secrets(&self) -> Self {
+        let mut settings = self.clone();
+        for provider in &mut settings.providers {
+            provider.api_key = None;
+        }
+        for source in &mut settings.data_sources {
+            source.api_key = None;
+        }
+        settings
+    }
+}
+
+/// Upgrade stored settings to `SETTINGS_SCHEMA_VERSION`, one version at a time
+fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+
+    // Version 0 is the unversioned layout, which matches version 1 field for field
+    if version < 1 {
+        if let Some(object) = value.as_object_mut() {
+            object.insert("version".to_string(), serde_json::json!(1));
+        }
+    }
+
+    value
+}
+
+/// Where settings are persisted
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsStore {
+    File(std::path::PathBuf),
+    LocalStorage,
+}
+
+impl SettingsStore {
+    /// `~/.dioxus-chat/settings.json` on desktop, local storage in the browser
+    pub fn platform_default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))
+            {
+                return SettingsStore::File(
+                    std::path::Path::new(&home)
+                        .join(".dioxus-chat")
+                        .join("settings.json"),
+                );
+            }
+        }
+        SettingsStore::LocalStorage
+    }
+
+    pub async fn load(&self) -> Option<AppSettings> {
+        let json = match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            SettingsStore::File(path) => tokio::fs::read_to_string(path).await.ok()?,
+            #[cfg(target_arch = "wasm32")]
+            SettingsStore::File(_) => return None,
+            SettingsStore::LocalStorage => document::eval(&format!(
+                "return localStorage.getItem('{}');",
+                SETTINGS_STORAGE_KEY
+            ))
+            .join::<Option<String>>()
+            .await
+            .ok()
+            .flatten()?,
+        };
+        AppSettings::from_json(&json).ok()
+    }
+
+    pub async fn save(&self, settings: &AppSettings) -> Result<(), String> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            SettingsStore::File(path) => {
+                let json = settings.to_json().map_err(|e| e.to_string())?;
+                write_private(path, json.as_bytes())
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            #[cfg(target_arch = "wasm32")]
+            SettingsStore::File(_) => Err("No settings file in the browser".to_string()),
+            SettingsStore::LocalStorage => {
+                // Local storage is readable by any script on the page, so keys stay in memory
+                let json = settings
+                    .without_secrets()
+                    .to_json()
+                    .map_err(|e| e.to_string())?;
+                let _ = document::eval(&format!(
+                    "localStorage.setItem('{}', {});",
+                    SETTINGS_STORAGE_KEY,
+                    serde_json::Value::String(json)
+                ));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Write the settings file readable by the owner only, since it holds API keys
+#[cfg(not(target_arch = "wasm32"))]
+async fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+
+    // `mode` only applies to new files; tighten one written by an older version too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+
+    file.write_all(contents).await?;
+    file.flush().await
+}
+
+/// Provide the app settings to the component tree. Call once at the app root; settings are
+/// loaded on mount and saved shortly after they stop changing.
+pub fn use_settings_provider() -> Signal<AppSettings> {
+    let mut settings = use_context_provider(|| Signal::new(AppSettings::default()));
+    let store = use_hook(SettingsStore::platform_default);
+    let mut loaded = use_signal(|| false);
+    let mut generation = use_signal(|| 0u64);
+
+    let load_store = store.clone();
+    use_effect(move || {
+        let store = load_store.clone();
+        spawn(async move {
+            if let Some(stored) = store.load().await {
+                settings.set(stored);
+            }
+            loaded.set(true);
+        });
+    });
+
+    use_effect(move || {
+        let current = settings.read().clone();
+        // Don't overwrite the stored settings with defaults before they've been loaded
+        if !loaded() {
+            return;
+        }
+
+        let this_generation = *generation.peek() + 1;
+        generation.set(this_generation);
+        let store = store.clone();
+        spawn(async move {
+            let _ = document::eval(&format!(
+                "await new Promise(r => setTimeout(r, {})); return true;",
+                SETTINGS_SAVE_DEBOUNCE_MS
+            ))
+            .join::<bool>()
+            .await;
+
+            // A newer change restarted the debounce
+            if *generation.peek() != this_generation {
+                return;
+            }
+            if let Err(e) = store.save(&current).await {
+                dioxus::logger::tracing::warn!("Failed to save settings: {}", e);
+            }
+        });
+    });
+
+    settings
+}
+
+/// The app settings provided by `use_settings_provider`
+pub fn use_settings() -> Signal<AppSettings> {
+    use_context::<Signal<AppSettings>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_settings_round_trip() {
+        let settings = AppSettings {
+            theme: Theme::Custom("high_contrast".to_string()),
+            language: "de".to_string(),
+            performance: PerformanceSettings {
+                network_timeout_seconds: 90,
+                log_level: LogLevel::Debug,
+                ..PerformanceSettings::default()
+            },
+            agent_config: AgentConfig {
+                require_confirmation: true,
+                ..AgentConfig::default()
+            },
+            providers: vec![Provider {
+                id: "openai".to_string(),
+                name: "OpenAI".to_string(),
+                api_key: Some("sk-test".to_string()),
+                base_url: None,
+                models: vec![Model {
+                    id: "gpt-4".to_string(),
+                    name: "GPT-4".to_string(),
+                    provider: "openai".to_string(),
+                    description: None,
+                    capabilities: vec!["tools".to_string()],
+                    context_limit: Some(8192),
+                    supports_tools: true,
+                    supports_streaming: true,
+                    supports_vision: false,
+                    supports_function_calling: true,
+                    pricing: Some(ModelPricing {
+                        input_tokens: 0.03,
+                        output_tokens: 0.06,
+                        currency: "USD".to_string(),
+                    }),
+                }],
+                active: true,
+                rate_limit: Some(RateLimit {
+                    requests_per_minute: 60,
+                    tokens_per_minute: 90_000,
+                }),
+                custom_headers: HashMap::from([("X-Team".to_string(), "chat".to_string())]),
+            }],
+            data_sources: vec![DataSource {
+                id: "docs".to_string(),
+                name: "Docs".to_string(),
+                type_: DataSourceType::GitHub,
+                url: Some("https://github.com/example/docs".to_string()),
+                api_key: None,
+                enabled: true,
+                last_sync: None,
+                sync_frequency: SyncFrequency::Daily,
+            }],
+            ..AppSettings::default()
+        };
+
+        let json = settings.to_json().unwrap();
+        assert_eq!(AppSettings::from_json(&json).unwrap(), settings);
+
+        // Unversioned data from before the schema version existed still loads
+        let legacy = r#"{"language": "fr"}"#;
+        let migrated = AppSettings::from_json(legacy).unwrap();
+        assert_eq!(migrated.version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(migrated.language, "fr");
+    }
+
+    #[test]
+    fn test_local_storage_copy_drops_api_keys() {
+        let settings = AppSettings {
+            providers: vec![Provider {
+                id: "openai".to_string(),
+                name: "OpenAI".to_string(),
+                api_key: Some("sk-test".to_string()),
+                base_url: None,
+                models: Vec::new(),
+                active: true,
+                rate_limit: None,
+                custom_headers: HashMap::new(),
+            }],
+            ..AppSettings::default()
+        };
+
+        let json = settings.without_secrets().to_json().unwrap();
+        assert!(!json.contains("sk-test"));
+        assert_eq!(settings.providers[0].api_key.as_deref(), Some("sk-test"));
+    }
+}
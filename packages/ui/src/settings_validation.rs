@@ -0,0 +1,117 @@
+// Bounds and parsing for the numeric fields in the settings panels
+use crate::settings_store::AppSettings;
+
+/// Inclusive range a numeric setting must stay within
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumericBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl NumericBounds {
+    pub const fn new(min: u64, max: u64) -> Self {
+        Self { min, max }
+    }
+
+    pub fn clamp(&self, value: u64) -> u64 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+pub const MAX_CONCURRENT_REQUESTS: NumericBounds = NumericBounds::new(1, 32);
+pub const CACHE_SIZE_MB: NumericBounds = NumericBounds::new(16, 8_192);
+pub const STREAMING_BUFFER_SIZE: NumericBounds = NumericBounds::new(256, 1_048_576);
+pub const MEMORY_LIMIT_MB: NumericBounds = NumericBounds::new(256, 65_536);
+pub const NETWORK_TIMEOUT_SECONDS: NumericBounds = NumericBounds::new(1, 600);
+pub const MAX_ITERATIONS: NumericBounds = NumericBounds::new(1, 100);
+pub const MAX_TURNS_WITHOUT_TOOLS: NumericBounds = NumericBounds::new(1, 50);
+pub const EXTENSION_TIMEOUT_SECONDS: NumericBounds = NumericBounds::new(1, 600);
+
+/// Parse a numeric settings input. The error is the message to show under the field; the
+/// caller should leave the setting unchanged until the input is valid.
+pub fn validate_numeric(input: &str, bounds: NumericBounds) -> Result<u64, String> {
+    let value: u64 = input
+        .trim()
+        .parse()
+        .map_err(|_| format!("Enter a whole number from {} to {}", bounds.min, bounds.max))?;
+
+    if value < bounds.min {
+        Err(format!("Must be at least {}", bounds.min))
+    } else if value > bounds.max {
+        Err(format!("Must be at most {}", bounds.max))
+    } else {
+        Ok(value)
+    }
+}
+
+impl AppSettings {
+    /// Pull numeric settings back into bounds, e.g. after loading a hand-edited settings file
+    pub fn clamp_numeric(&mut self) {
+        let performance = &mut self.performance;
+        performance.max_concurrent_requests =
+            MAX_CONCURRENT_REQUESTS.clamp(performance.max_concurrent_requests as u64) as u32;
+        performance.cache_size_mb = CACHE_SIZE_MB.clamp(performance.cache_size_mb as u64) as u32;
+        performance.streaming_buffer_size =
+            STREAMING_BUFFER_SIZE.clamp(performance.streaming_buffer_size as u64) as usize;
+        performance.memory_limit_mb =
+            MEMORY_LIMIT_MB.clamp(performance.memory_limit_mb as u64) as u32;
+        performance.network_timeout_seconds =
+            NETWORK_TIMEOUT_SECONDS.clamp(performance.network_timeout_seconds);
+
+        let agent = &mut self.agent_config;
+        agent.max_iterations = MAX_ITERATIONS.clamp(agent.max_iterations as u64) as usize;
+        agent.max_turns_without_tools =
+            MAX_TURNS_WITHOUT_TOOLS.clamp(agent.max_turns_without_tools as u64) as usize;
+        agent.extension_timeout = EXTENSION_TIMEOUT_SECONDS.clamp(agent.extension_timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_numeric_rejects_out_of_range_and_garbage() {
+        assert_eq!(validate_numeric("8", MAX_CONCURRENT_REQUESTS), Ok(8));
+        assert_eq!(validate_numeric(" 32 ", MAX_CONCURRENT_REQUESTS), Ok(32));
+
+        assert_eq!(
+            validate_numeric("0", CACHE_SIZE_MB),
+            Err("Must be at least 16".to_string())
+        );
+        assert_eq!(
+            validate_numeric("4000000000", MAX_CONCURRENT_REQUESTS),
+            Err("Must be at most 32".to_string())
+        );
+        for garbage in ["", "-5", "abc", "1.5"] {
+            assert_eq!(
+                validate_numeric(garbage, MAX_ITERATIONS),
+                Err("Enter a whole number from 1 to 100".to_string()),
+                "{:?}",
+                garbage
+            );
+        }
+    }
+
+    #[test]
+    fn test_clamp_numeric_pulls_stored_values_into_bounds() {
+        let mut settings = AppSettings::default();
+        settings.performance.cache_size_mb = 0;
+        settings.performance.max_concurrent_requests = u32::MAX;
+        settings.agent_config.max_iterations = 0;
+
+        settings.clamp_numeric();
+        assert_eq!(settings.performance.cache_size_mb, CACHE_SIZE_MB.min as u32);
+        assert_eq!(
+            settings.performance.max_concurrent_requests,
+            MAX_CONCURRENT_REQUESTS.max as u32
+        );
+        assert_eq!(settings.agent_config.max_iterations, 1);
+
+        // Defaults are already in bounds
+        let defaults = AppSettings::default();
+        let mut clamped = defaults.clone();
+        clamped.clamp_numeric();
+        assert_eq!(clamped, defaults);
+    }
+}
@@ -12,6 +12,8 @@ pub struct SidebarProps {
     pub on_select_conversation: EventHandler<String>,
     pub on_new_conversation: EventHandler,
     pub on_delete_conversation: EventHandler<String>,
+    /// Called with the conversation id and its new pinned state
+    pub on_toggle_pin: Option<EventHandler<(String, bool)>>,
     pub collapsed: Option<bool>,
 }
 
@@ -32,6 +34,7 @@ pub fn Sidebar(props: SidebarProps) -> Element {
     let on_select_conversation = props.on_select_conversation;
     let on_new_conversation = props.on_new_conversation;
     let on_delete_conversation = props.on_delete_conversation;
+    let on_toggle_pin = props.on_toggle_pin;
 
     rsx! {
         div {
@@ -77,6 +80,7 @@ pub fn Sidebar(props: SidebarProps) -> Element {
 
                             let select_id = conversation_id.clone();
                             let delete_id = conversation_id.clone();
+                            let pin_id = conversation_id.clone();
                             let class_id = conversation_id.clone();
 
                             rsx! {
@@ -105,7 +109,23 @@ pub fn Sidebar(props: SidebarProps) -> Element {
                                         }
                                         div {
                                             class: "flex items-center gap-1 ml-2",
-                                            if pinned {
+                                            if let Some(on_toggle_pin) = on_toggle_pin {
+                                                Button {
+                                                    onclick: move |event: dioxus::prelude::Event<dioxus::prelude::MouseData>| {
+                                                        event.stop_propagation();
+                                                        on_toggle_pin.call((pin_id.clone(), !pinned));
+                                                    },
+                                                    class: if pinned {
+                                                        "w-6 h-6 text-yellow-500"
+                                                    } else {
+                                                        "w-6 h-6 opacity-0 hover:opacity-100 transition-opacity"
+                                                    },
+                                                    variant: ButtonVariant::Ghost,
+                                                    size: "sm",
+                                                    title: if pinned { "Unpin" } else { "Pin" },
+                                                    "📌"
+                                                }
+                                            } else if pinned {
                                                 span {
                                                     class: "text-yellow-500 text-sm",
                                                     "📌"
@@ -0,0 +1,150 @@
+// Batches streamed text so the UI re-renders at most once per frame
+use dioxus::prelude::*;
+use futures::StreamExt;
+
+/// Roughly one animation frame at 60Hz
+pub const FRAME_MS: i64 = 16;
+
+/// Buffers incoming chunks and releases them no more than once per `frame_ms`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamCoalescer {
+    pending: String,
+    frame_ms: i64,
+    last_emit_ms: Option<i64>,
+}
+
+impl StreamCoalescer {
+    pub fn new(frame_ms: i64) -> Self {
+        Self {
+            pending: String::new(),
+            frame_ms,
+            last_emit_ms: None,
+        }
+    }
+
+    /// Buffer a chunk, returning the batched text if a frame has passed since the last release
+    pub fn push(&mut self, chunk: &str, now_ms: i64) -> Option<String> {
+        self.pending.push_str(chunk);
+        self.take_if_due(now_ms)
+    }
+
+    pub fn take_if_due(&mut self, now_ms: i64) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        if let Some(last) = self.last_emit_ms {
+            if now_ms - last < self.frame_ms {
+                return None;
+            }
+        }
+        self.last_emit_ms = Some(now_ms);
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Release whatever is buffered regardless of timing
+    pub fn flush(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.pending))
+        }
+    }
+}
+
+/// Handle returned by `use_coalesced_stream`
+#[derive(Clone, Copy)]
+pub struct CoalescedStream {
+    coalescer: CopyValue<StreamCoalescer>,
+    on_flush: Callback<String>,
+    ticker: Coroutine<()>,
+}
+
+impl CoalescedStream {
+    pub fn push(&self, chunk: &str) {
+        let (due, pending) = {
+            let mut coalescer = self.coalescer.write_unchecked();
+            let due = coalescer.push(chunk, chrono::Utc::now().timestamp_millis());
+            (due, coalescer.has_pending())
+        };
+        if let Some(text) = due {
+            self.on_flush.call(text);
+        }
+        if pending {
+            self.ticker.send(());
+        }
+    }
+
+    /// Hand over any buffered text now; call when the stream ends or the user stops it
+    pub fn finish(&self) {
+        let pending = self.coalescer.write_unchecked().flush();
+        if let Some(text) = pending {
+            self.on_flush.call(text);
+        }
+    }
+}
+
+/// Coalesce streamed text into at most one `on_flush` call per animation frame, so render
+/// cost stays bounded no matter how fast the provider sends tokens
+pub fn use_coalesced_stream(on_flush: Callback<String>) -> CoalescedStream {
+    let coalescer = use_hook(|| CopyValue::new(StreamCoalescer::new(FRAME_MS)));
+
+    // Releases the tail of a burst that no later chunk arrives to push out. It only ticks
+    // while text is buffered, and stops with the component.
+    let ticker = use_coroutine(move |mut wake: UnboundedReceiver<()>| async move {
+        while wake.next().await.is_some() {
+            while coalescer.read().has_pending() {
+                let _ = document::eval(
+                    "await new Promise(r => requestAnimationFrame(() => r(true))); return true;",
+                )
+                .join::<bool>()
+                .await;
+
+                let due = coalescer
+                    .write_unchecked()
+                    .take_if_due(chrono::Utc::now().timestamp_millis());
+                if let Some(text) = due {
+                    on_flush.call(text);
+                }
+            }
+        }
+    });
+
+    CoalescedStream {
+        coalescer,
+        on_flush,
+        ticker,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rapid_chunks_are_batched_per_frame() {
+        let mut coalescer = StreamCoalescer::new(FRAME_MS);
+        let mut rendered = String::new();
+        let mut updates = 0;
+
+        // 1000 tokens arriving one millisecond apart
+        for now_ms in 0..1000 {
+            if let Some(text) = coalescer.push("tok ", now_ms) {
+                rendered.push_str(&text);
+                updates += 1;
+            }
+        }
+        if let Some(text) = coalescer.flush() {
+            rendered.push_str(&text);
+            updates += 1;
+        }
+
+        assert!(updates <= 1000 / FRAME_MS as usize + 2, "{} updates", updates);
+        assert_eq!(rendered, "tok ".repeat(1000));
+        assert_eq!(coalescer.flush(), None);
+        assert!(!coalescer.has_pending());
+    }
+}
@@ -1,4 +1,5 @@
 // Improved UI Components based on React design patterns
+use api::{ProviderErrorKind, NO_CREDENTIALS};
 use dioxus::prelude::*;
 
 // Dialog Components
@@ -10,6 +11,8 @@ pub struct DialogProps {
     pub class: Option<String>,
     pub show_close_button: Option<bool>,
     pub max_width: Option<String>,
+    /// Accessible name announced when the dialog opens
+    pub aria_label: Option<String>,
 }
 
 #[component]
@@ -42,11 +45,20 @@ pub fn Dialog(props: DialogProps) -> Element {
                     "relative bg-white dark:bg-gray-800 rounded-lg shadow-lg p-6 w-full max-w-md mx-4 {}",
                     props.max_width.unwrap_or_default()
                 ),
+                role: "dialog",
+                "aria-modal": "true",
+                "aria-label": props.aria_label.clone(),
                 onclick: move |evt| evt.stop_propagation(),
+                onkeydown: move |evt| {
+                    if evt.key() == Key::Escape {
+                        props.on_open_change.call(false);
+                    }
+                },
 
                 if props.show_close_button.unwrap_or(true) {
                     button {
                         class: "absolute right-4 top-4 text-gray-400 hover:text-gray-600 dark:hover:text-gray-300",
+                        "aria-label": "Close",
                         onclick: move |_| props.on_open_change.call(false),
                         "×"
                     }
@@ -394,6 +406,65 @@ pub fn Switch(props: SwitchProps) -> Element {
     }
 }
 
+// Slider Component
+#[derive(Clone, PartialEq, Props)]
+pub struct SliderProps {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub value: f32,
+    /// Called with the new value, already clamped to `min..=max`
+    pub on_change: EventHandler<f32>,
+    pub disabled: Option<bool>,
+    pub id: Option<String>,
+    pub class: Option<String>,
+}
+
+/// Parse a range input's value, clamped to the slider's bounds
+pub fn slider_value(input: &str, min: f32, max: f32) -> Option<f32> {
+    let value: f32 = input.trim().parse().ok()?;
+    value.is_finite().then_some(value.clamp(min, max))
+}
+
+/// A value shown with as many decimals as the step has, so 0.05 steps read "0.75" and whole
+/// steps read "3"
+pub fn slider_label(value: f32, step: f32) -> String {
+    let decimals = format!("{}", step)
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len());
+    format!("{:.*}", decimals, value)
+}
+
+#[component]
+pub fn Slider(props: SliderProps) -> Element {
+    let (min, max) = (props.min, props.max);
+    let value = props.value.clamp(min, max);
+    let label = slider_label(value, props.step);
+
+    rsx! {
+        div { class: format!("flex items-center gap-3 {}", props.class.unwrap_or_default()),
+            input {
+                id: props.id.as_deref(),
+                r#type: "range",
+                class: "h-2 flex-1 cursor-pointer accent-blue-600 disabled:cursor-not-allowed disabled:opacity-50",
+                min: "{min}",
+                max: "{max}",
+                step: "{props.step}",
+                value: "{value}",
+                oninput: move |evt| {
+                    if let Some(value) = slider_value(&evt.value(), min, max) {
+                        props.on_change.call(value);
+                    }
+                },
+                disabled: props.disabled.unwrap_or(false),
+            }
+            span { class: "w-12 text-right text-sm tabular-nums text-gray-700 dark:text-gray-300",
+                "{label}"
+            }
+        }
+    }
+}
+
 // Avatar Component
 #[derive(Clone, PartialEq, Props)]
 pub struct AvatarProps {
@@ -490,4 +561,198 @@ pub fn Badge(props: BadgeProps) -> Element {
             {props.children}
         }
     }
-}
\ No newline at end of file
+}
+
+// Empty and Error States
+#[derive(Clone, PartialEq, Props)]
+pub struct EmptyStateProps {
+    pub icon: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub action_label: Option<String>,
+    pub on_action: Option<EventHandler>,
+}
+
+#[component]
+pub fn EmptyState(props: EmptyStateProps) -> Element {
+    rsx! {
+        div {
+            class: "flex flex-col items-center justify-center text-center py-8 px-4 text-gray-500 dark:text-gray-400",
+            div { class: "text-4xl mb-2", "aria-hidden": "true", "{props.icon}" }
+            p { class: "font-medium text-gray-700 dark:text-gray-200", "{props.title}" }
+            if let Some(description) = props.description {
+                p { class: "text-sm mt-1", "{description}" }
+            }
+            if let (Some(label), Some(on_action)) = (props.action_label, props.on_action) {
+                Button {
+                    onclick: move |_| on_action.call(()),
+                    variant: ButtonVariant::Secondary,
+                    size: ButtonSize::Sm,
+                    class: "mt-4",
+                    "{label}"
+                }
+            }
+        }
+    }
+}
+
+/// Icon, headline and what to do next for each kind of provider failure
+pub fn error_guidance(kind: ProviderErrorKind) -> (&'static str, &'static str, &'static str) {
+    match kind {
+        ProviderErrorKind::Timeout => (
+            "⏱️",
+            "The model took too long to respond",
+            "Try again, or raise the network timeout in Settings → Performance.",
+        ),
+        ProviderErrorKind::Network => (
+            "📡",
+            "Couldn't reach the provider",
+            "Check your internet connection and the provider's endpoint URL.",
+        ),
+        ProviderErrorKind::Authentication => (
+            "🔑",
+            "The provider rejected your credentials",
+            "Check the API key for this provider in Settings → Providers.",
+        ),
+        ProviderErrorKind::RateLimited => (
+            "🚦",
+            "Rate limit reached",
+            "Wait a moment before retrying, or switch to another model.",
+        ),
+        ProviderErrorKind::InvalidRequest => (
+            "⚠️",
+            "The provider couldn't process this request",
+            "Try a shorter conversation or different model parameters.",
+        ),
+        ProviderErrorKind::Server => (
+            "🛠️",
+            "The provider is having problems",
+            "This is usually temporary. Try again shortly.",
+        ),
+        ProviderErrorKind::Other => (
+            "❗",
+            "Something went wrong",
+            "Try again. If it keeps happening, check the logs.",
+        ),
+    }
+}
+
+#[derive(Clone, PartialEq, Props)]
+pub struct ErrorStateProps {
+    pub kind: ProviderErrorKind,
+    /// The underlying error, shown under the guidance
+    pub message: Option<String>,
+    /// The error's `ProviderError::code`; a missing API key gets its own guidance
+    pub code: Option<String>,
+    pub on_retry: Option<EventHandler>,
+    /// Defaults to "Try again"
+    pub retry_label: Option<String>,
+}
+
+#[component]
+pub fn ErrorState(props: ErrorStateProps) -> Element {
+    let (icon, title, guidance) = match props.code.as_deref() {
+        Some(NO_CREDENTIALS) => (
+            "🔑",
+            "This provider has no API key",
+            "Set its API key environment variable where the server runs, or switch to a local model.",
+        ),
+        _ => error_guidance(props.kind),
+    };
+    let retry_label = props.retry_label.unwrap_or_else(|| "Try again".to_string());
+
+    rsx! {
+        div {
+            class: "flex flex-col items-center justify-center text-center py-6 px-4 rounded-lg border border-red-200 dark:border-red-800 bg-red-50 dark:bg-red-950",
+            role: "alert",
+            div { class: "text-3xl mb-2", "aria-hidden": "true", "{icon}" }
+            p { class: "font-medium text-red-800 dark:text-red-200", "{title}" }
+            p { class: "text-sm mt-1 text-red-700 dark:text-red-300", "{guidance}" }
+            if let Some(message) = props.message {
+                p { class: "text-xs mt-2 font-mono text-red-600 dark:text-red-400 break-all", "{message}" }
+            }
+            if let Some(on_retry) = props.on_retry {
+                Button {
+                    onclick: move |_| on_retry.call(()),
+                    variant: ButtonVariant::Outline,
+                    size: ButtonSize::Sm,
+                    class: "mt-4",
+                    "{retry_label}"
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(app: fn() -> Element) -> String {
+        let mut dom = VirtualDom::new(app);
+        dom.rebuild_in_place();
+        dioxus_ssr::render(&dom)
+    }
+
+    #[test]
+    fn test_empty_state_renders_action_only_when_wired() {
+        let html = render(|| rsx! {
+            EmptyState {
+                icon: "💭",
+                title: "No conversations yet",
+                description: "Start a new conversation to see it here",
+                action_label: "New conversation",
+                on_action: move |_| {},
+            }
+        });
+        assert!(html.contains("No conversations yet"));
+        assert!(html.contains("New conversation</button>"));
+
+        let html = render(|| rsx! {
+            EmptyState { icon: "🔌", title: "No providers configured", action_label: "Add provider" }
+        });
+        assert!(!html.contains("<button"));
+    }
+
+    #[test]
+    fn test_error_state_shows_guidance_for_kind_and_retry() {
+        let html = render(|| rsx! {
+            ErrorState {
+                kind: ProviderErrorKind::Authentication,
+                message: "401 Unauthorized",
+                on_retry: move |_| {},
+            }
+        });
+        assert!(html.contains(r#"role="alert""#));
+        assert!(html.contains(error_guidance(ProviderErrorKind::Authentication).2));
+        assert!(html.contains("401 Unauthorized"));
+        assert!(html.contains("Try again</button>"));
+
+        let html = render(|| rsx! { ErrorState { kind: ProviderErrorKind::RateLimited } });
+        assert!(html.contains("Rate limit reached"));
+        assert!(!html.contains("<button"));
+
+        let html =
+            render(|| rsx! { ErrorState { kind: ProviderErrorKind::Other, code: NO_CREDENTIALS } });
+        assert!(html.contains("This provider has no API key"));
+    }
+
+    #[test]
+    fn test_slider_clamps_input_and_labels_to_step_precision() {
+        assert_eq!(slider_value("0.75", 0.0, 2.0), Some(0.75));
+        assert_eq!(slider_value("3.5", 0.0, 2.0), Some(2.0));
+        assert_eq!(slider_value("-1", 0.1, 1.0), Some(0.1));
+        assert_eq!(slider_value("warm", 0.0, 2.0), None);
+        assert_eq!(slider_value("NaN", 0.0, 2.0), None);
+
+        assert_eq!(slider_label(0.7, 0.05), "0.70");
+        assert_eq!(slider_label(3.0, 1.0), "3");
+
+        let html = render(|| rsx! {
+            Slider { min: 0.0, max: 2.0, step: 0.1, value: 5.0, on_change: move |_| {} }
+        });
+        assert!(html.contains(r#"type="range""#));
+        assert!(html.contains(r#"value="2""#));
+        assert!(html.contains(">2.0</span>"));
+    }
+}
@@ -28,6 +28,8 @@ fn main() {
 #[component]
 fn App() -> Element {
     // Build cool things ✌️
+    ui::use_appearance_provider();
+    ui::use_settings_provider();
 
     rsx! {
         // Global app resources
@@ -43,6 +45,9 @@ fn App() -> Element {
 /// which allows us to use the web-specific `Route` enum.
 #[component]
 fn WebNavbar() -> Element {
+    let provider_status = ui::use_provider_status(None);
+    let mut settings_open = use_signal(|| false);
+
     rsx! {
         Navbar {
             Link {
@@ -57,6 +62,16 @@ fn WebNavbar() -> Element {
                 to: Route::Blog { id: 1 },
                 "Blog"
             }
+            ui::ProviderStatusBadge { status: provider_status.status() }
+            button {
+                onclick: move |_| settings_open.set(true),
+                "Settings"
+            }
+        }
+
+        ui::SettingsDialog {
+            open: settings_open(),
+            on_open_change: move |open| settings_open.set(open),
         }
 
         Outlet::<Route> {}
@@ -26,6 +26,9 @@ pub fn Chat() -> Element {
                         provider: m.provider.clone(),
                         description: m.description.clone(),
                         capabilities: m.capabilities.clone(),
+                        supports_tools: m.supports_tools,
+                        supports_vision: m.supports_vision,
+                        supports_streaming: m.supports_streaming,
                     }).collect();
                     available_models.set(ui_models);
                     if let Some(first_model) = available_models().first() {
@@ -39,16 +42,8 @@ pub fn Chat() -> Element {
         });
     });
 
-    let handle_send_message = move |content: String| {
-        let user_message = ChatMessage {
-            id: format!("msg_{}", chrono::Utc::now().timestamp_nanos()),
-            content: content.clone(),
-            is_user: true,
-            timestamp: Some(chrono::Utc::now().to_rfc3339()),
-            avatar: None,
-        };
-
-        messages.with_mut(|msgs| msgs.push(user_message));
+    // Ask for a reply to `content`, the latest user message
+    let request_reply = move |content: String| {
         loading.set(true);
         streaming.set(true);
 
@@ -95,6 +90,33 @@ pub fn Chat() -> Element {
         });
     };
 
+    let handle_send_message = move |content: String| {
+        let user_message = ChatMessage {
+            id: format!("msg_{}", chrono::Utc::now().timestamp_nanos()),
+            content: content.clone(),
+            is_user: true,
+            timestamp: Some(chrono::Utc::now().to_rfc3339()),
+            avatar: None,
+        };
+
+        messages.with_mut(|msgs| msgs.push(user_message));
+        request_reply(content);
+    };
+
+    // Answer the latest user message again, replacing the reply to it
+    let handle_regenerate = move |_message_id: String| {
+        let mut prompt = None;
+        messages.with_mut(|msgs| {
+            while msgs.last().is_some_and(|msg| !msg.is_user) {
+                msgs.pop();
+            }
+            prompt = msgs.last().map(|msg| msg.content.clone());
+        });
+        if let Some(content) = prompt {
+            request_reply(content);
+        }
+    };
+
     let handle_new_conversation = move |_| {
         let new_conversation_id = format!("conv_{}", chrono::Utc::now().timestamp_nanos());
         let new_conversation = ConversationItem {
@@ -215,6 +237,7 @@ pub fn Chat() -> Element {
                     loading: loading(),
                     streaming: streaming(),
                     on_stop_streaming: handle_stop_streaming,
+                    on_regenerate: handle_regenerate,
                     placeholder: None,
                 }
             }